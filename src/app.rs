@@ -1,25 +1,44 @@
-use std::{sync::Arc, time::Instant};
+use std::{path::{Path, PathBuf}, sync::{mpsc::{self, Receiver, Sender, SyncSender}, Arc, Mutex}, time::{Duration, Instant}};
 
-use cpal::{traits::{DeviceTrait, HostTrait, StreamTrait}, Device, Host, Stream, SupportedStreamConfig};
+use cpal::{traits::{DeviceTrait, HostTrait, StreamTrait}, BuildStreamError, Device, Host, HostId, SampleRate, Stream, StreamError, SupportedStreamConfig};
 use eframe;
 use egui::{
     Align, CentralPanel, ComboBox, Context, FontData, FontDefinitions, FontFamily, Id, Label, MenuBar, Modal, RichText, TextStyle, TextWrapMode, TopBottomPanel, Ui, ViewportCommand
 };
 
 use crate::{
-    circuit::{CircuitBuilderSpecification, CircuitUiSlot}, patch::PatchEditor
+    audio_config::{self, ChannelMap, ChannelSide, ChannelSource, CueDestination}, audio_output::{self, CueControl, CuePlayer, CueSink, PatchRenderer, SharedRenderer}, bundle::ProjectBundle, circuit::{CircuitBuilderSpecification, CircuitUiSlot}, computer_keyboard::ComputerKeyboard, live_plugin_id::{LivePluginId, LivePluginIdManager, LivePluginKind}, patch::{Patch, PatchEditor}, patch_file::PatchFile, program_bank::ProgramBankError, meter::MeterDisplay, midi::{self, CcMap, MidiInput, MidiMessage, MidiOutput, MidiRouting, MpeSettings, ParameterTarget}, playback::{self, InputSpecification, PlaybackCommand}, recorder::Recording, limiter::LimiterSettings, pitch::{TuningSettings, TuningSystem}, settings::{AppSettings, Theme}, toast::Toasts
 };
 
+/// which project file dialog is open
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProjectFileAction {
+    Open,
+    Save,
+}
+
 #[derive(Debug, PartialEq, Eq)]
 enum AppMode {
     Editor,
     StartPlayback,
     Playback,
     EndPlayback,
+
+    /// the output stream failed during playback and was stopped
+    StreamError,
 }
 
 pub struct App<'a> {
     patch_editor: PatchEditor<'a>,
+    builders: &'a[CircuitBuilderSpecification],
+    draw_new_project_ui: bool,
+
+    // project files
+    /// the bundle the project was last opened from or saved to
+    project_bundle: ProjectBundle,
+    project_path: Option<PathBuf>,
+    project_path_input: String,
+    draw_project_file_ui: Option<ProjectFileAction>,
 
     // io configuration ui
     host: Host,
@@ -28,18 +47,66 @@ pub struct App<'a> {
     known_output_devices: Vec<Device>,
     draw_settings_ui: bool,
 
+    // persisted user preferences
+    settings: AppSettings,
+
+    // tuning ui and the tuning read from the tuning settings
+    tuning: TuningSystem,
+    new_tuning_scale: String,
+    new_tuning_mapping: String,
+
+    // midi
+    midi_input: Option<MidiInput>,
+    midi_routing: Arc<Mutex<MidiRouting>>,
+    known_midi_inputs: Vec<String>,
+    midi_output: Option<MidiOutput>,
+    known_midi_outputs: Vec<String>,
+    computer_keyboard: ComputerKeyboard,
+    program_changes: Receiver<u8>,
+
+    // program bank ui
+    new_program: u8,
+    new_program_path: String,
+
+    // commands for live plugins
+    playback_commands: SyncSender<PlaybackCommand>,
+
+    /// moved to the renderer while playing
+    playback_command_receiver: Option<Receiver<PlaybackCommand>>,
+
+    /// the synth id MIDI notes are routed to the playing patch by
+    patch_synth: LivePluginId,
+
     // playback data
     circuit_uis: Vec<CircuitUiSlot>,
+
+    /// the inputs of the playing patch and the values last set from the ui
+    patch_parameters: Vec<(InputSpecification, f64)>,
     stream: Option<Stream>,
+    renderer: Option<SharedRenderer>,
+    meter: Option<MeterDisplay>,
+    recording: Option<Recording>,
+    cue_control: CueControl,
+    cue_stream: Option<Stream>,
+
+    // errors reported by the output stream
+    stream_error_sender: Sender<StreamError>,
+    stream_error_receiver: Receiver<StreamError>,
+    stream_error_message: Option<String>,
+    last_device_check: Instant,
     
     // misc
     mode: AppMode,
+    toasts: Toasts,
 }
 
 impl<'a> App<'a> {
     const MIN_ZOOM: f32 = 0.25;
     const MAX_ZOOM: f32 = 1.0;
 
+    /// how often the output device is checked for disconnection during playback
+    const DEVICE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
     /// Called once before the first frame.
     pub fn new(cc: &eframe::CreationContext<'_>, builders: &'a[CircuitBuilderSpecification]) -> Self {
 
@@ -64,85 +131,514 @@ impl<'a> App<'a> {
             Arc::new(style)
         });
 
-        //setup audio
-        let host = cpal::default_host();
-        let output_device = host.default_output_device()
-            .expect("No output device available.");
+        let settings = AppSettings::load();
+        cc.egui_ctx.set_theme(settings.theme.preference());
 
-        let output_device_config = output_device.default_output_config()
-            .expect("Default config not found.");
+        //setup audio, preferring the host used last time
+        let host = settings.host
+            .as_ref()
+            .and_then(|name| cpal::available_hosts().into_iter().find(|id| id.name() == name))
+            .and_then(|id| cpal::host_from_id(id).ok())
+            .unwrap_or_else(cpal::default_host);
 
-        let known_output_devices = {
-            let iter_raw = host.output_devices();
-            if let Ok(iter) = iter_raw {
-                iter.collect()
-            } else {
-                Vec::new()
-            }
-        };
+        let (stream_error_sender, stream_error_receiver) = mpsc::channel();
+        let (playback_commands, playback_command_receiver) = playback::command_queue();
+        let (program_sender, program_changes) = mpsc::channel();
+        let patch_synth = LivePluginIdManager::new()
+            .get_id(LivePluginKind::Synth)
+            .expect("no synth ids available");
+        let mut midi_routing = MidiRouting::new();
+        midi_routing.set_program_changes(Some(program_sender));
+        midi_routing.set_route(patch_synth, settings.midi_channel);
 
         // Return initialized state
-        Self {
+        let mut app = Self {
             patch_editor: PatchEditor::new(builders),
+            builders,
+            draw_new_project_ui: true,
+            project_bundle: ProjectBundle::default(),
+            project_path: None,
+            project_path_input: String::new(),
+            draw_project_file_ui: None,
 
             stream: None,
+            renderer: None,
+            meter: None,
+            recording: None,
+            cue_control: CueControl::new(),
+            cue_stream: None,
             circuit_uis: Vec::new(),
+            patch_parameters: Vec::new(),
+            stream_error_sender,
+            stream_error_receiver,
+            stream_error_message: None,
+            last_device_check: Instant::now(),
             mode: AppMode::Editor,
+            toasts: Toasts::new(),
 
             host,
-            output_device: Some(output_device),
-            output_device_config: Some(output_device_config),
-            known_output_devices,
-            draw_settings_ui: false
+            output_device: None,
+            output_device_config: None,
+            known_output_devices: Vec::new(),
+            draw_settings_ui: false,
+            settings,
+            tuning: TuningSystem::default(),
+            new_tuning_scale: String::new(),
+            new_tuning_mapping: String::new(),
+            midi_input: None,
+            midi_routing: Arc::new(Mutex::new(midi_routing)),
+            known_midi_inputs: Vec::new(),
+            midi_output: None,
+            known_midi_outputs: Vec::new(),
+            computer_keyboard: ComputerKeyboard::new(),
+            program_changes,
+            new_program: 0,
+            new_program_path: String::new(),
+            playback_commands,
+            playback_command_receiver: Some(playback_command_receiver),
+            patch_synth,
+        };
+        app.refresh_devices();
+        app.apply_mpe_settings();
+        app.apply_tuning_settings();
+        app.computer_keyboard.enabled = app.settings.computer_keyboard;
+        if let Some(port) = app.settings.midi_input.clone() {
+            app.select_midi_input(Some(port));
+        }
+        if let Some(port) = app.settings.midi_output.clone() {
+            app.select_midi_output(Some(port));
+        }
+        app
+    }
+
+    /// Enumerates the devices of the current host and selects an output device,
+    /// preferring the device used last time and falling back to the default.
+    fn refresh_devices(&mut self) {
+        self.known_output_devices = {
+            let iter_raw = self.host.output_devices();
+            if let Ok(iter) = iter_raw {
+                iter.collect()
+            } else {
+                Vec::new()
+            }
+        };
+
+        self.output_device = self.settings.output_device
+            .as_ref()
+            .and_then(|name| self.known_output_devices
+                .iter()
+                .find(|device| device.name().ok().as_ref() == Some(name))
+                .cloned()
+            )
+            .or_else(|| self.host.default_output_device());
+
+        self.output_device_config = self.output_device
+            .as_ref()
+            .and_then(|device| Self::preferred_config(device, &self.settings));
+    }
+
+    /// Routes MIDI notes from the chosen channel to the playing patch
+    fn apply_midi_channel(&mut self) {
+        if let Ok(mut routing) = self.midi_routing.lock() {
+            routing.set_route(self.patch_synth, self.settings.midi_channel);
+        }
+    }
+
+    fn apply_mpe_settings(&mut self) {
+        if let Ok(mut routing) = self.midi_routing.lock() {
+            routing.set_mpe(self.settings.mpe);
         }
     }
 
+    /// Reads the tuning described by the settings and plays MIDI notes in it
+    /// If the Scala files can not be read, equal temperment at the reference pitch is used instead.
+    fn apply_tuning_settings(&mut self) {
+        let tuning = match self.settings.tuning.tuning_system() {
+            Ok(tuning) => tuning,
+            Err(err) => {
+                self.toasts.push(format!("Could not load the tuning, using equal temperment: {}", err));
+                TuningSystem::EqualTemperment(self.settings.tuning.reference_a4())
+            }
+        };
+        self.set_tuning(tuning);
+    }
+
+    fn set_tuning(&mut self, tuning: TuningSystem) {
+        if let Ok(mut routing) = self.midi_routing.lock() {
+            routing.set_tuning(tuning.clone());
+        }
+        self.tuning = tuning;
+    }
+
+    /// Replaces the tuning settings if the tuning they describe can be read, reporting the failure otherwise
+    /// Patches already playing keep their tuning until playback is restarted.
+    fn set_tuning_settings(&mut self, tuning: TuningSettings) {
+        match tuning.tuning_system() {
+            Ok(system) => {
+                self.settings.tuning = tuning;
+                self.set_tuning(system);
+            }
+            Err(err) => self.toasts.push(format!("Could not load the tuning: {}", err)),
+        }
+    }
+
+    /// Connects to the MIDI input port with the given name, or disconnects if none
+    fn select_midi_input(&mut self, port: Option<String>) {
+        self.midi_input = None;
+        self.settings.midi_input = port.clone();
+
+        let Some(port) = port else {
+            return;
+        };
+        match MidiInput::connect(&port, self.midi_routing.clone(), self.playback_commands.clone()) {
+            Ok(input) => self.midi_input = Some(input),
+            Err(err) => self.toasts.push(format!("Could not open MIDI input: {}", err)),
+        }
+    }
+
+    /// The port sequenced notes are played on, if any
+    pub fn midi_output_mut(&mut self) -> Option<&mut MidiOutput> {
+        self.midi_output.as_mut()
+    }
+
+    /// Swaps the playing patch for the one assigned to a program without stopping the stream
+    /// Programs without a patch are ignored.
+    fn select_program(&mut self, program: u8) {
+        let Some(renderer) = self.renderer.as_ref() else {
+            return;
+        };
+        let file = match self.settings.program_bank.load(program) {
+            Ok(file) => file,
+            Err(ProgramBankError::Unassigned(_)) => return,
+            Err(err) => {
+                self.toasts.push(err.to_string());
+                return;
+            }
+        };
+        let patch = match Patch::from_file(&file, self.builders) {
+            Ok(patch) => patch,
+            Err(err) => {
+                self.toasts.push(format!("Could not load program {}: {}", program, err));
+                return;
+            }
+        };
+
+        let internal_rate = renderer.lock().internal_rate();
+        let compiled = patch.compile(self.builders, &ProjectBundle::default(), internal_rate, crate::constants::SAMPLE_MULTIPLIER, &self.tuning);
+        let (mut compiled, circuit_uis) = match compiled {
+            Ok(compiled) => compiled,
+            Err(err) => {
+                self.toasts.push(format!("Could not compile program {}: {}", program, err));
+                return;
+            }
+        };
+        self.meter = Some(MeterDisplay::new(compiled.attach_meter(internal_rate)));
+
+        // the previous patch is dropped here rather than on the audio thread
+        let _previous = renderer.lock().set_patch(compiled);
+        self.circuit_uis = circuit_uis;
+        self.patch_parameters = Self::parameters_for(patch.inputs());
+        self.toasts.push(format!("Switched to program {}", program));
+    }
+
+    fn draw_tuning_ui(&mut self, ui: &mut Ui) {
+        ui.label("Tuning")
+            .on_hover_text("The tuning of every patch and of the notes played into them. Playing patches are retuned when playback restarts.");
+
+        let mut tuning = self.settings.tuning.clone();
+        let mapped = tuning.scale.is_some() && tuning.mapping.is_some();
+        ui.add_enabled_ui(!mapped, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Reference A4");
+                ui.add(egui::Slider::new(
+                    &mut tuning.reference_a4,
+                    TuningSettings::MIN_REFERENCE_A4..=TuningSettings::MAX_REFERENCE_A4
+                ).suffix(" Hz"));
+            });
+        }).response.on_disabled_hover_text("The keyboard mapping sets the reference pitch.");
+
+        Self::draw_tuning_file_ui(ui, "Scala Scale", &mut tuning.scale, &mut self.new_tuning_scale);
+        ui.add_enabled_ui(tuning.scale.is_some(), |ui| {
+            Self::draw_tuning_file_ui(ui, "Keyboard Mapping", &mut tuning.mapping, &mut self.new_tuning_mapping);
+        });
+
+        if tuning != self.settings.tuning {
+            self.set_tuning_settings(tuning);
+        }
+    }
+
+    /// Shows the path of a tuning file with a button to clear it, or a field to choose one if there is none
+    fn draw_tuning_file_ui(ui: &mut Ui, label: &str, path: &mut Option<PathBuf>, new_path: &mut String) {
+        ui.horizontal(|ui| {
+            ui.label(label);
+            if let Some(current) = path.as_ref() {
+                ui.label(current.display().to_string());
+                if ui.small_button("Clear").clicked() {
+                    *path = None;
+                }
+            } else {
+                ui.text_edit_singleline(new_path)
+                    .on_hover_text("The path of a Scala file");
+                let trimmed = new_path.trim();
+                if ui.add_enabled(!trimmed.is_empty(), egui::Button::new("Load")).clicked() {
+                    *path = Some(trimmed.into());
+                    new_path.clear();
+                }
+            }
+        });
+    }
+
+    fn draw_program_bank_ui(&mut self, ui: &mut Ui) {
+        ui.label("Program Bank")
+            .on_hover_text("Patches selected by MIDI program changes while playing.");
+
+        let mut removed = None;
+        for entry in self.settings.program_bank.entries() {
+            ui.horizontal(|ui| {
+                ui.label(format!("{:>3}", entry.program));
+                ui.label(entry.path.display().to_string());
+                if ui.small_button("Remove").clicked() {
+                    removed = Some(entry.program);
+                }
+            });
+        }
+        if let Some(program) = removed {
+            self.settings.program_bank.remove(program);
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(egui::DragValue::new(&mut self.new_program).range(0..=127));
+            ui.text_edit_singleline(&mut self.new_program_path)
+                .on_hover_text("The path of a saved patch file");
+            let path = self.new_program_path.trim();
+            if ui.add_enabled(!path.is_empty(), egui::Button::new("Assign")).clicked() {
+                self.settings.program_bank.set(self.new_program, path.into());
+                self.new_program_path.clear();
+            }
+        });
+    }
+
+    /// Releases every note of every synth, including those of the synths played through the MIDI output
+    /// The playing patch is reset directly if the command queue is full.
+    fn all_notes_off(&mut self) {
+        if self.playback_commands.try_send(PlaybackCommand::AllNotesOff).is_err()
+            && let Some(renderer) = self.renderer.as_ref()
+        {
+            renderer.all_notes_off();
+        }
+        if let Some(output) = self.midi_output.as_mut() {
+            for channel in 0..16 {
+                let _ = output.send(MidiMessage::ControlChange {
+                    channel,
+                    controller: midi::ALL_NOTES_OFF_CONTROLLER,
+                    value: 0
+                });
+            }
+        }
+    }
+
+    /// Connects to the MIDI output port with the given name, or disconnects if none
+    fn select_midi_output(&mut self, port: Option<String>) {
+        self.midi_output = None;
+        self.settings.midi_output = port.clone();
+
+        let Some(port) = port else {
+            return;
+        };
+        match MidiOutput::connect(&port) {
+            Ok(output) => self.midi_output = Some(output),
+            Err(err) => self.toasts.push(format!("Could not open MIDI output: {}", err)),
+        }
+    }
+
+    fn draw_midi_ui(&mut self, ui: &mut Ui) {
+        let current = self.midi_input.as_ref().map(|input| input.port_name().to_string());
+        let mut selected = current.clone();
+        ui.horizontal(|ui| {
+            ui.label("MIDI Input");
+            ComboBox::from_id_salt("midi input")
+                .selected_text(selected.clone().unwrap_or("None".to_string()))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut selected, None, "None");
+                    for port in &self.known_midi_inputs {
+                        ui.selectable_value(&mut selected, Some(port.clone()), port);
+                    }
+                });
+            if ui.button("Refresh").clicked() {
+                self.known_midi_inputs = midi::input_port_names();
+            }
+        });
+        if selected != current {
+            self.select_midi_input(selected);
+        }
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.settings.computer_keyboard, "Computer Keyboard Notes")
+                .on_hover_text("Play notes with the Z and Q rows. Minus/equals change octave, brackets change velocity.");
+            if self.settings.computer_keyboard {
+                ui.label(format!(
+                    "Octave {}, Velocity {}",
+                    self.computer_keyboard.octave(),
+                    self.computer_keyboard.velocity()
+                ));
+            }
+        });
+        self.computer_keyboard.enabled = self.settings.computer_keyboard;
+
+        let mut channel = self.settings.midi_channel;
+        let channel_text = |channel: Option<u8>| channel.map_or("All".to_string(), |channel| (channel + 1).to_string());
+        ui.horizontal(|ui| {
+            ui.label("Patch Channel");
+            ComboBox::from_id_salt("midi channel")
+                .selected_text(channel_text(channel))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut channel, None, channel_text(None));
+                    for value in 0..16 {
+                        ui.selectable_value(&mut channel, Some(value), channel_text(Some(value)));
+                    }
+                })
+                .response
+                .on_hover_text("The MIDI channel the playing patch receives notes from.");
+        });
+        if channel != self.settings.midi_channel {
+            self.settings.midi_channel = channel;
+            self.apply_midi_channel();
+        }
+
+        let mut mpe = self.settings.mpe;
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut mpe.enabled, "MPE")
+                .on_hover_text("Receive per-note pitch bend and pressure on channels 2-16.");
+            ui.add_enabled_ui(mpe.enabled, |ui| {
+                ui.label("Bend Range");
+                ui.add(egui::DragValue::new(&mut mpe.bend_range)
+                    .range(0.0..=MpeSettings::MAX_BEND_RANGE)
+                    .suffix(" st"));
+            });
+        });
+        if mpe != self.settings.mpe {
+            self.settings.mpe = mpe;
+            self.apply_mpe_settings();
+        }
+
+        let current = self.midi_output.as_ref().map(|output| output.port_name().to_string());
+        let mut selected = current.clone();
+        ui.horizontal(|ui| {
+            ui.label("MIDI Output");
+            ComboBox::from_id_salt("midi output")
+                .selected_text(selected.clone().unwrap_or("None".to_string()))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut selected, None, "None");
+                    for port in &self.known_midi_outputs {
+                        ui.selectable_value(&mut selected, Some(port.clone()), port);
+                    }
+                });
+            if ui.button("Refresh").clicked() {
+                self.known_midi_outputs = midi::output_port_names();
+            }
+        });
+        if selected != current {
+            self.select_midi_output(selected);
+        }
+    }
+
+    /// Switches to the given audio host and re-enumerates its devices
+    fn select_host(&mut self, id: HostId) {
+        match cpal::host_from_id(id) {
+            Ok(host) => {
+                self.host = host;
+                self.settings.host = Some(id.name().to_string());
+                self.refresh_devices();
+            }
+            Err(err) => eprintln!("could not open audio host '{}': {}", id.name(), err),
+        }
+    }
+
+    /// Finds the stream config for the device that best matches the saved preferences.
+    /// Falls back to the default config of the device if no supported config matches.
+    fn preferred_config(device: &Device, settings: &AppSettings) -> Option<SupportedStreamConfig> {
+        let default = device.default_output_config().ok();
+        let channels = settings.channels
+            .or(default.as_ref().map(|config| config.channels()));
+        let sample_rate = settings.sample_rate
+            .or(default.as_ref().map(|config| config.sample_rate().0));
+
+        let preferred = match (channels, sample_rate) {
+            (Some(channels), Some(sample_rate)) => device.supported_output_configs()
+                .ok()
+                .and_then(|mut configs| configs.find(|range| {
+                    range.channels() == channels
+                        && range.min_sample_rate().0 <= sample_rate
+                        && sample_rate <= range.max_sample_rate().0
+                }))
+                .map(|range| range.with_sample_rate(SampleRate(sample_rate))),
+            _ => None
+        };
+
+        preferred.or(default)
+    }
+
+    /// Saves the settings, reporting any failure
+    fn save_settings(&self) {
+        if let Err(err) = self.settings.save() {
+            eprintln!("could not save settings: {}", err);
+        }
+    }
+
+    /// Compiles the patch and starts playing it, entering playback mode on success
+    /// The stream error screen is shown if the stream can not be built.
     pub fn begin_playback(&mut self) {
+        let (Some(device), Some(config)) = (self.output_device.as_ref(), self.output_device_config.as_ref()) else {
+            self.toasts.push("No output device is available.");
+            self.mode = AppMode::Editor;
+            return;
+        };
         println!(
             "Starting playback on '{}' with sample format {}.",
-            self.output_device
-                .as_ref()
-                .expect("no output device")
-                .name()
-                .unwrap_or("N/A".to_string()),
-            self.output_device
-                .as_ref()
-                .unwrap()
-                .default_output_config()
-                .unwrap()
-                .sample_format()
+            device.name().unwrap_or("N/A".to_string()),
+            config.sample_format()
         );
+        let sample_rate = config.sample_rate();
 
-        let error_callback = |err| eprintln!("an error occurred on the output audio stream: {}", err);
-
-        let sample_rate = self.output_device_config
-            .as_ref()
-            .expect("no device config")
-            .sample_rate();
-        let sample_format = self.output_device_config
-            .as_ref()
-            .unwrap()
-            .sample_format();
+        // the patch may run at a lower rate than the device to save processing
+        let internal_rate = audio_config::internal_sample_rate(self.settings.internal_sample_rate, sample_rate.0);
 
         //setup backend data
         let build_backend_start = Instant::now();
-        let (backend_data, frontend_data) = self.patch_editor.playback_data(
+        let playback_data = self.patch_editor.playback_data(
+            &self.project_bundle,
+            internal_rate,
+            crate::constants::SAMPLE_MULTIPLIER,
+            &self.tuning
+        );
+        let (mut backend_data, frontend_data) = match playback_data {
+            Ok(playback_data) => playback_data,
+            Err(err) => {
+                self.toasts.push(format!("Could not compile the patch: {}", err));
+                self.mode = AppMode::Editor;
+                return;
+            }
+        };
+        self.meter = Some(MeterDisplay::new(backend_data.attach_meter(internal_rate)));
+        let mut renderer = PatchRenderer::new(
+            backend_data,
+            self.patch_synth,
+            internal_rate,
             sample_rate.0,
-            crate::constants::SAMPLE_MULTIPLIER
+            self.channel_map(),
+            self.settings.limiter
         );
+        self.reclaim_playback_commands();
+        renderer.set_commands(self.playback_command_receiver.take());
+        self.renderer = Some(SharedRenderer::new(renderer));
         let build_backend_end = Instant::now();
 
-        let config_copy = self.output_device_config.clone().unwrap();
-
         let build_stream_start = Instant::now();
-        let stream = backend_data.into_output_stream(
-            self.output_device.as_ref().unwrap(),
-            &config_copy.into(),
-            error_callback,
-            None,
-            sample_format,
-            sample_rate
-        ).expect("Audio stream could not be built.");
+        if let Err(err) = self.start_stream() {
+            self.circuit_uis = frontend_data;
+            self.enter_stream_error(format!("The audio stream could not be built: {}", err));
+            return;
+        }
         let build_stream_end = Instant::now();
 
         println!(
@@ -151,14 +647,383 @@ impl<'a> App<'a> {
             (build_stream_end - build_stream_start).as_secs_f64() * 1000.0,
         );
 
+        self.circuit_uis = frontend_data;
+        self.patch_parameters = Self::parameters_for(self.patch_editor.patch().inputs());
+        self.last_device_check = Instant::now();
+        self.mode = AppMode::Playback;
+        self.start_cue();
+    }
+
+    /// Sends the cue to its destination, if the user has chosen one
+    fn start_cue(&mut self) {
+        self.cue_stream = None;
+        let (Some(renderer), Some(config)) = (self.renderer.as_ref(), self.output_device_config.as_ref()) else {
+            return;
+        };
+
+        match self.settings.cue.clone() {
+            None => renderer.lock().set_cue(None),
+            Some(CueDestination::Channels(first)) => {
+                renderer.lock().set_cue(Some((self.cue_control.clone(), CueSink::Channels(first))));
+            }
+            Some(CueDestination::Device(name)) => {
+                // the cue device must run at the rate of the main device
+                let sample_rate = config.sample_rate();
+                let cue_device = self.known_output_devices
+                    .iter()
+                    .find(|device| device.name().ok().as_ref() == Some(&name));
+                let cue_config = cue_device.and_then(|device| device.supported_output_configs()
+                    .ok()?
+                    .find(|range| range.min_sample_rate() <= sample_rate && sample_rate <= range.max_sample_rate())
+                    .map(|range| range.with_sample_rate(sample_rate))
+                );
+                let (Some(cue_device), Some(cue_config)) = (cue_device, cue_config) else {
+                    renderer.lock().set_cue(None);
+                    self.toasts.push(format!("The cue device '{}' is unavailable at {} Hz.", name, sample_rate.0));
+                    return;
+                };
+
+                // allow up to 50 ms of drift between the devices
+                let (player, sink) = CuePlayer::new(sample_rate.0 as usize / 20);
+                let stream = audio_output::output_stream(
+                    player,
+                    cue_device,
+                    &audio_config::stream_config(&cue_config, self.settings.buffer_size),
+                    cue_config.sample_format(),
+                    |err| eprintln!("an error occurred on the cue audio stream: {}", err)
+                );
+
+                match stream {
+                    Ok(stream) => {
+                        let _ = stream.play();
+                        renderer.lock().set_cue(Some((self.cue_control.clone(), sink)));
+                        self.cue_stream = Some(stream);
+                    }
+                    Err(err) => {
+                        renderer.lock().set_cue(None);
+                        self.toasts.push(format!("Could not start the cue output on '{}': {}", name, err));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Builds and plays a stream for the current renderer on the current output device
+    fn start_stream(&mut self) -> Result<(), BuildStreamError> {
+        let (Some(renderer), Some(device), Some(config)) = (
+            self.renderer.as_ref(),
+            self.output_device.as_ref(),
+            self.output_device_config.as_ref()
+        ) else {
+            return Err(BuildStreamError::DeviceNotAvailable);
+        };
+
+        let stream_config = audio_config::stream_config(config, self.settings.buffer_size);
+        // errors are handled on the ui thread
+        let sender = self.stream_error_sender.clone();
+        let error_callback = move |err| {
+            let _ = sender.send(err);
+        };
+
+        let stream = renderer.output_stream(
+            device,
+            &stream_config,
+            config.sample_format(),
+            error_callback
+        )?;
         let _ = stream.play();
         self.stream = Some(stream);
-        self.circuit_uis = frontend_data;
+        Ok(())
+    }
+
+    /// Returns true if the output device is still present on the host
+    fn output_device_connected(&self) -> bool {
+        let Some(name) = self.output_device.as_ref().and_then(|device| device.name().ok()) else {
+            return false;
+        };
+        self.host.output_devices()
+            .map(|mut devices| devices.any(|device| device.name().ok().as_ref() == Some(&name)))
+            .unwrap_or(false)
+    }
+
+    /// Handles the errors reported by the stream during playback.
+    /// A lost output device is also detected by periodically checking the host's devices.
+    fn check_stream(&mut self) {
+        let mut lost = false;
+        let mut failure = None;
+        while let Ok(err) = self.stream_error_receiver.try_recv() {
+            match err {
+                StreamError::DeviceNotAvailable => lost = true,
+                err => failure = Some(err),
+            }
+        }
+
+        if !lost && self.last_device_check.elapsed() >= Self::DEVICE_CHECK_INTERVAL {
+            self.last_device_check = Instant::now();
+            lost = !self.output_device_connected();
+        }
+
+        if lost {
+            self.recover_from_lost_device();
+        } else if let Some(err) = failure {
+            self.enter_stream_error(format!("An error occurred on the output audio stream: {}", err));
+        }
+    }
+
+    /// Stops the stream, keeping the patch so that playback may be retried
+    fn enter_stream_error(&mut self, message: String) {
+        self.stream = None;
+        self.stream_error_message = Some(message);
+        self.mode = AppMode::StreamError;
+    }
+
+    /// Rebuilds the stream after an error, resuming the patch where it stopped
+    fn retry_stream(&mut self) {
+        while self.stream_error_receiver.try_recv().is_ok() {}
+
+        match self.start_stream() {
+            Ok(()) => {
+                self.stream_error_message = None;
+                self.last_device_check = Instant::now();
+                self.mode = AppMode::Playback;
+            }
+            Err(err) => {
+                self.stream_error_message = Some(format!("The audio stream could not be rebuilt: {}", err));
+            }
+        }
+    }
+
+    /// Starts recording the device channels to a new file
+    fn start_recording(&mut self) {
+        let (Some(renderer), Some(config)) = (self.renderer.as_ref(), self.output_device_config.as_ref()) else {
+            return;
+        };
+        let Some(path) = Recording::default_path() else {
+            self.toasts.push("Could not determine where to save the recording.");
+            return;
+        };
+
+        match Recording::start(&path, config.channels(), config.sample_rate().0) {
+            Ok((tap, recording)) => {
+                renderer.lock().set_recorder(Some(tap));
+                self.recording = Some(recording);
+            }
+            Err(err) => self.toasts.push(format!("Could not start recording: {}", err)),
+        }
+    }
+
+    /// Stops the current recording, waiting for it to be written
+    fn stop_recording(&mut self) {
+        let Some(recording) = self.recording.take() else {
+            return;
+        };
+
+        // dropping the tap lets the writer finish
+        if let Some(renderer) = self.renderer.as_ref() {
+            drop(renderer.lock().set_recorder(None));
+        }
+
+        let dropped = recording.dropped_frames();
+        match recording.finish() {
+            Ok(path) if dropped > 0 => self.toasts.push(format!(
+                "Saved recording to '{}' ({} frames were dropped).",
+                path.display(),
+                dropped
+            )),
+            Ok(path) => self.toasts.push(format!("Saved recording to '{}'.", path.display())),
+            Err(err) => self.toasts.push(format!("Could not save recording: {}", err)),
+        }
+    }
+
+    /// Moves playback to the host's default device, reusing the running patch.
+    /// The saved device preference is kept so it is chosen again once reconnected.
+    fn recover_from_lost_device(&mut self) {
+        // the new device may use a different format than the recording
+        self.stop_recording();
+
+        let lost_name = self.output_device
+            .as_ref()
+            .and_then(|device| device.name().ok())
+            .unwrap_or("[No Name]".to_string());
+        self.stream = None;
+
+        self.known_output_devices = self.host.output_devices()
+            .map(|devices| devices.collect())
+            .unwrap_or_default();
+        self.output_device = self.host.default_output_device();
+        self.output_device_config = self.output_device
+            .as_ref()
+            .and_then(|device| Self::preferred_config(device, &self.settings));
+
+        let (Some(device), Some(config), Some(renderer)) = (
+            self.output_device.as_ref(),
+            self.output_device_config.as_ref(),
+            self.renderer.as_ref()
+        ) else {
+            self.toasts.push(format!("'{}' was disconnected. No other output device is available, so playback was stopped.", lost_name));
+            self.end_playback();
+            self.mode = AppMode::Editor;
+            return;
+        };
+
+        let new_name = device.name().unwrap_or("[No Name]".to_string());
+        let sample_rate = config.sample_rate().0;
+        let channel_map = self.channel_map();
+        renderer.lock().set_output(sample_rate, channel_map);
+
+        match self.start_stream() {
+            Ok(()) => {
+                self.toasts.push(format!("'{}' was disconnected. Playback moved to '{}'.", lost_name, new_name));
+                self.start_cue();
+            }
+            Err(err) => self.enter_stream_error(format!(
+                "'{}' was disconnected and playback could not be moved to '{}': {}",
+                lost_name,
+                new_name,
+                err
+            )),
+        }
+    }
+
+    /// The channel map for the current device and patch
+    fn channel_map(&self) -> ChannelMap {
+        let device_channels = self.output_device_config
+            .as_ref()
+            .map_or(0, |config| config.channels() as usize);
+        match self.settings.channel_map.clone() {
+            Some(mut map) => {
+                map.resize(device_channels);
+                map
+            }
+            None => ChannelMap::default_for(device_channels, self.patch_editor.patch().outputs().len()),
+        }
+    }
+
+    fn draw_channel_ui(&mut self, ui: &mut Ui) {
+        let Some(device) = self.output_device.as_ref() else {
+            return;
+        };
+
+        // the channel counts the device supports
+        let mut channel_options: Vec<u16> = device.supported_output_configs()
+            .map(|configs| configs.map(|config| config.channels()).collect())
+            .unwrap_or_default();
+        channel_options.sort();
+        channel_options.dedup();
+
+        let current_channels = self.output_device_config.as_ref().map_or(0, |config| config.channels());
+        let mut channels = current_channels;
+        ui.horizontal(|ui| {
+            ui.label("Output Channels");
+            ComboBox::from_id_salt("output channels")
+                .selected_text(channels.to_string())
+                .show_ui(ui, |ui| {
+                    for option in channel_options {
+                        ui.selectable_value(&mut channels, option, option.to_string());
+                    }
+                });
+        });
+
+        if channels != current_channels {
+            self.settings.channels = Some(channels);
+            self.output_device_config = Self::preferred_config(device, &self.settings);
+        }
+
+        // map patch outputs to device channels
+        let output_count = self.patch_editor.patch().outputs().len();
+        let mut map = self.channel_map();
+        let mut changed = false;
+
+        ui.label("Channel Mapping");
+        for channel in 0..map.channels() {
+            let mut source = map.source(channel);
+            ui.horizontal(|ui| {
+                ui.label(format!("Channel {}", channel + 1));
+                let selected_text = source.map_or("Silent".to_string(), |source| source.to_string());
+                ComboBox::from_id_salt(("channel source", channel))
+                    .selected_text(selected_text)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut source, None, "Silent");
+                        for output in 0..output_count {
+                            for side in ChannelSide::ALL {
+                                let option = ChannelSource::new(output, side);
+                                ui.selectable_value(&mut source, Some(option), option.to_string());
+                            }
+                        }
+                    });
+            });
+            if source != map.source(channel) {
+                map.set_source(channel, source);
+                changed = true;
+            }
+        }
+
+        if changed {
+            self.settings.channel_map = Some(map);
+        }
+        if ui.button("Reset Mapping").clicked() {
+            self.settings.channel_map = None;
+        }
+    }
+
+    /// Takes the command queue back from the renderer, if it has one
+    fn reclaim_playback_commands(&mut self) {
+        if let Some(renderer) = self.renderer.as_ref()
+            && let Some(commands) = renderer.lock().set_commands(None)
+        {
+            self.playback_command_receiver = Some(commands);
+        }
+    }
+
+    /// the parameters shown for a patch with the given inputs, whose values start at their defaults
+    fn parameters_for(inputs: &[String]) -> Vec<(InputSpecification, f64)> {
+        PatchRenderer::input_specifications(inputs)
+            .into_iter()
+            .map(|spec| {
+                let value = spec.default;
+                (spec, value)
+            })
+            .collect()
+    }
+
+    /// Shows a slider for each input of the playing patch
+    /// Right-clicking a slider binds it to a MIDI controller.
+    fn draw_patch_parameters_ui(&mut self, ui: &mut Ui) {
+        if self.patch_parameters.is_empty() {
+            return;
+        }
+        ui.horizontal_wrapped(|ui| {
+            for (spec, value) in self.patch_parameters.iter_mut() {
+                let slider = egui::Slider::new(value, spec.range.0..=spec.range.1).text(&spec.short_name);
+                let response = ui.add(slider).on_hover_text(&spec.name);
+                if response.changed() {
+                    *value = spec.snap(*value);
+                    let _ = self.playback_commands.try_send(PlaybackCommand::SetInput {
+                        plugin: self.patch_synth,
+                        input: spec.id,
+                        value: *value
+                    });
+                }
+                let target = ParameterTarget { plugin: self.patch_synth, input: spec.id };
+                midi::parameter_context_menu(&response, &self.midi_routing, target, spec);
+            }
+        });
+        ui.separator();
     }
 
     pub fn end_playback(&mut self) {
+        self.stop_recording();
+        self.cue_stream = None;
         self.stream = None;
+        self.reclaim_playback_commands();
+        self.renderer = None;
+        self.meter = None;
         self.circuit_uis = Vec::new();
+        self.patch_parameters = Vec::new();
+        self.stream_error_message = None;
+
+        // discard errors from the stopped stream
+        while self.stream_error_receiver.try_recv().is_ok() {}
     }
 
     fn draw_io_configuration_ui(
@@ -169,6 +1034,22 @@ impl<'a> App<'a> {
         ui.add(Label::new(title).wrap());
         ui.separator();
 
+        let current_host = self.host.id();
+        let mut selected_host = current_host;
+        ui.horizontal(|ui| {
+            ui.label("Audio Host");
+            ComboBox::from_id_salt("audio host")
+                .selected_text(current_host.name())
+                .show_ui(ui, |ui| {
+                    for id in cpal::available_hosts() {
+                        ui.selectable_value(&mut selected_host, id, id.name());
+                    }
+                });
+        });
+        if selected_host != current_host {
+            self.select_host(selected_host);
+        }
+
         let mut new_select_index = None;
         ui.horizontal(|ui| {
             ui.label("Audio Output Device");
@@ -194,17 +1075,367 @@ impl<'a> App<'a> {
         });
 
         if let Some(selected) = new_select_index {
-            self.output_device = Some(self.known_output_devices[selected].clone());
+            let device = self.known_output_devices[selected].clone();
+            self.settings.output_device = device.name().ok();
+            self.output_device_config = Self::preferred_config(&device, &self.settings);
+            self.output_device = Some(device);
+        }
+
+        ui.separator();
+
+        self.draw_cue_destination_ui(ui);
+
+        ui.separator();
+
+        self.draw_midi_ui(ui);
+
+        ui.separator();
+
+        self.draw_program_bank_ui(ui);
+
+        ui.separator();
+
+        self.draw_buffer_size_ui(ui);
+
+        ui.separator();
+
+        self.draw_channel_ui(ui);
+
+        ui.separator();
+
+        let device_rate = self.output_device_config.as_ref().map(|config| config.sample_rate().0);
+        let mut internal_rate = self.settings.internal_sample_rate;
+        ui.horizontal(|ui| {
+            ui.label("Patch Sample Rate")
+                .on_hover_text("Rates above the device rate are not offered, and the device rate is used if the chosen rate is above it.");
+            let selected_text = match internal_rate {
+                Some(rate) => format!("{} Hz", rate),
+                None => "Device Rate".to_string(),
+            };
+            ComboBox::from_id_salt("internal sample rate")
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut internal_rate, None, "Device Rate");
+                    let options = audio_config::INTERNAL_SAMPLE_RATE_OPTIONS.into_iter()
+                        .filter(|rate| device_rate.is_none_or(|device_rate| *rate <= device_rate));
+                    for rate in options {
+                        ui.selectable_value(&mut internal_rate, Some(rate), format!("{} Hz", rate));
+                    }
+                });
+        });
+        self.settings.internal_sample_rate = internal_rate;
+
+        ui.separator();
+
+        ui.checkbox(&mut self.settings.limiter.enabled, "Safety Limiter")
+            .on_hover_text("Prevents the output from exceeding the threshold.");
+        ui.add_enabled_ui(self.settings.limiter.enabled, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Limiter Threshold");
+                ui.add(egui::Slider::new(
+                    &mut self.settings.limiter.threshold_db,
+                    LimiterSettings::MIN_THRESHOLD_DB..=LimiterSettings::MAX_THRESHOLD_DB
+                ).suffix(" dB"));
+            });
+        });
+
+        ui.separator();
+
+        self.draw_tuning_ui(ui);
+
+        ui.separator();
+
+        let mut theme = self.settings.theme;
+        ui.horizontal(|ui| {
+            ui.label("Theme");
+            ComboBox::from_id_salt("theme")
+                .selected_text(theme.display_string())
+                .show_ui(ui, |ui| {
+                    for option in Theme::ALL {
+                        ui.selectable_value(&mut theme, option, option.display_string());
+                    }
+                });
+        });
+
+        if theme != self.settings.theme {
+            self.settings.theme = theme;
+            ui.ctx().set_theme(theme.preference());
+        }
+
+        ui.separator();
+    }
+
+    fn draw_new_project_ui(&mut self, ui: &mut Ui) {
+        let title = RichText::new("New Project").text_style(TextStyle::Heading);
+        ui.add(Label::new(title).wrap());
+        ui.separator();
+
+        let mut chosen = None;
+        for template in PatchFile::TEMPLATES {
+            ui.horizontal(|ui| {
+                if ui.button(template.name).clicked() {
+                    chosen = Some(template.name);
+                }
+                ui.label(template.description);
+            });
+        }
+
+        if let Some(name) = chosen {
+            match Patch::from_template(name, self.builders) {
+                Ok(patch) => {
+                    self.patch_editor = PatchEditor::from_patch(self.builders, patch);
+                    self.project_bundle = ProjectBundle::default();
+                    self.project_path = None;
+                    if let Ok(mut routing) = self.midi_routing.lock() {
+                        routing.set_cc_map(CcMap::new());
+                    }
+                    self.draw_new_project_ui = false;
+                }
+                Err(err) => self.toasts.push(format!("Could not load template '{}': {}", name, err)),
+            }
         }
 
         ui.separator();
     }
 
+    fn draw_project_file_ui(&mut self, ui: &mut Ui, action: ProjectFileAction) {
+        let title = match action {
+            ProjectFileAction::Open => "Open Project",
+            ProjectFileAction::Save => "Save Project",
+        };
+        ui.add(Label::new(RichText::new(title).text_style(TextStyle::Heading)).wrap());
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.project_path_input)
+                .on_hover_text(format!("The path of a .{} file", ProjectBundle::EXTENSION));
+            let path = PathBuf::from(self.project_path_input.trim());
+            if ui.add_enabled(!path.as_os_str().is_empty(), egui::Button::new(title)).clicked() {
+                let done = match action {
+                    ProjectFileAction::Open => self.open_project(&path),
+                    ProjectFileAction::Save => self.save_project(path),
+                };
+                if done {
+                    self.draw_project_file_ui = None;
+                }
+            }
+        });
+
+        ui.separator();
+    }
+
+    /// Saves the patch being edited and every file its circuits use into a bundle
+    /// Files are taken from the bundle the project was opened from before being read from disk.
+    /// Returns whether the project was saved.
+    fn save_project(&mut self, mut path: PathBuf) -> bool {
+        if path.extension().is_none() {
+            path.set_extension(ProjectBundle::EXTENSION);
+        }
+
+        let patch = self.patch_editor.patch();
+        let bundle = patch.assets(self.builders)
+            .map_err(|err| err.to_string())
+            .and_then(|assets| {
+                self.project_bundle
+                    .repack(patch.to_file().to_string().into_bytes(), &assets)
+                    .map_err(|err| err.to_string())
+            });
+        let result = bundle.and_then(|mut bundle| {
+            if let Ok(routing) = self.midi_routing.lock() {
+                bundle.set_midi_mappings(routing.cc_map().to_string());
+            }
+            bundle.save(&path).map_err(|err| err.to_string())?;
+            Ok(bundle)
+        });
+
+        match result {
+            Ok(bundle) => {
+                self.project_bundle = bundle;
+                self.toasts.push(format!("Saved {}", path.display()));
+                if let Err(err) = self.patch_editor.save_history(&path) {
+                    self.toasts.push(format!("Could not save the edit history: {}", err));
+                }
+                self.project_path = Some(path);
+                true
+            }
+            Err(err) => {
+                self.toasts.push(format!("Could not save {}: {}", path.display(), err));
+                false
+            }
+        }
+    }
+
+    /// Opens a project saved by save_project, loading circuit files from the bundle
+    /// Returns whether the project was opened.
+    fn open_project(&mut self, path: &Path) -> bool {
+        let result = ProjectBundle::load(path)
+            .map_err(|err| err.to_string())
+            .and_then(|bundle| {
+                let file = String::from_utf8_lossy(bundle.patch())
+                    .parse::<PatchFile>()
+                    .map_err(|err| err.to_string())?;
+                let mut patch = Patch::from_file(&file, self.builders).map_err(|err| err.to_string())?;
+                patch.resolve_assets(&bundle);
+                Ok((bundle, file, patch))
+            });
+
+        match result {
+            Ok((bundle, file, mut patch)) => {
+                // the project is still usable without its edit history
+                if let Err(err) = patch.load_history(path, &file) {
+                    self.toasts.push(format!("Could not load the edit history: {}", err));
+                }
+                // mappings are bound to the inputs of the patch, as those are the parameters of the patch synth
+                let specs = PatchRenderer::input_specifications(patch.inputs());
+                let synth = self.patch_synth;
+                let cc_map = CcMap::load(bundle.midi_mappings(), |target| {
+                    specs.get(target.input as usize).filter(|_| target.plugin == synth).cloned()
+                });
+                if let Ok(mut routing) = self.midi_routing.lock() {
+                    routing.set_cc_map(cc_map);
+                }
+
+                self.patch_editor = PatchEditor::from_patch(self.builders, patch);
+                self.project_bundle = bundle;
+                self.project_path = Some(path.to_path_buf());
+                true
+            }
+            Err(err) => {
+                self.toasts.push(format!("Could not open {}: {}", path.display(), err));
+                false
+            }
+        }
+    }
+
+    fn draw_cue_destination_ui(&mut self, ui: &mut Ui) {
+        let device_channels = self.output_device_config.as_ref().map_or(0, |config| config.channels() as usize);
+        let current_name = self.output_device.as_ref().and_then(|device| device.name().ok());
+        let cue_text = |cue: &Option<CueDestination>| match cue {
+            None => "None".to_string(),
+            Some(CueDestination::Channels(first)) => format!("Channels {}-{}", first + 1, first + 2),
+            Some(CueDestination::Device(name)) => name.clone(),
+        };
+
+        let mut cue = self.settings.cue.clone();
+        ui.horizontal(|ui| {
+            ui.label("Cue Output");
+            ComboBox::from_id_salt("cue output")
+                .selected_text(cue_text(&cue))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut cue, None, "None");
+                    for first in (0..device_channels.saturating_sub(1)).step_by(2) {
+                        let option = Some(CueDestination::Channels(first));
+                        let text = cue_text(&option);
+                        ui.selectable_value(&mut cue, option, text);
+                    }
+                    for device in &self.known_output_devices {
+                        let Ok(name) = device.name() else {
+                            continue;
+                        };
+                        if Some(&name) != current_name.as_ref() {
+                            ui.selectable_value(&mut cue, Some(CueDestination::Device(name.clone())), name);
+                        }
+                    }
+                });
+        });
+        self.settings.cue = cue;
+    }
+
+    fn draw_cue_ui(&mut self, ui: &mut Ui) {
+        if self.settings.cue.is_none() {
+            return;
+        }
+
+        let output_count = self.patch_editor.patch().outputs().len();
+        let mut output = self.cue_control.output();
+        ComboBox::from_id_salt("cue source")
+            .selected_text(output.map_or("Cue Off".to_string(), |output| format!("Cue Out {}", output + 1)))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut output, None, "Cue Off");
+                for option in 0..output_count {
+                    ui.selectable_value(&mut output, Some(option), format!("Cue Out {}", option + 1));
+                }
+            });
+        self.cue_control.set_output(output);
+
+        let mut gain_db = 20.0 * self.cue_control.gain().max(1e-6).log10();
+        let response = ui.add(egui::Slider::new(&mut gain_db, -48.0..=6.0).suffix(" dB"))
+            .on_hover_text("Cue Gain");
+        if response.changed() {
+            self.cue_control.set_gain(10f32.powf(gain_db / 20.0));
+        }
+    }
+
+    fn draw_buffer_size_ui(&mut self, ui: &mut Ui) {
+        let Some(config) = self.output_device_config.as_ref() else {
+            return;
+        };
+
+        let supported = audio_config::supported_buffer_sizes(config);
+
+        // forget a preference the current device cannot honor
+        if let Some(frames) = self.settings.buffer_size {
+            if !audio_config::is_buffer_size_supported(config, frames) {
+                self.settings.buffer_size = None;
+            }
+        }
+
+        let mut buffer_size = self.settings.buffer_size;
+        ui.horizontal(|ui| {
+            ui.label("Buffer Size");
+            let selected_text = match buffer_size {
+                Some(frames) => format!("{} frames", frames),
+                None => "Device Default".to_string(),
+            };
+            ComboBox::from_id_salt("buffer size")
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut buffer_size, None, "Device Default");
+                    for frames in supported {
+                        ui.selectable_value(&mut buffer_size, Some(frames), format!("{} frames", frames));
+                    }
+                });
+        });
+        self.settings.buffer_size = buffer_size;
+
+        let sample_rate = config.sample_rate().0;
+        match buffer_size {
+            Some(frames) => {
+                ui.label(format!(
+                    "Buffer latency: {:.1} ms (round trip ~{:.1} ms)",
+                    audio_config::buffer_latency_ms(frames, sample_rate),
+                    audio_config::round_trip_latency_ms(frames, sample_rate)
+                ));
+            }
+            None => {
+                ui.label("Latency is determined by the device.");
+            }
+        }
+    }
+
     fn draw_editor_mode(&mut self, ctx: &Context) {
         TopBottomPanel::top("top_panel").show(ctx, |ui| {
             MenuBar::new().ui(ui, |ui| {
+                if ui.button("New").clicked() {
+                    self.draw_new_project_ui = true;
+                }
+
+                if ui.button("Open").clicked() {
+                    self.draw_project_file_ui = Some(ProjectFileAction::Open);
+                }
+
+                if ui.button("Save").clicked() {
+                    self.project_path_input = self.project_path
+                        .as_ref()
+                        .map(|path| path.display().to_string())
+                        .unwrap_or_default();
+                    self.draw_project_file_ui = Some(ProjectFileAction::Save);
+                }
+
                 if ui.button("Settings").clicked() {
                     self.draw_settings_ui = true;
+                    self.known_midi_inputs = midi::input_port_names();
+                    self.known_midi_outputs = midi::output_port_names();
                 }
 
                 if ui.button("Quit").clicked() {
@@ -231,6 +1462,31 @@ impl<'a> App<'a> {
                     ui.vertical_centered(|ui| {
                         if ui.button("Close").clicked() {
                             self.draw_settings_ui = false;
+                            self.save_settings();
+                        }
+                    })
+                });
+        }
+
+        if self.draw_new_project_ui {
+            Modal::new(Id::new("new_project"))
+                .show(ctx, |ui| {
+                    self.draw_new_project_ui(ui);
+                    ui.vertical_centered(|ui| {
+                        if ui.button("Cancel").clicked() {
+                            self.draw_new_project_ui = false;
+                        }
+                    })
+                });
+        }
+
+        if let Some(action) = self.draw_project_file_ui {
+            Modal::new(Id::new("project_file"))
+                .show(ctx, |ui| {
+                    self.draw_project_file_ui(ui, action);
+                    ui.vertical_centered(|ui| {
+                        if ui.button("Cancel").clicked() {
+                            self.draw_project_file_ui = None;
                         }
                     })
                 });
@@ -249,17 +1505,41 @@ impl<'a> App<'a> {
                 if ui.button("Quit").clicked() {
                     ctx.send_viewport_cmd(ViewportCommand::Close);
                 }
+                if ui.button("Panic").on_hover_text("Release all notes").clicked() {
+                    self.all_notes_off();
+                }
                 ui.add_space(16.0);
 
                 egui::warn_if_debug_build(ui);
 
-                //add stop button to far right edge
+                //add stop button and output meter to far right edge
                 ui.with_layout(egui::Layout::right_to_left(Align::Max),
                     |ui| {
                         if ui.button("Stop").clicked() {
                             self.end_playback();
                             self.mode = AppMode::EndPlayback;
                         }
+                        if let Some(meter) = self.meter.as_mut() {
+                            meter.show(ui);
+                        }
+
+                        self.draw_cue_ui(ui);
+
+                        let mut recording = self.recording.is_some();
+                        if ui.toggle_value(&mut recording, "Record").changed() {
+                            if recording {
+                                self.start_recording();
+                            } else {
+                                self.stop_recording();
+                            }
+                        }
+                        if let Some(recording) = self.recording.as_ref() {
+                            let seconds = recording.elapsed().as_secs();
+                            ui.colored_label(
+                                egui::Color32::RED,
+                                format!("REC {}:{:02}", seconds / 60, seconds % 60)
+                            );
+                        }
                     }
                 );
             });
@@ -268,6 +1548,7 @@ impl<'a> App<'a> {
         // todo this is a temporary solution
         CentralPanel::default()
             .show(ctx, |ui| {
+                self.draw_patch_parameters_ui(ui);
                 ui.with_layout(ui.layout().with_main_wrap(true), |ui| {
                     for circuit_ui in self.circuit_uis.iter_mut() {
                         circuit_ui.show(ui)
@@ -276,35 +1557,91 @@ impl<'a> App<'a> {
             });
 
     }
+
+    fn draw_stream_error_mode(&mut self, ctx: &Context) {
+        // keep the playback view visible behind the error
+        self.draw_playback_mode(ctx);
+        if self.mode != AppMode::StreamError {
+            return;
+        }
+
+        Modal::new(Id::new("stream_error"))
+            .show(ctx, |ui| {
+                let title = RichText::new("Playback Stopped").text_style(TextStyle::Heading);
+                ui.add(Label::new(title).wrap());
+                ui.separator();
+
+                if let Some(message) = &self.stream_error_message {
+                    ui.add(Label::new(message.as_str()).wrap());
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Retry").clicked() {
+                        self.retry_stream();
+                    }
+                    if ui.button("Stop").clicked() {
+                        self.end_playback();
+                        self.mode = AppMode::EndPlayback;
+                    }
+                });
+            });
+    }
 }
 
 impl eframe::App for App<'_> {
     /// Called each time the UI needs repainting, which may be many times per second.
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
+        // track the window size so it may be restored on the next run
+        if let Some(rect) = ctx.input(|i| i.viewport().inner_rect) {
+            self.settings.window_size = Some([rect.width(), rect.height()]);
+        }
+
         // handle transition states
         if self.mode == AppMode::StartPlayback {
             self.begin_playback();
-            self.mode = AppMode::Playback;
         } else if self.mode == AppMode::EndPlayback {
             self.end_playback();
             self.mode = AppMode::Editor;
         }
 
+        self.computer_keyboard.handle_input(ctx, &self.midi_routing, &self.playback_commands);
+
+        // notes played while nothing is playing are discarded rather than sounding once playback starts
+        if let Some(commands) = self.playback_command_receiver.as_ref() {
+            while commands.try_recv().is_ok() {}
+        }
+
+        // programs only switch while playing; changes received in the editor are discarded
+        while let Ok(program) = self.program_changes.try_recv() {
+            self.select_program(program);
+        }
+
+        if self.mode == AppMode::Playback {
+            self.check_stream();
+            ctx.request_repaint_after(Self::DEVICE_CHECK_INTERVAL);
+        }
+
         // run main states
         match self.mode {
             AppMode::Editor => self.draw_editor_mode(ctx),
             AppMode::Playback => self.draw_playback_mode(ctx),
+            AppMode::StreamError => self.draw_stream_error_mode(ctx),
             _ => unreachable!()
         }
+
+        self.toasts.show(ctx);
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.save_settings();
     }
 }
 
 // Todo:
 // - See connection_builder, write specificationwrapper class to handle special cases
-// - Add ability to select audio host
-// - Add error handling for devices being unavailable.
 // - Add ability to modify stream configuration
-// - Add ability to save/load states
+// - Add ability to save/load states (app settings are persisted, patches are not)
 // - Add ability to select/configure audio device before starting playback
 // - Add mouse coordinates, zoom to editor
 // - Clean up inspector ui
@@ -0,0 +1,255 @@
+use std::{fmt::Display, str::FromStr};
+
+use cpal::{BufferSize, StreamConfig, SupportedBufferSize, SupportedStreamConfig};
+
+use crate::frame::{self, Frame};
+
+/// The buffer sizes (in frames) offered to the user
+pub const BUFFER_SIZE_OPTIONS: [u32; 8] = [32, 64, 128, 256, 512, 1024, 2048, 4096];
+
+/// The sample rates a patch may be processed at, independent of the device
+/// Rates above the device rate are not used, since the resampler does not filter aliasing.
+pub const INTERNAL_SAMPLE_RATE_OPTIONS: [u32; 7] = [22050, 24000, 32000, 44100, 48000, 88200, 96000];
+
+/// Returns the rate a patch is processed at on a device
+/// The preferred rate is used if it is an option no higher than the device rate, otherwise the device rate is.
+pub fn internal_sample_rate(preferred: Option<u32>, device_rate: u32) -> u32 {
+    preferred
+        .filter(|rate| INTERNAL_SAMPLE_RATE_OPTIONS.contains(rate) && *rate <= device_rate)
+        .unwrap_or(device_rate)
+}
+
+/// Returns the buffer sizes from BUFFER_SIZE_OPTIONS that the config supports
+/// If the device does not report its supported range, every option is returned.
+pub fn supported_buffer_sizes(config: &SupportedStreamConfig) -> Vec<u32> {
+    BUFFER_SIZE_OPTIONS.into_iter()
+        .filter(|size| is_buffer_size_supported(config, *size))
+        .collect()
+}
+
+/// Returns true if the config supports buffers of the given size
+pub fn is_buffer_size_supported(config: &SupportedStreamConfig, frames: u32) -> bool {
+    match config.buffer_size() {
+        SupportedBufferSize::Range { min, max } => *min <= frames && frames <= *max,
+        SupportedBufferSize::Unknown => frames > 0,
+    }
+}
+
+/// Creates the config used to build a stream
+/// The buffer size is only fixed if it is supported, otherwise the device default is used.
+pub fn stream_config(config: &SupportedStreamConfig, buffer_size: Option<u32>) -> StreamConfig {
+    let mut stream_config: StreamConfig = config.clone().into();
+    stream_config.buffer_size = match buffer_size {
+        Some(frames) if is_buffer_size_supported(config, frames) => BufferSize::Fixed(frames),
+        _ => BufferSize::Default,
+    };
+    stream_config
+}
+
+/// The time in milliseconds it takes to play one buffer
+pub fn buffer_latency_ms(frames: u32, sample_rate: u32) -> f64 {
+    frames as f64 / sample_rate as f64 * 1000.0
+}
+
+/// An estimate of the round-trip latency in milliseconds, assuming one buffer of delay
+/// for input and one for output. Driver and hardware latency is not included.
+pub fn round_trip_latency_ms(frames: u32, sample_rate: u32) -> f64 {
+    2.0 * buffer_latency_ms(frames, sample_rate)
+}
+
+/// Which part of a stereo patch output is sent to a device channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelSide {
+    Left,
+    Right,
+    Mix,
+}
+
+impl ChannelSide {
+    const LEFT_TEXT: &'static str = "L";
+    const RIGHT_TEXT: &'static str = "R";
+    const MIX_TEXT: &'static str = "Mix";
+
+    pub const ALL: [Self; 3] = [Self::Left, Self::Right, Self::Mix];
+
+    pub fn display_string(&self) -> &'static str {
+        match self {
+            Self::Left => Self::LEFT_TEXT,
+            Self::Right => Self::RIGHT_TEXT,
+            Self::Mix => Self::MIX_TEXT,
+        }
+    }
+
+    /// gets the value of this side of the frame
+    pub fn sample(&self, frame: Frame) -> f32 {
+        match self {
+            Self::Left => frame[0],
+            Self::Right => frame[1],
+            Self::Mix => frame::to_mono(frame),
+        }
+    }
+}
+
+/// The patch output played on a device channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelSource {
+    /// the index of the patch's special output
+    pub output: usize,
+    pub side: ChannelSide,
+}
+
+impl ChannelSource {
+    pub fn new(output: usize, side: ChannelSide) -> Self {
+        Self {
+            output,
+            side
+        }
+    }
+}
+
+impl Display for ChannelSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Out {} {}", self.output + 1, self.side.display_string())
+    }
+}
+
+/// Maps the outputs of a patch to the channels of a device
+/// Channels without a source are silent.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ChannelMap {
+    sources: Vec<Option<ChannelSource>>,
+}
+
+impl ChannelMap {
+    /// Creates the default map for a device
+    /// A single output is played in stereo on the first two channels (mixed on mono devices).
+    /// Otherwise, each output is mixed down and played on the channel of the same index.
+    pub fn default_for(device_channels: usize, output_count: usize) -> Self {
+        let sources = (0..device_channels)
+            .map(|channel| match (output_count, device_channels) {
+                (0, _) => None,
+                (1, 1) => Some(ChannelSource::new(0, ChannelSide::Mix)),
+                (1, _) => match channel {
+                    0 => Some(ChannelSource::new(0, ChannelSide::Left)),
+                    1 => Some(ChannelSource::new(0, ChannelSide::Right)),
+                    _ => None,
+                },
+                _ => (channel < output_count).then(|| ChannelSource::new(channel, ChannelSide::Mix)),
+            })
+            .collect();
+
+        Self {
+            sources
+        }
+    }
+
+    /// the number of device channels in the map
+    pub fn channels(&self) -> usize {
+        self.sources.len()
+    }
+
+    pub fn source(&self, channel: usize) -> Option<ChannelSource> {
+        self.sources.get(channel).copied().flatten()
+    }
+
+    pub fn set_source(&mut self, channel: usize, source: Option<ChannelSource>) {
+        if let Some(slot) = self.sources.get_mut(channel) {
+            *slot = source;
+        }
+    }
+
+    /// changes the number of device channels, silencing any new channels
+    pub fn resize(&mut self, device_channels: usize) {
+        self.sources.resize(device_channels, None);
+    }
+
+    /// gets the value for a device channel from the patch's outputs
+    pub fn sample(&self, channel: usize, outputs: &[Frame]) -> f32 {
+        self.source(channel)
+            .and_then(|source| outputs.get(source.output).map(|frame| source.side.sample(*frame)))
+            .unwrap_or(0.0)
+    }
+}
+
+/// Written as a comma separated list with one entry per channel: '-' for silence,
+/// otherwise the output index followed by L, R or M. (e.g. '0L,0R,1M,-')
+impl Display for ChannelMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (channel, source) in self.sources.iter().enumerate() {
+            if channel > 0 {
+                write!(f, ",")?;
+            }
+            match source {
+                None => write!(f, "-")?,
+                Some(source) => {
+                    let side = match source.side {
+                        ChannelSide::Left => 'L',
+                        ChannelSide::Right => 'R',
+                        ChannelSide::Mix => 'M',
+                    };
+                    write!(f, "{}{}", source.output, side)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ChannelMap {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let sources = s.split(',')
+            .map(|entry| {
+                let entry = entry.trim();
+                if entry == "-" {
+                    return Ok(None);
+                }
+                let side = match entry.chars().last() {
+                    Some('L') => ChannelSide::Left,
+                    Some('R') => ChannelSide::Right,
+                    Some('M') => ChannelSide::Mix,
+                    _ => return Err(()),
+                };
+                let output = entry[..entry.len() - 1].parse().map_err(|_| ())?;
+                Ok(Some(ChannelSource::new(output, side)))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            sources
+        })
+    }
+}
+
+/// Where the cue (monitor) output is played
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CueDestination {
+    /// a stereo pair of channels on the main device, starting at the given channel
+    Channels(usize),
+
+    /// a second output device with the given name
+    Device(String),
+}
+
+/// Written as 'channels N' or 'device NAME'
+impl Display for CueDestination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Channels(first) => write!(f, "channels {}", first),
+            Self::Device(name) => write!(f, "device {}", name),
+        }
+    }
+}
+
+impl FromStr for CueDestination {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().split_once(' ') {
+            Some(("channels", first)) => first.trim().parse().map(Self::Channels).map_err(|_| ()),
+            Some(("device", name)) if !name.trim().is_empty() => Ok(Self::Device(name.trim().to_string())),
+            _ => Err(()),
+        }
+    }
+}
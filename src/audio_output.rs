@@ -0,0 +1,507 @@
+use std::sync::{atomic::{AtomicU32, AtomicUsize, Ordering}, mpsc::Receiver, Arc, Mutex, MutexGuard};
+
+use rtrb::{Consumer, Producer, RingBuffer};
+use cpal::{traits::DeviceTrait, BuildStreamError, Device, FromSample, OutputCallbackInfo, SampleFormat, SizedSample, Stream, StreamConfig, StreamError};
+
+use crate::{audio_config::ChannelMap, compiled_patch::CompiledPatch, frame::{self, Frame}, limiter::{Limiter, LimiterSettings}, live_plugin_id::LivePluginId, playback::{InputSpecification, NoteEvent, NotePedals, PlaybackCommand}, recorder::RecordingTap, sequencers::event_scheduler::ScheduledEvents};
+
+/// Converts frames produced at one sample rate to another using linear interpolation
+/// No low-pass filter is applied, so converting to a lower rate aliases.
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    /// the number of source frames advanced per output frame
+    step: f64,
+
+    /// the position of the output between the previous and current source frames [0, 1)
+    phase: f64,
+
+    previous: Vec<f32>,
+    current: Vec<f32>,
+    primed: bool,
+}
+
+impl Resampler {
+    /// creates a resampler converting from source_rate to target_rate
+    pub fn new(source_rate: u32, target_rate: u32, channels: usize) -> Self {
+        Self {
+            step: source_rate as f64 / target_rate as f64,
+            phase: 0.0,
+            previous: vec![0.0; channels],
+            current: vec![0.0; channels],
+            primed: false
+        }
+    }
+
+    /// returns true if the rates are equal, in which case frames are passed through unchanged
+    pub fn is_passthrough(&self) -> bool {
+        self.step == 1.0
+    }
+
+    /// writes the next output frame to out, requesting source frames as needed
+    pub fn next_frame(&mut self, out: &mut [f32], mut source: impl FnMut(&mut [f32])) {
+        if !self.primed {
+            source(&mut self.current);
+            self.previous.copy_from_slice(&self.current);
+            self.primed = true;
+        }
+
+        if self.is_passthrough() {
+            out.copy_from_slice(&self.current);
+            source(&mut self.current);
+            return;
+        }
+
+        let t = self.phase as f32;
+        for ((out, previous), current) in out.iter_mut().zip(&self.previous).zip(&self.current) {
+            *out = previous + (current - previous) * t;
+        }
+
+        self.phase += self.step;
+        while self.phase >= 1.0 {
+            self.phase -= 1.0;
+            self.previous.copy_from_slice(&self.current);
+            source(&mut self.current);
+        }
+    }
+}
+
+/// The cue settings that may be changed while playing
+/// Shared between the ui and the audio thread without locking.
+#[derive(Debug, Clone)]
+pub struct CueControl {
+    /// the patch output sent to the cue, or usize::MAX for none
+    output: Arc<AtomicUsize>,
+
+    /// the gain applied to the cue, stored as the bits of an f32
+    gain: Arc<AtomicU32>,
+}
+
+impl CueControl {
+    pub fn new() -> Self {
+        Self {
+            output: Arc::new(AtomicUsize::new(usize::MAX)),
+            gain: Arc::new(AtomicU32::new(1.0f32.to_bits())),
+        }
+    }
+
+    /// the patch output sent to the cue
+    pub fn output(&self) -> Option<usize> {
+        let output = self.output.load(Ordering::Relaxed);
+        (output != usize::MAX).then_some(output)
+    }
+
+    pub fn set_output(&self, output: Option<usize>) {
+        self.output.store(output.unwrap_or(usize::MAX), Ordering::Relaxed);
+    }
+
+    pub fn gain(&self) -> f32 {
+        f32::from_bits(self.gain.load(Ordering::Relaxed))
+    }
+
+    pub fn set_gain(&self, gain: f32) {
+        self.gain.store(gain.to_bits(), Ordering::Relaxed);
+    }
+
+    /// gets the cue frame from the patch outputs
+    /// the cue is not limited, so it is clamped to full scale instead
+    fn sample(&self, outputs: &[Frame]) -> Frame {
+        let Some(frame) = self.output().and_then(|output| outputs.get(output)) else {
+            return frame::SILENCE;
+        };
+        let gain = self.gain();
+        frame.map(|value| if value.is_finite() { (value * gain).clamp(-1.0, 1.0) } else { 0.0 })
+    }
+}
+
+impl Default for CueControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Where the renderer sends the cue
+#[derive(Debug)]
+pub enum CueSink {
+    /// replaces a stereo pair of channels of the main device, starting at the given channel
+    Channels(usize),
+
+    /// sends frames to a CuePlayer playing on another device
+    Device(Producer<Frame>),
+}
+
+/// Plays the cue sent by a renderer on a second device
+/// The devices are not synchronized, so frames are dropped or silence is played
+/// when one runs faster than the other.
+#[derive(Debug)]
+pub struct CuePlayer {
+    consumer: Consumer<Frame>,
+}
+
+impl CuePlayer {
+    /// creates a player and the sink the renderer sends to
+    /// capacity is the most frames of latency between the devices
+    pub fn new(capacity: usize) -> (Self, CueSink) {
+        let (producer, consumer) = RingBuffer::new(capacity.max(1));
+        (Self { consumer }, CueSink::Device(producer))
+    }
+}
+
+impl OutputSource for CuePlayer {
+    fn fill_output<T: SizedSample + FromSample<f32>>(&mut self, data: &mut [T], channels: usize) {
+        for device_frame in data.chunks_mut(channels) {
+            let cue = self.consumer.pop().unwrap_or(frame::SILENCE);
+            for (channel, sample) in device_frame.iter_mut().enumerate() {
+                let value = match (channels, channel) {
+                    (1, _) => frame::to_mono(cue),
+                    (_, 0 | 1) => cue[channel],
+                    _ => 0.0,
+                };
+                *sample = T::from_sample(value);
+            }
+        }
+    }
+}
+
+/// Drives a compiled patch from an output stream
+/// The patch may run at a different sample rate than the device; its output is resampled.
+pub struct PatchRenderer {
+    patch: CompiledPatch,
+    resampler: Resampler,
+    inputs: Vec<Frame>,
+    frame: Vec<Frame>,
+
+    /// the patch output played on each device channel
+    channel_map: ChannelMap,
+
+    /// protects the device from excessive levels, applied after the sample multiplier
+    limiter: Limiter,
+
+    /// receives the device channels while recording
+    recorder: Option<RecordingTap>,
+
+    /// the cue output and where it is sent
+    cue: Option<(CueControl, CueSink)>,
+
+    /// the events of a sequencer, sent to the patch on the samples they are scheduled for
+    events: Option<ScheduledEvents>,
+
+    /// the queue of commands sent by MIDI and other note sources, drained once per buffer
+    commands: Option<Receiver<PlaybackCommand>>,
+
+    /// the id note commands address the patch by, commands for other synths are dropped
+    synth: LivePluginId,
+
+    /// the pedals held on the patch
+    pedals: NotePedals,
+
+    /// the sample rate the patch was compiled with
+    internal_rate: u32,
+
+    /// the sample rate of the device
+    device_rate: u32,
+
+    /// the time between samples of the patch
+    delta: f32,
+}
+
+impl PatchRenderer {
+    /// patch must have been compiled with internal_rate as its sample rate
+    /// synth is the id note commands address the patch by
+    pub fn new(
+        patch: CompiledPatch,
+        synth: LivePluginId,
+        internal_rate: u32,
+        device_rate: u32,
+        channel_map: ChannelMap,
+        limiter: LimiterSettings
+    ) -> Self {
+        let input_count = patch.input_count;
+        let output_count = patch.output_count;
+        Self {
+            patch,
+            resampler: Resampler::new(internal_rate, device_rate, output_count * frame::CHANNELS),
+            inputs: vec![frame::SILENCE; input_count],
+            frame: vec![frame::SILENCE; output_count],
+            channel_map,
+            limiter: Limiter::new(limiter, device_rate),
+            recorder: None,
+            cue: None,
+            events: None,
+            commands: None,
+            synth,
+            pedals: NotePedals::new(),
+            internal_rate,
+            device_rate,
+            delta: (1.0 / internal_rate as f64) as f32
+        }
+    }
+
+    /// prepares the renderer for a different device, keeping the state of the patch
+    pub fn set_output(&mut self, device_rate: u32, channel_map: ChannelMap) {
+        self.resampler = Resampler::new(
+            self.internal_rate,
+            device_rate,
+            self.patch.output_count * frame::CHANNELS
+        );
+        self.channel_map = channel_map;
+        self.device_rate = device_rate;
+        self.limiter.set_sample_rate(device_rate);
+    }
+
+    /// the sample rate patches played by this renderer must be compiled with
+    pub fn internal_rate(&self) -> u32 {
+        self.internal_rate
+    }
+
+    /// replaces the patch without stopping the stream, returning the previous patch
+    /// patch must have been compiled with the internal rate
+    pub fn set_patch(&mut self, patch: CompiledPatch) -> CompiledPatch {
+        if patch.output_count != self.patch.output_count {
+            self.resampler = Resampler::new(
+                self.internal_rate,
+                self.device_rate,
+                patch.output_count * frame::CHANNELS
+            );
+            self.frame = vec![frame::SILENCE; patch.output_count];
+        }
+        self.inputs = vec![frame::SILENCE; patch.input_count];
+        std::mem::replace(&mut self.patch, patch)
+    }
+
+    /// the parameters of the patch's input circuits, set as inputs of the synth that addresses the patch
+    /// each input is given the same value on both channels
+    pub fn input_specifications(names: &[String]) -> Vec<InputSpecification> {
+        names.iter()
+            .enumerate()
+            .map(|(index, name)| InputSpecification {
+                id: index as u32,
+                name: name.clone(),
+                short_name: name.clone(),
+                is_note_input: false,
+                range: (0.0, 1.0),
+                input_values: 0,
+                default: 0.0
+            })
+            .collect()
+    }
+
+    /// sends a note event to the circuits of the current patch
+    pub fn send_note(&mut self, event: NoteEvent) {
+        self.patch.send_note(event);
+    }
+
+    pub fn limiter(&self) -> &Limiter {
+        &self.limiter
+    }
+
+    pub fn set_limiter(&mut self, settings: LimiterSettings) {
+        self.limiter.set_settings(settings);
+    }
+
+    /// starts sending the device channels to a recording, or stops if none
+    /// returns the previous tap, which ends its recording when dropped
+    pub fn set_recorder(&mut self, recorder: Option<RecordingTap>) -> Option<RecordingTap> {
+        std::mem::replace(&mut self.recorder, recorder)
+    }
+
+    /// sends a patch output to a cue, or stops cueing if none
+    pub fn set_cue(&mut self, cue: Option<(CueControl, CueSink)>) {
+        self.cue = cue;
+    }
+
+    /// starts sending the events of a sequencer to the patch, or stops if none
+    /// the events must be scheduled at the internal rate
+    /// returns the previous events
+    pub fn set_events(&mut self, events: Option<ScheduledEvents>) -> Option<ScheduledEvents> {
+        std::mem::replace(&mut self.events, events)
+    }
+
+    /// releases every note of the patch and lifts its pedals
+    pub fn all_notes_off(&mut self) {
+        self.patch.all_notes_off();
+        self.pedals.clear();
+    }
+
+    /// starts playing the notes of a command queue, or stops if none
+    /// returns the previous queue
+    pub fn set_commands(&mut self, commands: Option<Receiver<PlaybackCommand>>) -> Option<Receiver<PlaybackCommand>> {
+        std::mem::replace(&mut self.commands, commands)
+    }
+
+    /// handles the commands waiting in the queue without blocking
+    /// only notes, pedals and inputs addressed to the patch are applied, other commands do not apply to a single patch
+    fn process_commands(&mut self) {
+        let Self { patch, commands, synth, pedals, inputs, .. } = self;
+        let Some(commands) = commands.as_ref() else {
+            return;
+        };
+        while let Ok(command) = commands.try_recv() {
+            match command {
+                PlaybackCommand::Note { synth: target, event } if target == *synth => {
+                    pedals.send(event, |event| patch.send_note(event));
+                }
+                PlaybackCommand::Pedal { synth: target, pedal, pressed } if target == *synth => {
+                    pedals.set_pedal(pedal, pressed, |event| patch.send_note(event));
+                }
+                PlaybackCommand::SetInput { plugin, input, value } if plugin == *synth => {
+                    if let Some(frame) = inputs.get_mut(input as usize) {
+                        *frame = frame::mono(value as f32);
+                    }
+                }
+                PlaybackCommand::AllNotesOff => {
+                    patch.all_notes_off();
+                    pedals.clear();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// fills an interleaved device buffer with the given number of channels
+    /// each channel is filled according to the channel map
+    pub fn fill<T: SizedSample + FromSample<f32>>(&mut self, data: &mut [T], channels: usize) {
+        self.process_commands();
+        let Self { patch, resampler, inputs, frame, channel_map, limiter, recorder, cue, events, delta, .. } = self;
+        let multiplier = patch.sample_multiplier();
+
+        for device_frame in data.chunks_mut(channels) {
+            resampler.next_frame(
+                frame.as_flattened_mut(),
+                |buffer| {
+                    if let Some(events) = events.as_mut() {
+                        events.advance(|event| patch.send_note(event));
+                    }
+                    patch.update(inputs, buffer.as_chunks_mut().0, *delta)
+                }
+            );
+            for output in frame.iter_mut() {
+                *output = frame::scale(*output, multiplier);
+            }
+
+            // the cue is taken before the limiter so it is unaffected by the main output
+            let cue_frame = cue.as_mut().map(|(control, sink)| (control.sample(frame), sink));
+            limiter.process(frame.as_flattened_mut());
+
+            let cue_channels = match cue_frame {
+                Some((cue_frame, CueSink::Device(producer))) => {
+                    let _ = producer.push(cue_frame);
+                    None
+                }
+                Some((cue_frame, CueSink::Channels(first))) => Some((cue_frame, *first)),
+                None => None,
+            };
+
+            let record = recorder.as_ref().is_some_and(|tap| tap.reserve(device_frame.len()));
+            for (channel, sample) in device_frame.iter_mut().enumerate() {
+                let value = match cue_channels {
+                    Some((cue_frame, first)) if channel == first => cue_frame[0],
+                    Some((cue_frame, first)) if channel == first + 1 => cue_frame[1],
+                    _ => channel_map.sample(channel, frame),
+                };
+                if record && let Some(tap) = recorder.as_mut() {
+                    tap.push(value);
+                }
+                *sample = T::from_sample(value);
+            }
+        }
+    }
+
+}
+
+/// Something that fills the buffers of an output stream
+/// Sources are moved to the audio thread, so filling must never block.
+pub trait OutputSource: Send + 'static {
+    /// fills an interleaved device buffer with the given number of channels
+    fn fill_output<T: SizedSample + FromSample<f32>>(&mut self, data: &mut [T], channels: usize);
+}
+
+fn build_stream<S, T, E>(
+    mut source: S,
+    device: &Device,
+    config: &StreamConfig,
+    error_callback: E
+) -> Result<Stream, BuildStreamError>
+where
+    S: OutputSource,
+    T: SizedSample + FromSample<f32>,
+    E: FnMut(StreamError) + Send + 'static
+{
+    let channels = config.channels as usize;
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &OutputCallbackInfo| source.fill_output(data, channels),
+        error_callback,
+        None
+    )
+}
+
+/// Creates an output stream played by the given source
+pub fn output_stream<S: OutputSource, E: FnMut(StreamError) + Send + 'static>(
+    source: S,
+    device: &Device,
+    config: &StreamConfig,
+    sample_format: SampleFormat,
+    error_callback: E
+) -> Result<Stream, BuildStreamError> {
+    match sample_format {
+        SampleFormat::I8 => build_stream::<S, i8, E>(source, device, config, error_callback),
+        SampleFormat::I16 => build_stream::<S, i16, E>(source, device, config, error_callback),
+        SampleFormat::I32 => build_stream::<S, i32, E>(source, device, config, error_callback),
+        SampleFormat::I64 => build_stream::<S, i64, E>(source, device, config, error_callback),
+        SampleFormat::U8 => build_stream::<S, u8, E>(source, device, config, error_callback),
+        SampleFormat::U16 => build_stream::<S, u16, E>(source, device, config, error_callback),
+        SampleFormat::U32 => build_stream::<S, u32, E>(source, device, config, error_callback),
+        SampleFormat::U64 => build_stream::<S, u64, E>(source, device, config, error_callback),
+        SampleFormat::F32 => build_stream::<S, f32, E>(source, device, config, error_callback),
+        SampleFormat::F64 => build_stream::<S, f64, E>(source, device, config, error_callback),
+        _ => Err(BuildStreamError::StreamConfigNotSupported),
+    }
+}
+
+/// A renderer shared between the ui and its output stream
+/// Since the stream only borrows the renderer, a stream that dies (e.g. when its device
+/// is unplugged) may be replaced without recompiling the patch or losing its state.
+#[derive(Clone)]
+pub struct SharedRenderer {
+    renderer: Arc<Mutex<PatchRenderer>>,
+}
+
+impl SharedRenderer {
+    pub fn new(renderer: PatchRenderer) -> Self {
+        Self {
+            renderer: Arc::new(Mutex::new(renderer))
+        }
+    }
+
+    /// locks the renderer, pausing any stream playing it until the guard is dropped
+    pub fn lock(&self) -> MutexGuard<'_, PatchRenderer> {
+        self.renderer.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// releases every note of the patch without going through the command queue
+    /// waits for the stream to finish its current buffer, so it works even when the queue is full
+    pub fn all_notes_off(&self) {
+        self.lock().all_notes_off();
+    }
+
+    /// Creates an output stream that plays the shared patch
+    pub fn output_stream<E: FnMut(StreamError) + Send + 'static>(
+        &self,
+        device: &Device,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        error_callback: E
+    ) -> Result<Stream, BuildStreamError> {
+        output_stream(self.clone(), device, config, sample_format, error_callback)
+    }
+}
+
+impl OutputSource for SharedRenderer {
+    fn fill_output<T: SizedSample + FromSample<f32>>(&mut self, data: &mut [T], channels: usize) {
+        // never block the audio thread, output silence while the ui holds the lock
+        match self.renderer.try_lock() {
+            Ok(mut renderer) => renderer.fill(data, channels),
+            Err(_) => data.fill(T::EQUILIBRIUM),
+        }
+    }
+}
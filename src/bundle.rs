@@ -0,0 +1,285 @@
+use std::{borrow::Cow, collections::BTreeMap, fs, io, path::{Path, PathBuf}};
+
+use thiserror::Error;
+
+use crate::utils::ByteReader;
+
+/// The kind of file an asset refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetKind {
+    Sample,
+    Wavetable,
+    Tuning,
+}
+
+impl AssetKind {
+    /// the tag used to identify the kind when serialized
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::Sample => 0,
+            Self::Wavetable => 1,
+            Self::Tuning => 2,
+        }
+    }
+
+    /// gets the kind from a tag created by to_byte
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::Sample),
+            1 => Some(Self::Wavetable),
+            2 => Some(Self::Tuning),
+            _ => None
+        }
+    }
+}
+
+/// A reference to an external file used by a circuit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetReference {
+    pub kind: AssetKind,
+    pub path: PathBuf,
+}
+
+impl AssetReference {
+    pub fn new(kind: AssetKind, path: impl Into<PathBuf>) -> Self {
+        Self {
+            kind,
+            path: path.into()
+        }
+    }
+}
+
+/// An error occurring when reading or writing a bundle
+#[derive(Debug, Error)]
+pub enum BundleError {
+    #[error("The file is not a project bundle.")]
+    BadMagic,
+
+    #[error("Unsupported bundle format version {0}.")]
+    UnsupportedVersion(u8),
+
+    #[error("The bundle ended unexpectedly.")]
+    UnexpectedEnd,
+
+    #[error("Unrecognized asset kind tag {0}.")]
+    UnknownAssetKind(u8),
+
+    #[error("An asset path is not valid UTF-8.")]
+    InvalidPath,
+
+    #[error("The MIDI mappings are not valid UTF-8.")]
+    InvalidMidiMappings,
+
+    #[error("The asset '{0}' is not in the bundle and could not be read from disk.")]
+    MissingAsset(PathBuf),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// An asset packed into a bundle
+#[derive(Debug, Clone)]
+struct BundledAsset {
+    kind: AssetKind,
+    data: Vec<u8>,
+}
+
+/// A portable project, holding the patch file along with every asset it references
+/// Assets are keyed by the path they were originally loaded from
+#[derive(Debug, Clone, Default)]
+pub struct ProjectBundle {
+    patch: Vec<u8>,
+    assets: BTreeMap<String, BundledAsset>,
+
+    /// the project's MIDI controller mappings, as written by CcMap
+    midi_mappings: String,
+}
+
+impl ProjectBundle {
+    /// the identifier at the start of every bundle
+    const MAGIC: &'static [u8; 4] = b"SSPB";
+
+    /// the version of the bundle format
+    /// version 2 added MIDI mappings
+    const FORMAT_VERSION: u8 = 2;
+
+    /// the file extension used for bundles
+    pub const EXTENSION: &'static str = "ssbundle";
+
+    /// creates a new bundle with the given patch file contents and no assets
+    pub fn new(patch: Vec<u8>) -> Self {
+        Self {
+            patch,
+            assets: BTreeMap::new(),
+            midi_mappings: String::new()
+        }
+    }
+
+    /// creates a bundle containing the patch and all of the given assets read from disk
+    pub fn pack(patch: Vec<u8>, assets: &[AssetReference]) -> Result<Self, BundleError> {
+        let mut bundle = Self::new(patch);
+        for asset in assets {
+            bundle.add_asset_from_file(asset)?;
+        }
+        Ok(bundle)
+    }
+
+    /// creates a bundle containing the patch and all of the given assets
+    /// assets are taken from this bundle before being read from disk, so those only held here are kept
+    pub fn repack(&self, patch: Vec<u8>, assets: &[AssetReference]) -> Result<Self, BundleError> {
+        let mut bundle = Self::new(patch);
+        for asset in assets {
+            let data = self.resolve_asset(&asset.path)?.into_owned();
+            bundle.add_asset(asset, data);
+        }
+        Ok(bundle)
+    }
+
+    /// the contents of the bundled patch file
+    pub fn patch(&self) -> &[u8] {
+        &self.patch
+    }
+
+    /// replaces the contents of the bundled patch file
+    pub fn set_patch(&mut self, patch: Vec<u8>) {
+        self.patch = patch;
+    }
+
+    /// the project's MIDI controller mappings
+    pub fn midi_mappings(&self) -> &str {
+        &self.midi_mappings
+    }
+
+    pub fn set_midi_mappings(&mut self, mappings: String) {
+        self.midi_mappings = mappings;
+    }
+
+    /// adds an asset with the given contents, replacing any asset with the same path
+    pub fn add_asset(&mut self, reference: &AssetReference, data: Vec<u8>) {
+        self.assets.insert(
+            Self::asset_key(&reference.path),
+            BundledAsset { kind: reference.kind, data }
+        );
+    }
+
+    /// reads an asset from disk and adds it to the bundle
+    /// does nothing if the asset is already bundled
+    pub fn add_asset_from_file(&mut self, reference: &AssetReference) -> Result<(), BundleError> {
+        if self.contains_asset(&reference.path) {
+            return Ok(());
+        }
+        let data = fs::read(&reference.path)?;
+        self.add_asset(reference, data);
+        Ok(())
+    }
+
+    /// returns true if the asset at the given path is bundled
+    pub fn contains_asset(&self, path: &Path) -> bool {
+        self.assets.contains_key(&Self::asset_key(path))
+    }
+
+    /// iterates over the references to all bundled assets
+    pub fn asset_references(&self) -> impl Iterator<Item = AssetReference> + '_ {
+        self.assets.iter().map(|(path, asset)| AssetReference::new(asset.kind, path))
+    }
+
+    /// gets the contents of an asset
+    /// the bundled copy is used if present, otherwise the asset is read from disk
+    pub fn resolve_asset(&self, path: &Path) -> Result<Cow<'_, [u8]>, BundleError> {
+        if let Some(asset) = self.assets.get(&Self::asset_key(path)) {
+            return Ok(Cow::Borrowed(&asset.data));
+        }
+
+        match fs::read(path) {
+            Ok(data) => Ok(Cow::Owned(data)),
+            Err(_) => Err(BundleError::MissingAsset(path.to_path_buf())),
+        }
+    }
+
+    /// serializes the bundle
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(Self::MAGIC);
+        out.push(Self::FORMAT_VERSION);
+        Self::write_block(&mut out, &self.patch);
+        out.extend_from_slice(&(self.assets.len() as u32).to_le_bytes());
+        for (path, asset) in &self.assets {
+            out.push(asset.kind.to_byte());
+            Self::write_block(&mut out, path.as_bytes());
+            Self::write_block(&mut out, &asset.data);
+        }
+        Self::write_block(&mut out, self.midi_mappings.as_bytes());
+        out
+    }
+
+    /// deserializes a bundle created by to_bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BundleError> {
+        type E = BundleError;
+        let mut reader = ByteReader::new(bytes);
+
+        if reader.read_bytes(Self::MAGIC.len()).ok_or(E::BadMagic)? != Self::MAGIC {
+            return Err(E::BadMagic);
+        }
+
+        let version = reader.read_u8().ok_or(E::UnexpectedEnd)?;
+        if version == 0 || version > Self::FORMAT_VERSION {
+            return Err(E::UnsupportedVersion(version));
+        }
+
+        let patch = Self::read_block(&mut reader)?.to_vec();
+
+        let count = reader.read_u32().ok_or(E::UnexpectedEnd)?;
+        let mut assets = BTreeMap::new();
+        for _ in 0..count {
+            let tag = reader.read_u8().ok_or(E::UnexpectedEnd)?;
+            let kind = AssetKind::from_byte(tag).ok_or(E::UnknownAssetKind(tag))?;
+            let path = str::from_utf8(Self::read_block(&mut reader)?)
+                .map_err(|_| E::InvalidPath)?
+                .to_string();
+            let data = Self::read_block(&mut reader)?.to_vec();
+            assets.insert(path, BundledAsset { kind, data });
+        }
+
+        let midi_mappings = if version >= 2 {
+            str::from_utf8(Self::read_block(&mut reader)?)
+                .map_err(|_| E::InvalidMidiMappings)?
+                .to_string()
+        } else {
+            String::new()
+        };
+
+        Ok(Self {
+            patch,
+            assets,
+            midi_mappings
+        })
+    }
+
+    /// writes the bundle to the given file
+    pub fn save(&self, path: &Path) -> Result<(), BundleError> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    /// reads a bundle from the given file
+    pub fn load(path: &Path) -> Result<Self, BundleError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    /// the key used to store an asset
+    fn asset_key(path: &Path) -> String {
+        path.to_string_lossy().into_owned()
+    }
+
+    /// writes a length prefixed block of bytes
+    fn write_block(out: &mut Vec<u8>, data: &[u8]) {
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(data);
+    }
+
+    /// reads a block written by write_block
+    fn read_block<'a>(reader: &mut ByteReader<'a>) -> Result<&'a [u8], BundleError> {
+        let len = reader.read_u32().ok_or(BundleError::UnexpectedEnd)? as usize;
+        reader.read_bytes(len).ok_or(BundleError::UnexpectedEnd)
+    }
+}
@@ -2,7 +2,7 @@ use std::cell::{Cell, OnceCell};
 
 use egui::{Label, Ui, Vec2};
 
-use crate::{circuit_id::{CircuitId, CircuitPortId, PortId, PortKind}, pitch::TuningSystem};
+use crate::{bundle::{AssetReference, ProjectBundle}, circuit_id::{CircuitId, CircuitPortId, PortId, PortKind}, frame::{self, Frame}, patch_file::PatchFile, pitch::TuningSystem, playback::NoteEvent};
 
 /// The specification "skeleton" for a circuit. Describes basic top-level capabilities of
 /// the circuit.
@@ -100,12 +100,48 @@ pub trait CircuitBuilder: std::fmt::Debug {
     /// This size will be filled with the title, IO ports, padding, etc. along with your custom UI.
     /// Called every frame before drawing.
     fn request_size(&self) -> Option<egui::Vec2> { None }
+
+    /// Gets every external file (samples, wavetables, etc.) used by the circuit.
+    /// Used to pack the files into project bundles.
+    fn assets(&self) -> Vec<AssetReference> { Vec::new() }
+
+    /// Reloads the external files used by the circuit from a project bundle.
+    /// Called after load when a project is opened from a bundle; files the bundle does not hold
+    /// keep the copy read from disk by load.
+    fn resolve_assets(&mut self, _bundle: &ProjectBundle) {}
+
+    /// Serializes the user's settings for the circuit so that it may be saved to a patch file.
+    fn save(&self) -> String { String::new() }
+
+    /// Restores settings created by save.
+    /// Returns false if the data could not be understood.
+    fn load(&mut self, data: &str) -> bool { data.is_empty() }
+
+    /// Gets the patch embedded in the circuit, if it is a subpatch.
+    /// Subpatches are flattened into the patch containing them when it is instantiated.
+    fn subpatch(&self) -> Option<&PatchFile> { None }
 }
 
 /// A circuit that processes signals into outputs
 pub trait Circuit: std::fmt::Debug + Send {
     /// Handles a vector of signals to produce some output signals.
     fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], delta: f32);
+
+    /// Handles a vector of stereo signals to produce some stereo output signals.
+    /// This is what is called during playback. By default, inputs are mixed down to mono,
+    /// passed to operate, and the outputs are sent to both channels.
+    /// Override this to process each channel (panning, stereo delays, etc.).
+    fn operate_stereo(&mut self, inputs: &[Frame], outputs: &mut[Frame], delta: f32) {
+        frame::operate_mono(inputs, outputs, |inputs, outputs| self.operate(inputs, outputs, delta));
+    }
+
+    /// Receives a note event played on the patch, between samples.
+    /// Ignored by default; override this for circuits driven by notes.
+    fn note(&mut self, _event: NoteEvent) {}
+
+    /// Releases every note the circuit holds, such as to recover from stuck notes.
+    /// Ignored by default; override this along with note.
+    fn all_notes_off(&mut self) {}
 }
 
 /// The ui for a circuit
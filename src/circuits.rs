@@ -21,3 +21,69 @@ pub use interpolator::*;
 
 pub mod sample_quantizer;
 pub use sample_quantizer::*;
+
+mod gate;
+pub use gate::*;
+
+mod lfo;
+pub use lfo::*;
+
+mod math;
+pub use math::*;
+
+mod logic;
+pub use logic::*;
+
+mod vca;
+pub use vca::*;
+
+mod wavefolder;
+pub use wavefolder::*;
+
+mod sampler;
+pub use sampler::*;
+
+mod mod_fx;
+pub use mod_fx::*;
+
+mod slew;
+pub use slew::*;
+
+mod clock;
+pub use clock::*;
+
+mod chaos;
+pub use chaos::*;
+
+mod env_follower;
+pub use env_follower::*;
+
+mod pitch_detect;
+pub use pitch_detect::*;
+
+mod scale_quantizer;
+pub use scale_quantizer::*;
+
+mod gate_tools;
+pub use gate_tools::*;
+
+mod unison;
+pub use unison::*;
+
+mod tuner;
+pub use tuner::*;
+
+mod pluck;
+pub use pluck::*;
+
+mod formant;
+pub use formant::*;
+
+mod voice_alloc;
+pub use voice_alloc::*;
+
+mod script;
+pub use script::*;
+
+mod subpatch;
+pub use subpatch::*;
@@ -0,0 +1,110 @@
+use crate::circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification};
+
+/// Produces non-periodic modulation scaled by the Amplitude input
+/// - Smooth glides between random values in [-1, 1], picking a new one Rate times per second
+/// - Stepped holds a random value in [-1, 1], picking a new one Rate times per second
+/// - Lorenz follows the x coordinate of a Lorenz attractor, normalized to about [-1, 1],
+///   with Rate setting how quickly it is traced
+#[derive(Debug, Clone)]
+pub struct ChaosBuilder {
+}
+
+impl ChaosBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &["Rate", "Amplitude"],
+        output_names: &["Smooth", "Stepped", "Lorenz"],
+        size: egui::vec2(150.0, 150.0),
+        playback_size: None,
+    };
+
+    const NAME: &'static str = "Chaos";
+
+    pub fn new() -> Self {
+        Self{ }
+    }
+}
+
+impl CircuitBuilder for ChaosBuilder {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    fn build(&self, _: &BuildState) -> Box<dyn Circuit> {
+        let mut random = fastrand::Rng::new();
+        let previous = random.f32() * 2.0 - 1.0;
+        let next = random.f32() * 2.0 - 1.0;
+        Box::new(Chaos {
+            random,
+            phase: 0.0,
+            previous,
+            next,
+            lorenz: [1.0, 1.0, 1.0],
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Chaos {
+    random: fastrand::Rng,
+
+    /// the position between the previous and next random values [0, 1)
+    phase: f32,
+    previous: f32,
+    next: f32,
+
+    /// the position on the Lorenz attractor
+    lorenz: [f32; 3],
+}
+
+impl Chaos {
+    const SIGMA: f32 = 10.0;
+    const RHO: f32 = 28.0;
+    const BETA: f32 = 8.0 / 3.0;
+
+    /// the largest step of the attractor integrated at once, to keep it stable
+    const MAX_STEP: f32 = 0.005;
+
+    /// roughly the largest x reached by the attractor
+    const LORENZ_SCALE: f32 = 20.0;
+
+    /// advances the attractor by time t
+    fn step_lorenz(&mut self, t: f32) {
+        let steps = (t / Self::MAX_STEP).ceil().max(1.0);
+        let dt = t / steps;
+        for _ in 0..steps as usize {
+            let [x, y, z] = self.lorenz;
+            self.lorenz = [
+                x + Self::SIGMA * (y - x) * dt,
+                y + (x * (Self::RHO - z) - y) * dt,
+                z + (x * y - Self::BETA * z) * dt,
+            ];
+        }
+    }
+}
+
+impl Circuit for Chaos {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], delta: f32) {
+        let rate = inputs[0].max(0.0);
+        let amplitude = inputs[1];
+
+        // cosine interpolation so the glide has no corners at each value
+        let t = 0.5 - 0.5 * f32::cos(self.phase * std::f32::consts::PI);
+        outputs[0] = amplitude * (self.previous + (self.next - self.previous) * t);
+        outputs[1] = amplitude * self.previous;
+        outputs[2] = amplitude * (self.lorenz[0] / Self::LORENZ_SCALE);
+
+        self.phase += delta * rate;
+        if self.phase >= 1.0 {
+            self.phase = self.phase.rem_euclid(1.0);
+            self.previous = self.next;
+            self.next = self.random.f32() * 2.0 - 1.0;
+        }
+        if rate > 0.0 {
+            self.step_lorenz(delta * rate);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use crate::circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification};
+
+use super::EdgeDetector;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ClockUnit {
+    Bpm,
+    Hz,
+}
+
+impl ClockUnit {
+    const BPM_TEXT: &'static str = "BPM";
+    const HZ_TEXT: &'static str = "Hz";
+
+    fn display_string(&self) -> &'static str {
+        match self {
+            Self::Bpm => Self::BPM_TEXT,
+            Self::Hz => Self::HZ_TEXT,
+        }
+    }
+
+    /// gets the unit with the given display string
+    fn from_display_string(text: &str) -> Option<Self> {
+        [Self::Bpm, Self::Hz]
+            .into_iter()
+            .find(|unit| unit.display_string() == text)
+    }
+}
+
+/// Produces a square pulse train at the rate given by the Rate input, in BPM or Hz
+/// Each divided output pulses once for every few pulses of the clock.
+/// A rising edge on Reset restarts the clock and all divisions.
+#[derive(Debug, Clone)]
+pub struct ClockBuilder {
+    unit: ClockUnit,
+    divisions: [u32; ClockBuilder::DIVIDED_OUTPUTS],
+    division_texts: [String; ClockBuilder::DIVIDED_OUTPUTS],
+}
+
+impl ClockBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &["Rate", "Reset"],
+        output_names: &["Clock", "Div A", "Div B", "Div C"],
+        size: egui::vec2(200.0, 250.0),
+        playback_size: None,
+    };
+
+    const NAME: &'static str = "Clock";
+    const DIVIDED_OUTPUTS: usize = 3;
+    const DIVISION_LABELS: [&'static str; Self::DIVIDED_OUTPUTS] = ["Div A:", "Div B:", "Div C:"];
+
+    pub fn new() -> Self {
+        let divisions = [2, 4, 8];
+        Self {
+            unit: ClockUnit::Bpm,
+            divisions,
+            division_texts: divisions.map(|division| division.to_string()),
+        }
+    }
+}
+
+impl CircuitBuilder for ClockBuilder {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        ui.label("Rate:");
+        ui.radio_value(&mut self.unit, ClockUnit::Bpm, ClockUnit::BPM_TEXT);
+        ui.radio_value(&mut self.unit, ClockUnit::Hz, ClockUnit::HZ_TEXT);
+
+        ui.separator();
+        for ((label, text), division) in Self::DIVISION_LABELS.iter()
+            .zip(self.division_texts.iter_mut())
+            .zip(self.divisions.iter_mut())
+        {
+            ui.label(*label);
+            crate::utils::pos_number_input(ui, text, division);
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    /// saved as 'unit;division a;division b;division c'
+    fn save(&self) -> String {
+        let [a, b, c] = self.divisions;
+        format!("{};{};{};{}", self.unit.display_string(), a, b, c)
+    }
+
+    fn load(&mut self, data: &str) -> bool {
+        let mut parts = data.split(';');
+        let Some(unit) = parts.next().and_then(ClockUnit::from_display_string) else {
+            return false;
+        };
+        let mut divisions = [0; Self::DIVIDED_OUTPUTS];
+        for division in &mut divisions {
+            match parts.next().and_then(|text| text.parse::<u32>().ok()) {
+                Some(value) if value > 0 => *division = value,
+                _ => return false,
+            }
+        }
+
+        self.unit = unit;
+        self.divisions = divisions;
+        self.division_texts = divisions.map(|division| division.to_string());
+        true
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    fn build(&self, _: &BuildState) -> Box<dyn Circuit> {
+        Box::new(Clock {
+            unit: self.unit,
+            divisions: self.divisions,
+            period: self.divisions.iter().fold(1u64, |period, division| period.saturating_mul(*division as u64)),
+            phase: 0.0,
+            count: 0,
+            reset: EdgeDetector::new(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Clock {
+    unit: ClockUnit,
+    divisions: [u32; ClockBuilder::DIVIDED_OUTPUTS],
+
+    /// the pulses after which every division lines up again
+    period: u64,
+
+    /// the position within the current pulse [0, 1)
+    phase: f32,
+
+    /// the pulses completed since the last reset, wrapping at the period
+    count: u64,
+    reset: EdgeDetector,
+}
+
+impl Circuit for Clock {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], delta: f32) {
+        if self.reset.rising(inputs[1]) {
+            self.phase = 0.0;
+            self.count = 0;
+        }
+
+        outputs[0] = if self.phase < 0.5 { 1.0 } else { 0.0 };
+        for (output, division) in outputs[1..].iter_mut().zip(self.divisions) {
+            let division = division as u64;
+            let divided_phase = ((self.count % division) as f32 + self.phase) / division as f32;
+            *output = if divided_phase < 0.5 { 1.0 } else { 0.0 };
+        }
+
+        let frequency = match self.unit {
+            ClockUnit::Bpm => inputs[0] / 60.0,
+            ClockUnit::Hz => inputs[0],
+        };
+        self.phase += delta * frequency.max(0.0);
+        if self.phase >= 1.0 {
+            let pulses = self.phase.floor();
+            self.phase -= pulses;
+            self.count = (self.count + pulses as u64) % self.period;
+        }
+    }
+}
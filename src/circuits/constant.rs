@@ -1,6 +1,6 @@
 use std::{cell::RefCell, rc::Rc};
 
-use crate::{circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification}, utils::PitchOrValue};
+use crate::{circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification}, pitch::Pitch, utils::PitchOrValue};
 
 #[derive(Debug, Clone)]
 pub struct ConstantBuilder {
@@ -58,6 +58,25 @@ impl CircuitBuilder for ConstantBuilder {
         self.data.borrow_mut().show(ui);
     }
 
+    fn save(&self) -> String {
+        self.data.borrow().value.to_string()
+    }
+
+    fn load(&mut self, data: &str) -> bool {
+        let value = if let Ok(pitch) = data.parse::<Pitch>() {
+            PitchOrValue::Pitch(pitch)
+        } else if let Ok(value) = data.parse::<f32>() {
+            PitchOrValue::Value(value)
+        } else {
+            return false;
+        };
+
+        let mut builder_data = self.data.borrow_mut();
+        builder_data.value = value;
+        builder_data.text = value.to_string();
+        true
+    }
+
     fn specification(&self) -> &'static CircuitSpecification {
         &Self::SPECIFICATION
     }
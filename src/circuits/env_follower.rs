@@ -0,0 +1,165 @@
+use crate::{circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification}, frame::{self, Frame}};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DetectorKind {
+    Peak,
+    Rms,
+}
+
+impl DetectorKind {
+    const PEAK_TEXT: &'static str = "Peak";
+    const RMS_TEXT: &'static str = "RMS";
+
+    fn display_string(&self) -> &'static str {
+        match self {
+            Self::Peak => Self::PEAK_TEXT,
+            Self::Rms => Self::RMS_TEXT,
+        }
+    }
+
+    /// gets the kind with the given display string
+    fn from_display_string(text: &str) -> Option<Self> {
+        [Self::Peak, Self::Rms]
+            .into_iter()
+            .find(|kind| kind.display_string() == text)
+    }
+}
+
+/// Turns the loudness of a signal into a control signal
+/// The input is rectified, then smoothed so that the output rises over the attack time and falls over the release time.
+/// Peak follows the rectified signal directly, while RMS follows its power for a steadier output.
+/// For stereo signals, the louder channel is followed.
+#[derive(Debug, Clone)]
+pub struct EnvFollowerBuilder {
+    kind: DetectorKind,
+    attack_duration: f32,
+    attack_text: String,
+    release_duration: f32,
+    release_text: String,
+}
+
+impl EnvFollowerBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &["In"],
+        output_names: &["Env"],
+        size: egui::vec2(200.0, 225.0),
+        playback_size: None,
+    };
+
+    const NAME: &'static str = "Env Follower";
+
+    pub fn new() -> Self {
+        let attack_value = 10.0;
+        let release_value = 150.0;
+        Self {
+            kind: DetectorKind::Peak,
+            attack_duration: attack_value,
+            attack_text: attack_value.to_string(),
+            release_duration: release_value,
+            release_text: release_value.to_string(),
+        }
+    }
+}
+
+impl CircuitBuilder for EnvFollowerBuilder {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        ui.label("Detector:");
+        ui.radio_value(&mut self.kind, DetectorKind::Peak, DetectorKind::PEAK_TEXT);
+        ui.radio_value(&mut self.kind, DetectorKind::Rms, DetectorKind::RMS_TEXT);
+
+        ui.separator();
+        ui.label("Attack (ms):");
+        crate::utils::non_neg_number_input(ui, &mut self.attack_text, &mut self.attack_duration);
+        ui.label("Release (ms):");
+        crate::utils::non_neg_number_input(ui, &mut self.release_text, &mut self.release_duration);
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    /// saved as 'kind;attack duration;release duration'
+    fn save(&self) -> String {
+        format!("{};{};{}", self.kind.display_string(), self.attack_duration, self.release_duration)
+    }
+
+    fn load(&mut self, data: &str) -> bool {
+        let mut parts = data.split(';');
+        let Some(kind) = parts.next().and_then(DetectorKind::from_display_string) else {
+            return false;
+        };
+        let attack = parts.next().and_then(|text| text.parse::<f32>().ok());
+        let release = parts.next().and_then(|text| text.parse::<f32>().ok());
+        let (Some(attack), Some(release)) = (attack, release) else {
+            return false;
+        };
+        if attack < 0.0 || release < 0.0 {
+            return false;
+        }
+
+        self.kind = kind;
+        self.attack_duration = attack;
+        self.attack_text = attack.to_string();
+        self.release_duration = release;
+        self.release_text = release.to_string();
+        true
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    fn build(&self, _: &BuildState) -> Box<dyn Circuit> {
+        Box::new(EnvFollower {
+            kind: self.kind,
+            attack: self.attack_duration / 1000.0,
+            release: self.release_duration / 1000.0,
+            level: 0.0,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct EnvFollower {
+    kind: DetectorKind,
+
+    /// durations in seconds
+    attack: f32,
+    release: f32,
+
+    /// the smoothed level, as power for RMS detection
+    level: f32,
+}
+
+impl EnvFollower {
+    /// follows the rectified sample and returns the envelope
+    fn follow(&mut self, rectified: f32, delta: f32) -> f32 {
+        let target = match self.kind {
+            DetectorKind::Peak => rectified,
+            DetectorKind::Rms => rectified * rectified,
+        };
+        let duration = if target > self.level { self.attack } else { self.release };
+        self.level = if duration <= 0.0 {
+            target
+        } else {
+            self.level + (target - self.level) * (1.0 - f32::exp(-delta / duration))
+        };
+
+        match self.kind {
+            DetectorKind::Peak => self.level,
+            DetectorKind::Rms => self.level.sqrt(),
+        }
+    }
+}
+
+impl Circuit for EnvFollower {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], delta: f32) {
+        outputs[0] = self.follow(inputs[0].abs(), delta);
+    }
+
+    fn operate_stereo(&mut self, inputs: &[Frame], outputs: &mut[Frame], delta: f32) {
+        // mixing to mono first would let out of phase channels cancel out
+        let rectified = f32::max(inputs[0][0].abs(), inputs[0][1].abs());
+        outputs[0] = frame::mono(self.follow(rectified, delta));
+    }
+}
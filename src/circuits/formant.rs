@@ -0,0 +1,182 @@
+use crate::circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Vowel {
+    A,
+    E,
+    I,
+    O,
+    U,
+}
+
+impl Vowel {
+    const A_TEXT: &'static str = "A";
+    const E_TEXT: &'static str = "E";
+    const I_TEXT: &'static str = "I";
+    const O_TEXT: &'static str = "O";
+    const U_TEXT: &'static str = "U";
+
+    const ALL: [Self; 5] = [Self::A, Self::E, Self::I, Self::O, Self::U];
+
+    fn display_string(&self) -> &'static str {
+        match self {
+            Self::A => Self::A_TEXT,
+            Self::E => Self::E_TEXT,
+            Self::I => Self::I_TEXT,
+            Self::O => Self::O_TEXT,
+            Self::U => Self::U_TEXT,
+        }
+    }
+
+    /// gets the vowel with the given display string
+    fn from_display_string(text: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|vowel| vowel.display_string() == text)
+    }
+
+    /// the frequency in Hz and linear gain of each formant of the vowel
+    fn formants(&self) -> [(f32, f32); FORMANTS] {
+        match self {
+            Self::A => [(800.0, 1.0), (1150.0, 0.5), (2900.0, 0.025)],
+            Self::E => [(350.0, 1.0), (2000.0, 0.1), (2800.0, 0.18)],
+            Self::I => [(270.0, 1.0), (2140.0, 0.25), (2950.0, 0.05)],
+            Self::O => [(450.0, 1.0), (800.0, 0.28), (2830.0, 0.08)],
+            Self::U => [(325.0, 1.0), (700.0, 0.16), (2530.0, 0.018)],
+        }
+    }
+}
+
+/// the number of formants modeled per vowel
+const FORMANTS: usize = 3;
+
+/// Shapes its input into a vowel sound with parallel bandpass filters at the vowel's formants
+/// Morph [0, 1] moves the formants from the first vowel to the second.
+#[derive(Debug, Clone)]
+pub struct FormantBuilder {
+    from: Vowel,
+    to: Vowel,
+}
+
+impl FormantBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &["In", "Morph"],
+        output_names: &["Out"],
+        size: egui::vec2(200.0, 200.0),
+        playback_size: None,
+    };
+
+    const NAME: &'static str = "Formant";
+
+    pub fn new() -> Self {
+        Self {
+            from: Vowel::A,
+            to: Vowel::O,
+        }
+    }
+}
+
+impl CircuitBuilder for FormantBuilder {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        ui.label("From:");
+        ui.horizontal(|ui| {
+            for vowel in Vowel::ALL {
+                ui.radio_value(&mut self.from, vowel, vowel.display_string());
+            }
+        });
+        ui.label("To:");
+        ui.horizontal(|ui| {
+            for vowel in Vowel::ALL {
+                ui.radio_value(&mut self.to, vowel, vowel.display_string());
+            }
+        });
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    /// saved as 'from;to'
+    fn save(&self) -> String {
+        format!("{};{}", self.from.display_string(), self.to.display_string())
+    }
+
+    fn load(&mut self, data: &str) -> bool {
+        let Some((from, to)) = data.split_once(';') else {
+            return false;
+        };
+        let (Some(from), Some(to)) = (Vowel::from_display_string(from), Vowel::from_display_string(to)) else {
+            return false;
+        };
+
+        self.from = from;
+        self.to = to;
+        true
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    fn build(&self, state: &BuildState) -> Box<dyn Circuit> {
+        Box::new(Formant {
+            sample_rate: state.sample_rate.max(1) as f32,
+            from: self.from.formants(),
+            to: self.to.formants(),
+            filters: [Bandpass::default(); FORMANTS],
+        })
+    }
+}
+
+/// A state variable bandpass filter with unity gain at its center
+/// Stays stable while its frequency is modulated.
+#[derive(Debug, Clone, Copy, Default)]
+struct Bandpass {
+    ic1eq: f32,
+    ic2eq: f32,
+}
+
+impl Bandpass {
+    fn process(&mut self, input: f32, frequency: f32, q: f32, sample_rate: f32) -> f32 {
+        let g = f32::tan(std::f32::consts::PI * frequency.min(sample_rate * 0.45) / sample_rate);
+        let k = 1.0 / q;
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let a2 = g * a1;
+        let a3 = g * a2;
+
+        let v3 = input - self.ic2eq;
+        let v1 = a1 * self.ic1eq + a2 * v3;
+        let v2 = self.ic2eq + a2 * self.ic1eq + a3 * v3;
+        self.ic1eq = 2.0 * v1 - self.ic1eq;
+        self.ic2eq = 2.0 * v2 - self.ic2eq;
+        k * v1
+    }
+}
+
+#[derive(Debug)]
+pub struct Formant {
+    sample_rate: f32,
+    from: [(f32, f32); FORMANTS],
+    to: [(f32, f32); FORMANTS],
+    filters: [Bandpass; FORMANTS],
+}
+
+impl Formant {
+    /// the quality of each formant filter, which narrows as the frequency rises
+    const Q: [f32; FORMANTS] = [8.0, 12.0, 20.0];
+}
+
+impl Circuit for Formant {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], _: f32) {
+        let morph = inputs[1].clamp(0.0, 1.0);
+        let mut out = 0.0;
+        for index in 0..FORMANTS {
+            let (from_frequency, from_gain) = self.from[index];
+            let (to_frequency, to_gain) = self.to[index];
+
+            // frequencies are morphed exponentially so the movement sounds even
+            let frequency = from_frequency * f32::powf(to_frequency / from_frequency, morph);
+            let gain = from_gain + (to_gain - from_gain) * morph;
+            out += gain * self.filters[index].process(inputs[0], frequency, Self::Q[index], self.sample_rate);
+        }
+        outputs[0] = out;
+    }
+}
@@ -0,0 +1,33 @@
+/// The level above which a signal is treated as a high gate or trigger
+pub const GATE_THRESHOLD: f32 = 0.5;
+
+/// Detects when a gate signal goes from low to high
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EdgeDetector {
+    high: bool,
+}
+
+impl EdgeDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// returns true if the gate is high now but was low on the previous sample
+    pub fn rising(&mut self, value: f32) -> bool {
+        let was_high = self.high;
+        self.high = value > GATE_THRESHOLD;
+        self.high && !was_high
+    }
+
+    /// returns true if the gate is low now but was high on the previous sample
+    pub fn falling(&mut self, value: f32) -> bool {
+        let was_high = self.high;
+        self.high = value > GATE_THRESHOLD;
+        !self.high && was_high
+    }
+
+    /// whether the gate was high on the latest sample
+    pub fn is_high(&self) -> bool {
+        self.high
+    }
+}
@@ -0,0 +1,176 @@
+use crate::circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification};
+
+use super::{EdgeDetector, GATE_THRESHOLD};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GateToolKind {
+    RisingTrigger,
+    FallingTrigger,
+    Length,
+    Delay,
+}
+
+impl GateToolKind {
+    const RISING_TEXT: &'static str = "Rising Edge Trigger";
+    const FALLING_TEXT: &'static str = "Falling Edge Trigger";
+    const LENGTH_TEXT: &'static str = "Gate Length";
+    const DELAY_TEXT: &'static str = "Gate Delay";
+
+    const ALL: [Self; 4] = [Self::RisingTrigger, Self::FallingTrigger, Self::Length, Self::Delay];
+
+    fn display_string(&self) -> &'static str {
+        match self {
+            Self::RisingTrigger => Self::RISING_TEXT,
+            Self::FallingTrigger => Self::FALLING_TEXT,
+            Self::Length => Self::LENGTH_TEXT,
+            Self::Delay => Self::DELAY_TEXT,
+        }
+    }
+
+    /// gets the kind with the given display string
+    fn from_display_string(text: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.display_string() == text)
+    }
+
+    /// the label of the duration setting
+    fn duration_label(&self) -> &'static str {
+        match self {
+            Self::RisingTrigger | Self::FallingTrigger => "Trigger Length (ms):",
+            Self::Length => "Gate Length (ms):",
+            Self::Delay => "Delay (ms):",
+        }
+    }
+}
+
+/// Reshapes gate signals
+/// - Rising and Falling Edge Trigger output a short pulse when the gate opens or closes
+/// - Gate Length outputs a gate of a fixed length each time the gate opens, however long it was held
+/// - Gate Delay outputs the gate unchanged but late by the delay
+#[derive(Debug, Clone)]
+pub struct GateToolsBuilder {
+    kind: GateToolKind,
+    duration: f32,
+    duration_text: String,
+}
+
+impl GateToolsBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &["Gate"],
+        output_names: &["Out"],
+        size: egui::vec2(200.0, 200.0),
+        playback_size: None,
+    };
+
+    pub fn new() -> Self {
+        let duration_value = 10.0;
+        Self {
+            kind: GateToolKind::RisingTrigger,
+            duration: duration_value,
+            duration_text: duration_value.to_string(),
+        }
+    }
+}
+
+impl CircuitBuilder for GateToolsBuilder {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        for kind in GateToolKind::ALL {
+            ui.radio_value(&mut self.kind, kind, kind.display_string());
+        }
+
+        ui.separator();
+        ui.label(self.kind.duration_label());
+        crate::utils::non_neg_number_input(ui, &mut self.duration_text, &mut self.duration);
+    }
+
+    fn name(&self) -> &str {
+        self.kind.display_string()
+    }
+
+    /// saved as 'kind;duration'
+    fn save(&self) -> String {
+        format!("{};{}", self.kind.display_string(), self.duration)
+    }
+
+    fn load(&mut self, data: &str) -> bool {
+        let Some((kind, duration)) = data.split_once(';') else {
+            return false;
+        };
+        let (Some(kind), Ok(duration)) = (GateToolKind::from_display_string(kind), duration.parse::<f32>()) else {
+            return false;
+        };
+        if duration < 0.0 {
+            return false;
+        }
+
+        self.kind = kind;
+        self.duration = duration;
+        self.duration_text = duration.to_string();
+        true
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    fn build(&self, state: &BuildState) -> Box<dyn Circuit> {
+        let duration = self.duration / 1000.0;
+        match self.kind {
+            GateToolKind::RisingTrigger | GateToolKind::FallingTrigger | GateToolKind::Length => Box::new(Pulse {
+                falling: self.kind == GateToolKind::FallingTrigger,
+                duration,
+                remaining: 0.0,
+                edge: EdgeDetector::new(),
+            }),
+            GateToolKind::Delay => {
+                let samples = (duration * state.sample_rate as f32).round() as usize;
+                Box::new(GateDelay {
+                    history: vec![false; samples.max(1)],
+                    index: 0,
+                })
+            }
+        }
+    }
+}
+
+/// Outputs a gate of a fixed duration on each rising or falling edge
+#[derive(Debug)]
+pub struct Pulse {
+    falling: bool,
+
+    /// seconds
+    duration: f32,
+    remaining: f32,
+    edge: EdgeDetector,
+}
+
+impl Circuit for Pulse {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], delta: f32) {
+        let triggered = if self.falling {
+            self.edge.falling(inputs[0])
+        } else {
+            self.edge.rising(inputs[0])
+        };
+        if triggered {
+            // always last at least one sample so that short triggers are not lost
+            self.remaining = self.duration.max(delta);
+        }
+
+        outputs[0] = if self.remaining > 0.0 { 1.0 } else { 0.0 };
+        self.remaining -= delta;
+    }
+}
+
+/// Outputs the gate as it was a fixed number of samples ago
+#[derive(Debug)]
+pub struct GateDelay {
+    history: Vec<bool>,
+    index: usize,
+}
+
+impl Circuit for GateDelay {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], _: f32) {
+        outputs[0] = if self.history[self.index] { 1.0 } else { 0.0 };
+        self.history[self.index] = inputs[0] > GATE_THRESHOLD;
+        self.index = (self.index + 1) % self.history.len();
+    }
+}
@@ -16,6 +16,13 @@ impl InterpolatorKind {
             Self::LogLinear => Self::LOG_LINEAR_TEXT,
         }
     }
+
+    /// gets the kind with the given display string
+    fn from_display_string(text: &str) -> Option<Self> {
+        [Self::Linear, Self::LogLinear]
+            .into_iter()
+            .find(|kind| kind.display_string() == text)
+    }
 }
 
 impl std::fmt::Display for InterpolatorKind {
@@ -59,6 +66,20 @@ impl CircuitBuilder for InterpolatorBuilder {
         self.kind.display_string()
     }
 
+    fn save(&self) -> String {
+        self.kind.display_string().to_string()
+    }
+
+    fn load(&mut self, data: &str) -> bool {
+        match InterpolatorKind::from_display_string(data) {
+            Some(kind) => {
+                self.kind = kind;
+                true
+            }
+            None => false
+        }
+    }
+
     fn specification(&self) -> &'static CircuitSpecification {
         &Self::SPECIFICATION
     }
@@ -0,0 +1,243 @@
+use crate::circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification};
+
+use super::EdgeDetector;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LfoShape {
+    Sine,
+    Triangle,
+    Saw,
+    Square,
+    Random,
+}
+
+impl LfoShape {
+    const SINE_TEXT: &'static str = "Sine";
+    const TRI_TEXT: &'static str = "Triangle";
+    const SAW_TEXT: &'static str = "Sawtooth";
+    const SQR_TEXT: &'static str = "Square";
+    const RANDOM_TEXT: &'static str = "Random";
+
+    const ALL: [Self; 5] = [Self::Sine, Self::Triangle, Self::Saw, Self::Square, Self::Random];
+
+    fn display_string(&self) -> &'static str {
+        match self {
+            Self::Sine => Self::SINE_TEXT,
+            Self::Triangle => Self::TRI_TEXT,
+            Self::Saw => Self::SAW_TEXT,
+            Self::Square => Self::SQR_TEXT,
+            Self::Random => Self::RANDOM_TEXT,
+        }
+    }
+
+    /// gets the shape with the given display string
+    fn from_display_string(text: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|shape| shape.display_string() == text)
+    }
+}
+
+/// A length of one LFO cycle relative to the beat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BeatDivision {
+    Whole,
+    Half,
+    Quarter,
+    DottedEighth,
+    Eighth,
+    EighthTriplet,
+    Sixteenth,
+}
+
+impl BeatDivision {
+    const WHOLE_TEXT: &'static str = "1/1";
+    const HALF_TEXT: &'static str = "1/2";
+    const QUARTER_TEXT: &'static str = "1/4";
+    const DOTTED_EIGHTH_TEXT: &'static str = "1/8.";
+    const EIGHTH_TEXT: &'static str = "1/8";
+    const EIGHTH_TRIPLET_TEXT: &'static str = "1/8t";
+    const SIXTEENTH_TEXT: &'static str = "1/16";
+
+    const ALL: [Self; 7] = [
+        Self::Whole,
+        Self::Half,
+        Self::Quarter,
+        Self::DottedEighth,
+        Self::Eighth,
+        Self::EighthTriplet,
+        Self::Sixteenth,
+    ];
+
+    fn display_string(&self) -> &'static str {
+        match self {
+            Self::Whole => Self::WHOLE_TEXT,
+            Self::Half => Self::HALF_TEXT,
+            Self::Quarter => Self::QUARTER_TEXT,
+            Self::DottedEighth => Self::DOTTED_EIGHTH_TEXT,
+            Self::Eighth => Self::EIGHTH_TEXT,
+            Self::EighthTriplet => Self::EIGHTH_TRIPLET_TEXT,
+            Self::Sixteenth => Self::SIXTEENTH_TEXT,
+        }
+    }
+
+    /// gets the division with the given display string
+    fn from_display_string(text: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|division| division.display_string() == text)
+    }
+
+    /// the length of one cycle in beats (quarter notes)
+    fn beats(&self) -> f32 {
+        match self {
+            Self::Whole => 4.0,
+            Self::Half => 2.0,
+            Self::Quarter => 1.0,
+            Self::DottedEighth => 0.75,
+            Self::Eighth => 0.5,
+            Self::EighthTriplet => 1.0 / 3.0,
+            Self::Sixteenth => 0.25,
+        }
+    }
+}
+
+/// Produces a slow bipolar [-1, 1] modulation signal
+/// The rate is either set in Hz by the Rate input or synced to the Tempo input (in bpm).
+/// Phase offsets the cycle by [0, 1] and a rising edge on Retrigger restarts the cycle.
+#[derive(Debug, Clone)]
+pub struct LfoBuilder {
+    shape: LfoShape,
+
+    /// the cycle length when synced to tempo, or none to use the Rate input
+    division: Option<BeatDivision>,
+}
+
+impl LfoBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &["Rate", "Tempo", "Phase", "Retrigger"],
+        output_names: &["Out"],
+        size: egui::vec2(200.0, 250.0),
+        playback_size: None,
+    };
+
+    const NAME: &'static str = "LFO";
+    const FREE_TEXT: &'static str = "Free";
+
+    pub fn new() -> Self {
+        Self {
+            shape: LfoShape::Sine,
+            division: None,
+        }
+    }
+}
+
+impl CircuitBuilder for LfoBuilder {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        ui.label("Shape:");
+        for shape in LfoShape::ALL {
+            ui.radio_value(&mut self.shape, shape, shape.display_string());
+        }
+
+        ui.separator();
+        ui.label("Rate:");
+        ui.radio_value(&mut self.division, None, "Hz (Rate input)");
+        let mut synced = self.division.is_some();
+        if ui.radio_value(&mut synced, true, "Tempo Sync").clicked() && self.division.is_none() {
+            self.division = Some(BeatDivision::Quarter);
+        }
+        if let Some(division) = self.division.as_mut() {
+            egui::ComboBox::from_id_salt("lfo division")
+                .selected_text(division.display_string())
+                .show_ui(ui, |ui| {
+                    for option in BeatDivision::ALL {
+                        ui.selectable_value(division, option, option.display_string());
+                    }
+                });
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    /// saved as 'shape;division', where the division is 'Free' when not synced
+    fn save(&self) -> String {
+        let division = self.division.map_or(Self::FREE_TEXT, |division| division.display_string());
+        format!("{};{}", self.shape.display_string(), division)
+    }
+
+    fn load(&mut self, data: &str) -> bool {
+        let Some((shape, division)) = data.split_once(';') else {
+            return false;
+        };
+        let Some(shape) = LfoShape::from_display_string(shape) else {
+            return false;
+        };
+        let division = match division {
+            Self::FREE_TEXT => None,
+            text => match BeatDivision::from_display_string(text) {
+                Some(division) => Some(division),
+                None => return false,
+            },
+        };
+
+        self.shape = shape;
+        self.division = division;
+        true
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    fn build(&self, _: &BuildState) -> Box<dyn Circuit> {
+        Box::new(Lfo {
+            shape: self.shape,
+            beats_per_cycle: self.division.map(|division| division.beats()),
+            phase: 0.0,
+            retrigger: EdgeDetector::new(),
+            random: fastrand::Rng::new(),
+            held: 0.0,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Lfo {
+    shape: LfoShape,
+    beats_per_cycle: Option<f32>,
+
+    /// the position within the current cycle [0, 1)
+    phase: f32,
+    retrigger: EdgeDetector,
+
+    random: fastrand::Rng,
+
+    /// the random value held for the current cycle
+    held: f32,
+}
+
+impl Circuit for Lfo {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], delta: f32) {
+        if self.retrigger.rising(inputs[3]) {
+            self.phase = 0.0;
+            self.held = self.random.f32() * 2.0 - 1.0;
+        }
+
+        let t = (self.phase + inputs[2]).rem_euclid(1.0);
+        outputs[0] = match self.shape {
+            LfoShape::Sine => f32::sin(t * std::f32::consts::TAU),
+            LfoShape::Triangle => 1.0 - f32::abs(4.0 * t - 2.0),
+            LfoShape::Saw => 2.0 * t - 1.0,
+            LfoShape::Square => if t < 0.5 { 1.0 } else { -1.0 },
+            LfoShape::Random => self.held,
+        };
+
+        let frequency = match self.beats_per_cycle {
+            Some(beats) => inputs[1] / 60.0 / beats,
+            None => inputs[0],
+        };
+        self.phase += delta * frequency;
+        if self.phase >= 1.0 {
+            self.phase = self.phase.rem_euclid(1.0);
+            self.held = self.random.f32() * 2.0 - 1.0;
+        }
+    }
+}
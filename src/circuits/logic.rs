@@ -0,0 +1,173 @@
+use crate::circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification};
+
+use super::GATE_THRESHOLD;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogicKind {
+    Greater,
+    Less,
+    Window,
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+impl LogicKind {
+    const GREATER_TEXT: &'static str = "A > B";
+    const LESS_TEXT: &'static str = "A < B";
+    const WINDOW_TEXT: &'static str = "Window";
+    const AND_TEXT: &'static str = "AND";
+    const OR_TEXT: &'static str = "OR";
+    const XOR_TEXT: &'static str = "XOR";
+    const NOT_TEXT: &'static str = "NOT";
+
+    const ALL: [Self; 7] = [Self::Greater, Self::Less, Self::Window, Self::And, Self::Or, Self::Xor, Self::Not];
+
+    fn display_string(&self) -> &'static str {
+        match self {
+            Self::Greater => Self::GREATER_TEXT,
+            Self::Less => Self::LESS_TEXT,
+            Self::Window => Self::WINDOW_TEXT,
+            Self::And => Self::AND_TEXT,
+            Self::Or => Self::OR_TEXT,
+            Self::Xor => Self::XOR_TEXT,
+            Self::Not => Self::NOT_TEXT,
+        }
+    }
+
+    /// gets the kind with the given display string
+    fn from_display_string(text: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.display_string() == text)
+    }
+
+    /// whether the kind treats its inputs as gates
+    fn is_boolean(&self) -> bool {
+        matches!(self, Self::And | Self::Or | Self::Xor | Self::Not)
+    }
+}
+
+/// Compares or combines its inputs into a gate signal that is 1 when true and 0 otherwise
+/// - A > B and A < B compare the inputs directly
+/// - Window is true while A is within [low, high]
+/// - AND, OR, XOR, and NOT treat an input above the threshold as true, ignoring B for NOT
+#[derive(Debug, Clone)]
+pub struct LogicBuilder {
+    kind: LogicKind,
+    threshold: f32,
+    threshold_text: String,
+    low: f32,
+    low_text: String,
+    high: f32,
+    high_text: String,
+}
+
+impl LogicBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &["A", "B"],
+        output_names: &["Gate"],
+        size: egui::vec2(200.0, 250.0),
+        playback_size: None,
+    };
+
+    pub fn new() -> Self {
+        let threshold = GATE_THRESHOLD;
+        let low = -0.5;
+        let high = 0.5;
+        Self {
+            kind: LogicKind::Greater,
+            threshold,
+            threshold_text: threshold.to_string(),
+            low,
+            low_text: low.to_string(),
+            high,
+            high_text: high.to_string(),
+        }
+    }
+}
+
+impl CircuitBuilder for LogicBuilder {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        for kind in LogicKind::ALL {
+            ui.radio_value(&mut self.kind, kind, kind.display_string());
+        }
+
+        if self.kind == LogicKind::Window {
+            ui.separator();
+            ui.label("Low:");
+            crate::utils::number_input(ui, &mut self.low_text, &mut self.low);
+            ui.label("High:");
+            crate::utils::number_input(ui, &mut self.high_text, &mut self.high);
+        } else if self.kind.is_boolean() {
+            ui.separator();
+            ui.label("Threshold:");
+            crate::utils::number_input(ui, &mut self.threshold_text, &mut self.threshold);
+        }
+    }
+
+    fn name(&self) -> &str {
+        self.kind.display_string()
+    }
+
+    /// saved as 'kind;threshold;low;high'
+    fn save(&self) -> String {
+        format!("{};{};{};{}", self.kind.display_string(), self.threshold, self.low, self.high)
+    }
+
+    fn load(&mut self, data: &str) -> bool {
+        let mut parts = data.split(';');
+        let Some(kind) = parts.next().and_then(LogicKind::from_display_string) else {
+            return false;
+        };
+        let mut values = parts.map(|text| text.parse::<f32>().ok());
+        let (Some(Some(threshold)), Some(Some(low)), Some(Some(high))) = (values.next(), values.next(), values.next()) else {
+            return false;
+        };
+
+        self.kind = kind;
+        self.threshold = threshold;
+        self.threshold_text = threshold.to_string();
+        self.low = low;
+        self.low_text = low.to_string();
+        self.high = high;
+        self.high_text = high.to_string();
+        true
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    fn build(&self, _: &BuildState) -> Box<dyn Circuit> {
+        Box::new(Logic {
+            kind: self.kind,
+            threshold: self.threshold,
+            low: self.low.min(self.high),
+            high: self.low.max(self.high),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Logic {
+    kind: LogicKind,
+    threshold: f32,
+    low: f32,
+    high: f32,
+}
+
+impl Circuit for Logic {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], _: f32) {
+        let (a, b) = (inputs[0], inputs[1]);
+        let result = match self.kind {
+            LogicKind::Greater => a > b,
+            LogicKind::Less => a < b,
+            LogicKind::Window => self.low <= a && a <= self.high,
+            LogicKind::And => a > self.threshold && b > self.threshold,
+            LogicKind::Or => a > self.threshold || b > self.threshold,
+            LogicKind::Xor => (a > self.threshold) != (b > self.threshold),
+            LogicKind::Not => a <= self.threshold,
+        };
+        outputs[0] = if result { 1.0 } else { 0.0 };
+    }
+}
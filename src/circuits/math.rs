@@ -0,0 +1,100 @@
+use crate::{circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification}, expression::{Expression, ExpressionError}};
+
+/// Outputs the result of an expression over its inputs, such as 'a * 0.5 + b'
+/// The expression is checked as it is typed, and its compiled form is shared by every build.
+#[derive(Debug, Clone)]
+pub struct MathBuilder {
+    text: String,
+
+    /// the last expression that compiled
+    expression: Expression,
+
+    /// the error from compiling the current text, if any
+    error: Option<String>,
+}
+
+impl MathBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &["a", "b", "c", "d"],
+        output_names: &["Out"],
+        size: egui::vec2(200.0, 150.0),
+        playback_size: None,
+    };
+
+    const NAME: &'static str = "Math";
+    const DEFAULT_TEXT: &'static str = "a";
+
+    pub fn new() -> Self {
+        Self {
+            text: Self::DEFAULT_TEXT.to_string(),
+            expression: Self::compile(Self::DEFAULT_TEXT).expect("the default expression should compile"),
+            error: None,
+        }
+    }
+
+    fn compile(text: &str) -> Result<Expression, ExpressionError> {
+        Expression::compile(text, Self::SPECIFICATION.input_names)
+    }
+
+    /// compiles the current text, keeping the last expression that compiled if it does not
+    fn recompile(&mut self) {
+        match Self::compile(&self.text) {
+            Ok(expression) => {
+                self.expression = expression;
+                self.error = None;
+            }
+            Err(err) => self.error = Some(err.to_string()),
+        }
+    }
+}
+
+impl CircuitBuilder for MathBuilder {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        ui.label("Expression:");
+        if ui.text_edit_singleline(&mut self.text).changed() {
+            self.recompile();
+        }
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    /// saved as the expression text, even if it does not compile
+    fn save(&self) -> String {
+        self.text.clone()
+    }
+
+    /// text that does not compile is kept with its error, as if it had been typed
+    fn load(&mut self, data: &str) -> bool {
+        self.text = data.to_string();
+        self.recompile();
+        true
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    fn build(&self, _: &BuildState) -> Box<dyn Circuit> {
+        Box::new(Math {
+            stack: vec![0.0; self.expression.stack_size()],
+            expression: self.expression.clone(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Math {
+    expression: Expression,
+    stack: Vec<f32>,
+}
+
+impl Circuit for Math {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], _: f32) {
+        outputs[0] = self.expression.evaluate(inputs, &mut self.stack);
+    }
+}
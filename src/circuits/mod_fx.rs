@@ -0,0 +1,214 @@
+use crate::{circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification}, frame::{self, Frame}};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ModFxKind {
+    Chorus,
+    Flanger,
+    Phaser,
+}
+
+impl ModFxKind {
+    const CHORUS_TEXT: &'static str = "Chorus";
+    const FLANGER_TEXT: &'static str = "Flanger";
+    const PHASER_TEXT: &'static str = "Phaser";
+
+    fn display_string(&self) -> &'static str {
+        match self {
+            Self::Chorus => Self::CHORUS_TEXT,
+            Self::Flanger => Self::FLANGER_TEXT,
+            Self::Phaser => Self::PHASER_TEXT,
+        }
+    }
+
+    /// gets the kind with the given display string
+    fn from_display_string(text: &str) -> Option<Self> {
+        [Self::Chorus, Self::Flanger, Self::Phaser]
+            .into_iter()
+            .find(|kind| kind.display_string() == text)
+    }
+}
+
+/// A modulation effect mixing the input equally with a modulated copy of itself
+/// - Rate is the frequency of the modulation in Hz
+/// - Depth [0, 1] is how far the delay or allpass frequency is swept
+/// - Feedback [-0.95, 0.95] feeds the wet signal back into the effect
+///
+/// Chorus and Flanger sweep a short delay line, while Phaser sweeps a chain of allpass filters.
+/// The right channel is modulated a quarter cycle after the left to widen the stereo image.
+#[derive(Debug, Clone)]
+pub struct ModFxBuilder {
+    kind: ModFxKind,
+}
+
+impl ModFxBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &["In", "Rate", "Depth", "Feedback"],
+        output_names: &["Out"],
+        size: egui::vec2(150.0, 175.0),
+        playback_size: None,
+    };
+
+    pub fn new() -> Self {
+        Self {
+            kind: ModFxKind::Chorus,
+        }
+    }
+}
+
+impl CircuitBuilder for ModFxBuilder {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        ui.radio_value(&mut self.kind, ModFxKind::Chorus, ModFxKind::CHORUS_TEXT);
+        ui.radio_value(&mut self.kind, ModFxKind::Flanger, ModFxKind::FLANGER_TEXT);
+        ui.radio_value(&mut self.kind, ModFxKind::Phaser, ModFxKind::PHASER_TEXT);
+    }
+
+    fn name(&self) -> &str {
+        self.kind.display_string()
+    }
+
+    fn save(&self) -> String {
+        self.kind.display_string().to_string()
+    }
+
+    fn load(&mut self, data: &str) -> bool {
+        match ModFxKind::from_display_string(data) {
+            Some(kind) => {
+                self.kind = kind;
+                true
+            }
+            None => false
+        }
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    fn build(&self, state: &BuildState) -> Box<dyn Circuit> {
+        let sample_rate = state.sample_rate.max(1) as f32;
+        Box::new(ModFx {
+            kind: self.kind,
+            sample_rate,
+            phase: 0.0,
+            voices: [ModFxVoice::new(sample_rate), ModFxVoice::new(sample_rate)],
+        })
+    }
+}
+
+/// The delay line and allpass chain of one channel
+#[derive(Debug)]
+struct ModFxVoice {
+    delay: Vec<f32>,
+    write: usize,
+
+    /// the previous input and output of each allpass stage
+    allpass: [(f32, f32); ModFxVoice::ALLPASS_STAGES],
+
+    /// the last wet sample, fed back into the effect
+    feedback: f32,
+}
+
+impl ModFxVoice {
+    const ALLPASS_STAGES: usize = 6;
+    const MAX_DELAY: f32 = 0.03;
+
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            delay: vec![0.0; (Self::MAX_DELAY * sample_rate) as usize + 2],
+            write: 0,
+            allpass: [(0.0, 0.0); Self::ALLPASS_STAGES],
+            feedback: 0.0,
+        }
+    }
+
+    /// writes a sample to the delay line and reads back the sample from delay seconds ago
+    fn delay(&mut self, input: f32, delay: f32, sample_rate: f32) -> f32 {
+        let len = self.delay.len();
+        self.delay[self.write] = input;
+
+        let offset = (delay * sample_rate).clamp(1.0, (len - 2) as f32);
+        let read = (self.write + len) as f32 - offset;
+        let index = read as usize;
+        let t = read - index as f32;
+        let (a, b) = (self.delay[index % len], self.delay[(index + 1) % len]);
+
+        self.write = (self.write + 1) % len;
+        a + (b - a) * t
+    }
+
+    /// runs the sample through the allpass chain tuned to the given frequency
+    fn allpass(&mut self, input: f32, frequency: f32, sample_rate: f32) -> f32 {
+        let tan = f32::tan(std::f32::consts::PI * frequency.min(sample_rate * 0.45) / sample_rate);
+        let coefficient = (1.0 - tan) / (1.0 + tan);
+        self.allpass.iter_mut().fold(input, |x, (previous_x, previous_y)| {
+            let y = -coefficient * x + *previous_x + coefficient * *previous_y;
+            *previous_x = x;
+            *previous_y = y;
+            y
+        })
+    }
+
+    /// processes a sample, where lfo is the modulation in [0, 1]
+    fn process(&mut self, kind: ModFxKind, input: f32, lfo: f32, depth: f32, feedback: f32, sample_rate: f32) -> f32 {
+        let input_with_feedback = input + self.feedback * feedback;
+        let wet = match kind {
+            ModFxKind::Chorus => self.delay(input_with_feedback, 0.015 + 0.01 * depth * (lfo - 0.5), sample_rate),
+            ModFxKind::Flanger => self.delay(input_with_feedback, 0.0005 + 0.0045 * depth * lfo, sample_rate),
+            ModFxKind::Phaser => self.allpass(input_with_feedback, 200.0 * f32::exp2(4.0 * depth * lfo), sample_rate),
+        };
+        self.feedback = wet;
+        (input + wet) * 0.5
+    }
+}
+
+#[derive(Debug)]
+pub struct ModFx {
+    kind: ModFxKind,
+    sample_rate: f32,
+
+    /// the position in the modulation cycle [0, 1)
+    phase: f32,
+
+    /// the left and right channels
+    voices: [ModFxVoice; 2],
+}
+
+impl ModFx {
+    const MAX_FEEDBACK: f32 = 0.95;
+
+    /// the modulation at the given cycle position in [0, 1]
+    fn lfo(phase: f32) -> f32 {
+        0.5 - 0.5 * f32::cos(phase * std::f32::consts::TAU)
+    }
+
+    fn advance(&mut self, rate: f32, delta: f32) {
+        self.phase = (self.phase + rate * delta).rem_euclid(1.0);
+    }
+}
+
+impl Circuit for ModFx {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], delta: f32) {
+        let depth = inputs[2].clamp(0.0, 1.0);
+        let feedback = inputs[3].clamp(-Self::MAX_FEEDBACK, Self::MAX_FEEDBACK);
+        let lfo = Self::lfo(self.phase);
+        outputs[0] = self.voices[0].process(self.kind, inputs[0], lfo, depth, feedback, self.sample_rate);
+        self.advance(inputs[1], delta);
+    }
+
+    fn operate_stereo(&mut self, inputs: &[Frame], outputs: &mut[Frame], delta: f32) {
+        let depth = frame::to_mono(inputs[2]).clamp(0.0, 1.0);
+        let feedback = frame::to_mono(inputs[3]).clamp(-Self::MAX_FEEDBACK, Self::MAX_FEEDBACK);
+        let lfos = [Self::lfo(self.phase), Self::lfo(self.phase + 0.25)];
+        for channel in 0..frame::CHANNELS {
+            outputs[0][channel] = self.voices[channel].process(
+                self.kind,
+                inputs[0][channel],
+                lfos[channel],
+                depth,
+                feedback,
+                self.sample_rate
+            );
+        }
+        self.advance(frame::to_mono(inputs[1]), delta);
+    }
+}
@@ -1,5 +1,7 @@
 use crate::circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification};
 
+use super::EdgeDetector;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum OscillatorKind {
     Sine,
@@ -22,6 +24,13 @@ impl OscillatorKind {
             Self::Triangle => Self::TRI_TEXT,
         }
     }
+
+    /// gets the kind with the given display string
+    fn from_display_string(text: &str) -> Option<Self> {
+        [Self::Sine, Self::Saw, Self::Square, Self::Triangle]
+            .into_iter()
+            .find(|kind| kind.display_string() == text)
+    }
 }
 
 impl std::fmt::Display for OscillatorKind {
@@ -34,6 +43,9 @@ impl std::fmt::Display for OscillatorKind {
     }
 }
 
+/// Produces a periodic wave with the given amplitude and frequency
+/// A rising edge on Sync restarts the cycle, for hard sync sounds.
+/// Pulse Width [-1, 1] narrows or widens the high part of the square wave from half the cycle.
 #[derive(Debug, Clone)]
 pub struct OscillatorBuilder {
     kind: OscillatorKind
@@ -41,7 +53,7 @@ pub struct OscillatorBuilder {
 
 impl OscillatorBuilder {
     const SPECIFICATION: CircuitSpecification = CircuitSpecification {
-        input_names: &["Amplitude", "Frequency"],
+        input_names: &["Amplitude", "Frequency", "Sync", "Pulse Width"],
         output_names: &["Out"],
         size: egui::vec2(200.0, 200.0),
         playback_size: None,
@@ -66,6 +78,20 @@ impl CircuitBuilder for OscillatorBuilder {
         self.kind.display_string()
     }
 
+    fn save(&self) -> String {
+        self.kind.display_string().to_string()
+    }
+
+    fn load(&mut self, data: &str) -> bool {
+        match OscillatorKind::from_display_string(data) {
+            Some(kind) => {
+                self.kind = kind;
+                true
+            }
+            None => false
+        }
+    }
+
     fn specification(&self) -> &'static CircuitSpecification {
         &Self::SPECIFICATION
     }
@@ -82,11 +108,16 @@ impl CircuitBuilder for OscillatorBuilder {
 
 #[derive(Debug, Default)]
 pub struct Sine {
-    index: f32
+    index: f32,
+    sync: EdgeDetector
 }
 
 impl Circuit for Sine {
     fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], delta: f32) {
+        if self.sync.rising(inputs[2]) {
+            self.index = 0.0;
+        }
+
         //Sine function with amplitude inputs[0] and frequency 1hz
         outputs[0] = inputs[0] * f32::sin(self.index * std::f32::consts::TAU);
 
@@ -99,11 +130,16 @@ impl Circuit for Sine {
 
 #[derive(Debug, Default)]
 pub struct Saw {
-    index: f32
+    index: f32,
+    sync: EdgeDetector
 }
 
 impl Circuit for Saw {
     fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], delta: f32) {
+        if self.sync.rising(inputs[2]) {
+            self.index = 0.0;
+        }
+
         //reverse sawtooth function with amplitude inputs[0] and frequency 1hz
         outputs[0] = inputs[0] * (self.index - 1.0);
 
@@ -116,13 +152,25 @@ impl Circuit for Saw {
 
 #[derive(Debug, Default)]
 pub struct Square {
-    index: f32
+    index: f32,
+    sync: EdgeDetector
+}
+
+impl Square {
+    /// the narrowest the high or low part of the wave may be, as a fraction of the cycle
+    const MIN_WIDTH: f32 = 0.01;
 }
 
 impl Circuit for Square {
     fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], delta: f32) {
-        //squarewave function with amplitude inputs[0] and frequency 1hz
-        outputs[0] = inputs[0] * (2.0 * f32::floor(2.0 * (self.index % 2.0)) - 1.0);
+        if self.sync.rising(inputs[2]) {
+            self.index = 0.0;
+        }
+
+        //squarewave function with amplitude inputs[0], frequency 1hz, and
+        //a high part lasting width of the cycle
+        let width = (0.5 + 0.5 * inputs[3]).clamp(Self::MIN_WIDTH, 1.0 - Self::MIN_WIDTH);
+        outputs[0] = inputs[0] * if self.index < 1.0 - width { -1.0 } else { 1.0 };
 
         //Incriment index by interval * frequency, effectively making sine function
         //have a frequency of inputs[1]
@@ -133,19 +181,30 @@ impl Circuit for Square {
 
 #[derive(Debug)]
 pub struct Triangle {
-    index: f32
+    index: f32,
+    sync: EdgeDetector
+}
+
+impl Triangle {
+    /// the index at the start of the cycle, where the wave is 0 and rising
+    const START_INDEX: f32 = 0.75;
 }
 
 impl Default for Triangle {
     fn default() -> Self {
         Self {
-            index: 0.75
+            index: Self::START_INDEX,
+            sync: EdgeDetector::new()
         }
     }
 }
 
 impl Circuit for Triangle {
     fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], delta: f32) {
+        if self.sync.rising(inputs[2]) {
+            self.index = Self::START_INDEX;
+        }
+
         //triangle function with amplitude inputs[0] and frequency 1hz
         outputs[0] = inputs[0] * ( f32::abs(4.0 * ( self.index % 1.0 ) - 2.0) - 1.0 );
 
@@ -0,0 +1,215 @@
+use crate::circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification};
+
+/// Estimates the fundamental frequency of its input with the YIN algorithm
+/// Frequency holds the last detected pitch in Hz, and Confidence [0, 1] is how periodic the input was.
+/// Pitches are only searched for between the minimum and maximum frequencies; a lower minimum
+/// needs a longer analysis window, adding latency and work.
+#[derive(Debug, Clone)]
+pub struct PitchDetectBuilder {
+    min_frequency: f32,
+    min_text: String,
+    max_frequency: f32,
+    max_text: String,
+
+    /// the largest normalized difference accepted as periodic (0, 1)
+    threshold: f32,
+    threshold_text: String,
+}
+
+impl PitchDetectBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &["In"],
+        output_names: &["Frequency", "Confidence"],
+        size: egui::vec2(200.0, 250.0),
+        playback_size: None,
+    };
+
+    const NAME: &'static str = "Pitch Detect";
+
+    pub fn new() -> Self {
+        let min_value = 60.0;
+        let max_value = 1500.0;
+        let threshold_value = 0.15;
+        Self {
+            min_frequency: min_value,
+            min_text: min_value.to_string(),
+            max_frequency: max_value,
+            max_text: max_value.to_string(),
+            threshold: threshold_value,
+            threshold_text: threshold_value.to_string(),
+        }
+    }
+}
+
+impl CircuitBuilder for PitchDetectBuilder {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        ui.label("Min Frequency (Hz):");
+        crate::utils::pos_number_input(ui, &mut self.min_text, &mut self.min_frequency);
+        ui.label("Max Frequency (Hz):");
+        crate::utils::pos_number_input(ui, &mut self.max_text, &mut self.max_frequency);
+        ui.label("Threshold:");
+        if crate::utils::pos_number_input(ui, &mut self.threshold_text, &mut self.threshold) && self.threshold >= 1.0 {
+            self.threshold = 0.99;
+            self.threshold_text = self.threshold.to_string();
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    /// saved as 'min frequency;max frequency;threshold'
+    fn save(&self) -> String {
+        format!("{};{};{}", self.min_frequency, self.max_frequency, self.threshold)
+    }
+
+    fn load(&mut self, data: &str) -> bool {
+        let values: Vec<Option<f32>> = data.split(';').map(|text| text.parse().ok()).collect();
+        let [Some(min), Some(max), Some(threshold)] = values[..] else {
+            return false;
+        };
+        if min <= 0.0 || max <= 0.0 || threshold <= 0.0 || threshold >= 1.0 {
+            return false;
+        }
+
+        self.min_frequency = min;
+        self.min_text = min.to_string();
+        self.max_frequency = max;
+        self.max_text = max.to_string();
+        self.threshold = threshold;
+        self.threshold_text = threshold.to_string();
+        true
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    fn build(&self, state: &BuildState) -> Box<dyn Circuit> {
+        Box::new(PitchDetect::new(state.sample_rate, self.min_frequency, self.max_frequency, self.threshold))
+    }
+}
+
+#[derive(Debug)]
+pub struct PitchDetect {
+    sample_rate: f32,
+    threshold: f32,
+
+    /// the range of periods searched, in samples
+    min_lag: usize,
+    max_lag: usize,
+
+    /// the latest samples, as a ring buffer of twice the longest period
+    history: Vec<f32>,
+    write: usize,
+
+    /// the samples left before the next analysis
+    until_analysis: usize,
+
+    /// reused during analysis
+    window: Vec<f32>,
+    difference: Vec<f32>,
+
+    frequency: f32,
+    confidence: f32,
+}
+
+impl PitchDetect {
+    /// creates a detector searching for pitches between the given frequencies
+    pub fn new(sample_rate: u32, min_frequency: f32, max_frequency: f32, threshold: f32) -> Self {
+        let sample_rate = sample_rate.max(1) as f32;
+        let (min_frequency, max_frequency) = (min_frequency.min(max_frequency), min_frequency.max(max_frequency));
+        let max_lag = ((sample_rate / min_frequency).ceil() as usize).max(2);
+        let min_lag = ((sample_rate / max_frequency).floor() as usize).clamp(1, max_lag - 1);
+        Self {
+            sample_rate,
+            threshold,
+            min_lag,
+            max_lag,
+            history: vec![0.0; max_lag * 2],
+            write: 0,
+            until_analysis: max_lag,
+            window: vec![0.0; max_lag * 2],
+            difference: vec![0.0; max_lag + 1],
+            frequency: 0.0,
+            confidence: 0.0,
+        }
+    }
+
+    /// the last detected frequency in Hz
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    /// how periodic the input was at the last analysis [0, 1], or 0 if no pitch was found
+    pub fn confidence(&self) -> f32 {
+        self.confidence
+    }
+
+    /// adds a sample to the history, analyzing it once enough samples have arrived
+    /// returns true if an analysis was run
+    pub fn push(&mut self, sample: f32) -> bool {
+        self.history[self.write] = sample;
+        self.write = (self.write + 1) % self.history.len();
+
+        self.until_analysis -= 1;
+        if self.until_analysis == 0 {
+            self.until_analysis = self.max_lag;
+            self.analyze();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// runs YIN over the history, updating the frequency if a period was found
+    fn analyze(&mut self) {
+        let len = self.history.len();
+        for (index, sample) in self.window.iter_mut().enumerate() {
+            *sample = self.history[(self.write + index) % len];
+        }
+
+        // the difference function normalized by its running mean
+        let size = self.max_lag;
+        let mut running_sum = 0.0;
+        self.difference[0] = 1.0;
+        for lag in 1..=self.max_lag {
+            let sum: f32 = self.window[..size].iter()
+                .zip(&self.window[lag..lag + size])
+                .map(|(a, b)| (a - b) * (a - b))
+                .sum();
+            running_sum += sum;
+            self.difference[lag] = if running_sum > 0.0 { sum * lag as f32 / running_sum } else { 1.0 };
+        }
+
+        // the first dip below the threshold, followed down to its minimum
+        let Some(mut lag) = (self.min_lag..=self.max_lag).find(|lag| self.difference[*lag] < self.threshold) else {
+            self.confidence = 0.0;
+            return;
+        };
+        while lag < self.max_lag && self.difference[lag + 1] < self.difference[lag] {
+            lag += 1;
+        }
+
+        // refine the period between samples with a parabola through the neighbors
+        let mut period = lag as f32;
+        if lag > 1 && lag < self.max_lag {
+            let (a, b, c) = (self.difference[lag - 1], self.difference[lag], self.difference[lag + 1]);
+            let curvature = a - 2.0 * b + c;
+            if curvature > 0.0 {
+                period += 0.5 * (a - c) / curvature;
+            }
+        }
+
+        self.frequency = self.sample_rate / period;
+        self.confidence = (1.0 - self.difference[lag]).clamp(0.0, 1.0);
+    }
+}
+
+impl Circuit for PitchDetect {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], _: f32) {
+        self.push(inputs[0]);
+        outputs[0] = self.frequency;
+        outputs[1] = self.confidence;
+    }
+}
@@ -0,0 +1,97 @@
+use crate::circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification};
+
+use super::EdgeDetector;
+
+/// A plucked string using Karplus-Strong synthesis
+/// A rising edge on Trigger plucks the string by filling it with noise, which then rings at the Frequency input.
+/// Damping [0, 1] sets how quickly the string dies away, from a long sustain to a muted pluck.
+#[derive(Debug, Clone)]
+pub struct PluckBuilder {
+}
+
+impl PluckBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &["Frequency", "Damping", "Trigger"],
+        output_names: &["Out"],
+        size: egui::vec2(150.0, 150.0),
+        playback_size: None,
+    };
+
+    const NAME: &'static str = "Pluck";
+
+    pub fn new() -> Self {
+        Self{ }
+    }
+}
+
+impl CircuitBuilder for PluckBuilder {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    fn build(&self, state: &BuildState) -> Box<dyn Circuit> {
+        let sample_rate = state.sample_rate.max(1) as f32;
+        Box::new(Pluck {
+            sample_rate,
+            string: vec![0.0; (sample_rate / Pluck::MIN_FREQUENCY) as usize + 2],
+            write: 0,
+            previous: 0.0,
+            trigger: EdgeDetector::new(),
+            random: fastrand::Rng::new(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Pluck {
+    sample_rate: f32,
+
+    /// the delay line holding one period of the string
+    string: Vec<f32>,
+    write: usize,
+
+    /// the last sample read from the string
+    previous: f32,
+
+    trigger: EdgeDetector,
+    random: fastrand::Rng,
+}
+
+impl Pluck {
+    /// the lowest frequency the string can be tuned to
+    const MIN_FREQUENCY: f32 = 20.0;
+
+    /// the most energy lost per period with full damping
+    const MAX_DAMPING: f32 = 0.1;
+}
+
+impl Circuit for Pluck {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], _: f32) {
+        if self.trigger.rising(inputs[2]) {
+            for sample in &mut self.string {
+                *sample = self.random.f32() * 2.0 - 1.0;
+            }
+        }
+
+        // the averaging filter delays by half a sample, so it is taken off the period
+        let len = self.string.len();
+        let period = self.sample_rate / inputs[0].max(Self::MIN_FREQUENCY) - 0.5;
+        let delay = period.clamp(1.0, (len - 2) as f32);
+        let read = (self.write + len) as f32 - delay;
+        let index = read as usize;
+        let t = read - index as f32;
+        let (a, b) = (self.string[index % len], self.string[(index + 1) % len]);
+        let current = a + (b - a) * t;
+
+        let decay = 1.0 - Self::MAX_DAMPING * inputs[1].clamp(0.0, 1.0);
+        self.string[self.write] = decay * 0.5 * (current + self.previous);
+        self.write = (self.write + 1) % len;
+        self.previous = current;
+
+        outputs[0] = current;
+    }
+}
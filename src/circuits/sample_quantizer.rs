@@ -22,6 +22,13 @@ impl SampleQuantizerKind {
             Self::Microtone => Self::MICROTONE_TEXT,
         }
     }
+
+    /// gets the kind with the given display string
+    fn from_display_string(text: &str) -> Option<Self> {
+        [Self::Multiple, Self::MajorScale, Self::Semitone, Self::Microtone]
+            .into_iter()
+            .find(|kind| kind.display_string() == text)
+    }
 }
 
 impl std::fmt::Display for SampleQuantizerKind {
@@ -66,6 +73,20 @@ impl CircuitBuilder for SampleQuantizerBuilder {
         self.kind.display_string()
     }
 
+    fn save(&self) -> String {
+        self.kind.display_string().to_string()
+    }
+
+    fn load(&mut self, data: &str) -> bool {
+        match SampleQuantizerKind::from_display_string(data) {
+            Some(kind) => {
+                self.kind = kind;
+                true
+            }
+            None => false
+        }
+    }
+
     fn specification(&self) -> &'static CircuitSpecification {
         &Self::SPECIFICATION
     }
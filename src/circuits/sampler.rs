@@ -0,0 +1,313 @@
+use std::{path::PathBuf, sync::Arc};
+
+use crate::{bundle::{AssetKind, AssetReference, ProjectBundle}, circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification}, wav::{WavData, WavError}};
+
+use super::EdgeDetector;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SamplerMode {
+    Gate,
+    Trigger,
+}
+
+impl SamplerMode {
+    const GATE_TEXT: &'static str = "Gate";
+    const TRIGGER_TEXT: &'static str = "Trigger";
+
+    fn display_string(&self) -> &'static str {
+        match self {
+            Self::Gate => Self::GATE_TEXT,
+            Self::Trigger => Self::TRIGGER_TEXT,
+        }
+    }
+
+    /// gets the mode with the given display string
+    fn from_display_string(text: &str) -> Option<Self> {
+        [Self::Gate, Self::Trigger]
+            .into_iter()
+            .find(|mode| mode.display_string() == text)
+    }
+}
+
+/// A sample loaded from a WAV file, mixed down to mono
+#[derive(Debug)]
+struct SampleBuffer {
+    samples: Vec<f32>,
+    sample_rate: u32,
+}
+
+/// Plays a WAV file, pitched by the Frequency input relative to the sample's root frequency
+/// A rising edge on Gate starts the sample from the start offset.
+/// In gate mode the sample stops when the gate falls, and loops between the loop points while held if looping.
+/// In trigger mode the sample plays through once.
+/// Positions are fractions [0, 1] of the sample's length.
+#[derive(Debug, Clone)]
+pub struct SamplerBuilder {
+    path: Option<PathBuf>,
+    path_text: String,
+    buffer: Option<Arc<SampleBuffer>>,
+
+    /// the error from loading the sample, if any
+    error: Option<String>,
+
+    mode: SamplerMode,
+    looping: bool,
+
+    /// the frequency the sample plays at its original speed
+    root: f32,
+    root_text: String,
+
+    start: f32,
+    start_text: String,
+    loop_start: f32,
+    loop_start_text: String,
+    loop_end: f32,
+    loop_end_text: String,
+}
+
+impl SamplerBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &["Frequency", "Gate"],
+        output_names: &["Out"],
+        size: egui::vec2(250.0, 400.0),
+        playback_size: None,
+    };
+
+    const NAME: &'static str = "Sampler";
+
+    pub fn new() -> Self {
+        let root = 440.0;
+        let start = 0.0;
+        let loop_start = 0.0;
+        let loop_end = 1.0;
+        Self {
+            path: None,
+            path_text: String::new(),
+            buffer: None,
+            error: None,
+            mode: SamplerMode::Gate,
+            looping: false,
+            root,
+            root_text: root.to_string(),
+            start,
+            start_text: start.to_string(),
+            loop_start,
+            loop_start_text: loop_start.to_string(),
+            loop_end,
+            loop_end_text: loop_end.to_string(),
+        }
+    }
+
+    /// loads the sample at path, keeping the error if it could not be read
+    fn load_sample(&mut self, path: PathBuf) {
+        let wav = WavData::read(&path);
+        self.set_sample(path, wav);
+    }
+
+    /// uses the sample read from path, keeping the error if it could not be decoded
+    fn set_sample(&mut self, path: PathBuf, wav: Result<WavData, WavError>) {
+        self.path_text = path.display().to_string();
+        match wav {
+            Ok(wav) => {
+                self.buffer = Some(Arc::new(SampleBuffer {
+                    samples: wav.to_mono(),
+                    sample_rate: wav.sample_rate,
+                }));
+                self.error = None;
+            }
+            Err(err) => {
+                self.buffer = None;
+                self.error = Some(err.to_string());
+            }
+        }
+        self.path = Some(path);
+    }
+
+    /// shows a number input for a position [0, 1]
+    fn position_input(ui: &mut egui::Ui, label: &str, text: &mut String, value: &mut f32) {
+        ui.label(label);
+        if crate::utils::non_neg_number_input(ui, text, value) && *value > 1.0 {
+            *value = 1.0;
+            *text = value.to_string();
+        }
+    }
+}
+
+impl CircuitBuilder for SamplerBuilder {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        ui.label("WAV File:");
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.path_text);
+            if ui.button("Load").clicked() {
+                self.load_sample(PathBuf::from(self.path_text.trim()));
+            }
+        });
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+
+        ui.separator();
+        ui.label("Root Frequency (Hz):");
+        crate::utils::pos_number_input(ui, &mut self.root_text, &mut self.root);
+        Self::position_input(ui, "Start Offset:", &mut self.start_text, &mut self.start);
+
+        ui.separator();
+        ui.radio_value(&mut self.mode, SamplerMode::Gate, SamplerMode::GATE_TEXT);
+        ui.radio_value(&mut self.mode, SamplerMode::Trigger, SamplerMode::TRIGGER_TEXT);
+        if self.mode == SamplerMode::Gate {
+            ui.checkbox(&mut self.looping, "Loop");
+            if self.looping {
+                Self::position_input(ui, "Loop Start:", &mut self.loop_start_text, &mut self.loop_start);
+                Self::position_input(ui, "Loop End:", &mut self.loop_end_text, &mut self.loop_end);
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn assets(&self) -> Vec<AssetReference> {
+        self.path
+            .iter()
+            .map(|path| AssetReference::new(AssetKind::Sample, path))
+            .collect()
+    }
+
+    fn resolve_assets(&mut self, bundle: &ProjectBundle) {
+        let Some(path) = self.path.clone().filter(|path| bundle.contains_asset(path)) else {
+            return;
+        };
+        if let Ok(data) = bundle.resolve_asset(&path) {
+            let wav = WavData::from_bytes(&data);
+            self.set_sample(path, wav);
+        }
+    }
+
+    /// saved as 'mode;looping;root;start;loop start;loop end;path'
+    fn save(&self) -> String {
+        let path = self.path.as_ref().map(|path| path.display().to_string()).unwrap_or_default();
+        format!(
+            "{};{};{};{};{};{};{}",
+            self.mode.display_string(),
+            self.looping,
+            self.root,
+            self.start,
+            self.loop_start,
+            self.loop_end,
+            path
+        )
+    }
+
+    fn load(&mut self, data: &str) -> bool {
+        let parts: Vec<&str> = data.splitn(7, ';').collect();
+        let [mode, looping, root, start, loop_start, loop_end, path] = parts[..] else {
+            return false;
+        };
+        let Some(mode) = SamplerMode::from_display_string(mode) else {
+            return false;
+        };
+        let Ok(looping) = looping.parse::<bool>() else {
+            return false;
+        };
+        let positions = [root, start, loop_start, loop_end].map(|text| text.parse::<f32>().ok());
+        let [Some(root), Some(start), Some(loop_start), Some(loop_end)] = positions else {
+            return false;
+        };
+        if root <= 0.0 || ![start, loop_start, loop_end].iter().all(|value| (0.0..=1.0).contains(value)) {
+            return false;
+        }
+
+        self.mode = mode;
+        self.looping = looping;
+        self.root = root;
+        self.root_text = root.to_string();
+        self.start = start;
+        self.start_text = start.to_string();
+        self.loop_start = loop_start;
+        self.loop_start_text = loop_start.to_string();
+        self.loop_end = loop_end;
+        self.loop_end_text = loop_end.to_string();
+
+        // a missing sample is reported in the ui rather than failing the whole patch
+        if path.is_empty() {
+            self.path = None;
+            self.path_text.clear();
+            self.buffer = None;
+            self.error = None;
+        } else {
+            self.load_sample(PathBuf::from(path));
+        }
+        true
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    fn build(&self, _: &BuildState) -> Box<dyn Circuit> {
+        let buffer = self.buffer.clone().unwrap_or_else(|| Arc::new(SampleBuffer {
+            samples: Vec::new(),
+            sample_rate: 1,
+        }));
+        let last = buffer.samples.len().saturating_sub(1) as f64;
+        let loop_start = self.loop_start.min(self.loop_end) as f64 * last;
+        let loop_end = self.loop_start.max(self.loop_end) as f64 * last;
+        Box::new(Sampler {
+            start: self.start as f64 * last,
+            looping: self.looping && self.mode == SamplerMode::Gate && loop_end > loop_start,
+            loop_start,
+            loop_end,
+            buffer,
+            mode: self.mode,
+            root: self.root,
+            position: None,
+            gate: EdgeDetector::new(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Sampler {
+    buffer: Arc<SampleBuffer>,
+    mode: SamplerMode,
+    root: f32,
+
+    /// positions in frames
+    start: f64,
+    looping: bool,
+    loop_start: f64,
+    loop_end: f64,
+
+    /// the current position in frames, or none while stopped
+    position: Option<f64>,
+    gate: EdgeDetector,
+}
+
+impl Circuit for Sampler {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], delta: f32) {
+        if self.gate.rising(inputs[1]) {
+            self.position = Some(self.start);
+        } else if self.mode == SamplerMode::Gate && !self.gate.is_high() {
+            self.position = None;
+        }
+
+        let samples = &self.buffer.samples;
+        let Some(position) = self.position.filter(|position| *position >= 0.0 && (*position as usize) + 1 < samples.len()) else {
+            self.position = None;
+            outputs[0] = 0.0;
+            return;
+        };
+
+        let index = position as usize;
+        let t = (position - index as f64) as f32;
+        outputs[0] = samples[index] + (samples[index + 1] - samples[index]) * t;
+
+        let speed = inputs[0] / self.root;
+        let mut position = position + delta as f64 * self.buffer.sample_rate as f64 * speed as f64;
+        if self.looping && position >= self.loop_end {
+            position = self.loop_start + (position - self.loop_end) % (self.loop_end - self.loop_start);
+        }
+        self.position = Some(position);
+    }
+}
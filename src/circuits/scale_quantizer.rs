@@ -0,0 +1,99 @@
+use crate::{circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification}, pitch::{equal_temperment, Pitch, ScaleKind}};
+
+/// Snaps a frequency to the nearest note of a scale
+/// The root is given as a pitch, such as 'C4', whose octave does not matter.
+#[derive(Debug, Clone)]
+pub struct ScaleQuantizerBuilder {
+    kind: ScaleKind,
+    root: Pitch,
+    root_text: String,
+}
+
+impl ScaleQuantizerBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &["Frequency"],
+        output_names: &["Out"],
+        size: egui::vec2(200.0, 425.0),
+        playback_size: None,
+    };
+
+    const NAME: &'static str = "Scale Quantizer";
+
+    pub fn new() -> Self {
+        let root = Pitch::default();
+        Self {
+            kind: ScaleKind::Major,
+            root,
+            root_text: root.to_string(),
+        }
+    }
+}
+
+impl CircuitBuilder for ScaleQuantizerBuilder {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        ui.label("Root:");
+        crate::utils::number_input(ui, &mut self.root_text, &mut self.root);
+
+        ui.separator();
+        ui.label("Scale:");
+        for kind in ScaleKind::ALL {
+            ui.radio_value(&mut self.kind, kind, kind.display_string());
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    /// saved as 'scale;root'
+    fn save(&self) -> String {
+        format!("{};{}", self.kind.display_string(), self.root)
+    }
+
+    fn load(&mut self, data: &str) -> bool {
+        let Some((kind, root)) = data.split_once(';') else {
+            return false;
+        };
+        let (Some(kind), Ok(root)) = (ScaleKind::from_display_string(kind), root.parse::<Pitch>()) else {
+            return false;
+        };
+
+        self.kind = kind;
+        self.root = root;
+        self.root_text = root.to_string();
+        true
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    fn build(&self, state: &BuildState) -> Box<dyn Circuit> {
+        Box::new(ScaleQuantizer {
+            kind: self.kind,
+            root: self.root.frequency(&state.tuning, 0),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ScaleQuantizer {
+    kind: ScaleKind,
+
+    /// the frequency of the root in any octave
+    root: f64,
+}
+
+impl Circuit for ScaleQuantizer {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], _: f32) {
+        let frequency = inputs[0] as f64;
+        if frequency <= 0.0 {
+            outputs[0] = 0.0;
+            return;
+        }
+        outputs[0] = match self.kind {
+            ScaleKind::Major => equal_temperment::quantize_major_scale(self.root, frequency),
+            kind => equal_temperment::quantize_scale(self.root, frequency, kind.steps()),
+        } as f32;
+    }
+}
@@ -0,0 +1,190 @@
+use std::sync::{Arc, OnceLock};
+
+use rhai::{Engine, Map, Scope, AST};
+
+use crate::circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification, CircuitUi};
+
+/// Runs a rhai script once per sample, letting DSP be prototyped without recompiling
+/// The script reads the inputs from 'in1' to 'in4' and writes the outputs to 'out1' to 'out4'.
+/// 'dt' is the length of a sample in seconds and 'sample_rate' is its inverse.
+/// Variables declared with 'let' only last one sample; values kept between samples are stored in the 'state' map,
+/// such as 'state.phase = (state.phase ?? 0.0) + in1 * dt;'.
+/// A script that fails while playing stops and outputs silence, showing its error.
+#[derive(Debug, Clone)]
+pub struct ScriptBuilder {
+    text: String,
+
+    /// the last script that compiled
+    ast: AST,
+
+    /// the error from compiling the current text, if any
+    error: Option<String>,
+}
+
+impl ScriptBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &["In 1", "In 2", "In 3", "In 4"],
+        output_names: &["Out 1", "Out 2", "Out 3", "Out 4"],
+        size: egui::vec2(300.0, 300.0),
+        playback_size: Some(egui::vec2(200.0, 60.0)),
+    };
+
+    const NAME: &'static str = "Script";
+    const DEFAULT_TEXT: &'static str = "out1 = in1;";
+
+    const INPUT_VARIABLES: [&'static str; 4] = ["in1", "in2", "in3", "in4"];
+    const OUTPUT_VARIABLES: [&'static str; 4] = ["out1", "out2", "out3", "out4"];
+
+    /// the most operations a script may take for one sample before it is stopped
+    const MAX_OPERATIONS: u64 = 100_000;
+
+    pub fn new() -> Self {
+        Self {
+            text: Self::DEFAULT_TEXT.to_string(),
+            ast: Self::compile(Self::DEFAULT_TEXT).expect("the default script should compile"),
+            error: None,
+        }
+    }
+
+    fn engine() -> Engine {
+        let mut engine = Engine::new();
+        engine.set_max_operations(Self::MAX_OPERATIONS);
+        engine
+    }
+
+    /// the scope every run of the script starts from
+    fn scope(delta: f32) -> Scope<'static> {
+        let mut scope = Scope::new();
+        for name in Self::INPUT_VARIABLES.into_iter().chain(Self::OUTPUT_VARIABLES) {
+            scope.push(name, 0.0f32);
+        }
+        scope.push_constant("dt", delta);
+        scope.push_constant("sample_rate", 1.0 / delta);
+        scope.push("state", Map::new());
+        scope
+    }
+
+    fn compile(text: &str) -> Result<AST, String> {
+        // the variables are declared so they are known while optimizing
+        Self::engine()
+            .compile_with_scope(&Self::scope(1.0), text)
+            .map_err(|err| err.to_string())
+    }
+
+    /// compiles the current text, keeping the last script that compiled if it does not
+    fn recompile(&mut self) {
+        match Self::compile(&self.text) {
+            Ok(ast) => {
+                self.ast = ast;
+                self.error = None;
+            }
+            Err(err) => self.error = Some(err),
+        }
+    }
+}
+
+impl CircuitBuilder for ScriptBuilder {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        ui.label("Script:");
+        if ui.code_editor(&mut self.text).changed() {
+            self.recompile();
+        }
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    /// saved as the script text, even if it does not compile
+    fn save(&self) -> String {
+        self.text.clone()
+    }
+
+    /// text that does not compile is kept with its error, as if it had been typed
+    fn load(&mut self, data: &str) -> bool {
+        self.text = data.to_string();
+        self.recompile();
+        true
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    fn build(&self, state: &BuildState) -> Box<dyn Circuit> {
+        let error = Arc::new(OnceLock::new());
+        state.add_ui(Box::new(ScriptUi { error: error.clone() }));
+
+        let scope = Self::scope(1.0 / state.sample_rate.max(1) as f32);
+        Box::new(Script {
+            engine: Self::engine(),
+            ast: self.ast.clone(),
+            base_len: scope.len(),
+            scope,
+            error,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+
+    /// the length of the scope before the script runs, which it is rewound to afterwards
+    base_len: usize,
+
+    /// the error the script stopped with, if any
+    error: Arc<OnceLock<String>>,
+}
+
+impl Script {
+    /// reads a number from the scope, accepting integers as well
+    fn number(&self, name: &str) -> f32 {
+        self.scope.get(name)
+            .and_then(|value| value.as_float().ok().or_else(|| value.as_int().ok().map(|value| value as f32)))
+            .unwrap_or(0.0)
+    }
+}
+
+impl Circuit for Script {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], _: f32) {
+        if self.error.get().is_some() {
+            outputs.fill(0.0);
+            return;
+        }
+
+        for (name, input) in ScriptBuilder::INPUT_VARIABLES.into_iter().zip(inputs) {
+            self.scope.set_value(name, *input);
+        }
+        let result = self.engine.run_ast_with_scope(&mut self.scope, &self.ast);
+        self.scope.rewind(self.base_len);
+
+        if let Err(err) = result {
+            let _ = self.error.set(err.to_string());
+            outputs.fill(0.0);
+            return;
+        }
+        for (name, output) in ScriptBuilder::OUTPUT_VARIABLES.into_iter().zip(outputs) {
+            *output = self.number(name);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ScriptUi {
+    error: Arc<OnceLock<String>>,
+}
+
+impl CircuitUi for ScriptUi {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        match self.error.get() {
+            Some(error) => ui.colored_label(egui::Color32::RED, error),
+            None => ui.label("Running"),
+        };
+    }
+}
@@ -0,0 +1,154 @@
+use crate::circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SlewKind {
+    Linear,
+    Exponential,
+}
+
+impl SlewKind {
+    const LINEAR_TEXT: &'static str = "Linear Slew";
+    const EXPONENTIAL_TEXT: &'static str = "Exponential Slew";
+
+    fn display_string(&self) -> &'static str {
+        match self {
+            Self::Linear => Self::LINEAR_TEXT,
+            Self::Exponential => Self::EXPONENTIAL_TEXT,
+        }
+    }
+
+    /// gets the kind with the given display string
+    fn from_display_string(text: &str) -> Option<Self> {
+        [Self::Linear, Self::Exponential]
+            .into_iter()
+            .find(|kind| kind.display_string() == text)
+    }
+}
+
+/// Limits how quickly its output follows the input, with separate times for rising and falling
+/// With a linear slew, the times are how long the output takes to move by 1.
+/// With an exponential slew, the times are how long the output takes to cover about 63% of the distance to the input.
+#[derive(Debug, Clone)]
+pub struct SlewBuilder {
+    kind: SlewKind,
+    rise_duration: f32,
+    rise_text: String,
+    fall_duration: f32,
+    fall_text: String,
+}
+
+impl SlewBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &["In"],
+        output_names: &["Out"],
+        size: egui::vec2(200.0, 225.0),
+        playback_size: None,
+    };
+
+    pub fn new() -> Self {
+        let rise_value = 100.0;
+        let fall_value = 100.0;
+        Self {
+            kind: SlewKind::Linear,
+            rise_duration: rise_value,
+            rise_text: rise_value.to_string(),
+            fall_duration: fall_value,
+            fall_text: fall_value.to_string(),
+        }
+    }
+}
+
+impl CircuitBuilder for SlewBuilder {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        ui.radio_value(&mut self.kind, SlewKind::Linear, SlewKind::LINEAR_TEXT);
+        ui.radio_value(&mut self.kind, SlewKind::Exponential, SlewKind::EXPONENTIAL_TEXT);
+
+        ui.separator();
+        ui.label("Rise Time (ms):");
+        crate::utils::non_neg_number_input(ui, &mut self.rise_text, &mut self.rise_duration);
+        ui.label("Fall Time (ms):");
+        crate::utils::non_neg_number_input(ui, &mut self.fall_text, &mut self.fall_duration);
+    }
+
+    fn name(&self) -> &str {
+        self.kind.display_string()
+    }
+
+    /// saved as 'kind;rise duration;fall duration'
+    fn save(&self) -> String {
+        format!("{};{};{}", self.kind.display_string(), self.rise_duration, self.fall_duration)
+    }
+
+    fn load(&mut self, data: &str) -> bool {
+        let mut parts = data.split(';');
+        let Some(kind) = parts.next().and_then(SlewKind::from_display_string) else {
+            return false;
+        };
+        let rise = parts.next().and_then(|text| text.parse::<f32>().ok());
+        let fall = parts.next().and_then(|text| text.parse::<f32>().ok());
+        let (Some(rise), Some(fall)) = (rise, fall) else {
+            return false;
+        };
+        if rise < 0.0 || fall < 0.0 {
+            return false;
+        }
+
+        self.kind = kind;
+        self.rise_duration = rise;
+        self.rise_text = rise.to_string();
+        self.fall_duration = fall;
+        self.fall_text = fall.to_string();
+        true
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    fn build(&self, _: &BuildState) -> Box<dyn Circuit> {
+        Box::new(Slew {
+            kind: self.kind,
+            rise: self.rise_duration / 1000.0,
+            fall: self.fall_duration / 1000.0,
+            value: None,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Slew {
+    kind: SlewKind,
+
+    /// durations in seconds
+    rise: f32,
+    fall: f32,
+
+    /// the current output, or none before the first sample
+    value: Option<f32>,
+}
+
+impl Circuit for Slew {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], delta: f32) {
+        let target = inputs[0];
+
+        // start at the input rather than gliding up from 0
+        let value = self.value.unwrap_or(target);
+        let duration = if target > value { self.rise } else { self.fall };
+
+        let value = if duration <= 0.0 {
+            target
+        } else {
+            match self.kind {
+                SlewKind::Linear => {
+                    let step = delta / duration;
+                    value + (target - value).clamp(-step, step)
+                }
+                SlewKind::Exponential => {
+                    value + (target - value) * (1.0 - f32::exp(-delta / duration))
+                }
+            }
+        };
+        self.value = Some(value);
+        outputs[0] = value;
+    }
+}
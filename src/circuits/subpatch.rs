@@ -0,0 +1,177 @@
+use std::path::Path;
+
+use crate::{circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification}, frame::Frame, patch_file::{PatchFile, PatchFileError}};
+
+/// Embeds an entire patch as a single circuit
+/// Each input and output of the embedded patch is mapped to the port of the same index on the container.
+/// The embedded patch is flattened into the containing patch when it is instantiated, so it costs nothing to nest.
+/// Ports past the embedded patch's inputs and outputs are unused.
+#[derive(Debug, Clone)]
+pub struct SubpatchBuilder {
+    patch: PatchFile,
+    path_text: String,
+
+    /// the error from loading the patch, if any
+    error: Option<String>,
+}
+
+impl SubpatchBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &["In 1", "In 2", "In 3", "In 4", "In 5", "In 6", "In 7", "In 8"],
+        output_names: &["Out 1", "Out 2", "Out 3", "Out 4", "Out 5", "Out 6", "Out 7", "Out 8"],
+        size: egui::vec2(250.0, 350.0),
+        playback_size: None,
+    };
+
+    const NAME: &'static str = "Subpatch";
+
+    /// the most inputs or outputs an embedded patch may have
+    pub const MAX_PORTS: usize = 8;
+
+    pub fn new() -> Self {
+        Self {
+            patch: PatchFile::default(),
+            path_text: String::new(),
+            error: None,
+        }
+    }
+
+    /// reads a patch that fits in the container's ports
+    fn read_patch(text: &str) -> Result<PatchFile, String> {
+        let patch: PatchFile = text.parse().map_err(|err: PatchFileError| err.to_string())?;
+        if patch.inputs.len() > Self::MAX_PORTS || patch.outputs.len() > Self::MAX_PORTS {
+            return Err(format!("Subpatches may have at most {} inputs and outputs.", Self::MAX_PORTS));
+        }
+        Ok(patch)
+    }
+
+    /// embeds the patch file at path, keeping the error if it could not be read
+    fn load_patch(&mut self, path: &Path) {
+        let result = std::fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| Self::read_patch(&text));
+        match result {
+            Ok(patch) => {
+                self.patch = patch;
+                self.error = None;
+            }
+            Err(err) => self.error = Some(err),
+        }
+    }
+}
+
+impl CircuitBuilder for SubpatchBuilder {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        ui.label("Patch File:");
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.path_text);
+            if ui.button("Load").clicked() {
+                let path = self.path_text.trim().to_string();
+                self.load_patch(Path::new(&path));
+            }
+        });
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+
+        ui.separator();
+        ui.label(format!("{} circuits", self.patch.circuits.len()));
+        for (index, input) in self.patch.inputs.iter().enumerate() {
+            ui.label(format!("In {}: {}", index + 1, input));
+        }
+        for (index, output) in self.patch.outputs.iter().enumerate() {
+            ui.label(format!("Out {}: {}", index + 1, output));
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    /// saved as the text of the embedded patch file, so that the container does not depend on the file
+    fn save(&self) -> String {
+        self.patch.to_string()
+    }
+
+    fn load(&mut self, data: &str) -> bool {
+        let Ok(patch) = Self::read_patch(data) else {
+            return false;
+        };
+        self.patch = patch;
+        self.error = None;
+        true
+    }
+
+    fn subpatch(&self) -> Option<&PatchFile> {
+        Some(&self.patch)
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    /// patches are flattened before they are compiled, replacing subpatches with their circuits,
+    /// so this is only reached if a patch is compiled without flattening, where the subpatch is silent
+    fn build(&self, _: &BuildState) -> Box<dyn Circuit> {
+        Box::new(SilentSubpatch {})
+    }
+}
+
+#[derive(Debug)]
+pub struct SilentSubpatch {
+}
+
+impl Circuit for SilentSubpatch {
+    fn operate(&mut self, _: &[f32], outputs: &mut[f32], _: f32) {
+        outputs.fill(0.0);
+    }
+}
+
+/// Stands in for an input or output of a flattened subpatch, passing its signal through
+#[derive(Debug, Clone)]
+pub struct SubpatchPortBuilder {
+    name: String,
+}
+
+impl SubpatchPortBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &["In"],
+        output_names: &["Out"],
+        size: egui::vec2(100.0, 100.0),
+        playback_size: None,
+    };
+
+    pub fn new(name: String) -> Self {
+        Self {
+            name
+        }
+    }
+}
+
+impl CircuitBuilder for SubpatchPortBuilder {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    fn build(&self, _: &BuildState) -> Box<dyn Circuit> {
+        Box::new(SubpatchPort {})
+    }
+}
+
+#[derive(Debug)]
+pub struct SubpatchPort {
+}
+
+impl Circuit for SubpatchPort {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], _: f32) {
+        outputs[0] = inputs[0];
+    }
+
+    fn operate_stereo(&mut self, inputs: &[Frame], outputs: &mut[Frame], _: f32) {
+        outputs[0] = inputs[0];
+    }
+}
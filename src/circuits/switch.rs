@@ -45,6 +45,36 @@ impl CircuitBuilder for SwitchBuilder {
         }
     }
 
+    /// saved as 'kind;declick duration;one shot duration'
+    fn save(&self) -> String {
+        format!("{};{};{}", self.name(), self.declick_duration, self.one_shot_duration)
+    }
+
+    fn load(&mut self, data: &str) -> bool {
+        let mut parts = data.split(';');
+        let kind = match parts.next() {
+            Some(Self::HOLD_TEXT) => SwitchKind::PressAndHold,
+            Some(Self::TOGGLE_TEXT) => SwitchKind::Toggle,
+            Some(Self::ONE_SHOT_TEXT) => SwitchKind::OneShot,
+            _ => return false
+        };
+        let declick = parts.next().and_then(|text| text.parse::<f32>().ok());
+        let one_shot = parts.next().and_then(|text| text.parse::<f32>().ok());
+        let (Some(declick), Some(one_shot)) = (declick, one_shot) else {
+            return false;
+        };
+        if declick < 0.0 || one_shot <= 0.0 {
+            return false;
+        }
+
+        self.kind = kind;
+        self.declick_duration = declick;
+        self.declick_text = declick.to_string();
+        self.one_shot_duration = one_shot;
+        self.one_shot_text = one_shot.to_string();
+        true
+    }
+
     fn show(&mut self, ui: &mut egui::Ui) {
         ui.label("Switch Type:");
         ui.radio_value(&mut self.kind, SwitchKind::PressAndHold, Self::HOLD_TEXT);
@@ -0,0 +1,108 @@
+use std::sync::{atomic::{AtomicU32, Ordering}, Arc};
+
+use crate::{circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification, CircuitUi}, pitch::{Pitch, TuningSystem}};
+
+use super::PitchDetect;
+
+/// Shows the pitch of its input during playback as the nearest note and its deviation in cents
+/// The input is passed through unchanged, since only circuits leading to a speaker are run.
+#[derive(Debug, Clone)]
+pub struct TunerBuilder {
+}
+
+impl TunerBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &["In"],
+        output_names: &["Thru"],
+        size: egui::vec2(100.0, 100.0),
+        playback_size: Some(egui::vec2(150.0, 80.0)),
+    };
+
+    const NAME: &'static str = "Tuner";
+
+    const MIN_FREQUENCY: f32 = 30.0;
+    const MAX_FREQUENCY: f32 = 4200.0;
+    const THRESHOLD: f32 = 0.15;
+
+    pub fn new() -> Self {
+        Self{ }
+    }
+}
+
+impl CircuitBuilder for TunerBuilder {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    fn build(&self, state: &BuildState) -> Box<dyn Circuit> {
+        let frequency = Arc::new(AtomicU32::new(0.0f32.to_bits()));
+        state.add_ui(Box::new(TunerUi {
+            frequency: frequency.clone(),
+            tuning: state.tuning.clone(),
+        }));
+        Box::new(Tuner {
+            detector: PitchDetect::new(state.sample_rate, Self::MIN_FREQUENCY, Self::MAX_FREQUENCY, Self::THRESHOLD),
+            frequency,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Tuner {
+    detector: PitchDetect,
+
+    /// the bits of the detected frequency, or 0 while no pitch is found
+    frequency: Arc<AtomicU32>,
+}
+
+impl Circuit for Tuner {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], _: f32) {
+        outputs[0] = inputs[0];
+        if self.detector.push(inputs[0]) {
+            let frequency = if self.detector.confidence() > 0.0 {
+                self.detector.frequency()
+            } else {
+                0.0
+            };
+            self.frequency.store(frequency.to_bits(), Ordering::Relaxed);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TunerUi {
+    frequency: Arc<AtomicU32>,
+    tuning: TuningSystem,
+}
+
+impl TunerUi {
+    /// the nearest pitch to a frequency and the deviation from it in cents
+    fn nearest_pitch(&self, frequency: f32) -> Option<(Pitch, f64)> {
+        let cents = self.tuning.get_frequency_cent_delta_a4(frequency as f64);
+        let semitones = (cents / Pitch::CENTS_PER_SEMITONE as f64).round();
+        let pitch = Pitch::from_semitone_delta_a4(semitones as i32)?;
+        Some((pitch, cents - semitones * Pitch::CENTS_PER_SEMITONE as f64))
+    }
+}
+
+impl CircuitUi for TunerUi {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        let frequency = f32::from_bits(self.frequency.load(Ordering::Relaxed));
+        let Some((pitch, deviation)) = Some(frequency)
+            .filter(|frequency| *frequency > 0.0)
+            .and_then(|frequency| self.nearest_pitch(frequency))
+        else {
+            ui.label("--");
+            return;
+        };
+
+        ui.heading(pitch.to_string());
+        let color = if deviation.abs() < 5.0 { egui::Color32::GREEN } else { ui.visuals().text_color() };
+        ui.colored_label(color, format!("{:+.1}c", deviation));
+        ui.label(format!("{:.2} Hz", frequency));
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification}, frame::{self, Frame}};
+
+/// Splits a frequency into several detuned copies for thick unison patches
+/// The voices are spread evenly across the Spread input in cents, centered on the input frequency.
+/// Outputs past the voice count are silent.
+/// With stereo enabled, each voice's detune is mirrored in the right channel so the copies spread across the stereo field.
+#[derive(Debug, Clone)]
+pub struct UnisonBuilder {
+    voices: usize,
+    voices_text: String,
+    stereo: bool,
+}
+
+impl UnisonBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &["Frequency", "Spread"],
+        output_names: &["Voice 1", "Voice 2", "Voice 3", "Voice 4", "Voice 5", "Voice 6", "Voice 7", "Voice 8"],
+        size: egui::vec2(200.0, 300.0),
+        playback_size: None,
+    };
+
+    const NAME: &'static str = "Unison";
+    const MAX_VOICES: usize = 8;
+
+    pub fn new() -> Self {
+        let voices = 4;
+        Self {
+            voices,
+            voices_text: voices.to_string(),
+            stereo: false,
+        }
+    }
+}
+
+impl CircuitBuilder for UnisonBuilder {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!("Voices (1-{}):", Self::MAX_VOICES));
+        if crate::utils::pos_number_input(ui, &mut self.voices_text, &mut self.voices) && self.voices > Self::MAX_VOICES {
+            self.voices = Self::MAX_VOICES;
+            self.voices_text = self.voices.to_string();
+        }
+        ui.checkbox(&mut self.stereo, "Stereo");
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    /// saved as 'voices;stereo'
+    fn save(&self) -> String {
+        format!("{};{}", self.voices, self.stereo)
+    }
+
+    fn load(&mut self, data: &str) -> bool {
+        let Some((voices, stereo)) = data.split_once(';') else {
+            return false;
+        };
+        let (Ok(voices), Ok(stereo)) = (voices.parse::<usize>(), stereo.parse::<bool>()) else {
+            return false;
+        };
+        if voices == 0 || voices > Self::MAX_VOICES {
+            return false;
+        }
+
+        self.voices = voices;
+        self.voices_text = voices.to_string();
+        self.stereo = stereo;
+        true
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    fn build(&self, _: &BuildState) -> Box<dyn Circuit> {
+        Box::new(Unison {
+            voices: self.voices.clamp(1, Self::MAX_VOICES),
+            stereo: self.stereo,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Unison {
+    voices: usize,
+    stereo: bool,
+}
+
+impl Unison {
+    /// the frequency of a voice, detuned by its share of the spread in cents
+    fn voice_frequency(&self, frequency: f32, spread: f32, voice: usize) -> f32 {
+        if self.voices == 1 {
+            return frequency;
+        }
+        let position = voice as f32 / (self.voices - 1) as f32 - 0.5;
+        frequency * f32::exp2(position * spread / 1200.0)
+    }
+}
+
+impl Circuit for Unison {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], _: f32) {
+        for (voice, output) in outputs.iter_mut().enumerate() {
+            *output = if voice < self.voices {
+                self.voice_frequency(inputs[0], inputs[1], voice)
+            } else {
+                0.0
+            };
+        }
+    }
+
+    fn operate_stereo(&mut self, inputs: &[Frame], outputs: &mut[Frame], _: f32) {
+        let spread = frame::to_mono(inputs[1]);
+        for (voice, output) in outputs.iter_mut().enumerate() {
+            *output = if voice >= self.voices {
+                frame::SILENCE
+            } else if self.stereo {
+                let mirrored = self.voices - 1 - voice;
+                [
+                    self.voice_frequency(inputs[0][0], spread, voice),
+                    self.voice_frequency(inputs[0][1], spread, mirrored),
+                ]
+            } else {
+                frame::mono(self.voice_frequency(frame::to_mono(inputs[0]), spread, voice))
+            };
+        }
+    }
+}
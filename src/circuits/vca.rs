@@ -0,0 +1,119 @@
+use crate::circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VcaResponse {
+    Linear,
+    Exponential,
+}
+
+impl VcaResponse {
+    const LINEAR_TEXT: &'static str = "Linear";
+    const EXPONENTIAL_TEXT: &'static str = "Exponential";
+
+    fn display_string(&self) -> &'static str {
+        match self {
+            Self::Linear => Self::LINEAR_TEXT,
+            Self::Exponential => Self::EXPONENTIAL_TEXT,
+        }
+    }
+
+    /// gets the response with the given display string
+    fn from_display_string(text: &str) -> Option<Self> {
+        [Self::Linear, Self::Exponential]
+            .into_iter()
+            .find(|response| response.display_string() == text)
+    }
+}
+
+/// Scales a signal by a control voltage, such as an envelope
+/// The CV is clamped to [0, 1]. With an exponential response it is mapped to a gain
+/// across a 60 dB range, which sounds more even for fades than a linear response.
+#[derive(Debug, Clone)]
+pub struct VcaBuilder {
+    response: VcaResponse,
+}
+
+impl VcaBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &["In", "CV"],
+        output_names: &["Out"],
+        size: egui::vec2(150.0, 125.0),
+        playback_size: None,
+    };
+
+    const NAME: &'static str = "VCA";
+
+    pub fn new() -> Self {
+        Self {
+            response: VcaResponse::Linear,
+        }
+    }
+}
+
+impl CircuitBuilder for VcaBuilder {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        ui.label("Response:");
+        ui.radio_value(&mut self.response, VcaResponse::Linear, VcaResponse::LINEAR_TEXT);
+        ui.radio_value(&mut self.response, VcaResponse::Exponential, VcaResponse::EXPONENTIAL_TEXT);
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn save(&self) -> String {
+        self.response.display_string().to_string()
+    }
+
+    fn load(&mut self, data: &str) -> bool {
+        match VcaResponse::from_display_string(data) {
+            Some(response) => {
+                self.response = response;
+                true
+            }
+            None => false
+        }
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    fn build(&self, _: &BuildState) -> Box<dyn Circuit> {
+        match self.response {
+            VcaResponse::Linear => Box::new(LinearVca {}),
+            VcaResponse::Exponential => Box::new(ExponentialVca {}),
+        }
+    }
+}
+
+/// Multiplies the signal by the CV
+#[derive(Debug, Default)]
+pub struct LinearVca {}
+
+impl Circuit for LinearVca {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], _: f32) {
+        outputs[0] = inputs[0] * inputs[1].clamp(0.0, 1.0);
+    }
+}
+
+/// Multiplies the signal by a gain exponential in the CV
+#[derive(Debug, Default)]
+pub struct ExponentialVca {}
+
+impl ExponentialVca {
+    /// the range of gains covered by the CV in decibels
+    const RANGE_DB: f32 = 60.0;
+}
+
+impl Circuit for ExponentialVca {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], _: f32) {
+        let cv = inputs[1].clamp(0.0, 1.0);
+        let gain = if cv <= 0.0 {
+            0.0
+        } else {
+            f32::powf(10.0, (cv - 1.0) * Self::RANGE_DB / 20.0)
+        };
+        outputs[0] = inputs[0] * gain;
+    }
+}
@@ -0,0 +1,239 @@
+use crate::{circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification}, playback::{NoteEvent, NoteId}};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StealPolicy {
+    None,
+    Oldest,
+    Quietest,
+    Lowest,
+    Highest,
+}
+
+impl StealPolicy {
+    const NONE_TEXT: &'static str = "Don't Steal";
+    const OLDEST_TEXT: &'static str = "Oldest";
+    const QUIETEST_TEXT: &'static str = "Quietest";
+    const LOWEST_TEXT: &'static str = "Lowest";
+    const HIGHEST_TEXT: &'static str = "Highest";
+
+    const ALL: [Self; 5] = [Self::None, Self::Oldest, Self::Quietest, Self::Lowest, Self::Highest];
+
+    fn display_string(&self) -> &'static str {
+        match self {
+            Self::None => Self::NONE_TEXT,
+            Self::Oldest => Self::OLDEST_TEXT,
+            Self::Quietest => Self::QUIETEST_TEXT,
+            Self::Lowest => Self::LOWEST_TEXT,
+            Self::Highest => Self::HIGHEST_TEXT,
+        }
+    }
+
+    /// gets the policy with the given display string
+    fn from_display_string(text: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|policy| policy.display_string() == text)
+    }
+}
+
+/// Spreads the notes played on the patch across several voices, each with its own Pitch, Gate and Velocity outputs
+/// Patching each voice into a copy of a monophonic chain makes the patch polyphonic.
+/// When every voice is held, a new note steals a voice by the chosen policy or is dropped.
+/// Outputs past the voice count are silent.
+#[derive(Debug, Clone)]
+pub struct VoiceAllocBuilder {
+    voices: usize,
+    voices_text: String,
+    policy: StealPolicy,
+}
+
+impl VoiceAllocBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &[],
+        output_names: &[
+            "Pitch 1", "Gate 1", "Velocity 1",
+            "Pitch 2", "Gate 2", "Velocity 2",
+            "Pitch 3", "Gate 3", "Velocity 3",
+            "Pitch 4", "Gate 4", "Velocity 4",
+            "Pitch 5", "Gate 5", "Velocity 5",
+            "Pitch 6", "Gate 6", "Velocity 6",
+            "Pitch 7", "Gate 7", "Velocity 7",
+            "Pitch 8", "Gate 8", "Velocity 8",
+        ],
+        size: egui::vec2(200.0, 600.0),
+        playback_size: None,
+    };
+
+    const NAME: &'static str = "Voice Alloc";
+    const MAX_VOICES: usize = 8;
+
+    pub fn new() -> Self {
+        let voices = 4;
+        Self {
+            voices,
+            voices_text: voices.to_string(),
+            policy: StealPolicy::Oldest,
+        }
+    }
+}
+
+impl CircuitBuilder for VoiceAllocBuilder {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!("Voices (1-{}):", Self::MAX_VOICES));
+        if crate::utils::pos_number_input(ui, &mut self.voices_text, &mut self.voices) && self.voices > Self::MAX_VOICES {
+            self.voices = Self::MAX_VOICES;
+            self.voices_text = self.voices.to_string();
+        }
+
+        ui.separator();
+        ui.label("Steal:");
+        for policy in StealPolicy::ALL {
+            ui.radio_value(&mut self.policy, policy, policy.display_string());
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    /// saved as 'voices;policy'
+    fn save(&self) -> String {
+        format!("{};{}", self.voices, self.policy.display_string())
+    }
+
+    fn load(&mut self, data: &str) -> bool {
+        let Some((voices, policy)) = data.split_once(';') else {
+            return false;
+        };
+        let (Ok(voices), Some(policy)) = (voices.parse::<usize>(), StealPolicy::from_display_string(policy)) else {
+            return false;
+        };
+        if voices == 0 || voices > Self::MAX_VOICES {
+            return false;
+        }
+
+        self.voices = voices;
+        self.voices_text = voices.to_string();
+        self.policy = policy;
+        true
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    fn build(&self, _: &BuildState) -> Box<dyn Circuit> {
+        Box::new(VoiceAlloc {
+            voices: vec![Voice::default(); self.voices.clamp(1, Self::MAX_VOICES)],
+            policy: self.policy,
+            clock: 0,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Voice {
+    /// the note holding the voice, or none once it is released
+    note: Option<NoteId>,
+    freq: f32,
+
+    /// [0, 1]
+    velocity: f32,
+
+    /// when the voice was last started or released, used to pick the oldest
+    since: u64,
+
+    /// whether the gate must close for a sample so a stolen voice retriggers
+    retrigger: bool,
+}
+
+#[derive(Debug)]
+pub struct VoiceAlloc {
+    voices: Vec<Voice>,
+    policy: StealPolicy,
+
+    /// counts note events so voices can be ordered by age
+    clock: u64,
+}
+
+impl VoiceAlloc {
+    /// the voice a new note should play on, if any
+    /// free voices are taken in the order they were released so release tails can ring out
+    fn allocate(&self) -> Option<usize> {
+        let free = self.voices.iter()
+            .enumerate()
+            .filter(|(_, voice)| voice.note.is_none())
+            .min_by_key(|(_, voice)| voice.since);
+        if let Some((index, _)) = free {
+            return Some(index);
+        }
+
+        let voices = self.voices.iter().enumerate();
+        let stolen = match self.policy {
+            StealPolicy::None => None,
+            StealPolicy::Oldest => voices.min_by_key(|(_, voice)| voice.since),
+            StealPolicy::Quietest => voices.min_by(|(_, a), (_, b)| {
+                a.velocity.total_cmp(&b.velocity).then(a.since.cmp(&b.since))
+            }),
+            StealPolicy::Lowest => voices.min_by(|(_, a), (_, b)| a.freq.total_cmp(&b.freq)),
+            StealPolicy::Highest => voices.max_by(|(_, a), (_, b)| a.freq.total_cmp(&b.freq)),
+        };
+        stolen.map(|(index, _)| index)
+    }
+
+    /// the voice held by a note
+    fn find(&mut self, id: NoteId) -> Option<&mut Voice> {
+        self.voices.iter_mut().find(|voice| voice.note == Some(id))
+    }
+}
+
+impl Circuit for VoiceAlloc {
+    fn operate(&mut self, _: &[f32], outputs: &mut[f32], _: f32) {
+        outputs.fill(0.0);
+        for (voice, outputs) in self.voices.iter_mut().zip(outputs.chunks_exact_mut(3)) {
+            outputs[0] = voice.freq;
+            outputs[1] = if voice.note.is_some() && !voice.retrigger { 1.0 } else { 0.0 };
+            outputs[2] = voice.velocity;
+            voice.retrigger = false;
+        }
+    }
+
+    fn note(&mut self, event: NoteEvent) {
+        self.clock += 1;
+        let clock = self.clock;
+        match event {
+            NoteEvent::On { id, freq, velocity } => {
+                let Some(index) = self.allocate() else {
+                    return;
+                };
+                let voice = &mut self.voices[index];
+                voice.retrigger = voice.note.is_some();
+                voice.note = Some(id);
+                voice.freq = freq;
+                voice.velocity = velocity as f32 / 127.0;
+                voice.since = clock;
+            }
+            NoteEvent::Off { id, .. } => {
+                // the pitch is kept so the voice's release tail stays in tune
+                if let Some(voice) = self.find(id) {
+                    voice.note = None;
+                    voice.since = clock;
+                }
+            }
+            NoteEvent::Freq { id, freq } => {
+                if let Some(voice) = self.find(id) {
+                    voice.freq = freq;
+                }
+            }
+            NoteEvent::Aftertouch { .. } => {}
+        }
+    }
+
+    /// closes every gate, the pitch is kept so release tails stay in tune
+    fn all_notes_off(&mut self) {
+        self.clock += 1;
+        for voice in self.voices.iter_mut().filter(|voice| voice.note.is_some()) {
+            voice.note = None;
+            voice.retrigger = false;
+            voice.since = self.clock;
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use crate::circuit::{BuildState, Circuit, CircuitBuilder, CircuitSpecification};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WavefolderKind {
+    Triangle,
+    Sine,
+}
+
+impl WavefolderKind {
+    const TRI_TEXT: &'static str = "Triangle Fold";
+    const SINE_TEXT: &'static str = "Sine Fold";
+
+    fn display_string(&self) -> &'static str {
+        match self {
+            Self::Triangle => Self::TRI_TEXT,
+            Self::Sine => Self::SINE_TEXT,
+        }
+    }
+
+    /// gets the kind with the given display string
+    fn from_display_string(text: &str) -> Option<Self> {
+        [Self::Triangle, Self::Sine]
+            .into_iter()
+            .find(|kind| kind.display_string() == text)
+    }
+}
+
+/// Folds a signal back on itself whenever it leaves [-1, 1], adding harmonics as it is driven harder
+/// - Fold [0, 1] sets the drive into the folder from 1x to 10x
+/// - Symmetry [-1, 1] offsets the signal before folding so the positive and negative halves fold differently
+#[derive(Debug, Clone)]
+pub struct WavefolderBuilder {
+    kind: WavefolderKind,
+}
+
+impl WavefolderBuilder {
+    const SPECIFICATION: CircuitSpecification = CircuitSpecification {
+        input_names: &["In", "Fold", "Symmetry"],
+        output_names: &["Out"],
+        size: egui::vec2(150.0, 150.0),
+        playback_size: None,
+    };
+
+    pub fn new() -> Self {
+        Self {
+            kind: WavefolderKind::Triangle,
+        }
+    }
+}
+
+impl CircuitBuilder for WavefolderBuilder {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        ui.radio_value(&mut self.kind, WavefolderKind::Triangle, WavefolderKind::TRI_TEXT);
+        ui.radio_value(&mut self.kind, WavefolderKind::Sine, WavefolderKind::SINE_TEXT);
+    }
+
+    fn name(&self) -> &str {
+        self.kind.display_string()
+    }
+
+    fn save(&self) -> String {
+        self.kind.display_string().to_string()
+    }
+
+    fn load(&mut self, data: &str) -> bool {
+        match WavefolderKind::from_display_string(data) {
+            Some(kind) => {
+                self.kind = kind;
+                true
+            }
+            None => false
+        }
+    }
+
+    fn specification(&self) -> &'static CircuitSpecification {
+        &Self::SPECIFICATION
+    }
+
+    fn build(&self, _: &BuildState) -> Box<dyn Circuit> {
+        Box::new(Wavefolder { kind: self.kind })
+    }
+}
+
+#[derive(Debug)]
+pub struct Wavefolder {
+    kind: WavefolderKind,
+}
+
+impl Wavefolder {
+    const MAX_DRIVE: f32 = 10.0;
+}
+
+impl Circuit for Wavefolder {
+    fn operate(&mut self, inputs: &[f32], outputs: &mut[f32], _: f32) {
+        let drive = 1.0 + inputs[1].clamp(0.0, 1.0) * (Self::MAX_DRIVE - 1.0);
+        let x = inputs[0] * drive + inputs[2].clamp(-1.0, 1.0);
+        outputs[0] = match self.kind {
+            // a triangle wave of x with period 4, passing through the origin
+            WavefolderKind::Triangle => 1.0 - f32::abs((x + 1.0).rem_euclid(4.0) - 2.0),
+            WavefolderKind::Sine => f32::sin(x * std::f32::consts::FRAC_PI_2),
+        };
+    }
+}
@@ -1,7 +1,7 @@
 use std::{collections::{HashMap, HashSet}};
 
 use crate::{
-    circuit::{BuildState, Circuit, CircuitBuilder, CircuitUiSlot}, circuit_id::{CircuitId, CircuitPortId, PortId, PortKind}, connection_manager::ConnectionManager, pitch::TuningSystem
+    circuit::{BuildState, Circuit, CircuitBuilder, CircuitUiSlot}, circuit_id::{CircuitId, CircuitPortId, PortId, PortKind}, connection_manager::ConnectionManager, frame::{self, Frame}, meter::{LevelMeter, MeterReader}, pitch::TuningSystem, playback::NoteEvent
 };
 
 /// The intermediate representation of a patch, just before total compilation
@@ -325,16 +325,17 @@ impl<'a> PatchIr<'a> {
     }
 
     /// Constructs self as well as the associated ui slots
+    /// every circuit is built with the given tuning
     pub fn compile(
         &self,
         sample_rate: u32,
         sample_multiplier: f32,
-    ) -> CompiledPatch {
-        // initialize the input buffer
-        let input_buffer = vec![0.0; self.circuits.len() + self.output_count];
-
-        // todo TEMPORARY PLEASE DELETE
-        let tuning = TuningSystem::EqualTemperment(440.0);
+        tuning: &TuningSystem,
+    ) -> (CompiledPatch, Vec<CircuitUiSlot>) {
+        // initialize the input buffer (every circuit input port followed by the outputs)
+        let port_count = self.circuit_input_ranges.last().map_or(0, |(_, end)| *end);
+        let input_buffer = vec![frame::SILENCE; port_count + self.output_count];
+        let max_outputs = self.circuit_target_list.iter().map(|ports| ports.len()).max().unwrap_or(0);
 
         let mut built_circuits = Vec::with_capacity(self.circuits.len());
         let mut ui_slots = Vec::new();
@@ -356,7 +357,7 @@ impl<'a> PatchIr<'a> {
             let mut build_state = BuildState::new(
                 &input_counts,
                 &output_counts,
-                tuning,
+                tuning.clone(),
                 sample_rate,
                 expect_ui
             );
@@ -388,16 +389,20 @@ impl<'a> PatchIr<'a> {
         );
         */
 
-        CompiledPatch {
+        let compiled = CompiledPatch {
             circuits: built_circuits,
+            save_buffer: input_buffer.clone(),
             circuit_input_buffer: input_buffer,
+            output_buffer: vec![frame::SILENCE; max_outputs],
             circuit_input_ranges: self.circuit_input_ranges.clone(),
             circuit_target_list: self.circuit_target_list.clone(),
             input_target_lists: self.input_target_lists.clone(),
             sample_multiplier,
+            meter: None,
             input_count: self.input_target_lists.len(),
             output_count: self.output_count,
-        }
+        };
+        (compiled, ui_slots)
     }
 }
 
@@ -406,7 +411,13 @@ pub struct CompiledPatch {
     circuits: Vec<Box<dyn Circuit>>,
 
     /// The buffer that circuits read from
-    circuit_input_buffer: Vec<f32>,
+    circuit_input_buffer: Vec<Frame>,
+
+    /// The buffer where save-behavior items are stored, swapped with the input buffer each update
+    save_buffer: Vec<Frame>,
+
+    /// The buffer circuits write their outputs to
+    output_buffer: Vec<Frame>,
 
     /// The range of indices that each circuit takes input from, exclusive
     circuit_input_ranges: Vec<(usize, usize)>,
@@ -420,6 +431,9 @@ pub struct CompiledPatch {
     /// the value to multiply all samples by
     sample_multiplier: f32,
 
+    /// measures the level of the main output
+    meter: Option<LevelMeter>,
+
     /// the number of inputs this patch takes
     pub input_count: usize,
 
@@ -480,9 +494,36 @@ impl std::fmt::Debug for ConnectionBehavior {
 }
 
 impl CompiledPatch {
+    /// The value that all samples should be multiplied by before playback
+    pub fn sample_multiplier(&self) -> f32 {
+        self.sample_multiplier
+    }
+
+    /// Starts measuring the level of the main (first) output after the sample multiplier
+    /// Returns the reader used to poll the levels. Replaces any existing meter.
+    pub fn attach_meter(&mut self, sample_rate: u32) -> MeterReader {
+        let (meter, reader) = LevelMeter::new(sample_rate);
+        self.meter = Some(meter);
+        reader
+    }
+
+    /// Sends a note event to every circuit, to take effect from the next update
+    pub fn send_note(&mut self, event: NoteEvent) {
+        for circuit in &mut self.circuits {
+            circuit.note(event);
+        }
+    }
+
+    /// Releases every note held by the circuits, to take effect from the next update
+    pub fn all_notes_off(&mut self) {
+        for circuit in &mut self.circuits {
+            circuit.all_notes_off();
+        }
+    }
+
     /// Updates all circuits once and in order for one sample
-    /// Returns the value of the sample as an f32
-    pub fn update(&mut self, inputs: &[f32], output: &mut [f32], delta: f32) {
+    /// Writes the value of each special output to output
+    pub fn update(&mut self, inputs: &[Frame], output: &mut [Frame], delta: f32) {
         debug_assert!(inputs.len() == self.input_count, "Input array size must match input count.");
         debug_assert!(output.len() == self.output_count, "Output array size must match output count.");
 
@@ -491,15 +532,12 @@ impl CompiledPatch {
             let value = inputs[i];
             let input_target_list = &mut self.input_target_lists[i];
             for target in input_target_list {
-                self.circuit_input_buffer[*target] += value;
+                self.circuit_input_buffer[*target] = frame::add(self.circuit_input_buffer[*target], value);
             }
         }
 
-        // the buffer where save-behavior items are stored
-        let mut save_buffer = vec![0.0; self.circuit_input_buffer.len()];
-
         // handle internal updates
-        for i in self.input_count..self.circuits.len() {
+        for i in 0..self.circuits.len() {
             // the current circuit to update
             let circuit = &mut self.circuits[i];
 
@@ -510,9 +548,10 @@ impl CompiledPatch {
             let inputs = &self.circuit_input_buffer[range.0..range.1];
 
             // the buffer the circuit should write to
-            let mut output_buffer = vec![0.0; self.circuit_target_list[i].len()];
+            let output_buffer = &mut self.output_buffer[..self.circuit_target_list[i].len()];
+            output_buffer.fill(frame::SILENCE);
 
-            circuit.operate(&inputs, &mut output_buffer, delta);
+            circuit.operate_stereo(inputs, output_buffer, delta);
 
             // iterate through each output port to send or save the result
             for j in 0..output_buffer.len() {
@@ -524,25 +563,25 @@ impl CompiledPatch {
 
                 // iterate through each output target to send or save the result
                 for target in targets {
-                    match target.behavior() {
-                        Behavior::Send => {
-                            self.circuit_input_buffer[target.index()] += output_value;
-                        }
-                        Behavior::Save => {
-                            save_buffer[target.index()] += output_value;
-                        }
-                    }
+                    let buffer = match target.behavior() {
+                        Behavior::Send => &mut self.circuit_input_buffer,
+                        Behavior::Save => &mut self.save_buffer,
+                    };
+                    buffer[target.index()] = frame::add(buffer[target.index()], output_value);
                 }
             }
         }
 
         // send output
-        for i in (self.circuit_input_buffer.len() - self.input_count)..self.circuit_input_buffer.len() {
-            output[i] = self.circuit_input_buffer[i];
+        let out_start = self.circuit_input_buffer.len() - self.output_count;
+        output.copy_from_slice(&self.circuit_input_buffer[out_start..]);
+        if let (Some(meter), Some(main)) = (self.meter.as_mut(), output.first()) {
+            meter.process(frame::scale(*main, self.sample_multiplier));
         }
 
         // swap buffers
-        self.circuit_input_buffer = save_buffer;
+        std::mem::swap(&mut self.circuit_input_buffer, &mut self.save_buffer);
+        self.save_buffer.fill(frame::SILENCE);
     }
 
     /*
@@ -0,0 +1,145 @@
+use std::sync::{mpsc::SyncSender, Mutex};
+
+use egui::{Context, Event, Key};
+
+use crate::{midi::{MidiMessage, MidiRouting}, playback::PlaybackCommand};
+
+/// Plays notes from the computer keyboard, laid out like a piano on the two lower and two upper rows
+/// Notes are sent as MIDI messages through the routing, so they reach synths the same way as notes from a device.
+///
+/// - minus and equals move down and up an octave
+/// - the brackets lower and raise the velocity
+/// - notes are played at full velocity while shift is held
+#[derive(Debug, Clone)]
+pub struct ComputerKeyboard {
+    pub enabled: bool,
+
+    /// the octave of the lower row, where the z key plays C
+    octave: i8,
+    velocity: u8,
+
+    /// the keyboard keys held down with the MIDI key they play
+    held: Vec<(Key, u8)>,
+}
+
+impl ComputerKeyboard {
+    /// the channel notes are sent on
+    pub const CHANNEL: u8 = 0;
+
+    pub const MIN_OCTAVE: i8 = -1;
+    pub const MAX_OCTAVE: i8 = 8;
+    pub const DEFAULT_OCTAVE: i8 = 3;
+
+    pub const DEFAULT_VELOCITY: u8 = 100;
+    pub const VELOCITY_STEP: u8 = 16;
+    pub const ACCENT_VELOCITY: u8 = 127;
+
+    /// keys on the lower rows with their offset in semitones from the octave's C
+    const LOWER_ROWS: [(Key, u8); 17] = [
+        (Key::Z, 0), (Key::S, 1), (Key::X, 2), (Key::D, 3), (Key::C, 4), (Key::V, 5),
+        (Key::G, 6), (Key::B, 7), (Key::H, 8), (Key::N, 9), (Key::J, 10), (Key::M, 11),
+        (Key::Comma, 12), (Key::L, 13), (Key::Period, 14), (Key::Semicolon, 15), (Key::Slash, 16),
+    ];
+
+    /// keys on the upper rows with their offset in semitones from the octave's C
+    const UPPER_ROWS: [(Key, u8); 17] = [
+        (Key::Q, 12), (Key::Num2, 13), (Key::W, 14), (Key::Num3, 15), (Key::E, 16), (Key::R, 17),
+        (Key::Num5, 18), (Key::T, 19), (Key::Num6, 20), (Key::Y, 21), (Key::Num7, 22), (Key::U, 23),
+        (Key::I, 24), (Key::Num9, 25), (Key::O, 26), (Key::Num0, 27), (Key::P, 28),
+    ];
+
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            octave: Self::DEFAULT_OCTAVE,
+            velocity: Self::DEFAULT_VELOCITY,
+            held: Vec::new(),
+        }
+    }
+
+    pub fn octave(&self) -> i8 {
+        self.octave
+    }
+
+    pub fn velocity(&self) -> u8 {
+        self.velocity
+    }
+
+    /// the offset in semitones of a keyboard key from the lower row's C
+    fn key_offset(key: Key) -> Option<u8> {
+        Self::LOWER_ROWS.iter()
+            .chain(Self::UPPER_ROWS.iter())
+            .find(|(row_key, _)| *row_key == key)
+            .map(|(_, offset)| *offset)
+    }
+
+    /// the MIDI key played by a keyboard key in the current octave
+    fn midi_key(&self, key: Key) -> Option<u8> {
+        let key = (self.octave as i32 + 1) * 12 + Self::key_offset(key)? as i32;
+        u8::try_from(key).ok().filter(|key| *key <= 127)
+    }
+
+    /// plays notes for the key presses of this frame
+    /// keys are ignored while a text field has focus, and held notes are released when the window loses focus
+    pub fn handle_input(&mut self, ctx: &Context, routing: &Mutex<MidiRouting>, commands: &SyncSender<PlaybackCommand>) {
+        if !self.enabled || ctx.wants_keyboard_input() || !ctx.input(|input| input.focused) {
+            self.release_all(routing, commands);
+            return;
+        }
+
+        let events = ctx.input(|input| input.events.clone());
+        for event in events {
+            let Event::Key { key, physical_key, pressed, repeat, modifiers } = event else {
+                continue;
+            };
+            if repeat || modifiers.command || modifiers.alt {
+                continue;
+            }
+
+            // prefer the key's position so the layout does not depend on the keymap
+            let key = physical_key.unwrap_or(key);
+            if pressed {
+                match key {
+                    Key::Minus => self.octave = (self.octave - 1).max(Self::MIN_OCTAVE),
+                    Key::Equals => self.octave = (self.octave + 1).min(Self::MAX_OCTAVE),
+                    Key::OpenBracket => self.velocity = self.velocity.saturating_sub(Self::VELOCITY_STEP).max(1),
+                    Key::CloseBracket => self.velocity = self.velocity.saturating_add(Self::VELOCITY_STEP).min(127),
+                    _ => {
+                        let Some(midi_key) = self.midi_key(key) else {
+                            continue;
+                        };
+                        if self.held.iter().any(|(held, _)| *held == key) {
+                            continue;
+                        }
+                        let velocity = if modifiers.shift { Self::ACCENT_VELOCITY } else { self.velocity };
+                        self.held.push((key, midi_key));
+                        Self::send(routing, MidiMessage::NoteOn { channel: Self::CHANNEL, key: midi_key, velocity }, commands);
+                    }
+                }
+            } else if let Some(index) = self.held.iter().position(|(held, _)| *held == key) {
+                // release the key that was played, even if the octave has since changed
+                let (_, midi_key) = self.held.swap_remove(index);
+                Self::send(routing, MidiMessage::NoteOff { channel: Self::CHANNEL, key: midi_key, velocity: 0 }, commands);
+            }
+        }
+    }
+
+    /// releases every held note, such as when the window loses focus
+    pub fn release_all(&mut self, routing: &Mutex<MidiRouting>, commands: &SyncSender<PlaybackCommand>) {
+        for (_, midi_key) in self.held.drain(..) {
+            Self::send(routing, MidiMessage::NoteOff { channel: Self::CHANNEL, key: midi_key, velocity: 0 }, commands);
+        }
+    }
+
+    fn send(routing: &Mutex<MidiRouting>, message: MidiMessage, commands: &SyncSender<PlaybackCommand>) {
+        if let Ok(mut routing) = routing.lock() {
+            routing.send(message, 0, commands);
+        }
+    }
+}
+
+impl Default for ComputerKeyboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -274,6 +274,11 @@ impl ConnectionManager {
         }
     }
 
+    /// Returns an iterator over every connection
+    pub fn connections(&self) -> impl Iterator<Item = ConnectionId<CircuitId>> + '_ {
+        self.connections.iter().map(|(id, _)| *id)
+    }
+
     /// Returns a vec with all connections to the circuit
     pub fn circuit_query_connections(&self, circuit: CircuitId) -> Vec<ConnectionId<CircuitId>> {
         let mut output = vec![];
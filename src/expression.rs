@@ -0,0 +1,371 @@
+use thiserror::Error;
+
+/// An error occurring while compiling an expression
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ExpressionError {
+    #[error("Unexpected '{1}' at {0}.")]
+    UnexpectedChar(usize, char),
+
+    #[error("Unexpected end of expression.")]
+    UnexpectedEnd,
+
+    #[error("Unknown variable or function '{0}'.")]
+    UnknownName(String),
+
+    #[error("'{0}' takes {1} argument(s), but {2} were given.")]
+    ArgumentCount(String, usize, usize),
+
+    #[error("Expected '{0}' at {1}.")]
+    Expected(char, usize),
+}
+
+/// A built-in function callable from an expression
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Abs,
+    Sqrt,
+    Exp,
+    Ln,
+    Floor,
+    Ceil,
+    Round,
+    Sign,
+    Min,
+    Max,
+    Pow,
+    Clamp,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "sin" => Self::Sin,
+            "cos" => Self::Cos,
+            "tan" => Self::Tan,
+            "abs" => Self::Abs,
+            "sqrt" => Self::Sqrt,
+            "exp" => Self::Exp,
+            "ln" => Self::Ln,
+            "floor" => Self::Floor,
+            "ceil" => Self::Ceil,
+            "round" => Self::Round,
+            "sign" => Self::Sign,
+            "min" => Self::Min,
+            "max" => Self::Max,
+            "pow" => Self::Pow,
+            "clamp" => Self::Clamp,
+            _ => return None,
+        })
+    }
+
+    fn argument_count(&self) -> usize {
+        match self {
+            Self::Min | Self::Max | Self::Pow => 2,
+            Self::Clamp => 3,
+            _ => 1,
+        }
+    }
+}
+
+/// A single instruction of a compiled expression, run on a value stack
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Constant(f32),
+    Variable(usize),
+    Negate,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Power,
+    Call(Function),
+}
+
+/// An arithmetic expression over named variables, compiled once to be evaluated quickly
+///
+/// Supports numbers, the variables given when compiling, the constants `pi` and `tau`,
+/// `+ - * / %`, `^` for powers, parentheses, and the functions
+/// `sin cos tan abs sqrt exp ln floor ceil round sign min max pow clamp`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    ops: Vec<Op>,
+
+    /// the most values on the stack at once while evaluating
+    depth: usize,
+}
+
+impl Expression {
+    /// compiles an expression, where variables are referred to by their index in `variables`
+    pub fn compile(text: &str, variables: &[&str]) -> Result<Self, ExpressionError> {
+        let mut parser = Parser {
+            text,
+            position: 0,
+            variables,
+            ops: Vec::new(),
+        };
+        parser.parse_sum()?;
+        parser.skip_whitespace();
+        if let Some(c) = parser.peek() {
+            return Err(ExpressionError::UnexpectedChar(parser.position, c));
+        }
+
+        let ops = parser.ops;
+        let mut depth = 0;
+        let mut max_depth = 0;
+        for op in &ops {
+            depth = depth + 1 - Self::arguments(op);
+            max_depth = usize::max(max_depth, depth);
+        }
+        Ok(Self { ops, depth: max_depth })
+    }
+
+    /// the amount of stack space needed by evaluate
+    pub fn stack_size(&self) -> usize {
+        self.depth
+    }
+
+    /// the amount of values an op takes from the stack
+    fn arguments(op: &Op) -> usize {
+        match op {
+            Op::Constant(_) | Op::Variable(_) => 0,
+            Op::Negate => 1,
+            Op::Call(function) => function.argument_count(),
+            _ => 2,
+        }
+    }
+
+    /// evaluates the expression, where stack must hold at least stack_size values
+    /// variables missing from `variables` are treated as 0
+    pub fn evaluate(&self, variables: &[f32], stack: &mut [f32]) -> f32 {
+        let mut top = 0;
+        for op in &self.ops {
+            match *op {
+                Op::Constant(value) => {
+                    stack[top] = value;
+                    top += 1;
+                }
+                Op::Variable(index) => {
+                    stack[top] = variables.get(index).copied().unwrap_or(0.0);
+                    top += 1;
+                }
+                Op::Negate => stack[top - 1] = -stack[top - 1],
+                Op::Call(function) => {
+                    let count = function.argument_count();
+                    top -= count;
+                    let args = &stack[top..top + count];
+                    stack[top] = match function {
+                        Function::Sin => args[0].sin(),
+                        Function::Cos => args[0].cos(),
+                        Function::Tan => args[0].tan(),
+                        Function::Abs => args[0].abs(),
+                        Function::Sqrt => args[0].sqrt(),
+                        Function::Exp => args[0].exp(),
+                        Function::Ln => args[0].ln(),
+                        Function::Floor => args[0].floor(),
+                        Function::Ceil => args[0].ceil(),
+                        Function::Round => args[0].round(),
+                        Function::Sign => if args[0] == 0.0 { 0.0 } else { args[0].signum() },
+                        Function::Min => args[0].min(args[1]),
+                        Function::Max => args[0].max(args[1]),
+                        Function::Pow => args[0].powf(args[1]),
+                        Function::Clamp => args[0].max(args[1]).min(args[2]),
+                    };
+                    top += 1;
+                }
+                binary => {
+                    top -= 1;
+                    let (lhs, rhs) = (stack[top - 1], stack[top]);
+                    stack[top - 1] = match binary {
+                        Op::Add => lhs + rhs,
+                        Op::Subtract => lhs - rhs,
+                        Op::Multiply => lhs * rhs,
+                        Op::Divide => lhs / rhs,
+                        Op::Remainder => lhs % rhs,
+                        Op::Power => lhs.powf(rhs),
+                        _ => unreachable!("only binary operators should remain"),
+                    };
+                }
+            }
+        }
+        stack[0]
+    }
+}
+
+/// A recursive descent parser writing ops in postfix order
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+    variables: &'a [&'a str],
+    ops: Vec<Op>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() && c.is_whitespace() {
+            self.position += c.len_utf8();
+        }
+    }
+
+    /// consumes the next character if it is c
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.position += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// consumes characters while they match the predicate
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &str {
+        let start = self.position;
+        while let Some(c) = self.peek() && predicate(c) {
+            self.position += c.len_utf8();
+        }
+        &self.text[start..self.position]
+    }
+
+    /// sum = product (('+' | '-') product)*
+    fn parse_sum(&mut self) -> Result<(), ExpressionError> {
+        self.parse_product()?;
+        loop {
+            if self.eat('+') {
+                self.parse_product()?;
+                self.ops.push(Op::Add);
+            } else if self.eat('-') {
+                self.parse_product()?;
+                self.ops.push(Op::Subtract);
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// product = unary (('*' | '/' | '%') unary)*
+    fn parse_product(&mut self) -> Result<(), ExpressionError> {
+        self.parse_unary()?;
+        loop {
+            if self.eat('*') {
+                self.parse_unary()?;
+                self.ops.push(Op::Multiply);
+            } else if self.eat('/') {
+                self.parse_unary()?;
+                self.ops.push(Op::Divide);
+            } else if self.eat('%') {
+                self.parse_unary()?;
+                self.ops.push(Op::Remainder);
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// unary = '-' unary | '+' unary | power
+    fn parse_unary(&mut self) -> Result<(), ExpressionError> {
+        if self.eat('-') {
+            self.parse_unary()?;
+            self.ops.push(Op::Negate);
+            Ok(())
+        } else if self.eat('+') {
+            self.parse_unary()
+        } else {
+            self.parse_power()
+        }
+    }
+
+    /// power = atom ('^' unary)?, so that powers are right associative
+    fn parse_power(&mut self) -> Result<(), ExpressionError> {
+        self.parse_atom()?;
+        if self.eat('^') {
+            self.parse_unary()?;
+            self.ops.push(Op::Power);
+        }
+        Ok(())
+    }
+
+    /// atom = number | name | name '(' arguments ')' | '(' sum ')'
+    fn parse_atom(&mut self) -> Result<(), ExpressionError> {
+        self.skip_whitespace();
+        let start = self.position;
+        let Some(c) = self.peek() else {
+            return Err(ExpressionError::UnexpectedEnd);
+        };
+
+        if c == '(' {
+            self.position += 1;
+            self.parse_sum()?;
+            return self.expect(')');
+        }
+
+        if c.is_ascii_digit() || c == '.' {
+            let number = self.take_while(|c| c.is_ascii_digit() || c == '.');
+            let value = number.parse().map_err(|_| ExpressionError::UnexpectedChar(start, c))?;
+            self.ops.push(Op::Constant(value));
+            return Ok(());
+        }
+
+        if c.is_alphabetic() || c == '_' {
+            let name = self.take_while(|c| c.is_alphanumeric() || c == '_').to_string();
+            if self.eat('(') {
+                return self.parse_call(name);
+            }
+            if let Some(index) = self.variables.iter().position(|variable| *variable == name) {
+                self.ops.push(Op::Variable(index));
+            } else {
+                let value = match name.as_str() {
+                    "pi" => std::f32::consts::PI,
+                    "tau" => std::f32::consts::TAU,
+                    _ => return Err(ExpressionError::UnknownName(name)),
+                };
+                self.ops.push(Op::Constant(value));
+            }
+            return Ok(());
+        }
+
+        Err(ExpressionError::UnexpectedChar(start, c))
+    }
+
+    /// parses the arguments of a call after its opening parenthesis
+    fn parse_call(&mut self, name: String) -> Result<(), ExpressionError> {
+        let Some(function) = Function::from_name(&name) else {
+            return Err(ExpressionError::UnknownName(name));
+        };
+
+        let mut count = 0;
+        if !self.eat(')') {
+            loop {
+                self.parse_sum()?;
+                count += 1;
+                if !self.eat(',') {
+                    break;
+                }
+            }
+            self.expect(')')?;
+        }
+
+        if count != function.argument_count() {
+            return Err(ExpressionError::ArgumentCount(name, function.argument_count(), count));
+        }
+        self.ops.push(Op::Call(function));
+        Ok(())
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ExpressionError> {
+        if self.eat(c) {
+            Ok(())
+        } else if self.peek().is_none() {
+            Err(ExpressionError::UnexpectedEnd)
+        } else {
+            Err(ExpressionError::Expected(c, self.position))
+        }
+    }
+}
@@ -0,0 +1,70 @@
+/// A single stereo sample, stored as [left, right]
+pub type Frame = [f32; 2];
+
+/// A frame with no signal
+pub const SILENCE: Frame = [0.0, 0.0];
+
+/// The number of channels in a frame
+pub const CHANNELS: usize = 2;
+
+/// The largest number of ports handled without allocating when running mono circuits
+const MONO_STACK_PORTS: usize = 16;
+
+/// Creates a frame with the same value in both channels
+pub fn mono(value: f32) -> Frame {
+    [value, value]
+}
+
+/// Mixes a frame down to a single value
+pub fn to_mono(frame: Frame) -> f32 {
+    (frame[0] + frame[1]) * 0.5
+}
+
+/// Adds two frames
+pub fn add(a: Frame, b: Frame) -> Frame {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+/// Multiplies both channels of a frame by a value
+pub fn scale(frame: Frame, factor: f32) -> Frame {
+    [frame[0] * factor, frame[1] * factor]
+}
+
+/// Runs a mono process on stereo buffers.
+/// Inputs are mixed down to mono and each output is sent to both channels.
+pub fn operate_mono(
+    inputs: &[Frame],
+    outputs: &mut [Frame],
+    process: impl FnOnce(&[f32], &mut [f32])
+) {
+    if inputs.len() <= MONO_STACK_PORTS && outputs.len() <= MONO_STACK_PORTS {
+        let mut mono_inputs = [0.0; MONO_STACK_PORTS];
+        let mut mono_outputs = [0.0; MONO_STACK_PORTS];
+        operate_mono_with(inputs, outputs, &mut mono_inputs, &mut mono_outputs, process);
+    } else {
+        let mut mono_inputs = vec![0.0; inputs.len()];
+        let mut mono_outputs = vec![0.0; outputs.len()];
+        operate_mono_with(inputs, outputs, &mut mono_inputs, &mut mono_outputs, process);
+    }
+}
+
+fn operate_mono_with(
+    inputs: &[Frame],
+    outputs: &mut [Frame],
+    mono_inputs: &mut [f32],
+    mono_outputs: &mut [f32],
+    process: impl FnOnce(&[f32], &mut [f32])
+) {
+    let mono_inputs = &mut mono_inputs[..inputs.len()];
+    let mono_outputs = &mut mono_outputs[..outputs.len()];
+
+    for (mono_input, input) in mono_inputs.iter_mut().zip(inputs) {
+        *mono_input = to_mono(*input);
+    }
+
+    process(mono_inputs, mono_outputs);
+
+    for (output, mono_output) in outputs.iter_mut().zip(mono_outputs.iter()) {
+        *output = mono(*mono_output);
+    }
+}
@@ -34,5 +34,37 @@ pub mod plugin_graph;
 
 pub mod playback_tree;
 
+pub mod bundle;
+
+pub mod settings;
+
+pub mod audio_config;
+
+pub mod audio_output;
+
+pub mod frame;
+
+pub mod patch_file;
+
+pub mod wav;
+
+pub mod render_spec;
+
+pub mod toast;
+
+pub mod meter;
+
+pub mod limiter;
+
+pub mod recorder;
+
+pub mod midi;
+
+pub mod computer_keyboard;
+
+pub mod program_bank;
+
+pub mod expression;
+
 mod id_manager;
 pub use id_manager::IdManager;
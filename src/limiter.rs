@@ -0,0 +1,103 @@
+/// The user's preferences for the safety limiter on the main output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LimiterSettings {
+    pub enabled: bool,
+
+    /// the highest level allowed through the limiter in decibels
+    pub threshold_db: f32,
+}
+
+impl LimiterSettings {
+    pub const MIN_THRESHOLD_DB: f32 = -24.0;
+    pub const MAX_THRESHOLD_DB: f32 = 0.0;
+
+    /// the threshold as a linear gain
+    pub fn threshold(&self) -> f32 {
+        10f32.powf(self.threshold_db.clamp(Self::MIN_THRESHOLD_DB, Self::MAX_THRESHOLD_DB) / 20.0)
+    }
+}
+
+impl Default for LimiterSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold_db: -1.0,
+        }
+    }
+}
+
+/// A peak limiter with instant attack, so no sample ever exceeds the threshold
+/// Gain recovers exponentially once the signal falls below the threshold.
+/// Non-finite samples silence the output, as they would otherwise be played at full scale.
+#[derive(Debug, Clone)]
+pub struct Limiter {
+    settings: LimiterSettings,
+    threshold: f32,
+
+    /// the gain currently applied to the signal (0, 1]
+    gain: f32,
+
+    /// the fraction of the remaining gain reduction kept each sample
+    release: f32,
+}
+
+impl Limiter {
+    /// the time for the gain reduction to recover by ~63% in seconds
+    pub const RELEASE_TIME: f32 = 0.1;
+
+    pub fn new(settings: LimiterSettings, sample_rate: u32) -> Self {
+        Self {
+            settings,
+            threshold: settings.threshold(),
+            gain: 1.0,
+            release: Self::release_coefficient(sample_rate),
+        }
+    }
+
+    fn release_coefficient(sample_rate: u32) -> f32 {
+        (-1.0 / (Self::RELEASE_TIME * sample_rate as f32)).exp()
+    }
+
+    pub fn settings(&self) -> LimiterSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: LimiterSettings) {
+        self.settings = settings;
+        self.threshold = settings.threshold();
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.release = Self::release_coefficient(sample_rate);
+    }
+
+    /// the gain reduction currently applied in decibels (0 or negative)
+    pub fn gain_reduction_db(&self) -> f32 {
+        20.0 * self.gain.log10()
+    }
+
+    /// limits one frame of any number of channels, applying the same gain to every channel
+    pub fn process(&mut self, samples: &mut [f32]) {
+        if !self.settings.enabled {
+            return;
+        }
+
+        if samples.iter().any(|sample| !sample.is_finite()) {
+            samples.fill(0.0);
+            return;
+        }
+
+        let peak = samples.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        let target = if peak > self.threshold { self.threshold / peak } else { 1.0 };
+
+        self.gain = if target < self.gain {
+            target
+        } else {
+            target + (self.gain - target) * self.release
+        };
+
+        for sample in samples.iter_mut() {
+            *sample *= self.gain;
+        }
+    }
+}
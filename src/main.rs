@@ -1,6 +1,7 @@
 use starship_rust::{
     circuit::CircuitBuilderSpecification as Cbs,
-    circuits::{InterpolatorBuilder, OscillatorBuilder, RouterBuilder, SampleQuantizerBuilder, SwitchBuilder},
+    circuits::{ChaosBuilder, ClockBuilder, EnvFollowerBuilder, FormantBuilder, GateToolsBuilder, InterpolatorBuilder, LfoBuilder, LogicBuilder, MathBuilder, ModFxBuilder, OscillatorBuilder, PitchDetectBuilder, PluckBuilder, RouterBuilder, SampleQuantizerBuilder, SamplerBuilder, ScaleQuantizerBuilder, ScriptBuilder, SlewBuilder, SubpatchBuilder, SwitchBuilder, TunerBuilder, UnisonBuilder, VcaBuilder, VoiceAllocBuilder, WavefolderBuilder},
+    settings::AppSettings,
 };
 
 macro_rules! builder_defs {
@@ -12,9 +13,10 @@ macro_rules! builder_defs {
 }
 
 fn main() -> eframe::Result {
+    let window_size = AppSettings::load().window_size.unwrap_or([400.0, 300.0]);
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
-            .with_inner_size([400.0, 300.0])
+            .with_inner_size(window_size)
             .with_min_inner_size([300.0, 220.0]),
         ..Default::default()
     };
@@ -25,6 +27,27 @@ fn main() -> eframe::Result {
         {OscillatorBuilder: "Oscillator"}
         {SwitchBuilder: "Switch"}
         {SampleQuantizerBuilder: "S-Quantizer"}
+        {LfoBuilder: "LFO"}
+        {MathBuilder: "Math"}
+        {LogicBuilder: "Logic"}
+        {VcaBuilder: "VCA"}
+        {WavefolderBuilder: "Wavefolder"}
+        {SamplerBuilder: "Sampler"}
+        {ModFxBuilder: "Mod FX"}
+        {SlewBuilder: "Slew"}
+        {ClockBuilder: "Clock"}
+        {ChaosBuilder: "Chaos"}
+        {EnvFollowerBuilder: "Env Follower"}
+        {PitchDetectBuilder: "Pitch Detect"}
+        {ScaleQuantizerBuilder: "Scale Quantizer"}
+        {GateToolsBuilder: "Gate Tools"}
+        {UnisonBuilder: "Unison"}
+        {TunerBuilder: "Tuner"}
+        {PluckBuilder: "Pluck"}
+        {FormantBuilder: "Formant"}
+        {VoiceAllocBuilder: "Voice Alloc"}
+        {ScriptBuilder: "Script"}
+        {SubpatchBuilder: "Subpatch"}
     ];
 
     eframe::run_native(
@@ -0,0 +1,196 @@
+use std::{sync::{atomic::{AtomicBool, AtomicU32, Ordering}, Arc}, time::Instant};
+
+use egui::{pos2, vec2, Color32, Rect, Sense, Ui};
+
+use crate::frame::{self, Frame};
+
+/// Levels shared between the audio thread and the ui
+/// Levels are stored as the bits of an f32 so they may be updated without locking.
+#[derive(Debug, Default)]
+struct SharedLevels {
+    /// the highest absolute value of each channel since the last read
+    peak: [AtomicU32; frame::CHANNELS],
+
+    /// the rms of each channel over the most recent window
+    rms: [AtomicU32; frame::CHANNELS],
+
+    /// set when a sample reaches full scale, cleared by the ui
+    clipped: AtomicBool,
+}
+
+/// The levels of a stereo signal
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MeterLevels {
+    pub peak: Frame,
+    pub rms: Frame,
+}
+
+/// Measures the level of a signal on the audio thread
+#[derive(Debug)]
+pub struct LevelMeter {
+    shared: Arc<SharedLevels>,
+    sum_squares: Frame,
+    window_len: usize,
+    count: usize,
+}
+
+impl LevelMeter {
+    /// the length of the window rms is measured over in seconds
+    pub const RMS_WINDOW: f32 = 0.05;
+
+    /// creates a meter along with the reader used to poll it
+    pub fn new(sample_rate: u32) -> (Self, MeterReader) {
+        let shared = Arc::new(SharedLevels::default());
+        let meter = Self {
+            shared: shared.clone(),
+            sum_squares: frame::SILENCE,
+            window_len: ((sample_rate as f32 * Self::RMS_WINDOW) as usize).max(1),
+            count: 0,
+        };
+        (meter, MeterReader { shared })
+    }
+
+    /// measures one frame of the signal
+    pub fn process(&mut self, frame: Frame) {
+        for (channel, sample) in frame.into_iter().enumerate() {
+            let value = sample.abs();
+
+            // nan is treated as a clip but not recorded as a peak
+            if value >= 1.0 || value.is_nan() {
+                self.shared.clipped.store(true, Ordering::Relaxed);
+            }
+            if !value.is_nan() {
+                // non-negative floats are ordered the same as their bits
+                self.shared.peak[channel].fetch_max(value.to_bits(), Ordering::Relaxed);
+                self.sum_squares[channel] += value * value;
+            }
+        }
+
+        self.count += 1;
+        if self.count >= self.window_len {
+            for channel in 0..frame::CHANNELS {
+                let rms = (self.sum_squares[channel] / self.count as f32).sqrt();
+                self.shared.rms[channel].store(rms.to_bits(), Ordering::Relaxed);
+            }
+            self.sum_squares = frame::SILENCE;
+            self.count = 0;
+        }
+    }
+}
+
+/// Polls the levels measured by a LevelMeter
+#[derive(Debug, Clone)]
+pub struct MeterReader {
+    shared: Arc<SharedLevels>,
+}
+
+impl MeterReader {
+    /// takes the peak since the last read along with the latest rms
+    pub fn read(&self) -> MeterLevels {
+        let mut levels = MeterLevels::default();
+        for channel in 0..frame::CHANNELS {
+            levels.peak[channel] = f32::from_bits(self.shared.peak[channel].swap(0, Ordering::Relaxed));
+            levels.rms[channel] = f32::from_bits(self.shared.rms[channel].load(Ordering::Relaxed));
+        }
+        levels
+    }
+
+    /// returns true if the signal has clipped since the clip light was last reset
+    pub fn clipped(&self) -> bool {
+        self.shared.clipped.load(Ordering::Relaxed)
+    }
+
+    pub fn reset_clip(&self) {
+        self.shared.clipped.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Draws a stereo level meter with a latching clip light
+/// Click the clip light to reset it.
+#[derive(Debug, Clone)]
+pub struct MeterDisplay {
+    reader: MeterReader,
+
+    /// the displayed peak of each channel, which falls slowly after a transient
+    peak: Frame,
+    last_update: Instant,
+}
+
+impl MeterDisplay {
+    /// the quietest level shown on the meter in decibels
+    pub const FLOOR_DB: f32 = -60.0;
+
+    /// how quickly the displayed peak falls in decibels per second
+    pub const PEAK_FALL_DB: f32 = 24.0;
+
+    const WIDTH: f32 = 160.0;
+    const BAR_HEIGHT: f32 = 6.0;
+    const BAR_SPACING: f32 = 2.0;
+    const CLIP_RADIUS: f32 = 5.0;
+
+    pub fn new(reader: MeterReader) -> Self {
+        Self {
+            reader,
+            peak: frame::SILENCE,
+            last_update: Instant::now(),
+        }
+    }
+
+    /// converts a level to its position on the meter [0, 1]
+    pub fn meter_position(level: f32) -> f32 {
+        if level <= 0.0 {
+            return 0.0;
+        }
+        (1.0 - 20.0 * level.log10() / Self::FLOOR_DB).clamp(0.0, 1.0)
+    }
+
+    pub fn show(&mut self, ui: &mut Ui) {
+        let levels = self.reader.read();
+        let elapsed = self.last_update.elapsed().as_secs_f32();
+        self.last_update = Instant::now();
+
+        // let the displayed peak fall until a louder peak arrives
+        let fall = 10f32.powf(-Self::PEAK_FALL_DB * elapsed / 20.0);
+        for channel in 0..frame::CHANNELS {
+            self.peak[channel] = levels.peak[channel].max(self.peak[channel] * fall);
+        }
+
+        let height = Self::BAR_HEIGHT * frame::CHANNELS as f32 + Self::BAR_SPACING;
+        ui.horizontal(|ui| {
+            let (rect, _) = ui.allocate_exact_size(vec2(Self::WIDTH, height), Sense::hover());
+            let painter = ui.painter();
+            for channel in 0..frame::CHANNELS {
+                let top = rect.top() + channel as f32 * (Self::BAR_HEIGHT + Self::BAR_SPACING);
+                let bar = Rect::from_min_size(pos2(rect.left(), top), vec2(Self::WIDTH, Self::BAR_HEIGHT));
+                painter.rect_filled(bar, 0.0, Color32::from_gray(40));
+
+                let rms_width = Self::WIDTH * Self::meter_position(levels.rms[channel]);
+                painter.rect_filled(
+                    Rect::from_min_size(bar.min, vec2(rms_width, Self::BAR_HEIGHT)),
+                    0.0,
+                    Color32::from_rgb(40, 160, 70)
+                );
+
+                let peak_x = bar.left() + Self::WIDTH * Self::meter_position(self.peak[channel]);
+                painter.rect_filled(
+                    Rect::from_min_max(pos2(peak_x - 1.0, bar.top()), pos2(peak_x + 1.0, bar.bottom())),
+                    0.0,
+                    if self.peak[channel] >= 1.0 { Color32::RED } else { Color32::from_rgb(200, 220, 90) }
+                );
+            }
+
+            let (clip_rect, clip_response) = ui.allocate_exact_size(
+                vec2(Self::CLIP_RADIUS * 2.0, Self::CLIP_RADIUS * 2.0),
+                Sense::click()
+            );
+            let clip_color = if self.reader.clipped() { Color32::RED } else { Color32::from_gray(60) };
+            ui.painter().circle_filled(clip_rect.center(), Self::CLIP_RADIUS, clip_color);
+            if clip_response.on_hover_text("Clip (click to reset)").clicked() {
+                self.reader.reset_clip();
+            }
+        });
+
+        // levels change continuously, so keep polling
+        ui.ctx().request_repaint();
+    }
+}
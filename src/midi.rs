@@ -0,0 +1,702 @@
+use std::{fmt::Display, sync::{mpsc::{Sender, SyncSender}, Arc, Mutex}};
+
+use egui::Response;
+use midir::{ConnectError, Ignore, InitError, MidiInputConnection, MidiOutputConnection, SendError};
+use thiserror::Error;
+
+use crate::{live_plugin_id::LivePluginId, pitch::TuningSystem, playback::{InputId, InputSpecification, NoteEvent, NoteId, Pedal, PlaybackCommand}, sequencers::transport::MidiClockMessage};
+
+/// The name Starship uses when connecting to MIDI ports
+const CLIENT_NAME: &str = "Starship";
+
+/// An error occurring while connecting to a MIDI port
+#[derive(Debug, Error)]
+pub enum MidiError {
+    #[error(transparent)]
+    Init(#[from] InitError),
+
+    #[error("No MIDI port named '{0}' was found.")]
+    NoSuchPort(String),
+
+    #[error("Could not connect to MIDI port '{0}'.")]
+    Connect(String),
+
+    #[error(transparent)]
+    Send(#[from] SendError),
+}
+
+/// A MIDI channel voice or system realtime message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiMessage {
+    NoteOff{channel: u8, key: u8, velocity: u8},
+    NoteOn{channel: u8, key: u8, velocity: u8},
+    PolyPressure{channel: u8, key: u8, pressure: u8},
+    ControlChange{channel: u8, controller: u8, value: u8},
+    ProgramChange{channel: u8, program: u8},
+    ChannelPressure{channel: u8, pressure: u8},
+
+    /// the bend relative to center [-8192, 8191]
+    PitchBend{channel: u8, bend: i16},
+
+    Clock,
+    Start,
+    Continue,
+    Stop,
+}
+
+impl MidiMessage {
+    /// parses a message from raw bytes
+    /// a note on with zero velocity is read as a note off
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let status = *bytes.first()?;
+        let channel = status & 0x0F;
+        let data = |index: usize| bytes.get(index).map(|byte| byte & 0x7F);
+
+        let message = match status {
+            0xF8 => Self::Clock,
+            0xFA => Self::Start,
+            0xFB => Self::Continue,
+            0xFC => Self::Stop,
+            _ => match status & 0xF0 {
+                0x80 => Self::NoteOff { channel, key: data(1)?, velocity: data(2)? },
+                0x90 => match data(2)? {
+                    0 => Self::NoteOff { channel, key: data(1)?, velocity: 0 },
+                    velocity => Self::NoteOn { channel, key: data(1)?, velocity },
+                },
+                0xA0 => Self::PolyPressure { channel, key: data(1)?, pressure: data(2)? },
+                0xB0 => Self::ControlChange { channel, controller: data(1)?, value: data(2)? },
+                0xC0 => Self::ProgramChange { channel, program: data(1)? },
+                0xD0 => Self::ChannelPressure { channel, pressure: data(1)? },
+                0xE0 => {
+                    let value = (data(2)? as i16) << 7 | data(1)? as i16;
+                    Self::PitchBend { channel, bend: value - 8192 }
+                }
+                _ => return None,
+            },
+        };
+        Some(message)
+    }
+
+    /// the bytes of the message as sent over the wire
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match *self {
+            Self::NoteOff { channel, key, velocity } => vec![0x80 | channel, key, velocity],
+            Self::NoteOn { channel, key, velocity } => vec![0x90 | channel, key, velocity],
+            Self::PolyPressure { channel, key, pressure } => vec![0xA0 | channel, key, pressure],
+            Self::ControlChange { channel, controller, value } => vec![0xB0 | channel, controller, value],
+            Self::ProgramChange { channel, program } => vec![0xC0 | channel, program],
+            Self::ChannelPressure { channel, pressure } => vec![0xD0 | channel, pressure],
+            Self::PitchBend { channel, bend } => {
+                let value = (bend.clamp(-8192, 8191) + 8192) as u16;
+                vec![0xE0 | channel, (value & 0x7F) as u8, (value >> 7) as u8]
+            }
+            Self::Clock => vec![0xF8],
+            Self::Start => vec![0xFA],
+            Self::Continue => vec![0xFB],
+            Self::Stop => vec![0xFC],
+        }
+    }
+}
+
+/// The note id used for a key on a channel, unique among all channels
+pub fn note_id(channel: u8, key: u8) -> NoteId {
+    channel as NoteId * 128 + key as NoteId
+}
+
+/// The frequency of a MIDI key in 12 tone equal temperament with A4 (key 69) at 440Hz
+pub fn key_frequency(key: u8) -> f32 {
+    bent_key_frequency(key, 0, 0.0)
+}
+
+/// The frequency of a MIDI key moved by a pitch bend [-8192, 8191] over a range in semitones
+pub fn bent_key_frequency(key: u8, bend: i16, bend_range: f32) -> f32 {
+    tuned_key_frequency(&TuningSystem::default(), key, bend, bend_range)
+}
+
+/// The frequency of a MIDI key in the given tuning, moved by a pitch bend [-8192, 8191] over a range in semitones
+pub fn tuned_key_frequency(tuning: &TuningSystem, key: u8, bend: i16, bend_range: f32) -> f32 {
+    let bend_cents = bend as f64 / 8192.0 * bend_range as f64 * 100.0;
+    tuning.get_cent_delta_a4_frequency((key as f64 - 69.0) * 100.0 + bend_cents) as f32
+}
+
+/// How MIDI Polyphonic Expression is received
+/// Only the lower zone is supported: channel 0 is the master channel and the others are member channels,
+/// each carrying the pitch bend and pressure of the notes played on it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MpeSettings {
+    pub enabled: bool,
+
+    /// the pitch bend range of member channels in semitones
+    pub bend_range: f32,
+}
+
+impl MpeSettings {
+    pub const MASTER_CHANNEL: u8 = 0;
+    pub const DEFAULT_BEND_RANGE: f32 = 48.0;
+    pub const MAX_BEND_RANGE: f32 = 96.0;
+
+    /// returns true if the channel carries per-note expression
+    pub fn is_member_channel(&self, channel: u8) -> bool {
+        self.enabled && channel != Self::MASTER_CHANNEL
+    }
+}
+
+impl Default for MpeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bend_range: Self::DEFAULT_BEND_RANGE,
+        }
+    }
+}
+
+/// A key currently held down on a MIDI device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct HeldKey {
+    channel: u8,
+    key: u8,
+}
+
+/// The MIDI channels a synth receives notes from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MidiRoute {
+    pub synth: LivePluginId,
+
+    /// the channel [0, 15] notes are taken from, or none for all channels
+    pub channel: Option<u8>,
+
+    /// how far the notes of the synth are moved by a full pitch bend in semitones
+    pub bend_range: f32,
+}
+
+/// The controller of the sustain pedal
+pub const SUSTAIN_CONTROLLER: u8 = 64;
+
+/// The controller of the sostenuto pedal
+pub const SOSTENUTO_CONTROLLER: u8 = 66;
+
+/// The channel mode message releasing every note
+pub const ALL_NOTES_OFF_CONTROLLER: u8 = 123;
+
+impl MidiRoute {
+    /// the pitch bend range most synths default to in semitones
+    pub const DEFAULT_BEND_RANGE: f32 = 2.0;
+    pub const MAX_BEND_RANGE: f32 = 48.0;
+
+    pub fn accepts(&self, channel: u8) -> bool {
+        self.channel.is_none_or(|route_channel| route_channel == channel)
+    }
+}
+
+/// A MIDI controller on a channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CcSource {
+    pub channel: u8,
+    pub controller: u8,
+}
+
+/// A secondary input of a live plugin
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ParameterTarget {
+    pub plugin: LivePluginId,
+    pub input: InputId,
+}
+
+/// A controller bound to a parameter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CcMapping {
+    pub source: CcSource,
+    pub target: ParameterTarget,
+}
+
+#[derive(Debug, Clone)]
+struct CcBinding {
+    mapping: CcMapping,
+    spec: InputSpecification,
+}
+
+/// Binds MIDI controllers to plugin parameters
+/// A parameter is bound by starting to learn it, then moving a controller.
+#[derive(Debug, Clone, Default)]
+pub struct CcMap {
+    bindings: Vec<CcBinding>,
+    learning: Option<(ParameterTarget, InputSpecification)>,
+}
+
+impl CcMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// binds the next controller that is moved to the parameter
+    pub fn learn(&mut self, target: ParameterTarget, spec: InputSpecification) {
+        self.learning = Some((target, spec));
+    }
+
+    pub fn cancel_learn(&mut self) {
+        self.learning = None;
+    }
+
+    /// returns true if the parameter is waiting for a controller to be moved
+    pub fn is_learning(&self, target: ParameterTarget) -> bool {
+        self.learning.as_ref().is_some_and(|(learning, _)| *learning == target)
+    }
+
+    /// binds a controller to a parameter, replacing the parameter's previous binding
+    pub fn bind(&mut self, source: CcSource, target: ParameterTarget, spec: InputSpecification) {
+        self.unbind(target);
+        self.bindings.push(CcBinding {
+            mapping: CcMapping { source, target },
+            spec
+        });
+    }
+
+    pub fn unbind(&mut self, target: ParameterTarget) {
+        self.bindings.retain(|binding| binding.mapping.target != target);
+    }
+
+    /// the controller bound to a parameter
+    pub fn binding(&self, target: ParameterTarget) -> Option<CcSource> {
+        self.bindings.iter()
+            .find(|binding| binding.mapping.target == target)
+            .map(|binding| binding.mapping.source)
+    }
+
+    pub fn mappings(&self) -> impl Iterator<Item = CcMapping> + '_ {
+        self.bindings.iter().map(|binding| binding.mapping)
+    }
+
+    /// restores mappings created by to_string
+    /// mappings whose parameter has no specification are dropped
+    pub fn load(text: &str, spec: impl Fn(ParameterTarget) -> Option<InputSpecification>) -> Self {
+        let mut map = Self::new();
+        for line in text.lines() {
+            let values: Vec<u32> = line.split_whitespace()
+                .filter_map(|value| value.parse().ok())
+                .collect();
+            let &[channel, controller, plugin, input] = values.as_slice() else {
+                continue;
+            };
+            if channel > 15 || controller > 127 {
+                continue;
+            }
+
+            let source = CcSource { channel: channel as u8, controller: controller as u8 };
+            let target = ParameterTarget { plugin: plugin.into(), input };
+            if let Some(spec) = spec(target) {
+                map.bind(source, target, spec);
+            }
+        }
+        map
+    }
+
+    /// converts a controller value [0, 127] to a value of the parameter
+    /// note inputs are scaled logarithmically, so equal movements give equal intervals
+    pub fn parameter_value(spec: &InputSpecification, value: u8) -> f64 {
+        let t = value.min(127) as f64 / 127.0;
+        let (min, max) = spec.range;
+        let unsnapped = if spec.is_note_input && min > 0.0 {
+            min * (max / min).powf(t)
+        } else {
+            min + (max - min) * t
+        };
+        spec.snap(unsnapped)
+    }
+
+    /// handles a controller message, binding it if a parameter is being learned
+    /// every parameter bound to the controller is set through commands
+    pub fn send(&mut self, source: CcSource, value: u8, commands: &SyncSender<PlaybackCommand>) {
+        if let Some((target, spec)) = self.learning.take() {
+            self.bind(source, target, spec);
+        }
+
+        for binding in self.bindings.iter().filter(|binding| binding.mapping.source == source) {
+            let _ = commands.try_send(PlaybackCommand::SetInput {
+                plugin: binding.mapping.target.plugin,
+                input: binding.mapping.target.input,
+                value: Self::parameter_value(&binding.spec, value)
+            });
+        }
+    }
+}
+
+/// Written as one 'channel controller plugin input' line per mapping
+impl Display for CcMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for mapping in self.mappings() {
+            let plugin: u32 = mapping.target.plugin.into();
+            writeln!(
+                f,
+                "{} {} {} {}",
+                mapping.source.channel,
+                mapping.source.controller,
+                plugin,
+                mapping.target.input
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Adds the MIDI learn menu to the widget of a parameter, opened by right-clicking
+pub fn parameter_context_menu(
+    response: &Response,
+    routing: &Mutex<MidiRouting>,
+    target: ParameterTarget,
+    spec: &InputSpecification
+) {
+    response.context_menu(|ui| {
+        let Ok(mut routing) = routing.lock() else {
+            return;
+        };
+        let cc_map = routing.cc_map_mut();
+
+        if cc_map.is_learning(target) {
+            ui.label("Move a controller...");
+            if ui.button("Cancel MIDI Learn").clicked() {
+                cc_map.cancel_learn();
+                ui.close();
+            }
+        } else if ui.button("MIDI Learn").clicked() {
+            cc_map.learn(target, spec.clone());
+            ui.close();
+        }
+
+        if let Some(source) = cc_map.binding(target) {
+            ui.label(format!("CC {} on channel {}", source.controller, source.channel + 1));
+            if ui.button("Clear MIDI Binding").clicked() {
+                cc_map.unbind(target);
+                ui.close();
+            }
+        }
+    });
+}
+
+/// Decides which synths receive the notes of each channel,
+/// which parameters are set by each controller and where clock messages are sent
+#[derive(Debug, Clone, Default)]
+pub struct MidiRouting {
+    routes: Vec<MidiRoute>,
+    cc_map: CcMap,
+    clock: Option<Sender<MidiClockMessage>>,
+    program_changes: Option<Sender<u8>>,
+    mpe: MpeSettings,
+
+    /// the tuning keys are played in
+    tuning: TuningSystem,
+
+    /// the keys held down, whose frequency follows the pitch bend of their channel
+    held: Vec<HeldKey>,
+
+    /// the latest pitch bend of each channel, applied to the notes held on it
+    channel_bend: [i16; 16],
+}
+
+impl MidiRouting {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn routes(&self) -> &[MidiRoute] {
+        &self.routes
+    }
+
+    pub fn cc_map(&self) -> &CcMap {
+        &self.cc_map
+    }
+
+    pub fn cc_map_mut(&mut self) -> &mut CcMap {
+        &mut self.cc_map
+    }
+
+    pub fn set_cc_map(&mut self, cc_map: CcMap) {
+        self.cc_map = cc_map;
+    }
+
+    /// sends clock and transport messages to a MidiClock, or discards them if none
+    pub fn set_clock(&mut self, clock: Option<Sender<MidiClockMessage>>) {
+        self.clock = clock;
+    }
+
+    /// sends the program of program change messages, or discards them if none
+    pub fn set_program_changes(&mut self, program_changes: Option<Sender<u8>>) {
+        self.program_changes = program_changes;
+    }
+
+    pub fn mpe(&self) -> MpeSettings {
+        self.mpe
+    }
+
+    pub fn set_mpe(&mut self, mpe: MpeSettings) {
+        self.mpe = mpe;
+    }
+
+    pub fn tuning(&self) -> &TuningSystem {
+        &self.tuning
+    }
+
+    /// sets the tuning keys are played in
+    /// keys already held move to the new tuning on the next pitch bend of their channel
+    pub fn set_tuning(&mut self, tuning: TuningSystem) {
+        self.tuning = tuning;
+    }
+
+    /// routes a channel to a synth, replacing its previous route but keeping its bend range
+    pub fn set_route(&mut self, synth: LivePluginId, channel: Option<u8>) {
+        let bend_range = self.route(synth).map_or(MidiRoute::DEFAULT_BEND_RANGE, |route| route.bend_range);
+        self.remove_route(synth);
+        self.routes.push(MidiRoute { synth, channel, bend_range });
+    }
+
+    pub fn route(&self, synth: LivePluginId) -> Option<&MidiRoute> {
+        self.routes.iter().find(|route| route.synth == synth)
+    }
+
+    /// sets the pitch bend range of a routed synth in semitones
+    /// returns false if the synth is not routed
+    pub fn set_bend_range(&mut self, synth: LivePluginId, bend_range: f32) -> bool {
+        let Some(route) = self.routes.iter_mut().find(|route| route.synth == synth) else {
+            return false;
+        };
+        route.bend_range = bend_range.clamp(0.0, MidiRoute::MAX_BEND_RANGE);
+        true
+    }
+
+    /// stops sending notes to a synth
+    pub fn remove_route(&mut self, synth: LivePluginId) {
+        self.routes.retain(|route| route.synth != synth);
+    }
+
+    /// the synths receiving notes from a channel
+    pub fn synths(&self, channel: u8) -> impl Iterator<Item = LivePluginId> + '_ {
+        self.routes.iter()
+            .filter(move |route| route.accepts(channel))
+            .map(|route| route.synth)
+    }
+
+    /// the frequency a synth plays a key at, including the pitch bend of its channel
+    /// MPE member channels use the MPE bend range in place of the synth's
+    fn held_key_frequency(&self, route: &MidiRoute, channel: u8, key: u8) -> f32 {
+        let bend_range = if self.mpe.is_member_channel(channel) {
+            self.mpe.bend_range
+        } else {
+            route.bend_range
+        };
+        tuned_key_frequency(&self.tuning, key, self.channel_bend[channel as usize], bend_range)
+    }
+
+    /// queues an event for each synth routed to the channel
+    fn send_event(&self, channel: u8, event: impl Fn(&MidiRoute) -> NoteEvent, commands: &SyncSender<PlaybackCommand>) {
+        for route in self.routes.iter().filter(|route| route.accepts(channel)) {
+            // drop events rather than block when the playback thread falls behind
+            let _ = commands.try_send(PlaybackCommand::Note { synth: route.synth, event: event(route) });
+        }
+    }
+
+    /// sends an event for each note held on a channel, such as after a change in pitch bend
+    fn send_held_event(
+        &self,
+        channel: u8,
+        event: impl Fn(&MidiRoute, HeldKey) -> NoteEvent,
+        commands: &SyncSender<PlaybackCommand>
+    ) {
+        for &held in self.held.iter().filter(|held| held.channel == channel) {
+            self.send_event(channel, |route| event(route, held), commands);
+        }
+    }
+
+    /// converts a message to note events and queues them for each routed synth
+    /// program changes on any channel are sent to the program change receiver,
+    /// pedal controllers are sent as pedals, all notes off releases every synth,
+    /// and other controllers are sent to the parameters bound to them
+    /// pitch bend changes the frequency of the notes held on its channel,
+    /// and with MPE enabled pressure on member channels is sent as the aftertouch of their notes
+    /// timestamp is the time the message was received in microseconds
+    pub fn send(&mut self, message: MidiMessage, timestamp: u64, commands: &SyncSender<PlaybackCommand>) {
+        let clock_message = match message {
+            MidiMessage::Clock => Some(MidiClockMessage::Pulse(timestamp)),
+            MidiMessage::Start => Some(MidiClockMessage::Start),
+            MidiMessage::Stop => Some(MidiClockMessage::Stop),
+            MidiMessage::Continue => Some(MidiClockMessage::Continue),
+            _ => None,
+        };
+        if let Some(clock_message) = clock_message {
+            if let Some(clock) = &self.clock {
+                let _ = clock.send(clock_message);
+            }
+            return;
+        }
+
+        match message {
+            MidiMessage::ControlChange { channel, controller, value }
+                if controller == SUSTAIN_CONTROLLER || controller == SOSTENUTO_CONTROLLER =>
+            {
+                let pedal = if controller == SUSTAIN_CONTROLLER { Pedal::Sustain } else { Pedal::Sostenuto };
+                for synth in self.synths(channel) {
+                    let _ = commands.try_send(PlaybackCommand::Pedal { synth, pedal, pressed: value >= 64 });
+                }
+            }
+            MidiMessage::ProgramChange { program, .. } => {
+                if let Some(program_changes) = &self.program_changes {
+                    let _ = program_changes.send(program);
+                }
+            }
+            MidiMessage::ControlChange { controller: ALL_NOTES_OFF_CONTROLLER, .. } => {
+                self.held.clear();
+                let _ = commands.try_send(PlaybackCommand::AllNotesOff);
+            }
+            MidiMessage::ControlChange { channel, controller, value } => {
+                self.cc_map.send(CcSource { channel, controller }, value, commands);
+            }
+            MidiMessage::NoteOn { channel, key, velocity } => {
+                let held = HeldKey { channel, key };
+                if !self.held.contains(&held) {
+                    self.held.push(held);
+                }
+                self.send_event(channel, |route| NoteEvent::On {
+                    id: note_id(channel, key),
+                    freq: self.held_key_frequency(route, channel, key),
+                    velocity
+                }, commands);
+            }
+            MidiMessage::NoteOff { channel, key, .. } => {
+                self.held.retain(|held| *held != HeldKey { channel, key });
+                self.send_event(channel, |route| NoteEvent::Off {
+                    id: note_id(channel, key),
+                    freq: self.held_key_frequency(route, channel, key)
+                }, commands);
+            }
+            MidiMessage::PolyPressure { channel, key, pressure } => {
+                self.send_event(channel, |_| NoteEvent::Aftertouch {
+                    id: note_id(channel, key),
+                    aftertouch: pressure as f32 / 127.0
+                }, commands);
+            }
+            MidiMessage::PitchBend { channel, bend } => {
+                self.channel_bend[channel as usize] = bend;
+                self.send_held_event(channel, |route, held| NoteEvent::Freq {
+                    id: note_id(held.channel, held.key),
+                    freq: self.held_key_frequency(route, held.channel, held.key)
+                }, commands);
+            }
+            MidiMessage::ChannelPressure { channel, pressure } if self.mpe.is_member_channel(channel) => {
+                self.send_held_event(channel, |_, held| NoteEvent::Aftertouch {
+                    id: note_id(held.channel, held.key),
+                    aftertouch: pressure as f32 / 127.0
+                }, commands);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The names of the available MIDI input ports
+pub fn input_port_names() -> Vec<String> {
+    let Ok(input) = midir::MidiInput::new(CLIENT_NAME) else {
+        return Vec::new();
+    };
+    input.ports()
+        .iter()
+        .filter_map(|port| input.port_name(port).ok())
+        .collect()
+}
+
+/// A connection to a MIDI input port that sends notes to live synths
+pub struct MidiInput {
+    port_name: String,
+    routing: Arc<Mutex<MidiRouting>>,
+    _connection: MidiInputConnection<()>,
+}
+
+impl MidiInput {
+    /// connects to the port with the given name
+    /// incoming notes are routed according to routing and sent through commands
+    pub fn connect(
+        port_name: &str,
+        routing: Arc<Mutex<MidiRouting>>,
+        commands: SyncSender<PlaybackCommand>
+    ) -> Result<Self, MidiError> {
+        let mut input = midir::MidiInput::new(CLIENT_NAME)?;
+        input.ignore(Ignore::SysexAndActiveSense);
+
+        let port = input.ports()
+            .into_iter()
+            .find(|port| input.port_name(port).ok().as_deref() == Some(port_name))
+            .ok_or_else(|| MidiError::NoSuchPort(port_name.to_string()))?;
+
+        let callback_routing = routing.clone();
+        let connection = input.connect(
+            &port,
+            "starship-input",
+            move |timestamp, bytes, _| {
+                let Some(message) = MidiMessage::parse(bytes) else {
+                    return;
+                };
+                if let Ok(mut routing) = callback_routing.lock() {
+                    routing.send(message, timestamp, &commands);
+                }
+            },
+            ()
+        ).map_err(|_: ConnectError<midir::MidiInput>| MidiError::Connect(port_name.to_string()))?;
+
+        Ok(Self {
+            port_name: port_name.to_string(),
+            routing,
+            _connection: connection,
+        })
+    }
+
+    pub fn port_name(&self) -> &str {
+        &self.port_name
+    }
+
+    pub fn routing(&self) -> &Arc<Mutex<MidiRouting>> {
+        &self.routing
+    }
+}
+
+/// The names of the available MIDI output ports
+pub fn output_port_names() -> Vec<String> {
+    let Ok(output) = midir::MidiOutput::new(CLIENT_NAME) else {
+        return Vec::new();
+    };
+    output.ports()
+        .iter()
+        .filter_map(|port| output.port_name(port).ok())
+        .collect()
+}
+
+/// A connection to a MIDI output port, such as one leading to an external synth
+pub struct MidiOutput {
+    port_name: String,
+    connection: MidiOutputConnection,
+}
+
+impl MidiOutput {
+    /// connects to the port with the given name
+    pub fn connect(port_name: &str) -> Result<Self, MidiError> {
+        let output = midir::MidiOutput::new(CLIENT_NAME)?;
+
+        let port = output.ports()
+            .into_iter()
+            .find(|port| output.port_name(port).ok().as_deref() == Some(port_name))
+            .ok_or_else(|| MidiError::NoSuchPort(port_name.to_string()))?;
+
+        let connection = output.connect(&port, "starship-output")
+            .map_err(|_: ConnectError<midir::MidiOutput>| MidiError::Connect(port_name.to_string()))?;
+
+        Ok(Self {
+            port_name: port_name.to_string(),
+            connection,
+        })
+    }
+
+    pub fn port_name(&self) -> &str {
+        &self.port_name
+    }
+
+    pub fn send(&mut self, message: MidiMessage) -> Result<(), MidiError> {
+        self.connection.send(&message.to_bytes())?;
+        Ok(())
+    }
+}
@@ -1,11 +1,14 @@
-use std::collections::{HashSet, HashMap};
+use std::{collections::{HashSet, HashMap}, path::Path};
 
-use egui::{Pos2, Ui, Label, RichText, TextStyle, Rect, Context, Frame, Sense, Area, Scene, Response, Color32, ScrollArea, Vec2, CentralPanel, SidePanel};
+use egui::{Key, KeyboardShortcut, Modifiers, Pos2, Ui, Label, RichText, TextStyle, Rect, Context, Frame, Sense, Area, Scene, Response, Color32, ScrollArea, Vec2, CentralPanel, SidePanel};
 
 use crate::{
-    circuit::{CircuitBuilder, CircuitBuilderSpecification, CircuitUiSlot}, circuit_id::{CircuitId, CircuitIdManager, CircuitPortId, ConnectionId, PortKind}, circuit_input::{CircuitInput, PortInputState}, circuits::{ConstantBuilder, SpecialInputBuilder, SpecialOutputBuilder}, connection_builder::ConnectionBuilder, connection_manager::ConnectionManager, playback::CompiledPatch
+    bundle::{AssetReference, ProjectBundle}, circuit::{CircuitBuilder, CircuitBuilderSpecification, CircuitUiSlot}, circuit_id::{CircuitId, CircuitIdManager, CircuitPortId, ConnectionId, PortKind}, circuit_input::{CircuitInput, PortInputState}, circuits::{ConstantBuilder, SpecialInputBuilder, SpecialOutputBuilder}, compiled_patch::{CompiledPatch, PatchIr}, connection_builder::ConnectionBuilder, connection_manager::ConnectionManager, patch_file::{CircuitRecord, PatchFile, PatchFileError}, pitch::TuningSystem
 };
 
+mod history;
+pub use history::*;
+
 #[derive(Debug)]
 pub enum InspectorFocus {
     None,
@@ -24,6 +27,9 @@ pub struct Patch {
     // maps a circuit id to its builder
     builder_map: HashMap<CircuitId, Box<dyn CircuitBuilder>>,
 
+    // maps a circuit id to the kind written to patch files
+    builder_kinds: HashMap<CircuitId, String>,
+
     // maps a circuit id to its connection builder
     connection_builder_map: HashMap<CircuitId, ConnectionBuilder>,
 
@@ -40,6 +46,9 @@ pub struct Patch {
     // a list of possible special input/output names (order matters)
     inputs: Vec<String>,
     outputs: Vec<String>,
+
+    // the log of edits made to the patch
+    history: PatchHistory,
 }
 
 #[derive(Debug)]
@@ -49,6 +58,8 @@ pub struct PatchEditor<'a> {
     circuit_input: CircuitInput,
     inspector_focus: InspectorFocus,
     draw_new_circuit_ui: Option<Pos2>,
+    drag_origin: Option<Pos2>,
+    selection: HashSet<CircuitId>,
     builders: &'a[CircuitBuilderSpecification],
     data: Patch
 }
@@ -68,11 +79,38 @@ impl<'a> PatchEditor<'a> {
             circuit_input: Default::default(),
             inspector_focus: InspectorFocus::None,
             draw_new_circuit_ui: None,
+            drag_origin: None,
+            selection: HashSet::new(),
             builders,
             data: Patch::new(inputs, outputs)
         }
     }
 
+    /// Creates an editor for an existing patch
+    pub fn from_patch(builders: &'a[CircuitBuilderSpecification], patch: Patch) -> Self {
+        Self {
+            cam_pos: egui::vec2(0.0, 0.0),
+            zoom: 1.0,
+            circuit_input: Default::default(),
+            inspector_focus: InspectorFocus::None,
+            draw_new_circuit_ui: None,
+            drag_origin: None,
+            selection: HashSet::new(),
+            builders,
+            data: patch
+        }
+    }
+
+    /// The patch being edited
+    pub fn patch(&self) -> &Patch {
+        &self.data
+    }
+
+    /// Appends any unsaved edits to the command log stored next to the project file
+    pub fn save_history(&mut self, project_path: &Path) -> Result<(), HistoryError> {
+        self.data.save_history(project_path)
+    }
+
     pub fn draw(&mut self, ui: &mut Ui) {
         SidePanel::right("right_panel")
             .max_width(300.0)
@@ -123,7 +161,17 @@ impl<'a> PatchEditor<'a> {
                         if response.dragged() || response.clicked() {
                             self.inspector_focus = InspectorFocus::Circuit(*id);
                         }
-                        if response.dragged() {
+                        if response.clicked() {
+                            // shift-click adds to the selection, otherwise only the clicked circuit is selected
+                            if !ui.input(|i| i.modifiers.shift) {
+                                self.selection.clear();
+                            }
+                            self.selection.insert(*id);
+                        }
+                        if response.drag_started() {
+                            self.drag_origin = Some(self.data.connection_builder_pos[id]);
+                        }
+                        if response.dragged() || response.drag_stopped() {
                             mod_response = Some((*id, response))
                         }
                     }
@@ -190,9 +238,18 @@ impl<'a> PatchEditor<'a> {
 
             if let Some((id, inner)) = response.inner {
                 *self.data.connection_builder_pos.get_mut(&id).unwrap() += inner.drag_delta();
+                if inner.drag_stopped() {
+                    if let Some(from) = self.drag_origin.take() {
+                        let to = self.data.connection_builder_pos[&id];
+                        self.data.history.record(PatchCommand::MoveCircuit { id, from, to });
+                    }
+                }
             }
         });
 
+        self.handle_clipboard(ui.ctx(), scene_rect, clip_rect);
+        self.handle_undo(ui.ctx());
+
         let (p_cam, p_zoom) = (self.cam_pos, self.zoom);
 
         self.cam_pos = scene_rect.center().to_vec2();
@@ -203,6 +260,90 @@ impl<'a> PatchEditor<'a> {
         }
     }
 
+    /// Handles copy and paste events
+    /// Pasted circuits are placed at the mouse if it is over the editor
+    fn handle_clipboard(&mut self, ctx: &Context, scene_rect: Rect, clip_rect: Rect) {
+        // copying and pasting belongs to the text field being edited
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        let events = ctx.input(|i| i.events.clone());
+        for event in events {
+            match event {
+                egui::Event::Copy => {
+                    if let Some(text) = self.copy_selection() {
+                        ctx.copy_text(text);
+                    }
+                }
+                egui::Event::Paste(text) => {
+                    let position = ctx.input(|i| i.pointer.latest_pos())
+                        .filter(|pos| clip_rect.contains(*pos))
+                        .map(|pos| (pos - clip_rect.min) / self.zoom + scene_rect.min.to_vec2())
+                        .unwrap_or(scene_rect.center().to_vec2())
+                        .to_pos2();
+                    // text that is not a copied selection is ignored
+                    let _ = self.paste_clipboard(&text, position);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Handles the undo and redo shortcuts
+    fn handle_undo(&mut self, ctx: &Context) {
+        // text fields being edited have their own undo
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        // shift is ignored when matching shortcuts that do not use it, so redo is checked first
+        let (redo, undo) = ctx.input_mut(|i| {
+            let redo = i.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z))
+                || i.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::Y));
+            (redo, i.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::Z)))
+        });
+
+        // an edit to a circuit whose kind is no longer available cannot be applied
+        let _ = if redo {
+            self.data.redo(self.builders)
+        } else if undo {
+            self.data.undo(self.builders)
+        } else {
+            return;
+        };
+
+        // forget circuits the edit removed
+        let exists = |id: &CircuitId| self.data.builder_map.contains_key(id);
+        self.selection.retain(|id| exists(id));
+        let focused = match self.inspector_focus {
+            InspectorFocus::Port(port) => Some(port.unit_id),
+            InspectorFocus::Circuit(id) => Some(id),
+            InspectorFocus::None => None,
+        };
+        if focused.is_some_and(|id| !exists(&id)) {
+            self.inspector_focus = InspectorFocus::None;
+        }
+    }
+
+    /// Serializes the selected circuits, the connections between them and their relative
+    /// positions as text to be placed on the clipboard.
+    /// Returns None if nothing is selected.
+    pub fn copy_selection(&self) -> Option<String> {
+        if self.selection.is_empty() {
+            return None;
+        }
+        Some(self.data.copy(&self.selection).to_string())
+    }
+
+    /// Adds the circuits in text created by copy_selection (possibly by another instance)
+    /// so that their top left is at the given position.
+    /// The pasted circuits become the selection.
+    pub fn paste_clipboard(&mut self, text: &str, position: Pos2) -> Result<(), PatchFileError> {
+        let file: PatchFile = text.parse()?;
+        let added = self.data.insert_file(&file, self.builders, position.to_vec2())?;
+        self.selection = added.into_iter().collect();
+        Ok(())
+    }
+
     fn draw_new_circuit_ui(
         &mut self,
         ctx: &Context,
@@ -232,8 +373,8 @@ impl<'a> PatchEditor<'a> {
                             }
                             for builder in self.builders {
                                 if ui.button(&builder.display_name).clicked() {
-                                    let id = self.add_circuit_by_builder(
-                                        (builder.instance)(),
+                                    let id = self.data.add_circuit_by_specification(
+                                        builder,
                                         position
                                     );
                                     self.inspector_focus = InspectorFocus::Circuit(id);
@@ -306,7 +447,7 @@ impl<'a> PatchEditor<'a> {
                 }
             }
             if let Some(connection) = remove_connection {
-                self.data.connections.remove_connection(ConnectionId::new_auto(
+                self.data.remove_connection(ConnectionId::new_auto(
                     *connection,
                     id
                 ));
@@ -351,9 +492,10 @@ impl<'a> PatchEditor<'a> {
     pub fn add_circuit_by_builder(
         &mut self,
         circuit_builder: Box<dyn CircuitBuilder>,
+        kind: String,
         position: Pos2
     ) -> CircuitId {
-        self.data.add_circuit_by_builder(circuit_builder, position)
+        self.data.add_circuit_by_builder(circuit_builder, kind, position)
     }
 
     /// Adds a connection for the two given circuit ports
@@ -378,15 +520,20 @@ impl<'a> PatchEditor<'a> {
             InspectorFocus::None => {}
         }
 
+        self.selection.remove(&id);
         self.data.remove_circuit_builder(id);
     }
 
+    /// Compiles the patch being edited so that it may be played
+    /// assets are the bundle the project was opened from, used before files on disk
     pub fn playback_data(
         &self,
+        assets: &ProjectBundle,
         sample_rate: u32,
-        sample_multiplier: f32
-    ) -> (CompiledPatch, Vec<CircuitUiSlot>) {
-        self.data.compile(sample_rate, sample_multiplier)
+        sample_multiplier: f32,
+        tuning: &TuningSystem
+    ) -> Result<(CompiledPatch, Vec<CircuitUiSlot>), PatchFileError> {
+        self.data.compile(self.builders, assets, sample_rate, sample_multiplier, tuning)
     }
 
 }
@@ -413,13 +560,256 @@ impl Patch {
             id_manager: Default::default(),
             builder_ids: vec![],
             builder_map: HashMap::new(),
+            builder_kinds: HashMap::new(),
         	connection_builder_map: HashMap::new(),
             connection_builder_pos: HashMap::new(),
             connections: Default::default(),
             input_ids,
             output_ids,
             inputs,
-            outputs
+            outputs,
+            history: PatchHistory::new()
+        }
+    }
+
+    /// The edit history of the patch
+    pub fn history(&self) -> &PatchHistory {
+        &self.history
+    }
+
+    /// Appends any unsaved history to the command log stored next to the project file
+    pub fn save_history(&mut self, project_path: &Path) -> Result<(), HistoryError> {
+        self.history.append_to(&project_path.with_extension(PatchHistory::EXTENSION))
+    }
+
+    /// Restores the history from the command log stored next to the project file
+    /// file is the patch file the patch was restored from by from_file, whose circuit ids the log refers to.
+    /// Does nothing if no log exists.
+    pub fn load_history(&mut self, project_path: &Path, file: &PatchFile) -> Result<(), HistoryError> {
+        let path = project_path.with_extension(PatchHistory::EXTENSION);
+        if !path.exists() {
+            return Ok(());
+        }
+        let mut history = PatchHistory::load(&path)?;
+
+        // from_file gives circuits new ids in the order they appear in the file
+        let mut ids: HashMap<CircuitId, CircuitId> = file.circuits.iter()
+            .map(|record| record.id)
+            .zip(self.builder_ids.iter().copied())
+            .collect();
+
+        // circuits removed before the project was saved get unused ids, so undoing their removal cannot clash
+        history.map_ids(|id| *ids.entry(id).or_insert_with(|| {
+            self.id_manager.get_id().expect("no circuit ids available")
+        }));
+        self.history = history;
+        Ok(())
+    }
+
+    /// Creates a patch from one of the templates shipped with the crate
+    pub fn from_template(
+        name: &str,
+        builders: &[CircuitBuilderSpecification]
+    ) -> Result<Self, PatchFileError> {
+        Self::from_file(&PatchFile::template(name)?, builders)
+    }
+
+    /// Restores a patch from a patch file
+    /// Circuit ids are reassigned, so they may differ from those in the file.
+    pub fn from_file(
+        file: &PatchFile,
+        builders: &[CircuitBuilderSpecification]
+    ) -> Result<Self, PatchFileError> {
+        let mut patch = Self::new(file.inputs.clone(), file.outputs.clone());
+        patch.insert_file(file, builders, Vec2::ZERO)?;
+
+        // loading is not an edit the user should be able to undo
+        patch.history = PatchHistory::new();
+        Ok(patch)
+    }
+
+    /// Adds every circuit and connection in the file to the patch, offsetting positions by offset.
+    /// The file's inputs and outputs are ignored; special circuits refer to this patch's by index.
+    /// Nothing is added if any circuit cannot be restored.
+    /// Returns the ids of the added circuits in the order they appear in the file
+    pub fn insert_file(
+        &mut self,
+        file: &PatchFile,
+        builders: &[CircuitBuilderSpecification],
+        offset: Vec2
+    ) -> Result<Vec<CircuitId>, PatchFileError> {
+        enum Pending<'b> {
+            Constant(ConstantBuilder),
+            Input(usize),
+            Output(usize),
+            Builder(Box<dyn CircuitBuilder>, &'b CircuitBuilderSpecification),
+        }
+
+        impl Pending<'_> {
+            /// the number of input and output ports of the circuit
+            fn port_counts(&self) -> (usize, usize) {
+                match self {
+                    Pending::Constant(_) | Pending::Input(_) => (0, 1),
+                    Pending::Output(_) => (1, 0),
+                    Pending::Builder(builder, _) => {
+                        let specification = builder.specification();
+                        (specification.input_names.len(), specification.output_names.len())
+                    }
+                }
+            }
+        }
+
+        // create every builder before modifying the patch, so that failures leave it untouched
+        let mut pending = Vec::with_capacity(file.circuits.len());
+        for record in &file.circuits {
+            let invalid_data = || PatchFileError::InvalidCircuitData(record.id);
+            let special_index = |count: usize| record.data.parse::<usize>()
+                .ok()
+                .filter(|index| *index < count)
+                .ok_or_else(invalid_data);
+
+            pending.push(match record.kind.as_str() {
+                PatchFile::CONSTANT_KIND => {
+                    let mut builder = ConstantBuilder::new();
+                    if !builder.load(&record.data) {
+                        return Err(invalid_data());
+                    }
+                    Pending::Constant(builder)
+                }
+                PatchFile::INPUT_KIND => Pending::Input(special_index(self.inputs.len())?),
+                PatchFile::OUTPUT_KIND => Pending::Output(special_index(self.outputs.len())?),
+                kind => {
+                    let specification = builders.iter()
+                        .find(|builder| builder.display_name == kind)
+                        .ok_or_else(|| PatchFileError::UnknownKind(kind.to_string()))?;
+                    let mut builder = (specification.instance)();
+                    if !builder.load(&record.data) {
+                        return Err(invalid_data());
+                    }
+                    Pending::Builder(builder, specification)
+                }
+            });
+        }
+
+        // pasted text may come from anywhere, so connections must refer to ports the circuits have
+        for (src, dst) in &file.connections {
+            for (port, is_input) in [(src, false), (dst, true)] {
+                let index = file.circuits.iter()
+                    .position(|record| record.id == port.unit_id)
+                    .ok_or(PatchFileError::UnknownCircuit(port.unit_id))?;
+                let (inputs, outputs) = pending[index].port_counts();
+                let count = if is_input { inputs } else { outputs };
+                if port.port_id.index() >= count {
+                    return Err(PatchFileError::UnknownPort(port.unit_id, port.port_id.index()));
+                }
+            }
+        }
+
+        let mut id_map = HashMap::new();
+        let mut added = Vec::with_capacity(pending.len());
+        for (record, item) in file.circuits.iter().zip(pending) {
+            let position = record.position + offset;
+            let id = match item {
+                Pending::Constant(builder) => self.add_constant_builder(builder, position),
+                Pending::Input(index) => self.add_input(index, position),
+                Pending::Output(index) => self.add_output(index, position),
+                Pending::Builder(builder, specification) => {
+                    self.add_circuit_by_builder(builder, specification.display_name.clone(), position)
+                }
+            };
+            id_map.insert(record.id, id);
+            added.push(id);
+        }
+
+        // connections were validated above
+        for (src, dst) in &file.connections {
+            let remap = |port: &CircuitPortId| CircuitPortId::new(id_map[&port.unit_id], port.port_id);
+            self.add_connection(remap(src), remap(dst));
+        }
+
+        Ok(added)
+    }
+
+    /// Creates a patch file containing the given circuits and the connections between them
+    /// Positions are made relative to the top left of the circuits.
+    pub fn copy(&self, ids: &HashSet<CircuitId>) -> PatchFile {
+        let origin = ids.iter()
+            .filter_map(|id| self.connection_builder_pos.get(id))
+            .fold(Pos2::new(f32::INFINITY, f32::INFINITY), |min, pos| min.min(*pos));
+
+        let circuits = self.builder_ids.iter()
+            .filter(|id| ids.contains(id))
+            .map(|id| CircuitRecord {
+                id: *id,
+                kind: self.builder_kinds[id].clone(),
+                position: (self.connection_builder_pos[id] - origin).to_pos2(),
+                data: self.circuit_data(*id)
+            })
+            .collect();
+
+        let connections = self.connections.connections()
+            .filter(|connection| ids.contains(&connection.src().unit_id) && ids.contains(&connection.dst().unit_id))
+            .map(|connection| (connection.src(), connection.dst()))
+            .collect();
+
+        PatchFile {
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            circuits,
+            connections
+        }
+    }
+
+    /// Creates the patch file describing this patch
+    pub fn to_file(&self) -> PatchFile {
+        let circuits = self.builder_ids.iter()
+            .map(|id| CircuitRecord {
+                id: *id,
+                kind: self.builder_kinds[id].clone(),
+                position: self.connection_builder_pos[id],
+                data: self.circuit_data(*id)
+            })
+            .collect();
+
+        let connections = self.connections.connections()
+            .map(|connection| (connection.src(), connection.dst()))
+            .collect();
+
+        PatchFile {
+            inputs: self.inputs.clone(),
+            outputs: self.outputs.clone(),
+            circuits,
+            connections
+        }
+    }
+
+    /// the data written to patch files for a circuit
+    /// special circuits are written as the index of their input or output
+    fn circuit_data(&self, id: CircuitId) -> String {
+        let special_index = |sets: &[HashSet<CircuitId>]| sets.iter().position(|set| set.contains(&id));
+        match self.builder_kinds[&id].as_str() {
+            PatchFile::INPUT_KIND => special_index(&self.input_ids).map_or_else(String::new, |index| index.to_string()),
+            PatchFile::OUTPUT_KIND => special_index(&self.output_ids).map_or_else(String::new, |index| index.to_string()),
+            _ => self.builder_map[&id].save(),
+        }
+    }
+
+    /// Gets every external file used by the circuits of the patch, including those inside subpatches
+    pub fn assets(&self, builders: &[CircuitBuilderSpecification]) -> Result<Vec<AssetReference>, PatchFileError> {
+        let mut assets = Vec::new();
+        for builder in self.builder_map.values() {
+            assets.extend(builder.assets());
+            if let Some(file) = builder.subpatch() {
+                assets.extend(file.instantiate(builders)?.assets());
+            }
+        }
+        Ok(assets)
+    }
+
+    /// Reloads the external files used by the circuits from a project bundle
+    pub fn resolve_assets(&mut self, bundle: &ProjectBundle) {
+        for builder in self.builder_map.values_mut() {
+            builder.resolve_assets(bundle);
         }
     }
 
@@ -432,10 +822,17 @@ impl Patch {
     }
 
 	pub fn add_constant(&mut self, position: Pos2) -> CircuitId {
+        self.add_constant_builder(ConstantBuilder::new(), position)
+    }
+
+    /// Adds an already configured constant circuit at the given position
+    pub fn add_constant_builder(&mut self, builder: ConstantBuilder, position: Pos2) -> CircuitId {
         let id = self.id_manager.get_id();
-        let builder = Box::new(ConstantBuilder::new());
+        let builder = Box::new(builder);
         let frontend = ConnectionBuilder::new_constant(id, builder.data());
         self.add_circuit(builder, frontend, position);
+        self.builder_kinds.insert(id, PatchFile::CONSTANT_KIND.to_string());
+        self.record_added(id);
         id
     }
 
@@ -448,6 +845,9 @@ impl Patch {
         let builder = Box::new(SpecialInputBuilder::new(name.clone()));
         let frontend = ConnectionBuilder::new_special_input(id, name);
         self.add_circuit(builder, frontend, position);
+        self.builder_kinds.insert(id, PatchFile::INPUT_KIND.to_string());
+        self.input_ids[index].insert(id);
+        self.record_added(id);
         id
     }
 
@@ -460,25 +860,43 @@ impl Patch {
         let builder = Box::new(SpecialOutputBuilder::new(name.clone()));
         let frontend = ConnectionBuilder::new_special_output(id, name);
         self.add_circuit(builder, frontend, position);
+        self.builder_kinds.insert(id, PatchFile::OUTPUT_KIND.to_string());
+        self.output_ids[index].insert(id);
+        self.record_added(id);
         id
     }
 
     /// Convenience method. Adds a new circuit at the given position
+    /// kind is the display name of the specification the builder was created by.
     /// Do not use this method to add input or output circuits. Use add_input()/add_output().
     /// Do not use this method to add a constant circuit. Use add_constant().
     /// Returns the id of the new circuit
     pub fn add_circuit_by_builder(
         &mut self,
         circuit_builder: Box<dyn CircuitBuilder>,
+        kind: String,
         position: Pos2
     ) -> CircuitId {
         let id = self.id_manager.get_id();
         let frontend = ConnectionBuilder::new(id, circuit_builder.specification());
         self.add_circuit(circuit_builder, frontend, position);
+        self.builder_kinds.insert(id, kind);
+        self.record_added(id);
         id
     }
 
+    /// Adds a new circuit created by the given specification at the given position
+    /// Returns the id of the new circuit
+    pub fn add_circuit_by_specification(
+        &mut self,
+        specification: &CircuitBuilderSpecification,
+        position: Pos2
+    ) -> CircuitId {
+        self.add_circuit_by_builder((specification.instance)(), specification.display_name.clone(), position)
+    }
+
     /// Adds the circuit's associated builder and connection builder to the patch at the given position
+    /// The addition is not recorded in the history, as the kind of the circuit is not yet known.
     pub fn add_circuit(
         &mut self,
         circuit_builder: Box<dyn CircuitBuilder>,
@@ -494,12 +912,55 @@ impl Patch {
     /// Adds a connection for the two given circuit ports
     pub fn add_connection(&mut self, src: CircuitPortId, dst: CircuitPortId) {
         self.connections.add_connection(ConnectionId::new(src, dst));
+        self.history.record(PatchCommand::AddConnection { src, dst });
+    }
+
+    /// Removes the given connection
+    pub fn remove_connection(&mut self, connection: ConnectionId<CircuitId>) {
+        self.connections.remove_connection(connection);
+        self.history.record(PatchCommand::RemoveConnection {
+            src: connection.src(),
+            dst: connection.dst()
+        });
+    }
+
+    /// records the addition of a circuit once its kind and data are known
+    fn record_added(&mut self, id: CircuitId) {
+        let command = PatchCommand::AddCircuit {
+            id,
+            kind: self.builder_kinds[&id].clone(),
+            position: self.connection_builder_pos[&id],
+            data: self.circuit_data(id),
+            connections: Vec::new()
+        };
+        self.history.record(command);
     }
 
     /// Removes the circuit with the given id
     pub fn remove_circuit_builder(&mut self, id: CircuitId) {
+        if self.builder_map.contains_key(&id) {
+            // the circuit's data and connections are kept so that the removal may be undone
+            let connections = self.connections.connections()
+                .filter(|connection| connection.src().unit_id == id || connection.dst().unit_id == id)
+                .map(|connection| (connection.src(), connection.dst()))
+                .collect();
+            let command = PatchCommand::RemoveCircuit {
+                id,
+                kind: self.builder_kinds[&id].clone(),
+                position: self.connection_builder_pos[&id],
+                data: self.circuit_data(id),
+                connections
+            };
+            self.history.record(command);
+        }
+        self.detach_circuit(id);
+    }
+
+    /// removes a circuit and its connections without recording it in the history
+    fn detach_circuit(&mut self, id: CircuitId) {
         self.builder_ids.retain(|entry| *entry != id);
         self.builder_map.remove(&id);
+        self.builder_kinds.remove(&id);
         self.connection_builder_pos.remove(&id);
         self.connection_builder_map.remove(&id);
         self.connections.remove_circuit(id);
@@ -513,20 +974,128 @@ impl Patch {
         }
     }
 
+    /// recreates a circuit removed from the patch, keeping its id
+    fn restore_circuit(
+        &mut self,
+        id: CircuitId,
+        kind: &str,
+        data: &str,
+        position: Pos2,
+        builders: &[CircuitBuilderSpecification]
+    ) -> Result<(), PatchFileError> {
+        let invalid_data = || PatchFileError::InvalidCircuitData(id);
+        let special_index = |count: usize| data.parse::<usize>()
+            .ok()
+            .filter(|index| *index < count)
+            .ok_or_else(invalid_data);
+
+        let (builder, frontend): (Box<dyn CircuitBuilder>, ConnectionBuilder) = match kind {
+            PatchFile::CONSTANT_KIND => {
+                let mut builder = ConstantBuilder::new();
+                if !builder.load(data) {
+                    return Err(invalid_data());
+                }
+                let frontend = ConnectionBuilder::new_constant(id, builder.data());
+                (Box::new(builder), frontend)
+            }
+            PatchFile::INPUT_KIND => {
+                let index = special_index(self.inputs.len())?;
+                self.input_ids[index].insert(id);
+                let name = self.inputs[index].clone();
+                (Box::new(SpecialInputBuilder::new(name.clone())), ConnectionBuilder::new_special_input(id, name))
+            }
+            PatchFile::OUTPUT_KIND => {
+                let index = special_index(self.outputs.len())?;
+                self.output_ids[index].insert(id);
+                let name = self.outputs[index].clone();
+                (Box::new(SpecialOutputBuilder::new(name.clone())), ConnectionBuilder::new_special_output(id, name))
+            }
+            kind => {
+                let specification = builders.iter()
+                    .find(|builder| builder.display_name == kind)
+                    .ok_or_else(|| PatchFileError::UnknownKind(kind.to_string()))?;
+                let mut builder = (specification.instance)();
+                if !builder.load(data) {
+                    return Err(invalid_data());
+                }
+                let frontend = ConnectionBuilder::new(id, builder.specification());
+                (builder, frontend)
+            }
+        };
+
+        self.add_circuit(builder, frontend, position);
+        self.builder_kinds.insert(id, kind.to_string());
+        Ok(())
+    }
+
+    /// applies a command from the history without recording it
+    fn apply(&mut self, command: PatchCommand, builders: &[CircuitBuilderSpecification]) -> Result<(), PatchFileError> {
+        match command {
+            PatchCommand::AddCircuit { id, kind, position, data, connections } => {
+                self.restore_circuit(id, &kind, &data, position, builders)?;
+                for (src, dst) in connections {
+                    self.connections.add_connection(ConnectionId::new(src, dst));
+                }
+            }
+            PatchCommand::RemoveCircuit { id, .. } => self.detach_circuit(id),
+            PatchCommand::MoveCircuit { id, to, .. } => {
+                if let Some(position) = self.connection_builder_pos.get_mut(&id) {
+                    *position = to;
+                }
+            }
+            PatchCommand::AddConnection { src, dst } => {
+                self.connections.add_connection(ConnectionId::new(src, dst));
+            }
+            PatchCommand::RemoveConnection { src, dst } => {
+                self.connections.remove_connection(ConnectionId::new(src, dst));
+            }
+        }
+        Ok(())
+    }
+
+    /// Reverts the most recent edit
+    /// Returns false if there was nothing to undo.
+    pub fn undo(&mut self, builders: &[CircuitBuilderSpecification]) -> Result<bool, PatchFileError> {
+        match self.history.undo() {
+            Some(command) => self.apply(command, builders).map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Reapplies the most recently undone edit
+    /// Returns false if there was nothing to redo.
+    pub fn redo(&mut self, builders: &[CircuitBuilderSpecification]) -> Result<bool, PatchFileError> {
+        match self.history.redo() {
+            Some(command) => self.apply(command, builders).map(|_| true),
+            None => Ok(false),
+        }
+    }
+
     /// Creates the playback data for the patch
+    /// Compiles the patch so that it may be played, along with the ui slots of its circuits
+    /// Subpatches are flattened into the patch first, the same way they are when rendering,
+    /// which fails if an embedded patch can no longer be restored.
+    /// The circuits restored by flattening take their assets from the given bundle before disk.
     pub fn compile(
         &self,
+        builders: &[CircuitBuilderSpecification],
+        assets: &ProjectBundle,
         sample_rate: u32,
-        sample_multiplier: f32
-    ) -> (CompiledPatch, Vec<CircuitUiSlot>) {
-        CompiledPatch::new(
+        sample_multiplier: f32,
+        tuning: &TuningSystem
+    ) -> Result<(CompiledPatch, Vec<CircuitUiSlot>), PatchFileError> {
+        if self.builder_map.values().any(|builder| builder.subpatch().is_some()) {
+            let mut instance = self.to_file().instantiate(builders)?;
+            instance.resolve_assets(assets);
+            return Ok(instance.compile(sample_rate, sample_multiplier, tuning));
+        }
+
+        Ok(PatchIr::new(
             &self.builder_ids,
             &self.builder_map,
             &self.connections,
             &self.input_ids,
-            &self.output_ids,
-            sample_rate,
-            sample_multiplier
-        )
+            &self.output_ids
+        ).compile(sample_rate, sample_multiplier, tuning))
     }
 }
@@ -0,0 +1,425 @@
+use std::{fmt::Display, fs::{self, OpenOptions}, io::{self, Write}, path::{Path, PathBuf}, str::FromStr, time::{SystemTime, UNIX_EPOCH}};
+
+use egui::Pos2;
+use thiserror::Error;
+
+use crate::circuit_id::{CircuitId, CircuitPortId, PortId, PortKind};
+
+/// Identifies the editing session an entry in the history was made in
+pub type SessionId = u64;
+
+/// An edit made to a patch
+/// Circuits carry their saved data and connections so that removing one may be undone.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchCommand {
+    AddCircuit { id: CircuitId, kind: String, position: Pos2, data: String, connections: Vec<(CircuitPortId, CircuitPortId)> },
+    RemoveCircuit { id: CircuitId, kind: String, position: Pos2, data: String, connections: Vec<(CircuitPortId, CircuitPortId)> },
+    MoveCircuit { id: CircuitId, from: Pos2, to: Pos2 },
+    AddConnection { src: CircuitPortId, dst: CircuitPortId },
+    RemoveConnection { src: CircuitPortId, dst: CircuitPortId },
+}
+
+impl PatchCommand {
+    /// returns the command that reverts this command
+    pub fn inverse(&self) -> Self {
+        match self.clone() {
+            Self::AddCircuit { id, kind, position, data, connections } => {
+                Self::RemoveCircuit { id, kind, position, data, connections }
+            }
+            Self::RemoveCircuit { id, kind, position, data, connections } => {
+                Self::AddCircuit { id, kind, position, data, connections }
+            }
+            Self::MoveCircuit { id, from, to } => Self::MoveCircuit { id, from: to, to: from },
+            Self::AddConnection { src, dst } => Self::RemoveConnection { src, dst },
+            Self::RemoveConnection { src, dst } => Self::AddConnection { src, dst },
+        }
+    }
+
+    /// replaces every circuit id the command refers to
+    fn map_ids(&mut self, map: &mut impl FnMut(CircuitId) -> CircuitId) {
+        match self {
+            Self::AddCircuit { id, connections, .. } | Self::RemoveCircuit { id, connections, .. } => {
+                *id = map(*id);
+                for (src, dst) in connections {
+                    src.unit_id = map(src.unit_id);
+                    dst.unit_id = map(dst.unit_id);
+                }
+            }
+            Self::MoveCircuit { id, .. } => *id = map(*id),
+            Self::AddConnection { src, dst } | Self::RemoveConnection { src, dst } => {
+                src.unit_id = map(src.unit_id);
+                dst.unit_id = map(dst.unit_id);
+            }
+        }
+    }
+}
+
+/// A single line of the command log
+#[derive(Debug, Clone, PartialEq)]
+pub enum HistoryRecord {
+    /// a new edit was made, clearing the redo stack
+    Do(PatchCommand),
+    /// the most recent edit was undone
+    Undo,
+    /// the most recently undone edit was redone
+    Redo,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub session: SessionId,
+    pub record: HistoryRecord,
+}
+
+/// An error occurring while reading a command log
+#[derive(Debug, Error)]
+pub enum HistoryError {
+    #[error("Malformed history entry on line {0}.")]
+    Malformed(usize),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// The undo history of a patch, stored as an append-only command log
+/// Undo and redo are recorded as entries rather than removing entries, so the
+/// full log may be replayed to restore the undo and redo stacks.
+#[derive(Debug)]
+pub struct PatchHistory {
+    session: SessionId,
+
+    /// every entry in the log, in the order they occurred
+    entries: Vec<HistoryEntry>,
+
+    /// the number of entries already written to disk
+    persisted: usize,
+
+    /// the log the persisted entries were written to
+    log_path: Option<PathBuf>,
+
+    /// indices into entries of the commands that may be undone
+    undo_stack: Vec<usize>,
+
+    /// indices into entries of the commands that may be redone
+    redo_stack: Vec<usize>,
+}
+
+impl Default for PatchHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PatchHistory {
+    /// the file extension used for command logs saved next to a project
+    pub const EXTENSION: &'static str = "history";
+
+    /// creates an empty history for a new session
+    pub fn new() -> Self {
+        Self {
+            session: Self::new_session_id(),
+            entries: Vec::new(),
+            persisted: 0,
+            log_path: None,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new()
+        }
+    }
+
+    /// the id of the current editing session
+    pub fn session(&self) -> SessionId {
+        self.session
+    }
+
+    /// every entry in the log
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+
+    /// records a new edit
+    pub fn record(&mut self, command: PatchCommand) {
+        self.redo_stack.clear();
+        self.undo_stack.push(self.entries.len());
+        self.push(HistoryRecord::Do(command));
+    }
+
+    /// returns true if there is an edit that may be undone
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    /// returns true if there is an edit that may be redone
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// marks the most recent edit as undone
+    /// returns the command that must be applied to revert it
+    pub fn undo(&mut self) -> Option<PatchCommand> {
+        let index = self.undo_stack.pop()?;
+        self.redo_stack.push(index);
+        self.push(HistoryRecord::Undo);
+        Some(self.command_at(index).inverse())
+    }
+
+    /// marks the most recently undone edit as redone
+    /// returns the command that must be applied to reapply it
+    pub fn redo(&mut self) -> Option<PatchCommand> {
+        let index = self.redo_stack.pop()?;
+        self.undo_stack.push(index);
+        self.push(HistoryRecord::Redo);
+        Some(self.command_at(index).clone())
+    }
+
+    /// appends every entry not yet saved to the log at the given path
+    /// the whole history is written if the log was last saved elsewhere, replacing any existing log
+    pub fn append_to(&mut self, path: &Path) -> Result<(), HistoryError> {
+        let new_log = self.log_path.as_deref() != Some(path);
+        if new_log {
+            self.persisted = 0;
+        }
+        if self.persisted == self.entries.len() && !new_log {
+            return Ok(());
+        }
+
+        let mut text = String::new();
+        for entry in &self.entries[self.persisted..] {
+            text.push_str(&entry.to_string());
+            text.push('\n');
+        }
+
+        let mut file = OpenOptions::new().create(true).append(!new_log).write(true).truncate(new_log).open(path)?;
+        file.write_all(text.as_bytes())?;
+        self.persisted = self.entries.len();
+        self.log_path = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    /// restores the history from the log at the given path
+    /// new entries will be recorded under a new session
+    pub fn load(path: &Path) -> Result<Self, HistoryError> {
+        let text = fs::read_to_string(path)?;
+        let mut history = Self::new();
+
+        for (line_index, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = line.parse::<HistoryEntry>()
+                .map_err(|_| HistoryError::Malformed(line_index + 1))?;
+            history.replay(entry);
+        }
+
+        history.persisted = history.entries.len();
+        history.log_path = Some(path.to_path_buf());
+        Ok(history)
+    }
+
+    /// replaces every circuit id referred to by the log
+    /// used when the circuits of a reopened patch were given new ids
+    /// The saved log no longer matches, so the whole history is written on the next save.
+    pub fn map_ids(&mut self, mut map: impl FnMut(CircuitId) -> CircuitId) {
+        for entry in &mut self.entries {
+            if let HistoryRecord::Do(command) = &mut entry.record {
+                command.map_ids(&mut map);
+            }
+        }
+        self.log_path = None;
+    }
+
+    /// applies an entry read from a log to the undo and redo stacks
+    fn replay(&mut self, entry: HistoryEntry) {
+        match entry.record {
+            HistoryRecord::Do(_) => {
+                self.redo_stack.clear();
+                self.undo_stack.push(self.entries.len());
+            }
+            HistoryRecord::Undo => {
+                if let Some(index) = self.undo_stack.pop() {
+                    self.redo_stack.push(index);
+                }
+            }
+            HistoryRecord::Redo => {
+                if let Some(index) = self.redo_stack.pop() {
+                    self.undo_stack.push(index);
+                }
+            }
+        }
+        self.entries.push(entry);
+    }
+
+    fn push(&mut self, record: HistoryRecord) {
+        self.entries.push(HistoryEntry {
+            session: self.session,
+            record
+        });
+    }
+
+    fn command_at(&self, index: usize) -> &PatchCommand {
+        match &self.entries[index].record {
+            HistoryRecord::Do(command) => command,
+            _ => unreachable!("Undo stacks only hold indices of commands."),
+        }
+    }
+
+    /// generates a session id from the current time
+    fn new_session_id() -> SessionId {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos() as SessionId)
+            .unwrap_or(0)
+    }
+}
+
+/// writes circuit connections as src>dst pairs separated by commas, or - if there are none
+fn fmt_connections(connections: &[(CircuitPortId, CircuitPortId)]) -> String {
+    if connections.is_empty() {
+        return "-".to_string();
+    }
+    connections.iter()
+        .map(|(src, dst)| format!("{}>{}", fmt_port(src), fmt_port(dst)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// reads connections written by fmt_connections
+fn parse_connections(text: &str) -> Option<Vec<(CircuitPortId, CircuitPortId)>> {
+    if text == "-" {
+        return Some(Vec::new());
+    }
+    text.split(',')
+        .map(|pair| {
+            let (src, dst) = pair.split_once('>')?;
+            Some((parse_port(src, PortKind::Output)?, parse_port(dst, PortKind::Input)?))
+        })
+        .collect()
+}
+
+/// writes circuit data as a single word
+/// empty data is written as -, otherwise the escaped data follows an =
+fn fmt_data(data: &str) -> String {
+    if data.is_empty() {
+        return "-".to_string();
+    }
+    let escaped = data.replace('\\', "\\\\").replace('\n', "\\n").replace(' ', "\\s");
+    format!("={}", escaped)
+}
+
+/// reads data written by fmt_data
+fn parse_data(text: &str) -> Option<String> {
+    if text == "-" {
+        return Some(String::new());
+    }
+    let mut out = String::new();
+    let mut chars = text.strip_prefix('=')?.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next()? {
+                'n' => out.push('\n'),
+                's' => out.push(' '),
+                other => out.push(other),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    Some(out)
+}
+
+/// writes a port as circuit.index
+fn fmt_port(port: &CircuitPortId) -> String {
+    format!("{}.{}", port.unit_id, port.port_id.index())
+}
+
+/// reads a port written by fmt_port
+fn parse_port(text: &str, kind: PortKind) -> Option<CircuitPortId> {
+    let (circuit, index) = text.split_once('.')?;
+    Some(CircuitPortId::new(
+        circuit.parse().ok()?,
+        PortId::new(index.parse().ok()?, kind)
+    ))
+}
+
+impl Display for HistoryEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x} ", self.session)?;
+        match &self.record {
+            HistoryRecord::Undo => write!(f, "undo"),
+            HistoryRecord::Redo => write!(f, "redo"),
+            HistoryRecord::Do(command) => match command {
+                // the kind is written last as it may contain spaces
+                PatchCommand::AddCircuit { id, kind, position, data, connections } => write!(
+                    f,
+                    "add {} {} {} {} {} {}",
+                    id, position.x, position.y, fmt_connections(connections), fmt_data(data), kind
+                ),
+                PatchCommand::RemoveCircuit { id, kind, position, data, connections } => write!(
+                    f,
+                    "remove {} {} {} {} {} {}",
+                    id, position.x, position.y, fmt_connections(connections), fmt_data(data), kind
+                ),
+                PatchCommand::MoveCircuit { id, from, to } => {
+                    write!(f, "move {} {} {} {} {}", id, from.x, from.y, to.x, to.y)
+                }
+                PatchCommand::AddConnection { src, dst } => {
+                    write!(f, "connect {} {}", fmt_port(src), fmt_port(dst))
+                }
+                PatchCommand::RemoveConnection { src, dst } => {
+                    write!(f, "disconnect {} {}", fmt_port(src), fmt_port(dst))
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for HistoryEntry {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (session, rest) = s.split_once(' ').ok_or(())?;
+        let session = SessionId::from_str_radix(session, 16).map_err(|_| ())?;
+        let (action, args) = rest.split_once(' ').unwrap_or((rest, ""));
+
+        let float = |text: Option<&str>| text.and_then(|t| t.parse::<f32>().ok()).ok_or(());
+        let mut words = args.splitn(6, ' ');
+
+        let record = match action {
+            "undo" => HistoryRecord::Undo,
+            "redo" => HistoryRecord::Redo,
+            "add" | "remove" => {
+                let id = words.next().and_then(|t| t.parse().ok()).ok_or(())?;
+                let position = Pos2::new(float(words.next())?, float(words.next())?);
+                let connections = words.next().and_then(parse_connections).ok_or(())?;
+                let data = words.next().and_then(parse_data).ok_or(())?;
+                let kind = words.next().ok_or(())?.to_string();
+                HistoryRecord::Do(if action == "add" {
+                    PatchCommand::AddCircuit { id, kind, position, data, connections }
+                } else {
+                    PatchCommand::RemoveCircuit { id, kind, position, data, connections }
+                })
+            }
+            "move" => {
+                let mut words = args.split(' ');
+                let id = words.next().and_then(|t| t.parse().ok()).ok_or(())?;
+                let from = Pos2::new(float(words.next())?, float(words.next())?);
+                let to = Pos2::new(float(words.next())?, float(words.next())?);
+                HistoryRecord::Do(PatchCommand::MoveCircuit { id, from, to })
+            }
+            "connect" | "disconnect" => {
+                let src = words.next().and_then(|t| parse_port(t, PortKind::Output)).ok_or(())?;
+                let dst = words.next().and_then(|t| parse_port(t, PortKind::Input)).ok_or(())?;
+                HistoryRecord::Do(if action == "connect" {
+                    PatchCommand::AddConnection { src, dst }
+                } else {
+                    PatchCommand::RemoveConnection { src, dst }
+                })
+            }
+            _ => return Err(()),
+        };
+
+        Ok(Self {
+            session,
+            record
+        })
+    }
+}
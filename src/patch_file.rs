@@ -0,0 +1,437 @@
+use std::{collections::{HashMap, HashSet}, fmt::Display, str::FromStr};
+
+use egui::Pos2;
+use thiserror::Error;
+
+use crate::{
+    bundle::{AssetReference, ProjectBundle}, circuit::{CircuitBuilder, CircuitBuilderSpecification, CircuitUiSlot}, circuit_id::{CircuitId, CircuitPortId, ConnectionId, PortId, PortKind}, circuits::{ConstantBuilder, SpecialInputBuilder, SpecialOutputBuilder, SubpatchPortBuilder}, compiled_patch::{CompiledPatch, PatchIr}, connection_manager::ConnectionManager, pitch::TuningSystem
+};
+
+/// An error occurring while reading or restoring a patch file
+#[derive(Debug, Error)]
+pub enum PatchFileError {
+    #[error("The file is not a patch file.")]
+    BadHeader,
+
+    #[error("Unsupported patch file version {0}.")]
+    UnsupportedVersion(u32),
+
+    #[error("Malformed patch file on line {0}.")]
+    Malformed(usize),
+
+    #[error("Unknown circuit kind '{0}'.")]
+    UnknownKind(String),
+
+    #[error("The settings of circuit {0} could not be restored.")]
+    InvalidCircuitData(CircuitId),
+
+    #[error("A connection refers to circuit {0}, which does not exist.")]
+    UnknownCircuit(CircuitId),
+
+    #[error("A connection refers to port {1} of circuit {0}, which does not exist.")]
+    UnknownPort(CircuitId, usize),
+
+    #[error("Unknown template '{0}'.")]
+    UnknownTemplate(String),
+
+    #[error("The id {0} is used by more than one circuit.")]
+    DuplicateId(CircuitId),
+}
+
+/// A circuit stored in a patch file
+#[derive(Debug, Clone, PartialEq)]
+pub struct CircuitRecord {
+    /// the id of the circuit within the file
+    /// ids are only used to resolve connections and are reassigned when loaded
+    pub id: CircuitId,
+
+    /// the display name of the builder specification that creates the circuit
+    /// or one of the special kinds (PatchFile::CONSTANT_KIND, etc.)
+    pub kind: String,
+
+    pub position: Pos2,
+
+    /// the builder's settings as created by CircuitBuilder::save
+    pub data: String,
+}
+
+/// The serialized form of a patch
+/// Stored as text with one item per line
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PatchFile {
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    pub circuits: Vec<CircuitRecord>,
+    pub connections: Vec<(CircuitPortId, CircuitPortId)>,
+}
+
+/// The builders and connections of a patch file, without any editor state
+/// Used to compile patches outside of the editor
+#[derive(Debug)]
+pub struct PatchInstance {
+    pub ids: Vec<CircuitId>,
+    pub builders: HashMap<CircuitId, Box<dyn CircuitBuilder>>,
+    pub connections: ConnectionManager,
+    pub input_ids: Vec<HashSet<CircuitId>>,
+    pub output_ids: Vec<HashSet<CircuitId>>,
+}
+
+impl PatchInstance {
+    /// Compiles the patch so that it may be played, along with the ui slots of its circuits
+    pub fn compile(
+        &self,
+        sample_rate: u32,
+        sample_multiplier: f32,
+        tuning: &TuningSystem
+    ) -> (CompiledPatch, Vec<CircuitUiSlot>) {
+        PatchIr::new(
+            &self.ids,
+            &self.builders,
+            &self.connections,
+            &self.input_ids,
+            &self.output_ids
+        ).compile(sample_rate, sample_multiplier, tuning)
+    }
+
+    /// Gets every external file used by the circuits
+    pub fn assets(&self) -> Vec<AssetReference> {
+        self.builders.values().flat_map(|builder| builder.assets()).collect()
+    }
+
+    /// Reloads the external files used by the circuits from a project bundle
+    pub fn resolve_assets(&mut self, bundle: &ProjectBundle) {
+        for builder in self.builders.values_mut() {
+            builder.resolve_assets(bundle);
+        }
+    }
+
+    /// Replaces every subpatch with the circuits of the patch it embeds
+    /// The embedded patch's inputs and outputs become passthrough circuits wired to the container's connections.
+    fn flatten(&mut self, builders: &[CircuitBuilderSpecification]) -> Result<(), PatchFileError> {
+        let containers: Vec<CircuitId> = self.ids.iter()
+            .copied()
+            .filter(|id| self.builders[id].subpatch().is_some())
+            .collect();
+
+        for container in containers {
+            // nested subpatches are flattened while instantiating the child
+            let builder = self.builders.remove(&container).expect("containers should have builders");
+            let mut child = builder.subpatch()
+                .expect("containers should embed a patch")
+                .instantiate(builders)
+                .map_err(|_| PatchFileError::InvalidCircuitData(container))?;
+
+            let first_id = self.ids.iter().max().map_or(0, |id| id + 1);
+            let mut id_map = HashMap::with_capacity(child.ids.len());
+            let mut input_ports = vec![Vec::new(); child.input_ids.len()];
+            let mut output_ports = vec![Vec::new(); child.output_ids.len()];
+            for (new_id, id) in (first_id..).zip(&child.ids) {
+                id_map.insert(*id, new_id);
+
+                let mut builder = child.builders.remove(id).expect("instantiated circuits should have builders");
+                if let Some(index) = child.input_ids.iter().position(|ids| ids.contains(id)) {
+                    input_ports[index].push(new_id);
+                    builder = Box::new(SubpatchPortBuilder::new(builder.name().to_string()));
+                } else if let Some(index) = child.output_ids.iter().position(|ids| ids.contains(id)) {
+                    output_ports[index].push(new_id);
+                    builder = Box::new(SubpatchPortBuilder::new(builder.name().to_string()));
+                }
+                self.builders.insert(new_id, builder);
+                self.ids.push(new_id);
+            }
+
+            for connection in child.connections.connections() {
+                let (src, dst) = (connection.src(), connection.dst());
+                self.connections.add_connection(ConnectionId::new(
+                    CircuitPortId::new(id_map[&src.unit_id], src.port_id),
+                    CircuitPortId::new(id_map[&dst.unit_id], dst.port_id),
+                ));
+            }
+
+            // connections to the container's ports are moved to the passthroughs standing in for them
+            for connection in self.connections.circuit_query_connections(container) {
+                let (src, dst) = (connection.src(), connection.dst());
+                let sources = if src.unit_id == container {
+                    output_ports.get(src.port_id.index())
+                        .map(|ids| ids.iter().map(|id| CircuitPortId::new(*id, PortId::new(0, PortKind::Output))).collect())
+                        .unwrap_or_default()
+                } else {
+                    vec![src]
+                };
+                let destinations: Vec<CircuitPortId> = if dst.unit_id == container {
+                    input_ports.get(dst.port_id.index())
+                        .map(|ids| ids.iter().map(|id| CircuitPortId::new(*id, PortId::new(0, PortKind::Input))).collect())
+                        .unwrap_or_default()
+                } else {
+                    vec![dst]
+                };
+                for src in &sources {
+                    for dst in &destinations {
+                        self.connections.add_connection(ConnectionId::new(*src, *dst));
+                    }
+                }
+            }
+
+            self.connections.remove_circuit(container);
+            self.ids.retain(|id| *id != container);
+        }
+        Ok(())
+    }
+}
+
+/// A patch shipped with the crate that new projects may start from
+#[derive(Debug, Clone, Copy)]
+pub struct PatchTemplate {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub source: &'static str,
+}
+
+impl PatchFile {
+    const HEADER: &'static str = "starship-patch";
+    const VERSION: u32 = 1;
+
+    /// the file extension used for patch files
+    pub const EXTENSION: &'static str = "patch";
+
+    /// the kind of constant circuits, whose data is the constant's text
+    pub const CONSTANT_KIND: &'static str = "@constant";
+
+    /// the kind of special input circuits, whose data is the index of the input
+    pub const INPUT_KIND: &'static str = "@input";
+
+    /// the kind of special output circuits, whose data is the index of the output
+    pub const OUTPUT_KIND: &'static str = "@output";
+
+    /// every template shipped with the crate
+    pub const TEMPLATES: &'static [PatchTemplate] = &[
+        PatchTemplate {
+            name: "Empty",
+            description: "A patch with a single output and no circuits.",
+            source: include_str!("../assets/templates/empty.patch"),
+        },
+        PatchTemplate {
+            name: "Basic Synth Voice",
+            description: "An oscillator driven by frequency and amplitude inputs.",
+            source: include_str!("../assets/templates/basic_voice.patch"),
+        },
+        PatchTemplate {
+            name: "Drum Bus",
+            description: "Several drum inputs mixed into one output.",
+            source: include_str!("../assets/templates/drum_bus.patch"),
+        },
+    ];
+
+    /// gets the template with the given name
+    pub fn template(name: &str) -> Result<Self, PatchFileError> {
+        Self::TEMPLATES.iter()
+            .find(|template| template.name == name)
+            .ok_or_else(|| PatchFileError::UnknownTemplate(name.to_string()))?
+            .source
+            .parse()
+    }
+
+    /// gets the circuit with the given id
+    pub fn circuit(&self, id: CircuitId) -> Option<&CircuitRecord> {
+        self.circuits.iter().find(|circuit| circuit.id == id)
+    }
+
+    /// creates the builders and connections described by the file
+    /// circuits keep the ids they have in the file
+    pub fn instantiate(
+        &self,
+        builders: &[CircuitBuilderSpecification]
+    ) -> Result<PatchInstance, PatchFileError> {
+        let mut instance = PatchInstance {
+            ids: Vec::with_capacity(self.circuits.len()),
+            builders: HashMap::with_capacity(self.circuits.len()),
+            connections: ConnectionManager::default(),
+            input_ids: vec![HashSet::new(); self.inputs.len()],
+            output_ids: vec![HashSet::new(); self.outputs.len()],
+        };
+
+        for record in &self.circuits {
+            let invalid_data = || PatchFileError::InvalidCircuitData(record.id);
+            let special_index = |count: usize| record.data.parse::<usize>()
+                .ok()
+                .filter(|index| *index < count)
+                .ok_or_else(invalid_data);
+
+            let builder: Box<dyn CircuitBuilder> = match record.kind.as_str() {
+                Self::CONSTANT_KIND => {
+                    let mut builder = ConstantBuilder::new();
+                    if !builder.load(&record.data) {
+                        return Err(invalid_data());
+                    }
+                    Box::new(builder)
+                }
+                Self::INPUT_KIND => {
+                    let index = special_index(self.inputs.len())?;
+                    instance.input_ids[index].insert(record.id);
+                    Box::new(SpecialInputBuilder::new(self.inputs[index].clone()))
+                }
+                Self::OUTPUT_KIND => {
+                    let index = special_index(self.outputs.len())?;
+                    instance.output_ids[index].insert(record.id);
+                    Box::new(SpecialOutputBuilder::new(self.outputs[index].clone()))
+                }
+                kind => {
+                    let specification = builders.iter()
+                        .find(|builder| builder.display_name == kind)
+                        .ok_or_else(|| PatchFileError::UnknownKind(kind.to_string()))?;
+                    let mut builder = (specification.instance)();
+                    if !builder.load(&record.data) {
+                        return Err(invalid_data());
+                    }
+                    builder
+                }
+            };
+
+            if instance.builders.insert(record.id, builder).is_some() {
+                return Err(PatchFileError::DuplicateId(record.id));
+            }
+            instance.ids.push(record.id);
+        }
+
+        for (src, dst) in &self.connections {
+            instance.connections.add_connection(ConnectionId::new(*src, *dst));
+        }
+
+        instance.flatten(builders)?;
+        Ok(instance)
+    }
+}
+
+/// escapes circuit data so it fits on a single line
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+/// reverses escape
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// writes a port as circuit.index
+fn fmt_port(port: &CircuitPortId) -> String {
+    format!("{}.{}", port.unit_id, port.port_id.index())
+}
+
+/// reads a port written by fmt_port
+fn parse_port(text: &str, kind: PortKind) -> Option<CircuitPortId> {
+    let (circuit, index) = text.split_once('.')?;
+    Some(CircuitPortId::new(
+        circuit.parse().ok()?,
+        PortId::new(index.parse().ok()?, kind)
+    ))
+}
+
+impl Display for PatchFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} {}", Self::HEADER, Self::VERSION)?;
+        for input in &self.inputs {
+            writeln!(f, "input {}", input)?;
+        }
+        for output in &self.outputs {
+            writeln!(f, "output {}", output)?;
+        }
+        for circuit in &self.circuits {
+            // the kind is written last as it may contain spaces
+            writeln!(
+                f,
+                "circuit {} {} {} {}",
+                circuit.id,
+                circuit.position.x,
+                circuit.position.y,
+                circuit.kind
+            )?;
+            if !circuit.data.is_empty() {
+                writeln!(f, "data {}", escape(&circuit.data))?;
+            }
+        }
+        for (src, dst) in &self.connections {
+            writeln!(f, "connect {} {}", fmt_port(src), fmt_port(dst))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PatchFile {
+    type Err = PatchFileError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().enumerate();
+
+        let header = lines.next().map(|(_, line)| line).unwrap_or_default();
+        let version = header.strip_prefix(Self::HEADER)
+            .and_then(|rest| rest.trim().parse::<u32>().ok())
+            .ok_or(PatchFileError::BadHeader)?;
+        if version != Self::VERSION {
+            return Err(PatchFileError::UnsupportedVersion(version));
+        }
+
+        let mut file = Self::default();
+        for (index, line) in lines {
+            let line_number = index + 1;
+            let malformed = || PatchFileError::Malformed(line_number);
+
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+            match keyword {
+                "input" => file.inputs.push(rest.to_string()),
+                "output" => file.outputs.push(rest.to_string()),
+                "circuit" => {
+                    let mut words = rest.splitn(4, ' ');
+                    let mut next = || words.next().ok_or_else(malformed);
+                    let id = next()?.parse().map_err(|_| malformed())?;
+                    let x = next()?.parse().map_err(|_| malformed())?;
+                    let y = next()?.parse().map_err(|_| malformed())?;
+                    let kind = next()?.to_string();
+                    file.circuits.push(CircuitRecord {
+                        id,
+                        kind,
+                        position: Pos2::new(x, y),
+                        data: String::new()
+                    });
+                }
+                "data" => {
+                    let circuit = file.circuits.last_mut().ok_or_else(malformed)?;
+                    circuit.data = unescape(rest);
+                }
+                "connect" => {
+                    let (src, dst) = rest.split_once(' ').ok_or_else(malformed)?;
+                    let src = parse_port(src, PortKind::Output).ok_or_else(malformed)?;
+                    let dst = parse_port(dst, PortKind::Input).ok_or_else(malformed)?;
+                    file.connections.push((src, dst));
+                }
+                _ => return Err(malformed()),
+            }
+        }
+
+        for (src, dst) in &file.connections {
+            for id in [src.unit_id, dst.unit_id] {
+                if file.circuit(id).is_none() {
+                    return Err(PatchFileError::UnknownCircuit(id));
+                }
+            }
+        }
+
+        Ok(file)
+    }
+}
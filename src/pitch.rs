@@ -1,7 +1,16 @@
-use std::{fmt::Display, str::FromStr};
+use std::{fmt::Display, path::PathBuf, str::FromStr, sync::Arc};
 
 use thiserror::Error;
 
+/// reading Scala scale (.scl) and keyboard mapping (.kbm) files into tuning tables
+pub mod scala;
+
+/// distances between pitches, named or counted in quarter tones
+pub mod interval;
+
+use interval::Interval;
+use scala::{KeyboardMapping, ScalaError, ScalaScale};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Tone {
     C,
@@ -74,6 +83,22 @@ impl FromStr for Tone {
 }
 
 impl Tone {
+    /// Every tone in order from C
+    pub const ALL: [Self; 7] = [Self::C, Self::D, Self::E, Self::F, Self::G, Self::A, Self::B];
+
+    /// The number of letter names above C
+    pub fn letter_delta(&self) -> u32 {
+        match self {
+            Self::C => 0,
+            Self::D => 1,
+            Self::E => 2,
+            Self::F => 3,
+            Self::G => 4,
+            Self::A => 5,
+            Self::B => 6
+        }
+    }
+
     /// The number of semitones above C
     pub fn semitone_delta(&self) -> u32 {
         match self {
@@ -146,6 +171,17 @@ impl Display for Accidental {
 }
 
 impl Accidental {
+    /// Every accidental in order from lowest to highest
+    pub const ALL: [Self; 7] = [
+        Self::QtrFlat,
+        Self::Flat,
+        Self::ThreeQtrFlat,
+        Self::Natural,
+        Self::QtrSharp,
+        Self::Sharp,
+        Self::ThreeQtrSharp,
+    ];
+
     /// The number of quarter tones a pitch would be changed by
     pub fn quarter_delta(&self) -> i32 {
         match self {
@@ -274,7 +310,7 @@ impl FromStr for Pitch {
                         Some('b') | Some('#') => raw.unwrap(),
                         Some(t) => {
                             return Err(PitchParseError::UnrecognizedAccidental(
-                                (octave_or_accidental.to_string() + &t.to_string()).to_string())
+                                format!("{}{}", octave_or_accidental, t))
                             );
                         }
                         None => {
@@ -386,13 +422,91 @@ impl Pitch {
         self.quarter_delta_a4() * Self::CENTS_PER_MICROTONE as i32
     }
 
+    /// Gets the pitch the given number of semitones from A4, spelled with sharps
+    /// Returns none if the pitch would be below octave 0 or above octave 255
+    pub fn from_semitone_delta_a4(semitones: i32) -> Option<Self> {
+        const SPELLINGS: [(Tone, Accidental); 12] = [
+            (Tone::C, Accidental::Natural), (Tone::C, Accidental::Sharp),
+            (Tone::D, Accidental::Natural), (Tone::D, Accidental::Sharp),
+            (Tone::E, Accidental::Natural), (Tone::F, Accidental::Natural),
+            (Tone::F, Accidental::Sharp), (Tone::G, Accidental::Natural),
+            (Tone::G, Accidental::Sharp), (Tone::A, Accidental::Natural),
+            (Tone::A, Accidental::Sharp), (Tone::B, Accidental::Natural),
+        ];
+
+        // semitones above C0
+        let semitones = semitones + 4 * Self::SEMITONES_PER_OCTAVE as i32 + Tone::A.semitone_delta() as i32;
+        let octave = u8::try_from(semitones.div_euclid(Self::SEMITONES_PER_OCTAVE as i32)).ok()?;
+        let (tone, accidental) = SPELLINGS[semitones.rem_euclid(Self::SEMITONES_PER_OCTAVE as i32) as usize];
+        Some(Self { octave, tone, accidental })
+    }
+
+    /// Gets the pitch the given number of quarter tones from A4, spelled with sharps and quarter sharps
+    /// Returns none if the pitch would be below octave 0 or above octave 255
+    pub fn from_quarter_delta_a4(quarters: i32) -> Option<Self> {
+        let mut pitch = Self::from_semitone_delta_a4(quarters.div_euclid(2))?;
+        if quarters.rem_euclid(2) == 1 {
+            pitch.accidental = match pitch.accidental {
+                Accidental::Sharp => Accidental::ThreeQtrSharp,
+                _ => Accidental::QtrSharp,
+            };
+        }
+        Some(pitch)
+    }
+
+    /// Gets the pitch the given number of quarter tones above this one, or below if negative
+    /// Pitches moved by whole octaves keep their spelling, and other pitches are spelled with sharps
+    /// Returns none if the pitch would be below octave 0 or above octave 255
+    pub fn transposed(&self, quarters: i32) -> Option<Self> {
+        let microtones_per_octave = Self::MICROTONES_PER_OCTAVE as i32;
+        if quarters.rem_euclid(microtones_per_octave) == 0 {
+            let octave = u8::try_from(self.octave as i32 + quarters.div_euclid(microtones_per_octave)).ok()?;
+            return Some(Self { octave, ..*self });
+        }
+        Self::from_quarter_delta_a4(self.quarter_delta_a4().checked_add(quarters)?)
+    }
+
+    /// Gets the pitch the interval above this one, or below if the interval descends
+    /// Named intervals are spelled from the letter names they span, so C4 up a minor third is Eb4 rather than D#4,
+    /// unless the spelling would need an accidental that can not be represented.
+    /// Other intervals are spelled as by transposed.
+    /// Returns none if the pitch would be below octave 0 or above octave 255
+    pub fn transpose(&self, interval: Interval) -> Option<Self> {
+        let Some(steps) = interval.steps else {
+            return self.transposed(interval.quarters);
+        };
+
+        let tones = Tone::ALL.len() as i32;
+        let letters = (self.tone.letter_delta() as i32).checked_add(steps)?;
+        let octave = u8::try_from(self.octave as i32 + letters.div_euclid(tones)).ok()?;
+        let tone = Tone::ALL[letters.rem_euclid(tones) as usize];
+        let natural = Self { octave, tone, accidental: Accidental::Natural };
+
+        let quarters = self.quarter_delta_a4().checked_add(interval.quarters)?;
+        let offset = quarters - natural.quarter_delta_a4();
+        match Accidental::ALL.into_iter().find(|accidental| accidental.quarter_delta() == offset) {
+            Some(accidental) => Some(Self { accidental, ..natural }),
+            None => Self::from_quarter_delta_a4(quarters),
+        }
+    }
+
+    /// Gets the interval from this pitch to the other, descending if the other is lower
+    /// The interval counts the letter names between the pitches, so it is named when the spelling allows.
+    pub fn interval_to(&self, other: &Self) -> Interval {
+        let letters = |pitch: &Self| pitch.octave as i32 * Tone::ALL.len() as i32 + pitch.tone.letter_delta() as i32;
+        Interval {
+            quarters: other.quarter_delta_a4() - self.quarter_delta_a4(),
+            steps: Some(letters(other) - letters(self)),
+        }
+    }
+
     /// Get the frequency of the pitch using the given tuning system
-    pub fn frequency(&self, tuning_system: TuningSystem, detune: i32) -> f64 {
+    pub fn frequency(&self, tuning_system: &TuningSystem, detune: i32) -> f64 {
         tuning_system.get_pitch_frequency(&self, detune)
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct DetunedPitch {
     /// base pitch
     pub base_pitch: Pitch,
@@ -404,9 +518,53 @@ pub struct DetunedPitch {
     pub detune: i8
 }
 
+/// Written as the base pitch followed by the signed detune in cents, such as "A4+12c" or "C#3-25c"
+/// The detune is left out when it is zero.
 impl Display for DetunedPitch {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}, +{:.2}c", self.base_pitch, self.detune)
+        if self.detune == 0 {
+            write!(f, "{}", self.base_pitch)
+        } else {
+            write!(f, "{}{:+}c", self.base_pitch, self.detune)
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum DetunedPitchParseError {
+    #[error(transparent)]
+    Pitch(#[from] PitchParseError),
+
+    #[error("Unable to parse detune '{0}'. Detune must be a whole number of cents with a sign, such as '+12c' or '-25c'.")]
+    InvalidDetune(String),
+
+    #[error("Detune must be between -128 and 127 cents, inclusive.")]
+    DetuneOverflow,
+}
+
+/// Parses a pitch optionally followed by a detune in cents, such as "A4", "A4+12c" or "C#3-25c"
+/// The trailing 'c' of the detune may be left out.
+impl FromStr for DetunedPitch {
+    type Err = DetunedPitchParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        let Some(split) = trimmed.find(['+', '-']) else {
+            return Ok(Self { base_pitch: trimmed.parse()?, detune: 0 });
+        };
+
+        let (pitch, detune) = trimmed.split_at(split);
+        let base_pitch = pitch.parse()?;
+        let digits = detune[1..].trim();
+        let digits = digits.strip_suffix('c').unwrap_or(digits);
+        if digits.is_empty() || !digits.chars().all(|character| character.is_ascii_digit()) {
+            return Err(DetunedPitchParseError::InvalidDetune(detune.to_string()));
+        }
+
+        let magnitude: i32 = digits.parse().map_err(|_| DetunedPitchParseError::DetuneOverflow)?;
+        let cents = if detune.starts_with('-') { -magnitude } else { magnitude };
+        let detune = i8::try_from(cents).map_err(|_| DetunedPitchParseError::DetuneOverflow)?;
+        Ok(Self { base_pitch, detune })
     }
 }
 
@@ -419,18 +577,336 @@ impl DetunedPitch {
         self.base_pitch.cent_delta_a4() + self.detune as i32
     }
 
+    /// gets the pitch the given number of quarter tones above this one, or below if negative, keeping the detune
+    /// fails if the pitch would be out of the representable range
+    pub fn transposed(&self, quarters: i32) -> Option<Self> {
+        Some(Self {
+            base_pitch: self.base_pitch.transposed(quarters)?,
+            detune: self.detune,
+        })
+    }
+
+    /// gets the pitch the interval above this one, or below if the interval descends, keeping the detune
+    /// fails if the pitch would be out of the representable range
+    pub fn transpose(&self, interval: Interval) -> Option<Self> {
+        Some(Self {
+            base_pitch: self.base_pitch.transpose(interval)?,
+            detune: self.detune,
+        })
+    }
+
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+/// A named set of steps within an octave
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScaleKind {
+    Major,
+    NaturalMinor,
+    HarmonicMinor,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Locrian,
+    MajorPentatonic,
+    MinorPentatonic,
+    Blues,
+    WholeTone,
+    Chromatic,
+}
+
+impl ScaleKind {
+    const MAJOR_TEXT: &'static str = "Major";
+    const NATURAL_MINOR_TEXT: &'static str = "Natural Minor";
+    const HARMONIC_MINOR_TEXT: &'static str = "Harmonic Minor";
+    const DORIAN_TEXT: &'static str = "Dorian";
+    const PHRYGIAN_TEXT: &'static str = "Phrygian";
+    const LYDIAN_TEXT: &'static str = "Lydian";
+    const MIXOLYDIAN_TEXT: &'static str = "Mixolydian";
+    const LOCRIAN_TEXT: &'static str = "Locrian";
+    const MAJOR_PENTATONIC_TEXT: &'static str = "Major Pentatonic";
+    const MINOR_PENTATONIC_TEXT: &'static str = "Minor Pentatonic";
+    const BLUES_TEXT: &'static str = "Blues";
+    const WHOLE_TONE_TEXT: &'static str = "Whole Tone";
+    const CHROMATIC_TEXT: &'static str = "Chromatic";
+
+    pub const ALL: [Self; 13] = [
+        Self::Major,
+        Self::NaturalMinor,
+        Self::HarmonicMinor,
+        Self::Dorian,
+        Self::Phrygian,
+        Self::Lydian,
+        Self::Mixolydian,
+        Self::Locrian,
+        Self::MajorPentatonic,
+        Self::MinorPentatonic,
+        Self::Blues,
+        Self::WholeTone,
+        Self::Chromatic,
+    ];
+
+    pub fn display_string(&self) -> &'static str {
+        match self {
+            Self::Major => Self::MAJOR_TEXT,
+            Self::NaturalMinor => Self::NATURAL_MINOR_TEXT,
+            Self::HarmonicMinor => Self::HARMONIC_MINOR_TEXT,
+            Self::Dorian => Self::DORIAN_TEXT,
+            Self::Phrygian => Self::PHRYGIAN_TEXT,
+            Self::Lydian => Self::LYDIAN_TEXT,
+            Self::Mixolydian => Self::MIXOLYDIAN_TEXT,
+            Self::Locrian => Self::LOCRIAN_TEXT,
+            Self::MajorPentatonic => Self::MAJOR_PENTATONIC_TEXT,
+            Self::MinorPentatonic => Self::MINOR_PENTATONIC_TEXT,
+            Self::Blues => Self::BLUES_TEXT,
+            Self::WholeTone => Self::WHOLE_TONE_TEXT,
+            Self::Chromatic => Self::CHROMATIC_TEXT,
+        }
+    }
+
+    /// gets the kind with the given display string
+    pub fn from_display_string(text: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.display_string() == text)
+    }
+
+    /// the semitones above the root of each note of the scale
+    pub fn steps(&self) -> &'static [u32] {
+        match self {
+            Self::Major => &equal_temperment::MAJOR_SCALE,
+            Self::NaturalMinor => &equal_temperment::NATURAL_MINOR_SCALE,
+            Self::HarmonicMinor => &equal_temperment::HARMONIC_MINOR_SCALE,
+            Self::Dorian => &equal_temperment::DORIAN_SCALE,
+            Self::Phrygian => &equal_temperment::PHRYGIAN_SCALE,
+            Self::Lydian => &equal_temperment::LYDIAN_SCALE,
+            Self::Mixolydian => &equal_temperment::MIXOLYDIAN_SCALE,
+            Self::Locrian => &equal_temperment::LOCRIAN_SCALE,
+            Self::MajorPentatonic => &equal_temperment::MAJOR_PENTATONIC_SCALE,
+            Self::MinorPentatonic => &equal_temperment::MINOR_PENTATONIC_SCALE,
+            Self::Blues => &equal_temperment::BLUES_SCALE,
+            Self::WholeTone => &equal_temperment::WHOLE_TONE_SCALE,
+            Self::Chromatic => &equal_temperment::CHROMATIC_SCALE,
+        }
+    }
+}
+
+/// A scale in a key, such as D dorian
+/// The octave of the root does not matter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scale {
+    pub root: Pitch,
+    pub kind: ScaleKind,
+}
+
+impl Scale {
+    pub fn new(root: Pitch, kind: ScaleKind) -> Self {
+        Self { root, kind }
+    }
+
+    /// the cents above the root within an octave [0, 1200) of the given number of cents from A4
+    fn cents_above_root(&self, cents_a4: i32) -> i32 {
+        (cents_a4 - self.root.cent_delta_a4()).rem_euclid(Pitch::CENTS_PER_OCTAVE as i32)
+    }
+
+    /// returns true if the pitch the given number of semitones from A4 is in the scale,
+    /// such as for highlighting the rows of a piano roll
+    pub fn contains_semitone_a4(&self, semitones: i32) -> bool {
+        let cents = self.cents_above_root(semitones * Pitch::CENTS_PER_SEMITONE as i32);
+        self.kind.steps().iter().any(|step| (*step * Pitch::CENTS_PER_SEMITONE) as i32 == cents)
+    }
+
+    /// returns true if the pitch is exactly on a note of the scale, so detuned pitches are not
+    pub fn contains(&self, pitch: &DetunedPitch) -> bool {
+        let cents = self.cents_above_root(pitch.cent_delta_a4());
+        self.kind.steps().iter().any(|step| (*step * Pitch::CENTS_PER_SEMITONE) as i32 == cents)
+    }
+
+    /// gets the note of the scale nearest to the pitch, rounding down when halfway between two notes
+    /// pitches in the scale are returned unchanged, and so are pitches whose nearest note is out of the representable range
+    pub fn snap(&self, pitch: DetunedPitch) -> DetunedPitch {
+        if self.contains(&pitch) {
+            return pitch;
+        }
+
+        let cents = pitch.cent_delta_a4();
+        let within = self.cents_above_root(cents);
+        // the root of the next octave is also a candidate when rounding up
+        let nearest = self.kind.steps().iter()
+            .map(|step| (*step * Pitch::CENTS_PER_SEMITONE) as i32)
+            .chain(std::iter::once(Pitch::CENTS_PER_OCTAVE as i32))
+            .min_by_key(|step| (step - within).abs())
+            .unwrap_or(0);
+
+        let semitones = (cents - within + nearest).div_euclid(Pitch::CENTS_PER_SEMITONE as i32);
+        match Pitch::from_semitone_delta_a4(semitones) {
+            Some(base_pitch) => DetunedPitch { base_pitch, detune: 0 },
+            None => pitch,
+        }
+    }
+}
+
+/// The frequency of every MIDI key, such as for a tuning read from Scala files
+/// Pitches between keys (quarter tones and detunes) are placed between the frequencies of
+/// the keys around them by the same fraction of the interval as they are in equal temperment.
+/// Pitches beyond the keys continue from the nearest key in equal tempered semitones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuningTable {
+    /// the frequency of each key, increasing
+    frequencies: [f64; Self::KEYS],
+}
+
+impl TuningTable {
+    /// the number of MIDI keys
+    pub const KEYS: usize = 128;
+
+    /// the MIDI key of A4
+    pub const A4_KEY: i32 = 69;
+
+    /// creates a table from the frequency of every MIDI key
+    /// fails if any frequency is not positive and finite, or the frequencies do not increase, returning None
+    pub fn new(frequencies: [f64; Self::KEYS]) -> Option<Self> {
+        let valid = frequencies.iter().all(|frequency| frequency.is_finite() && *frequency > 0.0)
+            && frequencies.windows(2).all(|pair| pair[0] < pair[1]);
+        valid.then_some(Self { frequencies })
+    }
+
+    /// gets the frequency of each MIDI key
+    pub fn frequencies(&self) -> &[f64; Self::KEYS] {
+        &self.frequencies
+    }
+
+    /// gets the frequency of a MIDI key
+    pub fn key_frequency(&self, key: u8) -> Option<f64> {
+        self.frequencies.get(key as usize).copied()
+    }
+
+    /// gets the frequency of a pitch the given number of cents from A4
+    pub fn get_cent_delta_a4_frequency(&self, cents: f64) -> f64 {
+        let key = Self::A4_KEY as f64 + cents / Pitch::CENTS_PER_SEMITONE as f64;
+        let last = (Self::KEYS - 1) as f64;
+        let (low, fraction) = if key < 0.0 {
+            (0, key)
+        } else if key >= last {
+            (Self::KEYS - 1, key - last)
+        } else {
+            (key as usize, key.fract())
+        };
+
+        let base = self.frequencies[low];
+        match self.frequencies.get(low + 1) {
+            Some(high) if (0.0..1.0).contains(&fraction) => base * (high / base).powf(fraction),
+            _ => base * 2.0_f64.powf(fraction / Pitch::SEMITONES_PER_OCTAVE as f64),
+        }
+    }
+
+    /// gets the number of cents from A4 of the pitch with the given frequency, the inverse of get_cent_delta_a4_frequency
+    pub fn get_frequency_cent_delta_a4(&self, frequency: f64) -> f64 {
+        let semitones_from = |key: usize| {
+            Pitch::SEMITONES_PER_OCTAVE as f64 * (frequency / self.frequencies[key]).log2()
+        };
+        let low = self.frequencies.partition_point(|key_frequency| *key_frequency <= frequency);
+        let key = match low {
+            0 => semitones_from(0),
+            Self::KEYS => (Self::KEYS - 1) as f64 + semitones_from(Self::KEYS - 1),
+            high => {
+                let low = high - 1;
+                let interval = (self.frequencies[high] / self.frequencies[low]).ln();
+                low as f64 + (frequency / self.frequencies[low]).ln() / interval
+            }
+        };
+        (key - Self::A4_KEY as f64) * Pitch::CENTS_PER_SEMITONE as f64
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum TuningSystem {
     /// Twelve-tone equal temperment. Value contains pitch of A4.
-    EqualTemperment(f64)
+    EqualTemperment(f64),
+
+    /// A frequency for each MIDI key, such as one read from Scala files
+    Custom(Arc<TuningTable>),
 }
 
 impl TuningSystem {
     pub fn get_pitch_frequency(&self, pitch: &Pitch, detune: i32) -> f64 {
         match self {
             Self::EqualTemperment(a4) => equal_temperment::get_pitch_frequency(*a4, pitch, detune),
+            Self::Custom(table) => table.get_cent_delta_a4_frequency(pitch.cent_delta_a4() as f64 + detune as f64),
+        }
+    }
+
+    /// gets the frequency given the difference in cents from a4
+    pub fn get_cent_delta_a4_frequency(&self, cents: f64) -> f64 {
+        match self {
+            Self::EqualTemperment(a4) => equal_temperment::get_cent_delta_a4_frequency(*a4, cents),
+            Self::Custom(table) => table.get_cent_delta_a4_frequency(cents),
+        }
+    }
+
+    /// gets the difference in cents from a4 of the given frequency
+    /// frequency must be positive
+    pub fn get_frequency_cent_delta_a4(&self, frequency: f64) -> f64 {
+        match self {
+            Self::EqualTemperment(a4) => Pitch::CENTS_PER_OCTAVE as f64 * (frequency / a4).log2(),
+            Self::Custom(table) => table.get_frequency_cent_delta_a4(frequency),
+        }
+    }
+}
+
+/// Twelve-tone equal temperment with A4 at 440Hz
+impl Default for TuningSystem {
+    fn default() -> Self {
+        Self::EqualTemperment(TuningSettings::DEFAULT_REFERENCE_A4)
+    }
+}
+
+/// The user's preferences for how every patch and sequencer is tuned
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuningSettings {
+    /// the frequency of A4 in Hz
+    pub reference_a4: f64,
+
+    /// the Scala scale file the keys are tuned to
+    /// if none, twelve-tone equal temperment is used
+    pub scale: Option<PathBuf>,
+
+    /// the Scala keyboard mapping file assigning the notes of the scale to keys, which sets its own reference frequency
+    /// if none, the root of the scale is placed on middle C with A4 at the reference frequency
+    pub mapping: Option<PathBuf>,
+}
+
+impl TuningSettings {
+    pub const MIN_REFERENCE_A4: f64 = 400.0;
+    pub const MAX_REFERENCE_A4: f64 = 480.0;
+    pub const DEFAULT_REFERENCE_A4: f64 = 440.0;
+
+    /// the frequency of A4, clamped to the allowed range
+    pub fn reference_a4(&self) -> f64 {
+        self.reference_a4.clamp(Self::MIN_REFERENCE_A4, Self::MAX_REFERENCE_A4)
+    }
+
+    /// reads the tuning system described by the settings, loading the Scala files if there are any
+    pub fn tuning_system(&self) -> Result<TuningSystem, ScalaError> {
+        let Some(scale) = &self.scale else {
+            return Ok(TuningSystem::EqualTemperment(self.reference_a4()));
+        };
+        let scale = ScalaScale::load(scale)?;
+        let mapping = match &self.mapping {
+            Some(path) => KeyboardMapping::load(path)?,
+            None => KeyboardMapping {
+                reference_frequency: self.reference_a4(),
+                ..KeyboardMapping::default()
+            },
+        };
+        Ok(TuningSystem::Custom(Arc::new(mapping.tuning_table(&scale)?)))
+    }
+}
+
+impl Default for TuningSettings {
+    fn default() -> Self {
+        Self {
+            reference_a4: Self::DEFAULT_REFERENCE_A4,
+            scale: None,
+            mapping: None,
         }
     }
 }
@@ -472,4 +948,60 @@ pub mod equal_temperment {
         let s = i + p(i - 1.0) + p(i - 3.0) + p(i - 6.0) + p(i - 8.0) + p(i - 10.0) - 5.0;
         root * f64::powf(2.0, s / 12.0)
     }
+
+    /// semitones above the root of each note of the major scale
+    pub const MAJOR_SCALE: [u32; 7] = [0, 2, 4, 5, 7, 9, 11];
+
+    /// semitones above the root of each note of the natural minor scale
+    pub const NATURAL_MINOR_SCALE: [u32; 7] = [0, 2, 3, 5, 7, 8, 10];
+
+    /// semitones above the root of each note of the harmonic minor scale
+    pub const HARMONIC_MINOR_SCALE: [u32; 7] = [0, 2, 3, 5, 7, 8, 11];
+
+    /// semitones above the root of each note of the dorian mode
+    pub const DORIAN_SCALE: [u32; 7] = [0, 2, 3, 5, 7, 9, 10];
+
+    /// semitones above the root of each note of the mixolydian mode
+    pub const MIXOLYDIAN_SCALE: [u32; 7] = [0, 2, 4, 5, 7, 9, 10];
+
+    /// semitones above the root of each note of the major pentatonic scale
+    pub const MAJOR_PENTATONIC_SCALE: [u32; 5] = [0, 2, 4, 7, 9];
+
+    /// semitones above the root of each note of the minor pentatonic scale
+    pub const MINOR_PENTATONIC_SCALE: [u32; 5] = [0, 3, 5, 7, 10];
+
+    /// semitones above the root of each note of the blues scale
+    pub const BLUES_SCALE: [u32; 6] = [0, 3, 5, 6, 7, 10];
+
+    /// semitones above the root of each note of the phrygian mode
+    pub const PHRYGIAN_SCALE: [u32; 7] = [0, 1, 3, 5, 7, 8, 10];
+
+    /// semitones above the root of each note of the lydian mode
+    pub const LYDIAN_SCALE: [u32; 7] = [0, 2, 4, 6, 7, 9, 11];
+
+    /// semitones above the root of each note of the locrian mode
+    pub const LOCRIAN_SCALE: [u32; 7] = [0, 1, 3, 5, 6, 8, 10];
+
+    /// semitones above the root of each note of the whole tone scale
+    pub const WHOLE_TONE_SCALE: [u32; 6] = [0, 2, 4, 6, 8, 10];
+
+    /// every semitone
+    pub const CHROMATIC_SCALE: [u32; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+
+    /// quantizes x to the nearest note of a scale with the given root
+    /// the scale lists the semitones above the root of each note in [0, 12), starting with the root at 0
+    /// Assumes x is greater than zero and the scale is not empty
+    pub fn quantize_scale(root: f64, x: f64, scale: &[u32]) -> f64 {
+        let semitones = 12.0 * f64::log2(x / root);
+        let octave = f64::floor(semitones / 12.0);
+        let within = semitones - octave * 12.0;
+
+        // the root of the next octave is also a candidate when rounding up
+        let nearest = scale.iter()
+            .map(|step| *step as f64)
+            .chain(std::iter::once(12.0))
+            .min_by(|a, b| (a - within).abs().total_cmp(&(b - within).abs()))
+            .unwrap_or(0.0);
+        root * f64::powf(2.0, (octave * 12.0 + nearest) / 12.0)
+    }
 }
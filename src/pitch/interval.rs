@@ -0,0 +1,239 @@
+use std::{fmt::Display, ops::{Add, Neg, Sub}, str::FromStr};
+
+use thiserror::Error;
+
+use super::{Pitch, Tone};
+
+/// How far a named interval is from the perfect or major interval of the same number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IntervalQuality {
+    Diminished,
+    Minor,
+    Perfect,
+    Major,
+    Augmented,
+}
+
+impl IntervalQuality {
+    pub const ALL: [Self; 5] = [Self::Diminished, Self::Minor, Self::Perfect, Self::Major, Self::Augmented];
+
+    /// the letter used to write the quality, as in "m3" or "P5"
+    pub fn symbol(&self) -> char {
+        match self {
+            Self::Diminished => 'd',
+            Self::Minor => 'm',
+            Self::Perfect => 'P',
+            Self::Major => 'M',
+            Self::Augmented => 'A',
+        }
+    }
+
+    /// the number of quarter tones the quality moves an interval from its perfect or major size
+    /// returns none if intervals of that kind can not have the quality, such as a major fifth
+    fn quarter_offset(&self, perfect: bool) -> Option<i32> {
+        match (self, perfect) {
+            (Self::Diminished, true) => Some(-2),
+            (Self::Perfect, true) => Some(0),
+            (Self::Diminished, false) => Some(-4),
+            (Self::Minor, false) => Some(-2),
+            (Self::Major, false) => Some(0),
+            (Self::Augmented, _) => Some(2),
+            _ => None,
+        }
+    }
+}
+
+impl Display for IntervalQuality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum IntervalParseError {
+    #[error("Missing interval. Intervals are written as a quality and number such as 'P5' or 'm3', or as a count such as '7 semitones'.")]
+    Empty,
+
+    #[error("Unrecognized quality '{0}'. Must be one of 'P', 'M', 'm', 'A' or 'd'.")]
+    UnrecognizedQuality(String),
+
+    #[error("Unable to parse number '{0}'.")]
+    InvalidNumber(String),
+
+    #[error("No interval is written '{0}'. Unisons, fourths, fifths and octaves may be perfect, others may be major or minor, and unisons may not be diminished.")]
+    InvalidName(String),
+}
+
+/// A distance between two pitches
+/// Intervals are counted in quarter tones, and named intervals such as a perfect fifth also count the
+/// letter names they span, so that pitches moved by them are spelled as the name implies.
+/// Compound intervals (those wider than an octave) are named by their full number, such as "M9".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Interval {
+    /// the number of quarter tones spanned, negative when descending
+    pub quarters: i32,
+
+    /// the number of letter names moved, 0 for a unison and 4 for a fifth, negative when descending
+    /// None for intervals counted only in quarter tones
+    pub steps: Option<i32>,
+}
+
+impl Interval {
+    /// the number of semitones above the root of the major or perfect interval spanning each number of letter names
+    const MAJOR_SEMITONES: [i32; 7] = [0, 2, 4, 5, 7, 9, 11];
+
+    pub const UNISON: Self = Self { quarters: 0, steps: Some(0) };
+
+    /// creates an interval of the given number of quarter tones, descending if negative
+    pub fn from_quarter_tones(quarters: i32) -> Self {
+        Self { quarters, steps: None }
+    }
+
+    /// creates an interval of the given number of semitones, descending if negative
+    pub fn from_semitones(semitones: i32) -> Self {
+        Self::from_quarter_tones(semitones * 2)
+    }
+
+    /// creates an interval of the given number of octaves, descending if negative
+    pub fn octaves(octaves: i32) -> Self {
+        Self {
+            quarters: octaves * Pitch::MICROTONES_PER_OCTAVE as i32,
+            steps: Some(octaves * Tone::ALL.len() as i32),
+        }
+    }
+
+    /// creates the ascending interval with the given quality and number, where 1 is a unison and 8 is an octave
+    /// returns none if no interval has the quality and number, such as a major fifth
+    pub fn named(quality: IntervalQuality, number: u32) -> Option<Self> {
+        let steps = i32::try_from(number).ok()?.checked_sub(1)?;
+        if steps == 0 && quality == IntervalQuality::Diminished {
+            return None;
+        }
+        let letters = Tone::ALL.len() as i32;
+        let simple = steps.rem_euclid(letters);
+        let offset = quality.quarter_offset(Self::is_perfect_kind(simple))?;
+        let semitones = steps.div_euclid(letters).checked_mul(Pitch::SEMITONES_PER_OCTAVE as i32)?
+            .checked_add(Self::MAJOR_SEMITONES[simple as usize])?;
+        Some(Self {
+            quarters: semitones.checked_mul(2)?.checked_add(offset)?,
+            steps: Some(steps),
+        })
+    }
+
+    /// returns true for unisons, fourths, fifths and their compounds, which are perfect rather than major or minor
+    fn is_perfect_kind(simple_steps: i32) -> bool {
+        matches!(simple_steps, 0 | 3 | 4)
+    }
+
+    /// returns true if the interval moves down
+    pub fn is_descending(&self) -> bool {
+        match self.steps {
+            Some(steps) if steps != 0 => steps < 0,
+            _ => self.quarters < 0,
+        }
+    }
+
+    /// gets the size of the interval in cents, negative when descending
+    pub fn cents(&self) -> i32 {
+        self.quarters * Pitch::CENTS_PER_MICROTONE as i32
+    }
+
+    /// gets the quality and number of the interval, ignoring its direction
+    /// returns none if the interval is not named, or spans a size no quality describes (such as a quarter tone)
+    pub fn name(&self) -> Option<(IntervalQuality, u32)> {
+        let sign = if self.is_descending() { -1 } else { 1 };
+        let steps = self.steps? * sign;
+        let quarters = self.quarters * sign;
+        if steps < 0 {
+            return None;
+        }
+        IntervalQuality::ALL.into_iter()
+            .map(|quality| (quality, steps as u32 + 1))
+            .find(|(quality, number)| Self::named(*quality, *number).is_some_and(|named| named.quarters == quarters))
+    }
+}
+
+impl Neg for Interval {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self {
+            quarters: -self.quarters,
+            steps: self.steps.map(|steps| -steps),
+        }
+    }
+}
+
+/// Stacks two intervals, such as a major third and a minor third making a perfect fifth
+/// The result is named only if both intervals are named.
+impl Add for Interval {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            quarters: self.quarters + rhs.quarters,
+            steps: self.steps.zip(rhs.steps).map(|(steps, other)| steps + other),
+        }
+    }
+}
+
+impl Sub for Interval {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self + -rhs
+    }
+}
+
+/// Written as the quality and number for named intervals, such as "P5", or "-m3" when descending,
+/// and as a count such as "7 semitones" or "3 quarter tones" otherwise
+impl Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some((quality, number)) = self.name() {
+            let sign = if self.is_descending() { "-" } else { "" };
+            write!(f, "{}{}{}", sign, quality, number)
+        } else if self.quarters % 2 == 0 {
+            write!(f, "{} semitones", self.quarters / 2)
+        } else {
+            write!(f, "{} quarter tones", self.quarters)
+        }
+    }
+}
+
+/// Parses a named interval such as "P5", "m3" or "M9", descending if preceded by '-',
+/// or a count of semitones or quarter tones such as "7 semitones" or "-3 quarter tones"
+impl FromStr for Interval {
+    type Err = IntervalParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        if trimmed.is_empty() {
+            return Err(IntervalParseError::Empty);
+        }
+
+        let parse_count = |count: &str| count.trim().parse::<i32>()
+            .map_err(|_| IntervalParseError::InvalidNumber(count.trim().to_string()));
+        let strip_unit = |unit: &str| trimmed.strip_suffix('s').unwrap_or(trimmed).strip_suffix(unit);
+        if let Some(count) = strip_unit("quarter tone") {
+            return Ok(Self::from_quarter_tones(parse_count(count)?));
+        }
+        if let Some(count) = strip_unit("semitone") {
+            return Ok(Self::from_semitones(parse_count(count)?));
+        }
+
+        let (descending, name) = match trimmed.strip_prefix('-') {
+            Some(name) => (true, name),
+            None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+        };
+        let mut chars = name.chars();
+        let symbol = chars.next().ok_or(IntervalParseError::Empty)?;
+        let quality = IntervalQuality::ALL.into_iter()
+            .find(|quality| quality.symbol() == symbol)
+            .ok_or_else(|| IntervalParseError::UnrecognizedQuality(symbol.to_string()))?;
+        let number = chars.as_str();
+        let number: u32 = number.parse().map_err(|_| IntervalParseError::InvalidNumber(number.to_string()))?;
+
+        let interval = Self::named(quality, number).ok_or_else(|| IntervalParseError::InvalidName(name.to_string()))?;
+        Ok(if descending { -interval } else { interval })
+    }
+}
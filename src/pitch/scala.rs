@@ -0,0 +1,297 @@
+use std::{fs, io, path::Path, str::FromStr, sync::Arc};
+
+use thiserror::Error;
+
+use super::{Pitch, TuningSystem, TuningTable};
+
+/// an error occurring when reading Scala files
+#[derive(Debug, Error)]
+pub enum ScalaError {
+    #[error("Unable to read the file: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Missing {0}. The file ended early.")]
+    MissingLine(&'static str),
+
+    #[error("Unable to parse number '{0}'.")]
+    InvalidNumber(String),
+
+    #[error("Unable to parse pitch '{0}'. Pitches must be cents containing a '.', or a positive ratio such as '3/2' or '2'.")]
+    InvalidPitch(String),
+
+    #[error("The scale must have at least one note.")]
+    EmptyScale,
+
+    #[error("The scale does not rise, so it can not be mapped to increasing frequencies.")]
+    NotIncreasing,
+
+    #[error("The reference key {0} is not mapped to a note of the scale.")]
+    UnmappedReference(u8),
+
+    #[error("The mapping refers to note {0}, which is not in the scale.")]
+    DegreeOutOfBounds(usize),
+
+    #[error("The key {0} is not a MIDI key. Keys must be between 0 and 127, inclusive.")]
+    KeyOutOfRange(i64),
+
+    #[error("The reference frequency must be positive.")]
+    InvalidFrequency,
+
+    #[error("The mapping has {0} keys, but may have at most {max}.", max = TuningTable::KEYS)]
+    MappingTooLarge(usize),
+}
+
+/// the lines of a Scala file that are not comments, which start with '!'
+fn content_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines().filter(|line| !line.starts_with('!'))
+}
+
+/// gets the first word of the next line, naming the value expected if there is none
+fn next_value<'a>(lines: &mut impl Iterator<Item = &'a str>, name: &'static str) -> Result<&'a str, ScalaError> {
+    let line = lines.next().ok_or(ScalaError::MissingLine(name))?;
+    line.split_whitespace().next().ok_or(ScalaError::MissingLine(name))
+}
+
+fn parse_number<T: FromStr>(value: &str) -> Result<T, ScalaError> {
+    value.parse().map_err(|_| ScalaError::InvalidNumber(value.to_string()))
+}
+
+fn parse_key(value: &str) -> Result<u8, ScalaError> {
+    let key: i64 = parse_number(value)?;
+    u8::try_from(key).ok()
+        .filter(|key| (*key as usize) < TuningTable::KEYS)
+        .ok_or(ScalaError::KeyOutOfRange(key))
+}
+
+/// A scale read from a Scala scale file (.scl)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScalaScale {
+    pub description: String,
+
+    /// the cents above the root of each note after the root, the last being the interval at which the scale repeats
+    pub degrees: Vec<f64>,
+}
+
+impl ScalaScale {
+    /// reads a scale from a file
+    pub fn load(path: &Path) -> Result<Self, ScalaError> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// gets the number of notes in each repetition of the scale
+    pub fn len(&self) -> usize {
+        self.degrees.len()
+    }
+
+    /// returns true if the scale has no notes
+    pub fn is_empty(&self) -> bool {
+        self.degrees.is_empty()
+    }
+
+    /// gets the interval in cents at which the scale repeats, usually an octave
+    pub fn period(&self) -> f64 {
+        self.degrees.last().copied().unwrap_or(Pitch::CENTS_PER_OCTAVE as f64)
+    }
+
+    /// gets the cents above the root of a note of the scale, counting repetitions above and below the root
+    /// the scale must not be empty
+    pub fn degree_cents(&self, degree: i64) -> f64 {
+        let len = self.degrees.len() as i64;
+        let within = degree.rem_euclid(len) as usize;
+        let base = if within == 0 { 0.0 } else { self.degrees[within - 1] };
+        degree.div_euclid(len) as f64 * self.period() + base
+    }
+
+    /// parses a single pitch, in cents if it contains a '.' and as a ratio otherwise
+    fn parse_pitch(value: &str) -> Result<f64, ScalaError> {
+        let invalid = || ScalaError::InvalidPitch(value.to_string());
+        if value.contains('.') {
+            return value.parse::<f64>().ok().filter(|cents| cents.is_finite()).ok_or_else(invalid);
+        }
+
+        let (numerator, denominator) = value.split_once('/').unwrap_or((value, "1"));
+        let numerator: u64 = numerator.parse().map_err(|_| invalid())?;
+        let denominator: u64 = denominator.parse().map_err(|_| invalid())?;
+        if numerator == 0 || denominator == 0 {
+            return Err(invalid());
+        }
+        Ok(Pitch::CENTS_PER_OCTAVE as f64 * (numerator as f64 / denominator as f64).log2())
+    }
+}
+
+/// Parses the contents of a Scala scale file
+/// The first line that is not a comment describes the scale, the next gives the number of notes,
+/// and each following line gives the pitch of a note in cents (such as "701.955") or as a ratio (such as "3/2").
+/// Text after the first word of a line is ignored.
+impl FromStr for ScalaScale {
+    type Err = ScalaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = content_lines(s);
+        let description = lines.next().ok_or(ScalaError::MissingLine("description"))?.trim().to_string();
+        let count: usize = parse_number(next_value(&mut lines, "number of notes")?)?;
+        if count == 0 {
+            return Err(ScalaError::EmptyScale);
+        }
+
+        let degrees = (0..count)
+            .map(|_| ScalaScale::parse_pitch(next_value(&mut lines, "note")?))
+            .collect::<Result<Vec<f64>, ScalaError>>()?;
+        Ok(Self { description, degrees })
+    }
+}
+
+/// A keyboard mapping read from a Scala keyboard mapping file (.kbm), assigning notes of a scale to MIDI keys
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyboardMapping {
+    /// the lowest key mapped
+    pub first_key: u8,
+
+    /// the highest key mapped
+    pub last_key: u8,
+
+    /// the key playing the root of the scale
+    pub middle_key: u8,
+
+    /// the key tuned to the reference frequency
+    pub reference_key: u8,
+    pub reference_frequency: f64,
+
+    /// the note of the scale the mapping repeats at
+    pub octave_degree: usize,
+
+    /// the note of the scale played by each key in a repetition of the mapping, starting at the middle key
+    /// None leaves the key unmapped, and an empty mapping maps each key to the next note of the scale
+    pub mapping: Vec<Option<usize>>,
+}
+
+impl KeyboardMapping {
+    /// reads a keyboard mapping from a file
+    pub fn load(path: &Path) -> Result<Self, ScalaError> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// gets the note of the scale played by a key, counting repetitions of the scale
+    /// returns None if the key is unmapped
+    fn key_degree(&self, key: u8, scale: &ScalaScale) -> Option<i64> {
+        if key < self.first_key || self.last_key < key {
+            return None;
+        }
+        let offset = key as i64 - self.middle_key as i64;
+        if self.mapping.is_empty() {
+            return Some(offset);
+        }
+
+        let size = self.mapping.len() as i64;
+        let octave_degree = if self.octave_degree == 0 { scale.len() } else { self.octave_degree };
+        let degree = self.mapping[offset.rem_euclid(size) as usize]?;
+        Some(degree as i64 + offset.div_euclid(size) * octave_degree as i64)
+    }
+
+    /// tunes the MIDI keys to the scale
+    /// keys left unmapped are placed between the mapped keys around them
+    pub fn tuning_table(&self, scale: &ScalaScale) -> Result<TuningTable, ScalaError> {
+        if scale.is_empty() {
+            return Err(ScalaError::EmptyScale);
+        }
+        if let Some(degree) = self.mapping.iter().flatten().find(|degree| **degree > scale.len()) {
+            return Err(ScalaError::DegreeOutOfBounds(*degree));
+        }
+        if !(self.reference_frequency.is_finite() && self.reference_frequency > 0.0) {
+            return Err(ScalaError::InvalidFrequency);
+        }
+
+        let reference = self.key_degree(self.reference_key, scale)
+            .ok_or(ScalaError::UnmappedReference(self.reference_key))?;
+        let reference_cents = scale.degree_cents(reference);
+        let cents: Vec<Option<f64>> = (0..TuningTable::KEYS as u8)
+            .map(|key| self.key_degree(key, scale).map(|degree| scale.degree_cents(degree) - reference_cents))
+            .collect();
+
+        let mut frequencies = [0.0; TuningTable::KEYS];
+        for (key, frequency) in frequencies.iter_mut().enumerate() {
+            let below = cents[..=key].iter().enumerate().rev().find_map(|(key, cents)| cents.map(|cents| (key, cents)));
+            let above = cents[key..].iter().enumerate().find_map(|(offset, cents)| cents.map(|cents| (key + offset, cents)));
+            let semitone = Pitch::CENTS_PER_SEMITONE as f64;
+            let key_cents = match (below, above) {
+                (Some((low, low_cents)), Some((high, high_cents))) if low != high => {
+                    low_cents + (high_cents - low_cents) * (key - low) as f64 / (high - low) as f64
+                }
+                (Some((low, low_cents)), _) => low_cents + (key - low) as f64 * semitone,
+                (None, Some((high, high_cents))) => high_cents - (high - key) as f64 * semitone,
+                (None, None) => unreachable!("the reference key is mapped"),
+            };
+            *frequency = self.reference_frequency * 2.0_f64.powf(key_cents / Pitch::CENTS_PER_OCTAVE as f64);
+        }
+        TuningTable::new(frequencies).ok_or(ScalaError::NotIncreasing)
+    }
+}
+
+/// The mapping used without a keyboard mapping file, mapping each key to the next note of the scale,
+/// with the root on middle C (key 60) and A4 (key 69) at 440Hz
+impl Default for KeyboardMapping {
+    fn default() -> Self {
+        Self {
+            first_key: 0,
+            last_key: (TuningTable::KEYS - 1) as u8,
+            middle_key: 60,
+            reference_key: TuningTable::A4_KEY as u8,
+            reference_frequency: 440.0,
+            octave_degree: 0,
+            mapping: Vec::new(),
+        }
+    }
+}
+
+/// Parses the contents of a Scala keyboard mapping file
+/// The lines that are not comments give the size of the mapping, the first, last and middle keys,
+/// the reference key and its frequency, the note the mapping repeats at, and then the note of each key
+/// of the mapping, where 'x' leaves the key unmapped.
+/// Keys missing from the end of the mapping are left unmapped, and mappings may have at most 128 keys.
+impl FromStr for KeyboardMapping {
+    type Err = ScalaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = content_lines(s).filter(|line| !line.trim().is_empty());
+        let size: usize = parse_number(next_value(&mut lines, "size of the mapping")?)?;
+        if size > TuningTable::KEYS {
+            return Err(ScalaError::MappingTooLarge(size));
+        }
+        let first_key = parse_key(next_value(&mut lines, "first key")?)?;
+        let last_key = parse_key(next_value(&mut lines, "last key")?)?;
+        let middle_key = parse_key(next_value(&mut lines, "middle key")?)?;
+        let reference_key = parse_key(next_value(&mut lines, "reference key")?)?;
+        let reference_frequency: f64 = parse_number(next_value(&mut lines, "reference frequency")?)?;
+        let octave_degree: usize = parse_number(next_value(&mut lines, "octave degree")?)?;
+
+        let mut mapping = Vec::new();
+        for value in lines.take(size).filter_map(|line| line.split_whitespace().next()) {
+            mapping.push(match value {
+                "x" | "X" => None,
+                degree => Some(parse_number(degree)?),
+            });
+        }
+        mapping.resize(size, None);
+
+        Ok(Self {
+            first_key,
+            last_key,
+            middle_key,
+            reference_key,
+            reference_frequency,
+            octave_degree,
+            mapping,
+        })
+    }
+}
+
+/// reads a tuning from a Scala scale file and an optional keyboard mapping file,
+/// using the default mapping if there is no keyboard mapping file
+pub fn load_tuning(scale: &Path, mapping: Option<&Path>) -> Result<TuningSystem, ScalaError> {
+    let scale = ScalaScale::load(scale)?;
+    let mapping = match mapping {
+        Some(path) => KeyboardMapping::load(path)?,
+        None => KeyboardMapping::default(),
+    };
+    Ok(TuningSystem::Custom(Arc::new(mapping.tuning_table(&scale)?)))
+}
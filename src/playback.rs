@@ -1,6 +1,6 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::mpsc::{self, Receiver, SyncSender}};
 
-use crate::{live_plugin_id::LivePluginId, pitch::equal_temperment, plugin_graph::{EffectGraph, PlaybackOrder}};
+use crate::{frame::{self, Frame}, live_plugin_id::{LivePluginId, LivePluginKind}, pitch::equal_temperment, plugin_graph::{EffectGraph, PlaybackOrder}};
 
 pub type NoteId = u32;
 pub type InputId = u32;
@@ -48,6 +48,152 @@ pub enum PlaybackCommand {
 
     /// disconnect a synth/drum directly from the output of an effects group
     DisconnectDirectInput{group: LivePluginId, src: LivePluginId},
+
+    /// send a note event to the synthesizer with the given id
+    Note{synth: LivePluginId, event: NoteEvent},
+
+    /// press or release a pedal of the synthesizer with the given id
+    Pedal{synth: LivePluginId, pedal: Pedal, pressed: bool},
+
+    /// hit, hold or release the drum with the given id
+    Drum{drum: LivePluginId, state: DrumState},
+
+    /// release every voice of every synthesizer immediately, such as to recover from stuck notes
+    AllNotesOff,
+
+    /// set a secondary input of a plugin to an already snapped value
+    SetInput{plugin: LivePluginId, input: InputId, value: f64},
+}
+
+/// The number of commands that may be queued before new commands are dropped
+pub const COMMAND_QUEUE_CAPACITY: usize = 1024;
+
+/// Creates the queue used to send commands to the playback thread
+/// Senders should use try_send so they never block when the queue is full.
+pub fn command_queue() -> (SyncSender<PlaybackCommand>, Receiver<PlaybackCommand>) {
+    mpsc::sync_channel(COMMAND_QUEUE_CAPACITY)
+}
+
+/// A change to a note played by a synthesizer
+/// Every source of notes (MIDI, the computer keyboard, sequencers) goes through this type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoteEvent {
+    On{id: NoteId, freq: f32, velocity: u8},
+    Off{id: NoteId, freq: f32},
+    Freq{id: NoteId, freq: f32},
+    Aftertouch{id: NoteId, aftertouch: f32},
+}
+
+impl NoteEvent {
+    /// sends the event to a synth
+    /// frequency and aftertouch changes are dropped if the synth does not allow them
+    pub fn apply(self, synth: &mut dyn LiveSynth) {
+        match self {
+            Self::On { id, freq, velocity } => synth.set_note_on(id, freq, velocity),
+            Self::Off { id, freq } => synth.set_note_off(id, freq),
+            Self::Freq { id, freq } => {
+                if synth.allow_frequency_change() {
+                    synth.set_note_freq(id, freq);
+                }
+            }
+            Self::Aftertouch { id, aftertouch } => {
+                if synth.allow_aftertouch() {
+                    synth.set_note_aftertouch(id, aftertouch);
+                }
+            }
+        }
+    }
+}
+
+/// A pedal holding notes after their keys are released
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pedal {
+    /// holds every note released while pressed
+    Sustain,
+
+    /// holds only the notes whose keys were down when it was pressed
+    Sostenuto,
+}
+
+/// Defers the release of notes held by pedals
+/// Synths only see note offs once neither their key nor a pedal holds them.
+#[derive(Debug, Clone, Default)]
+pub struct NotePedals {
+    sustain: bool,
+
+    /// the notes held by the sostenuto pedal, empty while it is up
+    sostenuto: Vec<NoteId>,
+
+    /// the notes whose keys are down
+    down: Vec<NoteId>,
+
+    /// the notes whose keys were released while held by a pedal
+    deferred: Vec<(NoteId, f32)>,
+}
+
+impl NotePedals {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn is_held(&self, id: NoteId) -> bool {
+        self.sustain || self.sostenuto.contains(&id)
+    }
+
+    /// sends an event with send, deferring note offs of notes held by a pedal
+    pub fn send(&mut self, event: NoteEvent, mut send: impl FnMut(NoteEvent)) {
+        match event {
+            NoteEvent::On { id, .. } => {
+                // a held note played again is released first so the synth can retrigger it
+                if let Some(index) = self.deferred.iter().position(|(deferred, _)| *deferred == id) {
+                    let (id, freq) = self.deferred.swap_remove(index);
+                    send(NoteEvent::Off { id, freq });
+                }
+                if !self.down.contains(&id) {
+                    self.down.push(id);
+                }
+            }
+            NoteEvent::Off { id, freq } => {
+                self.down.retain(|down| *down != id);
+                if self.is_held(id) {
+                    self.deferred.push((id, freq));
+                    return;
+                }
+            }
+            _ => {}
+        }
+        send(event);
+    }
+
+    /// forgets every key and pedal, such as after the synth released all of its voices
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// presses or releases a pedal, sending the note offs it was deferring once released
+    pub fn set_pedal(&mut self, pedal: Pedal, pressed: bool, mut send: impl FnMut(NoteEvent)) {
+        match (pedal, pressed) {
+            (Pedal::Sustain, _) => self.sustain = pressed,
+            (Pedal::Sostenuto, true) => {
+                // pressing again while down does not capture more notes
+                if self.sostenuto.is_empty() {
+                    self.sostenuto = self.down.clone();
+                }
+            }
+            (Pedal::Sostenuto, false) => self.sostenuto.clear(),
+        }
+
+        let mut index = 0;
+        while index < self.deferred.len() {
+            let (id, freq) = self.deferred[index];
+            if self.is_held(id) {
+                index += 1;
+            } else {
+                self.deferred.swap_remove(index);
+                send(NoteEvent::Off { id, freq });
+            }
+        }
+    }
 }
 
 pub struct ComponentFactory {
@@ -71,9 +217,99 @@ pub struct PlaybackState {
     /// the id of the main output
     main_output_id: LivePluginId,
 
+    /// the pedals of each synth that has received a note or pedal
+    pedals: HashMap<LivePluginId, NotePedals>,
+
     order: PlaybackOrder,
 }
 
+impl PlaybackState {
+    /// sends a note event to a synth
+    /// returns false if there is no synth with the given id
+    /// note offs are deferred while the synth's pedals hold the note
+    pub fn send_note(&mut self, synth: LivePluginId, event: NoteEvent) -> bool {
+        let Some(metadata) = self.synths.get(&synth) else {
+            return false;
+        };
+        self.pedals.entry(synth)
+            .or_default()
+            .send(event, |event| event.apply(unsafe { &mut *metadata.component }));
+        true
+    }
+
+    /// presses or releases a pedal of a synth
+    /// returns false if there is no synth with the given id
+    pub fn set_pedal(&mut self, synth: LivePluginId, pedal: Pedal, pressed: bool) -> bool {
+        let Some(metadata) = self.synths.get(&synth) else {
+            return false;
+        };
+        self.pedals.entry(synth)
+            .or_default()
+            .set_pedal(pedal, pressed, |event| event.apply(unsafe { &mut *metadata.component }));
+        true
+    }
+
+    /// hits, holds or releases a drum
+    /// returns false if there is no drum with the given id
+    pub fn set_drum_state(&mut self, drum: LivePluginId, state: DrumState) -> bool {
+        let Some(metadata) = self.drums.get(&drum) else {
+            return false;
+        };
+        unsafe { (*metadata.component).set_state(state) };
+        true
+    }
+
+    /// sets a secondary input of a synth, drum or effect
+    /// returns false if there is no plugin with the given id
+    pub fn set_input(&mut self, plugin: LivePluginId, input: InputId, value: f64) -> bool {
+        match plugin.kind() {
+            LivePluginKind::Synth => self.synths.get(&plugin)
+                .map(|metadata| LivePlugin::set_input(unsafe { &mut *metadata.component }, input, value))
+                .is_some(),
+            LivePluginKind::Drum => self.drums.get(&plugin)
+                .map(|metadata| unsafe { (*metadata.component).set_input(input, value) })
+                .is_some(),
+            LivePluginKind::Effect => self.effects.get(&plugin)
+                .map(|metadata| unsafe { (*metadata.component).set_input(input, value) })
+                .is_some(),
+            _ => false,
+        }
+    }
+
+    /// releases every voice of every synth and lifts their pedals
+    pub fn all_notes_off(&mut self) {
+        for metadata in self.synths.values() {
+            unsafe { (*metadata.component).all_notes_off() };
+        }
+        for pedals in self.pedals.values_mut() {
+            pedals.clear();
+        }
+    }
+
+    /// handles the commands waiting in the queue without blocking
+    pub fn process_commands(&mut self, commands: &Receiver<PlaybackCommand>) {
+        while let Ok(command) = commands.try_recv() {
+            match command {
+                PlaybackCommand::Note { synth, event } => {
+                    self.send_note(synth, event);
+                }
+                PlaybackCommand::Pedal { synth, pedal, pressed } => {
+                    self.set_pedal(synth, pedal, pressed);
+                }
+                PlaybackCommand::Drum { drum, state } => {
+                    self.set_drum_state(drum, state);
+                }
+                PlaybackCommand::SetInput { plugin, input, value } => {
+                    self.set_input(plugin, input, value);
+                }
+                PlaybackCommand::AllNotesOff => self.all_notes_off(),
+                // graph editing is not yet supported while playing
+                _ => {}
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ComponentMetadata<T> {
     /// a pointer to the data of the component
@@ -104,11 +340,14 @@ pub trait LivePlugin {
 }
 
 pub trait LiveEffect: LivePlugin {
-    fn update(&mut self, sample: f32, sample_rate: u32) -> f32;
+    fn update(&mut self, sample: Frame, sample_rate: u32) -> Frame;
 }
 
 pub trait LiveDrum: LivePlugin {
-    fn update(&mut self, sample_rate: u32) -> f32;
+    /// hits, holds or releases the drum
+    fn set_state(&mut self, state: DrumState);
+
+    fn update(&mut self, sample_rate: u32) -> Frame;
 }
 
 pub trait LiveSynth: LivePlugin {
@@ -133,6 +372,9 @@ pub trait LiveSynth: LivePlugin {
     /// if allow_aftertouch is false, this function will never be called
     fn set_note_aftertouch(&mut self, id: NoteId, aftertouch: f32);
 
+    /// silences every voice immediately, without waiting for their release
+    fn all_notes_off(&mut self);
+
     /// Set the input to the given value.
     /// We guarantee that only ids as specified in the get_inputs() function will be passed as
     /// arguments to this function.
@@ -141,7 +383,7 @@ pub trait LiveSynth: LivePlugin {
     /// produces a sample
     /// for each sample production cycle, it is guaranteed that this function
     /// is called last.
-    fn update(&mut self, sample_rate: u32) -> f32;
+    fn update(&mut self, sample_rate: u32) -> Frame;
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -270,10 +512,10 @@ pub struct LiveEffectContainer {
     automations: Vec<f32>,
 
     /// the sample to pass to the effect
-    sample: f32,
+    sample: Frame,
 
     /// the sample to pass to the effect on the next update
-    buffered_sample: f32,
+    buffered_sample: Frame,
 }
 
 impl LiveEffectContainer {
@@ -282,24 +524,28 @@ impl LiveEffectContainer {
         Self {
             effect,
             automations: vec![0.0; automation_count],
-            sample: 0.0,
-            buffered_sample: 0.0,
+            sample: frame::SILENCE,
+            buffered_sample: frame::SILENCE,
         }
     }
 
-    pub fn update(&mut self, sample_rate: u32) -> f32 {
+    pub fn update(&mut self, sample_rate: u32) -> Frame {
         let out = self.effect.update(self.sample, sample_rate);
         self.sample = self.buffered_sample;
-        self.buffered_sample = 0.0;
+        self.buffered_sample = frame::SILENCE;
         out
     }
 
-    pub fn send(&mut self, sample: f32) {
-        self.sample += sample;
+    pub fn send(&mut self, sample: Frame) {
+        self.sample = frame::add(self.sample, sample);
+    }
+
+    pub fn save(&mut self, sample: Frame) {
+        self.buffered_sample = frame::add(self.buffered_sample, sample);
     }
 
-    pub fn save(&mut self, sample: f32) {
-        self.buffered_sample += sample;
+    pub fn set_input(&mut self, id: InputId, value: f64) {
+        self.effect.set_input(id, value);
     }
 }
 
@@ -1,6 +1,6 @@
 use std::collections::{HashMap, HashSet, VecDeque};
 
-use crate::{circuit_id::{ConnectionId, GlobalPortId}, live_plugin_id::LivePluginId};
+use crate::{circuit_id::{ConnectionId, GlobalPortId}, live_plugin_id::LivePluginId, sequencers::{note::BeatUnits, piano_sequencer::{PatternRepeat, PatternSpan, PianoPattern}}};
 
 /// The representation of plugins during playback
 /// This structure is optimized specifically for playback and should only be used on audio
@@ -145,3 +145,213 @@ impl PlaybackTree {
     }
 
 }
+
+/// identifies a pattern stored in an arrangement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PatternId(u32);
+
+/// a pattern placed on a track of an arrangement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Clip {
+    /// the pattern played
+    pub pattern: PatternId,
+
+    /// the beat the first repetition of the pattern starts on
+    pub start: BeatUnits,
+
+    /// how many times the pattern plays back to back, used in place of the pattern's own repeat
+    pub repeat: PatternRepeat,
+
+    /// the change in pitch of every note in cents
+    pub transpose: i32,
+}
+
+impl Clip {
+    /// creates a clip playing the pattern once at the given beat
+    pub fn new(pattern: PatternId, start: BeatUnits) -> Self {
+        Self {
+            pattern,
+            start,
+            repeat: PatternRepeat::Times(1),
+            transpose: 0,
+        }
+    }
+}
+
+/// a lane of clips played on one synth
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub name: String,
+
+    /// the synth the notes of the track are played on
+    pub synth: Option<LivePluginId>,
+
+    /// nothing on a muted track is played
+    pub muted: bool,
+
+    /// the clips of the track, kept in order of increasing start time
+    clips: Vec<Clip>,
+}
+
+impl Track {
+    pub fn new(name: String, synth: Option<LivePluginId>) -> Self {
+        Self {
+            name,
+            synth,
+            muted: false,
+            clips: Vec::new(),
+        }
+    }
+
+    /// gets the clips of the track in order of start time
+    pub fn clips(&self) -> &[Clip] {
+        &self.clips
+    }
+
+    /// adds a clip to the track, returning its index
+    /// clips may overlap, in which case both are played
+    pub fn add_clip(&mut self, clip: Clip) -> usize {
+        let index = self.clips.partition_point(|other| other.start <= clip.start);
+        self.clips.insert(index, clip);
+        index
+    }
+
+    /// removes the clip with the given index
+    /// fails if the index is out of bounds
+    pub fn remove_clip(&mut self, index: usize) -> Option<Clip> {
+        if index < self.clips.len() {
+            Some(self.clips.remove(index))
+        } else {
+            None
+        }
+    }
+
+    /// gets a clip mutably within a closure, then moves it to keep clips in order of start time
+    /// returns the new index of the clip
+    /// fails if index is out of bounds
+    pub fn get_clip_mut(&mut self, index: usize, f: impl FnOnce(&mut Clip)) -> Option<usize> {
+        let mut clip = self.remove_clip(index)?;
+        f(&mut clip);
+        Some(self.add_clip(clip))
+    }
+}
+
+/// A song made of tracks, each playing clips of shared patterns at beat positions
+/// The playback engine asks the arrangement for the sections of patterns reached on each track
+/// as the transport moves, see track_spans.
+pub struct Arrangement {
+    patterns: HashMap<PatternId, PianoPattern>,
+
+    /// the id given to the next pattern added
+    next_pattern: u32,
+
+    tracks: Vec<Track>,
+}
+
+impl Arrangement {
+    pub fn new() -> Self {
+        Self {
+            patterns: HashMap::new(),
+            next_pattern: 0,
+            tracks: Vec::new(),
+        }
+    }
+
+    /// stores the pattern so that clips may play it, returning its id
+    pub fn add_pattern(&mut self, pattern: PianoPattern) -> PatternId {
+        let id = PatternId(self.next_pattern);
+        self.next_pattern += 1;
+        self.patterns.insert(id, pattern);
+        id
+    }
+
+    pub fn pattern(&self, id: PatternId) -> Option<&PianoPattern> {
+        self.patterns.get(&id)
+    }
+
+    pub fn pattern_mut(&mut self, id: PatternId) -> Option<&mut PianoPattern> {
+        self.patterns.get_mut(&id)
+    }
+
+    /// removes the pattern along with every clip playing it
+    pub fn remove_pattern(&mut self, id: PatternId) -> Option<PianoPattern> {
+        let pattern = self.patterns.remove(&id)?;
+        for track in &mut self.tracks {
+            track.clips.retain(|clip| clip.pattern != id);
+        }
+        Some(pattern)
+    }
+
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    pub fn track(&self, index: usize) -> Option<&Track> {
+        self.tracks.get(index)
+    }
+
+    pub fn track_mut(&mut self, index: usize) -> Option<&mut Track> {
+        self.tracks.get_mut(index)
+    }
+
+    /// adds a track after the others, returning its index
+    pub fn add_track(&mut self, track: Track) -> usize {
+        self.tracks.push(track);
+        self.tracks.len() - 1
+    }
+
+    /// removes the track with the given index
+    /// fails if the index is out of bounds
+    pub fn remove_track(&mut self, index: usize) -> Option<Track> {
+        if index < self.tracks.len() {
+            Some(self.tracks.remove(index))
+        } else {
+            None
+        }
+    }
+
+    /// gets the beat the clip stops playing on
+    /// fails if the clip's pattern does not exist or the clip loops forever
+    pub fn clip_end(&self, clip: &Clip) -> Option<BeatUnits> {
+        let length = self.patterns.get(&clip.pattern)?.repeated_length(clip.repeat)?;
+        Some(clip.start + length)
+    }
+
+    /// gets the beat the last clip stops playing on, or 0 if there are no clips
+    /// fails if any clip loops forever
+    pub fn length(&self) -> Option<BeatUnits> {
+        let mut length = BeatUnits(0);
+        for clip in self.tracks.iter().flat_map(|track| &track.clips) {
+            if !self.patterns.contains_key(&clip.pattern) {
+                continue;
+            }
+            length = length.max(self.clip_end(clip)?);
+        }
+        Some(length)
+    }
+
+    /// gets the sections of patterns reached on the track while the transport passes [start, end) in beats,
+    /// along with the pattern each belongs to
+    /// nothing is reached on a muted track or a track that does not exist
+    pub fn track_spans(&self, track: usize, start: f64, end: f64) -> impl Iterator<Item = (&PianoPattern, PatternSpan)> + '_ {
+        let clips = match self.tracks.get(track) {
+            Some(track) if !track.muted => track.clips.as_slice(),
+            _ => &[],
+        };
+
+        clips.iter()
+            .take_while(move |clip| clip.start.into_beats() < end)
+            .filter_map(|clip| Some((clip, self.patterns.get(&clip.pattern)?)))
+            .flat_map(move |(clip, pattern)| {
+                pattern.spans_from(clip.start.into_beats(), clip.repeat, start, end)
+                    .with_transpose(clip.transpose as f64)
+                    .map(move |span| (pattern, span))
+            })
+    }
+}
+
+impl Default for Arrangement {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::{HashMap, VecDeque};
 
-use crate::{live_plugin_id::{LivePluginId, LivePluginKind}, playback::{InputSpecification, LiveDrum, LiveEffect, LiveEffectContainer, LivePlugin, LiveSynth}};
+use crate::{frame::{self, Frame}, live_plugin_id::{LivePluginId, LivePluginKind}, playback::{InputSpecification, LiveDrum, LiveEffect, LiveEffectContainer, LivePlugin, LiveSynth}};
 
 pub struct EffectGraph {
     /// Contains all nodes without children
@@ -553,7 +553,7 @@ pub struct PlaybackOrder {
 
 impl PlaybackOrder {
     /// updates all components and gets the output
-    pub fn update(&self, sample_rate: u32) -> f32 {
+    pub fn update(&self, sample_rate: u32) -> Frame {
         // update drums
         for (drum, sends) in self.drums.iter().zip(self.drum_sends.iter()) {
             let sample = unsafe { (**drum).update(sample_rate) };
@@ -686,7 +686,7 @@ impl EffectGraphOrder {
     // updates effects and sends outputs to targets
     // returns the sample following updates
     // safety: you must ensure that all of the contained effects are valid
-    pub unsafe fn update(&self, sample_rate: u32) -> f32 {
+    pub unsafe fn update(&self, sample_rate: u32) -> Frame {
         for (effect, target) in self.effects.iter().zip(self.targets.iter()) {
             let sample = unsafe { (**effect).update(sample_rate) };
             for i in 0..target.start_save {
@@ -751,11 +751,11 @@ impl LivePlugin for EffectGroupOutput {
 }
 
 impl LiveEffect for EffectGroupOutput {
-    fn update(&mut self, sample: f32, _sample_rate: u32) -> f32 {
+    fn update(&mut self, sample: Frame, _sample_rate: u32) -> Frame {
         if self.muted {
-            0.0
+            frame::SILENCE
         } else {
-            sample * self.volume
+            frame::scale(sample, self.volume)
         }
     }
 
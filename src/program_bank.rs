@@ -0,0 +1,92 @@
+use std::{collections::BTreeMap, fmt::Display, fs, io, path::{Path, PathBuf}, str::FromStr};
+
+use thiserror::Error;
+
+use crate::patch_file::{PatchFile, PatchFileError};
+
+/// An error occurring while loading the patch of a program
+#[derive(Debug, Error)]
+pub enum ProgramBankError {
+    #[error("No patch is assigned to program {0}.")]
+    Unassigned(u8),
+
+    #[error("Could not read '{0}': {1}")]
+    Io(PathBuf, io::Error),
+
+    #[error("Could not load '{0}': {1}")]
+    PatchFile(PathBuf, PatchFileError),
+}
+
+/// A program number [0, 127] and the patch file it selects
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramEntry {
+    pub program: u8,
+    pub path: PathBuf,
+}
+
+/// Written as 'program path', as stored in the settings file
+impl Display for ProgramEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.program, self.path.display())
+    }
+}
+
+impl FromStr for ProgramEntry {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (program, path) = s.trim().split_once(' ').ok_or(())?;
+        let program = program.parse().ok().filter(|program| *program <= 127).ok_or(())?;
+        let path = path.trim();
+        if path.is_empty() {
+            return Err(());
+        }
+        Ok(Self { program, path: PathBuf::from(path) })
+    }
+}
+
+/// The saved patches selected by MIDI program changes
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ProgramBank {
+    programs: BTreeMap<u8, PathBuf>,
+}
+
+impl ProgramBank {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// assigns a patch file to a program [0, 127], replacing any previous assignment
+    pub fn set(&mut self, program: u8, path: PathBuf) {
+        self.programs.insert(program.min(127), path);
+    }
+
+    pub fn remove(&mut self, program: u8) -> Option<PathBuf> {
+        self.programs.remove(&program)
+    }
+
+    pub fn get(&self, program: u8) -> Option<&Path> {
+        self.programs.get(&program).map(PathBuf::as_path)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.programs.is_empty()
+    }
+
+    /// the assigned programs in increasing order
+    pub fn entries(&self) -> impl Iterator<Item = ProgramEntry> + '_ {
+        self.programs.iter().map(|(program, path)| ProgramEntry {
+            program: *program,
+            path: path.clone(),
+        })
+    }
+
+    /// reads the patch file assigned to a program
+    pub fn load(&self, program: u8) -> Result<PatchFile, ProgramBankError> {
+        let path = self.get(program).ok_or(ProgramBankError::Unassigned(program))?;
+        let text = fs::read_to_string(path)
+            .map_err(|err| ProgramBankError::Io(path.to_path_buf(), err))?;
+        text.parse()
+            .map_err(|err| ProgramBankError::PatchFile(path.to_path_buf(), err))
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{atomic::{AtomicU64, Ordering}, Arc},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH}
+};
+
+use directories::{ProjectDirs, UserDirs};
+use rtrb::{Consumer, Producer, RingBuffer};
+
+use crate::wav::WavWriter;
+
+/// The audio thread's end of a recording
+/// Samples are pushed into a ring buffer and written to disk by another thread,
+/// so the audio thread never blocks on the file system.
+#[derive(Debug)]
+pub struct RecordingTap {
+    producer: Producer<f32>,
+
+    /// the number of frames dropped because the ring buffer was full
+    dropped: Arc<AtomicU64>,
+}
+
+impl RecordingTap {
+    /// returns true if there is room for a frame of the given number of channels
+    /// frames that do not fit are counted as dropped
+    pub fn reserve(&self, channels: usize) -> bool {
+        let fits = self.producer.slots() >= channels;
+        if !fits {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        fits
+    }
+
+    /// pushes a single sample, which must have been reserved
+    pub fn push(&mut self, sample: f32) {
+        let _ = self.producer.push(sample);
+    }
+}
+
+/// The ui's end of a recording
+/// Dropping the RecordingTap ends the recording once the remaining samples are written.
+#[derive(Debug)]
+pub struct Recording {
+    path: PathBuf,
+    started: Instant,
+    dropped: Arc<AtomicU64>,
+    writer: JoinHandle<io::Result<()>>,
+}
+
+impl Recording {
+    /// the length of audio the ring buffer may hold before frames are dropped
+    pub const BUFFER_DURATION: Duration = Duration::from_secs(2);
+
+    /// how long the writer thread waits when there are no samples to write
+    const WRITE_INTERVAL: Duration = Duration::from_millis(10);
+
+    /// creates the file and starts the thread that writes to it
+    pub fn start(path: &Path, channels: u16, sample_rate: u32) -> io::Result<(RecordingTap, Self)> {
+        let writer = WavWriter::create(path, channels, sample_rate)?;
+        let capacity = (Self::BUFFER_DURATION.as_secs_f64() * sample_rate as f64) as usize
+            * channels as usize;
+        let (producer, consumer) = RingBuffer::new(capacity);
+        let dropped = Arc::new(AtomicU64::new(0));
+
+        let writer = thread::Builder::new()
+            .name("recording writer".to_string())
+            .spawn(move || Self::write(writer, consumer))?;
+
+        let tap = RecordingTap {
+            producer,
+            dropped: dropped.clone(),
+        };
+        let recording = Self {
+            path: path.to_path_buf(),
+            started: Instant::now(),
+            dropped,
+            writer,
+        };
+        Ok((tap, recording))
+    }
+
+    /// writes samples until the tap is dropped and the buffer is empty
+    /// the header is finished even if writing fails, so the samples already written stay playable
+    fn write(mut writer: WavWriter, consumer: Consumer<f32>) -> io::Result<()> {
+        let result = Self::write_samples(&mut writer, consumer);
+        writer.finish()?;
+        result
+    }
+
+    fn write_samples(writer: &mut WavWriter, mut consumer: Consumer<f32>) -> io::Result<()> {
+        loop {
+            let available = consumer.slots();
+            if available > 0 {
+                let chunk = consumer.read_chunk(available)
+                    .expect("the available slots should be readable");
+                let (first, second) = chunk.as_slices();
+                writer.write_samples(first)?;
+                writer.write_samples(second)?;
+                chunk.commit_all();
+            } else if consumer.is_abandoned() {
+                // the tap may have pushed samples just before being dropped
+                if consumer.is_empty() {
+                    break;
+                }
+            } else {
+                thread::sleep(Self::WRITE_INTERVAL);
+            }
+        }
+        Ok(())
+    }
+
+    /// a path for a new recording in the user's audio directory
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = UserDirs::new()
+            .and_then(|dirs| dirs.audio_dir().map(Path::to_path_buf))
+            .or_else(|| ProjectDirs::from("", "", "Starship").map(|dirs| dirs.data_dir().to_path_buf()))?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        Some(dir.join(format!("starship-recording-{}.wav", timestamp)))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// the time since the recording started
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// the number of frames dropped because the writer fell behind
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// waits for the remaining samples to be written
+    /// the tap must have been dropped, otherwise this blocks forever
+    pub fn finish(self) -> io::Result<PathBuf> {
+        self.writer.join()
+            .map_err(|_| io::Error::other("the recording writer panicked"))??;
+        Ok(self.path)
+    }
+}
@@ -0,0 +1,158 @@
+use std::{fs, io, path::{Path, PathBuf}};
+
+use thiserror::Error;
+
+use crate::{circuit::CircuitBuilderSpecification, frame, patch_file::{PatchFile, PatchFileError}, pitch::{scala::ScalaError, TuningSettings}, wav::WavWriter};
+
+/// An error occurring while reading or executing a render spec
+#[derive(Debug, Error)]
+pub enum RenderError {
+    #[error("Missing required key '{0}'.")]
+    MissingKey(&'static str),
+
+    #[error("Invalid value for '{0}' on line {1}.")]
+    InvalidValue(&'static str, usize),
+
+    #[error("Unknown key '{0}' on line {1}.")]
+    UnknownKey(String, usize),
+
+    #[error("The patch has no outputs to render.")]
+    NoOutputs,
+
+    #[error(transparent)]
+    Patch(#[from] PatchFileError),
+
+    #[error("Unable to load the tuning: {0}")]
+    Tuning(#[from] ScalaError),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Describes an offline render of a patch
+/// Written as 'key = value' lines:
+///     patch = path to the patch file
+///     duration = length of the render in seconds
+///     sample_rate = samples per second (optional, defaults to 44100)
+///     output = path of the WAV file to write
+///     reference_a4 = frequency of A4 in Hz (optional, defaults to 440)
+///     scale = path to a Scala scale file to tune to (optional, defaults to equal temperment)
+///     mapping = path to a Scala keyboard mapping file for the scale (optional)
+/// Relative paths are resolved against the directory of the spec file.
+/// Every output of the patch becomes a stereo pair of channels in the WAV file.
+/// Inputs are held at zero.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderSpec {
+    pub patch: PathBuf,
+    pub duration: f64,
+    pub sample_rate: u32,
+    pub output: PathBuf,
+    pub tuning: TuningSettings,
+}
+
+impl RenderSpec {
+    pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+    /// reads a spec from text, resolving relative paths against base_dir
+    pub fn parse(text: &str, base_dir: &Path) -> Result<Self, RenderError> {
+        let mut patch = None;
+        let mut duration = None;
+        let mut sample_rate = Self::DEFAULT_SAMPLE_RATE;
+        let mut output = None;
+        let mut tuning = TuningSettings::default();
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line.split_once('=')
+                .ok_or_else(|| RenderError::UnknownKey(line.to_string(), line_number))?;
+            let value = value.trim();
+
+            match key.trim() {
+                "patch" => patch = Some(base_dir.join(value)),
+                "output" => output = Some(base_dir.join(value)),
+                "scale" => tuning.scale = Some(base_dir.join(value)),
+                "mapping" => tuning.mapping = Some(base_dir.join(value)),
+                "reference_a4" => {
+                    tuning.reference_a4 = value.parse::<f64>()
+                        .ok()
+                        .filter(|a4| (TuningSettings::MIN_REFERENCE_A4..=TuningSettings::MAX_REFERENCE_A4).contains(a4))
+                        .ok_or(RenderError::InvalidValue("reference_a4", line_number))?;
+                }
+                "duration" => {
+                    duration = Some(value.parse::<f64>()
+                        .ok()
+                        .filter(|duration| duration.is_finite() && *duration > 0.0)
+                        .ok_or(RenderError::InvalidValue("duration", line_number))?
+                    );
+                }
+                "sample_rate" => {
+                    sample_rate = value.parse::<u32>()
+                        .ok()
+                        .filter(|rate| *rate > 0)
+                        .ok_or(RenderError::InvalidValue("sample_rate", line_number))?;
+                }
+                other => return Err(RenderError::UnknownKey(other.to_string(), line_number)),
+            }
+        }
+
+        Ok(Self {
+            patch: patch.ok_or(RenderError::MissingKey("patch"))?,
+            duration: duration.ok_or(RenderError::MissingKey("duration"))?,
+            sample_rate,
+            output: output.ok_or(RenderError::MissingKey("output"))?,
+            tuning,
+        })
+    }
+
+    /// reads the spec file at the given path
+    pub fn load(path: &Path) -> Result<Self, RenderError> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text, path.parent().unwrap_or(Path::new("")))
+    }
+
+    /// the number of frames the render will contain
+    pub fn frame_count(&self) -> u64 {
+        (self.duration * self.sample_rate as f64).round() as u64
+    }
+
+    /// compiles the patch and writes the rendered audio to the output path
+    pub fn render(&self, builders: &[CircuitBuilderSpecification]) -> Result<(), RenderError> {
+        let file: PatchFile = fs::read_to_string(&self.patch)?.parse()?;
+        if file.outputs.is_empty() {
+            return Err(RenderError::NoOutputs);
+        }
+
+        let tuning = self.tuning.tuning_system()?;
+        // circuit uis are only shown while playing live
+        let (mut patch, _) = file.instantiate(builders)?
+            .compile(self.sample_rate, crate::constants::SAMPLE_MULTIPLIER, &tuning);
+
+        let delta = (1.0 / self.sample_rate as f64) as f32;
+        let multiplier = patch.sample_multiplier();
+        let inputs = vec![frame::SILENCE; patch.input_count];
+        let mut outputs = vec![frame::SILENCE; patch.output_count];
+
+        let channels = (file.outputs.len() * frame::CHANNELS) as u16;
+        let mut writer = WavWriter::create(&self.output, channels, self.sample_rate)?;
+        for _ in 0..self.frame_count() {
+            patch.update(&inputs, &mut outputs, delta);
+            for output in outputs.iter_mut() {
+                *output = frame::scale(*output, multiplier);
+            }
+            writer.write_samples(outputs.as_flattened())?;
+        }
+        writer.finish()?;
+
+        Ok(())
+    }
+}
+
+/// Renders the patch described by the spec file at the given path
+pub fn execute(spec_path: &Path, builders: &[CircuitBuilderSpecification]) -> Result<(), RenderError> {
+    RenderSpec::load(spec_path)?.render(builders)
+}
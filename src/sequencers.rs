@@ -13,5 +13,50 @@ pub mod track_sequencer;
 /// transition curves for non-note inputs
 pub mod curve;
 
+/// undo and redo for curve edits
+pub mod curve_history;
+
+/// undo and redo for piano pattern edits
+pub mod pattern_history;
+
+/// selecting notes of piano patterns to edit them together, and naming groups of notes
+pub mod note_selection;
+
+/// curves sampled into tables for constant time playback
+pub mod baked_curve;
+
+/// recording parameter movements into curves
+pub mod curve_recorder;
+
+/// an editor for curves, embedded in envelopes and automation lanes
+pub mod curve_widget;
+
+/// a step grid editor for drum patterns
+pub mod drum_grid_widget;
+
+/// an editor for the notes of piano patterns, down to quarter tones and cents
+pub mod piano_roll_widget;
+
 /// curves for note inputs
 pub mod note;
+
+/// building chords from a root and naming the chords formed by notes
+pub mod chord;
+
+/// playing the chords of piano patterns one note at a time
+pub mod arpeggiator;
+
+/// playback position and tempo, driven by an internal or external clock
+pub mod transport;
+
+/// tempo changes and ramps over a song
+pub mod tempo_map;
+
+/// plays piano patterns on external synths over MIDI
+pub mod midi_output;
+
+/// plays piano patterns on live synths
+pub mod pattern_player;
+
+/// plays piano patterns ahead of the audio thread, stamping their events with the sample they are sent on
+pub mod event_scheduler;
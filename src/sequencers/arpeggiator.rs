@@ -0,0 +1,145 @@
+use crate::sequencers::{chord::stack_intervals, note::{BeatUnits, Note}, piano_sequencer::{NoteHandle, OwnedNote, PianoPattern}};
+
+/// The order an arpeggiator steps through the held notes in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArpeggioOrder {
+    /// lowest to highest, then starting over
+    Up,
+
+    /// highest to lowest, then starting over
+    Down,
+
+    /// lowest to highest and back, without repeating the ends
+    UpDown,
+
+    /// any held note on each step, the same each time for the same seed
+    Random,
+}
+
+/// Plays the notes held at once in a pattern one at a time, stepping at a fixed rate
+/// Steps fall on multiples of the rate, and the order starts over whenever the held notes change.
+/// Each step plays the starting pitch and velocity of a held note, so glides within held notes are not followed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Arpeggiator {
+    pub order: ArpeggioOrder,
+
+    /// the time between steps, at least MIN_RATE
+    rate: BeatUnits,
+
+    /// the number of octaves the held notes are repeated over, within 1 and MAX_OCTAVES
+    octaves: u8,
+
+    /// the seed of the random order
+    pub seed: u64,
+}
+
+impl Arpeggiator {
+    /// a 64th note
+    pub const MIN_RATE: BeatUnits = BeatUnits::note_value(64);
+    pub const MAX_OCTAVES: u8 = 4;
+
+    pub fn new(order: ArpeggioOrder, rate: BeatUnits) -> Self {
+        Self {
+            order,
+            rate: rate.max(Self::MIN_RATE),
+            octaves: 1,
+            seed: 0,
+        }
+    }
+
+    pub fn rate(&self) -> BeatUnits {
+        self.rate
+    }
+
+    /// sets the time between steps
+    /// does not let rate go below MIN_RATE
+    pub fn set_rate(&mut self, rate: BeatUnits) {
+        self.rate = rate.max(Self::MIN_RATE);
+    }
+
+    pub fn octaves(&self) -> u8 {
+        self.octaves
+    }
+
+    /// sets the number of octaves the held notes are repeated over
+    /// will be clamped to 1, MAX_OCTAVES
+    pub fn set_octaves(&mut self, octaves: u8) {
+        self.octaves = octaves.clamp(1, Self::MAX_OCTAVES);
+    }
+
+    /// gets the index into the sequence of held notes played on the given step since they changed
+    fn position(&self, step: usize, len: usize, random: &mut fastrand::Rng) -> usize {
+        match self.order {
+            ArpeggioOrder::Up => step % len,
+            ArpeggioOrder::Down => len - 1 - step % len,
+            ArpeggioOrder::UpDown => {
+                if len == 1 {
+                    return 0;
+                }
+                let period = 2 * (len - 1);
+                let index = step % period;
+                if index < len { index } else { period - index }
+            }
+            ArpeggioOrder::Random => random.usize(..len),
+        }
+    }
+
+    /// creates the pattern of arpeggiated notes played in place of the given pattern's notes
+    /// the created pattern has the same length and repeat, and steps after the length are not played
+    pub fn apply(&self, pattern: &PianoPattern) -> PianoPattern {
+        let mut output = PianoPattern::new();
+        output.set_length(pattern.length());
+        output.set_repeat(pattern.repeat());
+
+        let notes = pattern.query_range_inplace(0.0, f64::INFINITY);
+        let bounds = notes.iter()
+            .filter_map(|handle| handle.note(|note| note.map(|note| (note.start_time(), note.end_time()))))
+            .reduce(|(start, end), (other_start, other_end)| (start.min(other_start), end.max(other_end)));
+        let Some((first, last)) = bounds else {
+            return output;
+        };
+
+        let rate = self.rate.0 as i64;
+        let end = last.min(pattern.length());
+        let mut time = BeatUnits(((first.0 as i64 + rate - 1).div_euclid(rate) * rate) as i32);
+
+        let mut random = fastrand::Rng::with_seed(self.seed);
+        let mut previous: Vec<NoteHandle> = Vec::new();
+        let mut step = 0;
+        let mut held = Vec::new();
+        while time < end {
+            // the notes sounding at this step, from lowest to highest
+            held.clear();
+            pattern.query_time(&mut held, time.into_beats());
+            held.retain(|handle| handle.note(|note| note.is_some_and(|note| note.start_time() <= time && time < note.end_time())));
+            held.sort_by_key(|handle| handle.note(|note| note.map(|note| note.get_partial(0).unwrap().pitch.cent_delta_a4())));
+
+            if held.len() != previous.len() || held.iter().zip(&previous).any(|(a, b)| !a.ptr_eq(b)) {
+                step = 0;
+                previous.clone_from(&held);
+            }
+
+            if !held.is_empty() {
+                let position = self.position(step, held.len() * self.octaves as usize, &mut random);
+                let octave = (position / held.len()) as u32;
+                let played = held[position % held.len()].note(|note| {
+                    let note = note.unwrap();
+                    let pitch = note.get_partial(0).unwrap().pitch;
+                    (pitch, note.velocity(), note.end_time())
+                });
+                let (pitch, velocity, note_end) = played;
+
+                if let Some(pitch) = stack_intervals(pitch, &[octave * 12]).and_then(|pitches| pitches.first().copied()) {
+                    let duration = self.rate.min(note_end - time).min(end - time);
+                    let mut note = Note::new(pitch, time, duration);
+                    note.set_velocity(velocity);
+                    output.insert(OwnedNote::new(note));
+                }
+                step += 1;
+            }
+
+            time += self.rate;
+        }
+        output
+    }
+}
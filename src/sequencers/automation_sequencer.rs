@@ -1,4 +1,4 @@
-use crate::{live_plugin_id::LivePluginId, playback::{InputId, InputSpecification}, sequencers::curve::{Curve, CurvePointId, CurveSegmentId, CurveShape}};
+use crate::{live_plugin_id::LivePluginId, playback::{InputId, InputSpecification}, sequencers::curve::{Curve, CurvePointId, CurveRange, CurveSegmentId, CurveShape}};
 
 #[derive(Debug)]
 pub enum AutomationId {
@@ -9,6 +9,7 @@ pub enum AutomationId {
 #[derive(Debug)]
 pub struct AutomationSequencer {
     /// the curve representing the automation
+    /// its range is that of the input, so its values are edited in the input's units
     /// Invariants:
     /// 	1) total_duration == 1
     curve: Curve,
@@ -18,12 +19,25 @@ pub struct AutomationSequencer {
 }
 
 impl AutomationSequencer {
-    /// returns the value at the given time
+    /// creates an automation holding the input's default value
+    pub fn new(id: AutomationId, spec: InputSpecification, duration: f64) -> Self {
+        let range = CurveRange::for_input(&spec);
+        let mut curve = Curve::new(range.from_output(spec.default), 1.0);
+        curve.set_range(range);
+        Self {
+            curve,
+            spec,
+            id,
+            duration
+        }
+    }
+
+    /// returns the value of the input at the given time
     /// NOTE: if time is ZERO OR LESS, it will return the first value in the curve
     /// if time is greater than what the curve covers, it will return the last value in the curve
     /// O(log n)
     pub fn value_at_time(&self, time: f64) -> f64 {
-        self.curve.value_at_time(time / self.duration)
+        self.curve.output_at_time(time / self.duration)
     }
 
     /// returns the total duration of the automation
@@ -92,9 +106,15 @@ impl AutomationSequencer {
         }
     }
 
-    /// sets the value of the given point, clamped to the input's range
+    /// sets the value of the given point in the input's units, clamped to the input's range
     pub fn set_point_value(&mut self, point: CurvePointId, value: f64) {
-        self.curve.set_point_value(point, value.clamp(self.spec.range.0, self.spec.range.1));
+        let value = value.clamp(self.spec.range.0, self.spec.range.1);
+        self.curve.set_point_value(point, self.curve.range().from_output(value));
+    }
+
+    /// shows the value of the given point in the input's units
+    pub fn format_point_value(&self, point: CurvePointId) -> String {
+        self.curve.range().format(self.curve.get_point_value(point))
     }
 
     /// moves the time of the given point
@@ -0,0 +1,78 @@
+use super::curve::Curve;
+
+/// A curve sampled into a flat table, so that it may be read in constant time, such as by the audio thread
+/// Values between entries are linearly interpolated, so anything shorter than an entry (such as a discontinuity)
+/// is smoothed over one entry.
+#[derive(Debug, Clone)]
+pub struct BakedCurve {
+    /// the value of the curve at evenly spaced times, the first at 0 and the last at the end of the curve
+    table: Vec<f64>,
+
+    /// the number of entries per unit of time
+    scale: f64,
+
+    /// the revision of the curve the table was sampled from
+    revision: u64,
+}
+
+impl BakedCurve {
+    /// the most entries a table may hold, past which the resolution is lowered
+    pub const MAX_ENTRIES: usize = 1 << 22;
+
+    /// samples the curve with the given number of entries per unit of time
+    /// resolution must be positive, though at least the start and end of the curve are always sampled
+    pub fn new(curve: &Curve, resolution: f64) -> Self {
+        debug_assert!(resolution > 0.0 && resolution.is_finite(), "resolution must be positive");
+        let duration = curve.total_duration();
+
+        // the step is shortened slightly so that the last entry lands exactly on the end of the curve
+        let intervals = ((duration * resolution).ceil() as usize).clamp(1, Self::MAX_ENTRIES - 1);
+        let scale = intervals as f64 / duration;
+        let table = (0..=intervals)
+            .map(|index| curve.value_at_time(index as f64 / scale))
+            .collect();
+
+        Self {
+            table,
+            scale,
+            revision: curve.revision(),
+        }
+    }
+
+    /// returns the value at the given time
+    /// like the curve, the first value is held before 0 and the last value is held after the end
+    /// O(1)
+    pub fn value_at(&self, time: f64) -> f64 {
+        let position = time * self.scale;
+        if position <= 0.0 || position.is_nan() {
+            return self.table[0];
+        }
+
+        let index = position as usize;
+        if index >= self.table.len() - 1 {
+            return *self.table.last().unwrap();
+        }
+        let fraction = position - index as f64;
+        self.table[index] + (self.table[index + 1] - self.table[index]) * fraction
+    }
+
+    /// the duration of the curve the table was sampled from
+    pub fn total_duration(&self) -> f64 {
+        (self.table.len() - 1) as f64 / self.scale
+    }
+
+    /// returns true if the table was sampled from the curve as it is now
+    pub fn is_current(&self, curve: &Curve) -> bool {
+        self.revision == curve.revision()
+    }
+
+    /// samples the curve again with the same resolution if it was edited since the table was sampled
+    /// returns true if the table was sampled again
+    pub fn refresh(&mut self, curve: &Curve) -> bool {
+        if self.is_current(curve) {
+            return false;
+        }
+        *self = Self::new(curve, self.scale);
+        true
+    }
+}
@@ -0,0 +1,186 @@
+use std::fmt::Display;
+
+use crate::{pitch::{DetunedPitch, Pitch, Tone}, sequencers::piano_sequencer::NoteHandle};
+
+/// The kind of a chord, given by the intervals of its notes above the root
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChordQuality {
+    Power,
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    Sus2,
+    Sus4,
+    Major6,
+    Minor6,
+    Dominant7,
+    Major7,
+    Minor7,
+    MinorMajor7,
+    HalfDiminished7,
+    Diminished7,
+    Add9,
+    Dominant9,
+}
+
+impl ChordQuality {
+    /// every quality, in the order they are preferred when detecting chords
+    pub const ALL: [Self; 17] = [
+        Self::Major, Self::Minor, Self::Dominant7, Self::Major7, Self::Minor7,
+        Self::Diminished, Self::Augmented, Self::Sus4, Self::Sus2, Self::HalfDiminished7,
+        Self::Diminished7, Self::MinorMajor7, Self::Major6, Self::Minor6, Self::Add9,
+        Self::Dominant9, Self::Power,
+    ];
+
+    /// the semitones above the root of each note, starting with the root
+    pub fn intervals(&self) -> &'static [u32] {
+        match self {
+            Self::Power => &[0, 7],
+            Self::Major => &[0, 4, 7],
+            Self::Minor => &[0, 3, 7],
+            Self::Diminished => &[0, 3, 6],
+            Self::Augmented => &[0, 4, 8],
+            Self::Sus2 => &[0, 2, 7],
+            Self::Sus4 => &[0, 5, 7],
+            Self::Major6 => &[0, 4, 7, 9],
+            Self::Minor6 => &[0, 3, 7, 9],
+            Self::Dominant7 => &[0, 4, 7, 10],
+            Self::Major7 => &[0, 4, 7, 11],
+            Self::Minor7 => &[0, 3, 7, 10],
+            Self::MinorMajor7 => &[0, 3, 7, 11],
+            Self::HalfDiminished7 => &[0, 3, 6, 10],
+            Self::Diminished7 => &[0, 3, 6, 9],
+            Self::Add9 => &[0, 4, 7, 14],
+            Self::Dominant9 => &[0, 4, 7, 10, 14],
+        }
+    }
+
+    /// the symbol written after the root when naming a chord, such as "m7"
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::Power => "5",
+            Self::Major => "",
+            Self::Minor => "m",
+            Self::Diminished => "dim",
+            Self::Augmented => "aug",
+            Self::Sus2 => "sus2",
+            Self::Sus4 => "sus4",
+            Self::Major6 => "6",
+            Self::Minor6 => "m6",
+            Self::Dominant7 => "7",
+            Self::Major7 => "maj7",
+            Self::Minor7 => "m7",
+            Self::MinorMajor7 => "mMaj7",
+            Self::HalfDiminished7 => "m7b5",
+            Self::Diminished7 => "dim7",
+            Self::Add9 => "add9",
+            Self::Dominant9 => "9",
+        }
+    }
+
+    /// the set of semitones above the root within one octave, as bits
+    fn pitch_classes(&self) -> u16 {
+        self.intervals().iter().fold(0, |set, interval| set | 1 << (interval % 12))
+    }
+}
+
+/// A chord named by its root and quality
+/// When detected from notes, the bass is the lowest note, which is not the root for inverted chords.
+#[derive(Debug, Clone, Copy)]
+pub struct Chord {
+    pub root: DetunedPitch,
+    pub quality: ChordQuality,
+
+    /// the lowest note of the chord
+    pub bass: DetunedPitch,
+}
+
+impl Display for Chord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let root = self.root.base_pitch;
+        write!(f, "{}{}{}", root.tone, root.accidental, self.quality.symbol())?;
+
+        let bass = self.bass.base_pitch;
+        if pitch_class(&self.bass) != pitch_class(&self.root) {
+            write!(f, "/{}{}", bass.tone, bass.accidental)?;
+        }
+        Ok(())
+    }
+}
+
+/// the semitone [0, 12) above C nearest to the pitch
+fn pitch_class(pitch: &DetunedPitch) -> u32 {
+    let cents_c4 = pitch.cent_delta_a4() + Tone::A.cent_delta() as i32;
+    let semitones_c4 = (cents_c4 as f64 / Pitch::CENTS_PER_SEMITONE as f64).round() as i32;
+    semitones_c4.rem_euclid(Pitch::SEMITONES_PER_OCTAVE as i32) as u32
+}
+
+impl Chord {
+    /// creates a chord in root position
+    pub fn new(root: DetunedPitch, quality: ChordQuality) -> Self {
+        Self {
+            root,
+            quality,
+            bass: root,
+        }
+    }
+
+    /// gets the pitches of the chord in root position, from the root up
+    /// fails if any pitch would be out of the representable range
+    pub fn pitches(&self) -> Option<Vec<DetunedPitch>> {
+        stack_intervals(self.root, self.quality.intervals())
+    }
+
+    /// names the chord formed by the given pitches, in any order, voicing or octave
+    /// pitches are rounded to the nearest semitone, and a root matching the lowest pitch is preferred
+    /// fails if the pitches do not form a known chord
+    pub fn detect(pitches: &[DetunedPitch]) -> Option<Self> {
+        let bass = *pitches.iter().min_by_key(|pitch| pitch.cent_delta_a4())?;
+        let classes = pitches.iter().fold(0u16, |set, pitch| set | 1 << pitch_class(pitch));
+
+        // try the bass first, then the other pitches from lowest to highest
+        let mut roots: Vec<&DetunedPitch> = pitches.iter().collect();
+        roots.sort_by_key(|pitch| pitch.cent_delta_a4());
+
+        for root in roots {
+            let root_class = pitch_class(root);
+            let relative = (0..12)
+                .filter(|class| classes & 1 << class != 0)
+                .fold(0u16, |set, class| set | 1 << ((class + 12 - root_class) % 12));
+
+            if let Some(quality) = ChordQuality::ALL.iter().find(|quality| quality.pitch_classes() == relative) {
+                return Some(Self {
+                    root: *root,
+                    quality: *quality,
+                    bass,
+                });
+            }
+        }
+        None
+    }
+
+    /// names the chord formed by the notes, using the pitch each note starts on
+    /// notes that no longer exist are ignored
+    pub fn detect_notes(notes: &[NoteHandle]) -> Option<Self> {
+        let pitches: Vec<DetunedPitch> = notes.iter()
+            .filter_map(|handle| handle.note(|note| note.and_then(|note| note.get_partial(0).map(|partial| partial.pitch))))
+            .collect();
+        Self::detect(&pitches)
+    }
+}
+
+/// gets the pitches the given numbers of semitones above the root, keeping the root's detune and any quarter tone
+/// fails if any pitch would be out of the representable range
+pub fn stack_intervals(root: DetunedPitch, intervals: &[u32]) -> Option<Vec<DetunedPitch>> {
+    let cents_per_semitone = Pitch::CENTS_PER_SEMITONE as i32;
+    intervals.iter()
+        .map(|interval| {
+            let cents = root.cent_delta_a4() + *interval as i32 * cents_per_semitone;
+            Some(DetunedPitch {
+                base_pitch: Pitch::from_semitone_delta_a4(cents.div_euclid(cents_per_semitone))?,
+                detune: cents.rem_euclid(cents_per_semitone) as i8,
+            })
+        })
+        .collect()
+}
@@ -1,6 +1,11 @@
-use std::{cmp::Ordering, f64, fmt::Display};
+use std::{cmp::Ordering, f64, fmt::Display, sync::atomic::{AtomicU64, Ordering as AtomicOrdering}};
 
 use egui::Pos2;
+use thiserror::Error;
+
+use crate::{pitch::Pitch, playback::InputSpecification, utils::ByteReader};
+
+use super::baked_curve::BakedCurve;
 
 /// the identifier for a segment in a curve unique within the curve that produced it
 /// may become invalid after mutating the producing curve
@@ -156,10 +161,29 @@ impl SmoothingDirection {
         }
     }
 
+    /// the tag used to identify the direction when serialized
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::In => 0,
+            Self::Out => 1,
+            Self::InOut => 2,
+        }
+    }
+
+    /// gets the direction from a tag created by to_byte
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::In),
+            1 => Some(Self::Out),
+            2 => Some(Self::InOut),
+            _ => None
+        }
+    }
+
 } 
 
 /// the shape of an easing function
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SmoothingShape {
     /// Linear
     Linear,
@@ -176,9 +200,28 @@ pub enum SmoothingShape {
     /// Quartic bezier
     Quartic,
 
-    // TODO: write function to get the range of this shape
-    // /// Overshoot before going to target
-    // Back(f32),
+    /// Overshoot before going to target
+    /// Holds the amount of overshoot, where 0 is a cubic without overshoot; negative amounts are treated as 0.
+    Back(f32),
+
+    /// Oscillates around the target as if on a spring
+    Elastic,
+
+    /// Bounces off of the target as if dropped onto it
+    Bounce,
+
+    /// Steps between values without passing through those between them, such as for switching programs
+    /// Eased in, the start value is held until the end point; eased out, the end value is jumped to immediately.
+    /// Eased in and out, the step happens halfway through the segment.
+    Hold,
+
+    /// Cubic bezier with user placed control points
+    /// The points are relative to the segment, where (0, 0) is its start and (1, 1) is its end.
+    /// Their x coordinates are kept within [0, 1] so that the shape never goes back in time.
+    CustomBezier {
+        p1: Pos2,
+        p2: Pos2,
+    },
 }
 
 impl Display for SmoothingShape {
@@ -196,6 +239,11 @@ impl SmoothingShape {
             Self::Circular => "Circular",
             Self::Cubic => "Cubic",
             Self::Quartic => "Quartic",
+            Self::Back(_) => "Back",
+            Self::Elastic => "Elastic",
+            Self::Bounce => "Bounce",
+            Self::Hold => "Hold",
+            Self::CustomBezier { .. } => "Custom",
         }
     }
 
@@ -207,6 +255,11 @@ impl SmoothingShape {
             Self::Circular => "Cir",
             Self::Cubic => "Cub",
             Self::Quartic => "Qrt",
+            Self::Back(_) => "Bck",
+            Self::Elastic => "Els",
+            Self::Bounce => "Bnc",
+            Self::Hold => "Hld",
+            Self::CustomBezier { .. } => "Cst",
         }
     }
 
@@ -218,30 +271,228 @@ impl SmoothingShape {
             Self::Circular => "Circ",
             Self::Cubic => "Cubc",
             Self::Quartic => "Qtic",
+            Self::Back(_) => "Back",
+            Self::Elastic => "Elas",
+            Self::Bounce => "Bnce",
+            Self::Hold => "Hold",
+            Self::CustomBezier { .. } => "Cust",
         }
     }
 
     /// provides a global method of cycling through shapes
     /// reverse direction
+    /// cycling onto the back or custom bezier shapes gives them their default overshoot or control points
     pub fn prev(&self) -> Self {
         match self {
-            Self::Linear => Self::Quartic,
+            Self::Linear => Self::DEFAULT_BEZIER,
             Self::Sine => Self::Linear,
             Self::Circular => Self::Sine,
             Self::Cubic => Self::Circular,
             Self::Quartic => Self::Cubic,
+            Self::Back(_) => Self::Quartic,
+            Self::Elastic => Self::DEFAULT_BACK,
+            Self::Bounce => Self::Elastic,
+            Self::Hold => Self::Bounce,
+            Self::CustomBezier { .. } => Self::Hold,
         }
     }
 
     /// provides a global method of cycling through shapes
     /// forward direction
+    /// cycling onto the back or custom bezier shapes gives them their default overshoot or control points
     pub fn next(&self) -> Self {
         match self {
             Self::Linear => Self::Sine,
             Self::Sine => Self::Circular,
             Self::Circular => Self::Cubic,
             Self::Cubic => Self::Quartic,
-            Self::Quartic => Self::Linear,
+            Self::Quartic => Self::DEFAULT_BACK,
+            Self::Back(_) => Self::Elastic,
+            Self::Elastic => Self::Bounce,
+            Self::Bounce => Self::Hold,
+            Self::Hold => Self::DEFAULT_BEZIER,
+            Self::CustomBezier { .. } => Self::Linear,
+        }
+    }
+
+    /// the back shape a shape starts as when first chosen, overshooting by about 10%
+    pub const DEFAULT_BACK: Self = Self::Back(1.70158);
+
+    /// the custom bezier a shape starts as when first chosen
+    pub const DEFAULT_BEZIER: Self = Self::CustomBezier {
+        p1: Pos2::new(0.25, 0.1),
+        p2: Pos2::new(0.25, 1.0),
+    };
+
+    /// creates a custom bezier with the given control points
+    /// the x coordinates of the points are clamped to [0, 1]
+    pub fn custom_bezier(p1: Pos2, p2: Pos2) -> Self {
+        Self::CustomBezier {
+            p1: Pos2::new(p1.x.clamp(0.0, 1.0), p1.y),
+            p2: Pos2::new(p2.x.clamp(0.0, 1.0), p2.y),
+        }
+    }
+
+    /// returns false if the shape is the same regardless of its smoothing direction
+    pub fn uses_direction(&self) -> bool {
+        !matches!(self, Self::Linear | Self::CustomBezier { .. })
+    }
+
+    /// the tag used to identify the shape when serialized
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::Linear => 0,
+            Self::Sine => 1,
+            Self::Circular => 2,
+            Self::Cubic => 3,
+            Self::Quartic => 4,
+            Self::CustomBezier { .. } => 5,
+            Self::Back(_) => 6,
+            Self::Elastic => 7,
+            Self::Bounce => 8,
+            Self::Hold => 9,
+        }
+    }
+
+    /// gets the shape from a tag created by to_byte
+    /// back shapes and custom beziers are given their defaults, as their parameters are stored after the tag
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::Linear),
+            1 => Some(Self::Sine),
+            2 => Some(Self::Circular),
+            3 => Some(Self::Cubic),
+            4 => Some(Self::Quartic),
+            5 => Some(Self::DEFAULT_BEZIER),
+            6 => Some(Self::DEFAULT_BACK),
+            7 => Some(Self::Elastic),
+            8 => Some(Self::Bounce),
+            9 => Some(Self::Hold),
+            _ => None
+        }
+    }
+
+    /// finds the y value of a cubic bezier from (0, 0) to (1, 1) at the given x
+    /// the x coordinates of the control points must be within [0, 1], making x increase along the bezier
+    fn cubic_bezier_y_for_x(p1: Pos2, p2: Pos2, x: f64) -> f64 {
+        let t = Self::cubic_bezier_t_for_x(p1, p2, x);
+        Self::bezier_coordinate(p1.y as f64, p2.y as f64, t)
+    }
+
+    /// finds the slope of a cubic bezier from (0, 0) to (1, 1) at the given x
+    /// the slope is infinite where the bezier is vertical
+    fn cubic_bezier_slope_for_x(p1: Pos2, p2: Pos2, x: f64) -> f64 {
+        let t = Self::cubic_bezier_t_for_x(p1, p2, x);
+        Self::bezier_coordinate_slope(p1.y as f64, p2.y as f64, t)
+            / Self::bezier_coordinate_slope(p1.x as f64, p2.x as f64, t)
+    }
+
+    /// a coordinate of a cubic bezier from 0 to 1 at parameter t, given the coordinates of its control points
+    fn bezier_coordinate(a_1: f64, a_2: f64, t: f64) -> f64 {
+        let u = 1.0 - t;
+        3.0 * u * u * t * a_1 + 3.0 * u * t * t * a_2 + t * t * t
+    }
+
+    /// the derivative of bezier_coordinate with respect to t
+    fn bezier_coordinate_slope(a_1: f64, a_2: f64, t: f64) -> f64 {
+        let u = 1.0 - t;
+        3.0 * u * u * a_1 + 6.0 * u * t * (a_2 - a_1) + 3.0 * t * t * (1.0 - a_2)
+    }
+
+    /// finds the parameter at which a cubic bezier from (0, 0) to (1, 1) reaches the given x
+    fn cubic_bezier_t_for_x(p1: Pos2, p2: Pos2, x: f64) -> f64 {
+        let (x_1, x_2) = (p1.x as f64, p2.x as f64);
+        const EPSILON: f64 = 1e-9;
+
+        // newton's method converges quickly unless the bezier is nearly vertical
+        let mut t = x;
+        for _ in 0..8 {
+            let error = Self::bezier_coordinate(x_1, x_2, t) - x;
+            if error.abs() < EPSILON {
+                return t;
+            }
+            let derivative = Self::bezier_coordinate_slope(x_1, x_2, t);
+            if derivative.abs() < EPSILON {
+                break;
+            }
+            t -= error / derivative;
+        }
+
+        // otherwise fall back to bisection, which always converges as x never decreases
+        let (mut low, mut high) = (0.0, 1.0);
+        t = x;
+        while high - low > EPSILON {
+            if Self::bezier_coordinate(x_1, x_2, t) < x {
+                low = t;
+            } else {
+                high = t;
+            }
+            t = (low + high) / 2.0;
+        }
+        t
+    }
+
+    /// finds the lowest and highest y values of a cubic bezier from (0, 0) to (1, 1)
+    fn cubic_bezier_y_range(p1: Pos2, p2: Pos2) -> (f64, f64) {
+        let (y_1, y_2) = (p1.y as f64, p2.y as f64);
+        let sample = |t: f64| Self::bezier_coordinate(y_1, y_2, t);
+
+        // the extremes lie at the ends or where the derivative, a quadratic in t, is zero
+        let (d_0, d_1, d_2) = (y_1, y_2 - y_1, 1.0 - y_2);
+        let a = d_0 - 2.0 * d_1 + d_2;
+        let b = 2.0 * (d_1 - d_0);
+        let c = d_0;
+        let roots = if a.abs() < 1e-12 {
+            [-c / b, f64::NAN]
+        } else {
+            let discriminant = b * b - 4.0 * a * c;
+            if discriminant < 0.0 {
+                [f64::NAN; 2]
+            } else {
+                let root = discriminant.sqrt();
+                [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+            }
+        };
+
+        roots.into_iter()
+            .filter(|t| *t > 0.0 && *t < 1.0)
+            .map(sample)
+            .fold((0.0, 1.0), |(low, high), y| (low.min(y), high.max(y)))
+    }
+
+    /// finds the lowest value of the back shape's in easing function
+    fn back_in_minimum(overshoot: f64) -> f64 {
+        // the derivative 3(c + 1)x^2 - 2cx is zero at x = 2c / 3(c + 1)
+        let x = 2.0 * overshoot / (3.0 * (overshoot + 1.0));
+        (overshoot + 1.0) * x.powi(3) - overshoot * x.powi(2)
+    }
+
+    /// the out easing function of the bounce shape
+    fn bounce_out(x: f64) -> f64 {
+        let (offset, floor) = Self::bounce_arc(x);
+        let x = x - offset;
+        Self::BOUNCE_SCALE * x * x + floor
+    }
+
+    /// the derivative of bounce_out
+    fn bounce_out_slope(x: f64) -> f64 {
+        let (offset, _) = Self::bounce_arc(x);
+        2.0 * Self::BOUNCE_SCALE * (x - offset)
+    }
+
+    const BOUNCE_SCALE: f64 = 7.5625;
+
+    /// finds the center and lowest value of the bounce_out arc containing x
+    fn bounce_arc(x: f64) -> (f64, f64) {
+        const D: f64 = 2.75;
+        if x < 1.0 / D {
+            (0.0, 0.0)
+        } else if x < 2.0 / D {
+            (1.5 / D, 0.75)
+        } else if x < 2.5 / D {
+            (2.25 / D, 0.9375)
+        } else {
+            (2.625 / D, 0.984375)
         }
     }
 }
@@ -254,13 +505,10 @@ pub struct CurveShape {
 
 impl PartialEq for CurveShape {
     fn eq(&self, other: &Self) -> bool {
-        type S = SmoothingShape;
-        self.shape == other.shape && (self.direction == other.direction || self.shape == S::Linear)
+        self.shape == other.shape && (self.direction == other.direction || !self.shape.uses_direction())
     }
 }
 
-impl Eq for CurveShape {}
-
 impl CurveShape {
     pub const LINEAR: Self = Self::new(SmoothingShape::Linear, SmoothingDirection::InOut);
 
@@ -283,6 +531,55 @@ impl CurveShape {
         }
     }
 
+    /// gets the shape tracing this shape backward in time, from its end value to its start value
+    pub fn reversed(&self) -> Self {
+        type S = SmoothingShape;
+        type D = SmoothingDirection;
+        match self.shape {
+            // reflected through the center of the segment
+            S::CustomBezier { p1, p2 } => Self::new(
+                S::CustomBezier {
+                    p1: Pos2::new(1.0 - p2.x, 1.0 - p2.y),
+                    p2: Pos2::new(1.0 - p1.x, 1.0 - p1.y),
+                },
+                self.direction
+            ),
+
+            // every other shape eased out is its shape eased in reflected through the center of the segment,
+            // and eased in and out is its own reflection
+            _ => self.with_direction(match self.direction {
+                D::In => D::Out,
+                D::Out => D::In,
+                D::InOut => D::InOut,
+            }),
+        }
+    }
+
+    /// gets the lowest and highest values the shape reaches, where it runs from 0 to 1
+    /// shapes that overshoot their target extend past [0, 1]
+    pub fn range(&self) -> (f64, f64) {
+        type S = SmoothingShape;
+        type D = SmoothingDirection;
+        match (self.shape, self.direction) {
+            (S::Back(c), D::In) => (S::back_in_minimum(c.max(0.0) as f64), 1.0),
+            (S::Back(c), D::Out) => (0.0, 1.0 - S::back_in_minimum(c.max(0.0) as f64)),
+            (S::Back(c), D::InOut) => {
+                // each half is the in shape with a larger overshoot, scaled by half
+                let minimum = S::back_in_minimum(c.max(0.0) as f64 * 1.525) / 2.0;
+                (minimum, 1.0 - minimum)
+            }
+
+            // found numerically, rounded away from [0, 1]
+            (S::Elastic, D::In) => (-0.3731, 1.0),
+            (S::Elastic, D::Out) => (0.0, 1.3731),
+            (S::Elastic, D::InOut) => (-0.11835, 1.11835),
+
+            (S::CustomBezier { p1, p2 }, _) => S::cubic_bezier_y_range(p1, p2),
+
+            _ => (0.0, 1.0),
+        }
+    }
+
     pub fn interpolate(&self, x: f64, x_1: f64, x_2: f64, y_1: f64, y_2: f64) -> f64 {
 		type S = SmoothingShape;
         type D = SmoothingDirection;
@@ -296,6 +593,12 @@ impl CurveShape {
                 (x - x_1) * (y_2 - y_1) / (x_2 - x_1) + y_1
             }
 
+            (S::CustomBezier { p1, p2 }, _) => {
+                self.generic_interpolate(x, x_1, x_2, y_1, y_2, |x| {
+                    S::cubic_bezier_y_for_x(p1, p2, x)
+                })
+            }
+
             (S::Sine, D::In) => {
                 self.generic_interpolate(x, x_1, x_2, y_1, y_2, |x| {
                     1.0 - f64::cos((x * f64::consts::PI) / 2.0)
@@ -344,7 +647,7 @@ impl CurveShape {
 
             (S::Quartic, D::Out) => {
                 self.generic_interpolate(x, x_1, x_2, y_1, y_2, |x| {
-                    1.0 + (x - 1.0).powi(4)
+                    1.0 - (x - 1.0).powi(4)
                 })
             }
 
@@ -383,25 +686,23 @@ impl CurveShape {
                 })
             }
 
-            /*
-            Self::In(S::Back(c)) => {
+            (S::Back(c), D::In) => {
                 self.generic_interpolate(x, x_1, x_2, y_1, y_2, |x| {
-                    let c = *c as f64;
+                    let c = c.max(0.0) as f64;
                     (c + 1.0) * x * x * x - c * x * x
                 })
             }
 
-            Self::Out(S::Back(c)) => {
+            (S::Back(c), D::Out) => {
                 self.generic_interpolate(x, x_1, x_2, y_1, y_2, |x| {
-                    let c = *c as f64;
+                    let c = c.max(0.0) as f64;
                     1.0 + (c + 1.0) * (x - 1.0).powi(3) + c * (x - 1.0).powi(2)
                 })
             }
 
-            Self::InOut(S::Back(c)) => {
+            (S::Back(c), D::InOut) => {
                 self.generic_interpolate(x, x_1, x_2, y_1, y_2, |x| {
-                    let c = *c as f64;
-                    let c2 = c * 1.525;
+                    let c2 = c.max(0.0) as f64 * 1.525;
                     if x < 0.5 {
                         ( (2.0 * x).powi(2) * ( (c2 + 1.0) * 2.0 * x - c2 ) ) / 2.0
                     } else {
@@ -409,12 +710,169 @@ impl CurveShape {
                     }
                 })
             }
-*/
 
+            (S::Elastic, D::In) => {
+                self.generic_interpolate(x, x_1, x_2, y_1, y_2, |x| {
+                    let c = 2.0 * f64::consts::PI / 3.0;
+                    -f64::powf(2.0, 10.0 * x - 10.0) * f64::sin((x * 10.0 - 10.75) * c)
+                })
+            }
+
+            (S::Elastic, D::Out) => {
+                self.generic_interpolate(x, x_1, x_2, y_1, y_2, |x| {
+                    let c = 2.0 * f64::consts::PI / 3.0;
+                    f64::powf(2.0, -10.0 * x) * f64::sin((x * 10.0 - 0.75) * c) + 1.0
+                })
+            }
+
+            (S::Elastic, D::InOut) => {
+                self.generic_interpolate(x, x_1, x_2, y_1, y_2, |x| {
+                    let c = 2.0 * f64::consts::PI / 4.5;
+                    let wave = f64::sin((20.0 * x - 11.125) * c);
+                    if x < 0.5 {
+                        -f64::powf(2.0, 20.0 * x - 10.0) * wave / 2.0
+                    } else {
+                        f64::powf(2.0, -20.0 * x + 10.0) * wave / 2.0 + 1.0
+                    }
+                })
+            }
+
+            (S::Bounce, D::In) => {
+                self.generic_interpolate(x, x_1, x_2, y_1, y_2, |x| {
+                    1.0 - S::bounce_out(1.0 - x)
+                })
+            }
+
+            (S::Bounce, D::Out) => {
+                self.generic_interpolate(x, x_1, x_2, y_1, y_2, S::bounce_out)
+            }
+
+            (S::Bounce, D::InOut) => {
+                self.generic_interpolate(x, x_1, x_2, y_1, y_2, |x| {
+                    if x < 0.5 {
+                        (1.0 - S::bounce_out(1.0 - 2.0 * x)) / 2.0
+                    } else {
+                        (1.0 + S::bounce_out(2.0 * x - 1.0)) / 2.0
+                    }
+                })
+            }
+
+            (S::Hold, D::In) => y_1,
+            (S::Hold, D::Out) => y_2,
+            (S::Hold, D::InOut) => {
+                if (x - x_1) * 2.0 < x_2 - x_1 { y_1 } else { y_2 }
+            }
+
+        }
+    }
+
+    /// gets the slope of the interpolation at x, in units of y per unit of x
+    /// the slope is 0 outside of [x_1, x_2], and is infinite where the shape is vertical
+    /// at x_1 and x_2, the slope is that approaching from inside the segment
+    pub fn slope(&self, x: f64, x_1: f64, x_2: f64, y_1: f64, y_2: f64) -> f64 {
+        if x < x_1 || x > x_2 {
+            return 0.0;
+        }
+        self.normalized_slope((x - x_1) / (x_2 - x_1)) * (y_2 - y_1) / (x_2 - x_1)
+    }
+
+    /// the derivative of the function with range and domain [0, 1] used by interpolate
+    fn normalized_slope(&self, x: f64) -> f64 {
+        type S = SmoothingShape;
+        type D = SmoothingDirection;
+        const HALF_PI: f64 = f64::consts::PI / 2.0;
+        match (self.shape, self.direction) {
+            (S::Linear, _) => 1.0,
+
+            (S::CustomBezier { p1, p2 }, _) => S::cubic_bezier_slope_for_x(p1, p2, x),
+
+            (S::Sine, D::In) => HALF_PI * f64::sin(x * HALF_PI),
+            (S::Sine, D::Out) => HALF_PI * f64::cos(x * HALF_PI),
+            (S::Sine, D::InOut) => HALF_PI * f64::sin(x * f64::consts::PI),
+
+            (S::Cubic, D::In) => 3.0 * x.powi(2),
+            (S::Cubic, D::Out) => 3.0 * (x - 1.0).powi(2),
+            (S::Cubic, D::InOut) => {
+                if x < 0.5 {
+                    12.0 * x.powi(2)
+                } else {
+                    3.0 * (-2.0 * x + 2.0).powi(2)
+                }
+            }
+
+            (S::Quartic, D::In) => 4.0 * x.powi(3),
+            (S::Quartic, D::Out) => -4.0 * (x - 1.0).powi(3),
+            (S::Quartic, D::InOut) => {
+                if x < 0.5 {
+                    32.0 * x.powi(3)
+                } else {
+                    4.0 * (-2.0 * x + 2.0).powi(3)
+                }
+            }
+
+            (S::Circular, D::In) => (1.0 - x) / f64::sqrt(1.0 - (x - 1.0).powi(2)),
+            (S::Circular, D::Out) => x / f64::sqrt(1.0 - x * x),
+            (S::Circular, D::InOut) => {
+                if x < 0.5 {
+                    2.0 * x / f64::sqrt(1.0 - 4.0 * x * x)
+                } else {
+                    let adj_x = 2.0 * x - 2.0;
+                    -adj_x / f64::sqrt(1.0 - adj_x * adj_x)
+                }
+            }
+
+            (S::Back(c), D::In) => {
+                let c = c.max(0.0) as f64;
+                3.0 * (c + 1.0) * x * x - 2.0 * c * x
+            }
+            (S::Back(c), D::Out) => {
+                let c = c.max(0.0) as f64;
+                3.0 * (c + 1.0) * (x - 1.0).powi(2) + 2.0 * c * (x - 1.0)
+            }
+            (S::Back(c), D::InOut) => {
+                let c2 = c.max(0.0) as f64 * 1.525;
+                let adj_x = if x < 0.5 { 2.0 * x } else { 2.0 * x - 2.0 };
+                let sign = if x < 0.5 { -1.0 } else { 1.0 };
+                3.0 * (c2 + 1.0) * adj_x * adj_x + sign * 2.0 * c2 * adj_x
+            }
+
+            // the product rule over a decaying power of two and a sine
+            (S::Elastic, D::In) => {
+                let c = 2.0 * f64::consts::PI / 3.0;
+                let angle = (x * 10.0 - 10.75) * c;
+                -f64::powf(2.0, 10.0 * x - 10.0) * (10.0 * f64::consts::LN_2 * angle.sin() + 10.0 * c * angle.cos())
+            }
+            (S::Elastic, D::Out) => {
+                let c = 2.0 * f64::consts::PI / 3.0;
+                let angle = (x * 10.0 - 0.75) * c;
+                f64::powf(2.0, -10.0 * x) * (10.0 * c * angle.cos() - 10.0 * f64::consts::LN_2 * angle.sin())
+            }
+            (S::Elastic, D::InOut) => {
+                let c = 2.0 * f64::consts::PI / 4.5;
+                let angle = (20.0 * x - 11.125) * c;
+                if x < 0.5 {
+                    -f64::powf(2.0, 20.0 * x - 10.0) * (20.0 * f64::consts::LN_2 * angle.sin() + 20.0 * c * angle.cos()) / 2.0
+                } else {
+                    f64::powf(2.0, -20.0 * x + 10.0) * (20.0 * c * angle.cos() - 20.0 * f64::consts::LN_2 * angle.sin()) / 2.0
+                }
+            }
+
+            (S::Bounce, D::In) => S::bounce_out_slope(1.0 - x),
+            (S::Bounce, D::Out) => S::bounce_out_slope(x),
+            (S::Bounce, D::InOut) => {
+                if x < 0.5 {
+                    S::bounce_out_slope(1.0 - 2.0 * x)
+                } else {
+                    S::bounce_out_slope(2.0 * x - 1.0)
+                }
+            }
+
+            (S::Hold, _) => 0.0,
         }
     }
 
     /// gets the points for a bezier approximation of the shape between the given poitns
+    /// elastic, bounce and hold shapes are only roughly followed, so sample interpolate to draw them exactly
     pub fn bezier_approximation(&self, start: Pos2, end: Pos2) -> [Pos2; 4] {
 		type S = SmoothingShape;
         type D = SmoothingDirection;
@@ -436,6 +894,25 @@ impl CurveShape {
             (S::Quartic, D::In) => [(0.5, 0.0), (0.75, 0.0)],
             (S::Quartic, D::Out) => [(0.25, 1.0), (0.5, 1.0)],
             (S::Quartic, D::InOut) => [(0.76, 0.0), (0.24, 1.0)],
+
+            // the overshoot of the control points grows with that of the shape
+            (S::Back(c), D::In) => [(0.36, 0.0), (0.66, -0.56 * c.max(0.0) / 1.70158)],
+            (S::Back(c), D::Out) => [(0.34, 1.0 + 0.56 * c.max(0.0) / 1.70158), (0.64, 1.0)],
+            (S::Back(c), D::InOut) => [(0.68, -0.6 * c.max(0.0) / 1.70158), (0.32, 1.0 + 0.6 * c.max(0.0) / 1.70158)],
+
+            (S::Elastic, D::In) => [(0.7, 0.0), (0.84, -0.3)],
+            (S::Elastic, D::Out) => [(0.16, 1.3), (0.3, 1.0)],
+            (S::Elastic, D::InOut) => [(0.87, -0.1), (0.13, 1.1)],
+
+            (S::Bounce, D::In) => [(0.6, 0.0), (0.9, 0.3)],
+            (S::Bounce, D::Out) => [(0.1, 0.7), (0.4, 1.0)],
+            (S::Bounce, D::InOut) => [(0.7, 0.0), (0.3, 1.0)],
+
+            (S::Hold, D::In) => [(1.0, 0.0), (1.0, 0.0)],
+            (S::Hold, D::Out) => [(0.0, 1.0), (0.0, 1.0)],
+            (S::Hold, D::InOut) => [(0.5, 0.0), (0.5, 1.0)],
+
+            (S::CustomBezier { p1, p2 }, _) => [(p1.x, p1.y), (p2.x, p2.y)],
         };
 
         [
@@ -452,6 +929,52 @@ impl CurveShape {
         ]
     }
 
+    /// appends the serialized shape to the given buffer
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        out.push(self.shape.to_byte());
+        out.push(self.direction.to_byte());
+        match self.shape {
+            SmoothingShape::CustomBezier { p1, p2 } => {
+                for coordinate in [p1.x, p1.y, p2.x, p2.y] {
+                    out.extend_from_slice(&coordinate.to_le_bytes());
+                }
+            }
+            SmoothingShape::Back(overshoot) => out.extend_from_slice(&overshoot.to_le_bytes()),
+            _ => {}
+        }
+    }
+
+    /// reads a shape written by write_bytes
+    fn read_bytes(reader: &mut ByteReader) -> Result<Self, CurveDecodeError> {
+        let shape_byte = reader.read_u8().ok_or(CurveDecodeError::UnexpectedEnd)?;
+        let direction_byte = reader.read_u8().ok_or(CurveDecodeError::UnexpectedEnd)?;
+        let mut shape = SmoothingShape::from_byte(shape_byte)
+            .ok_or(CurveDecodeError::UnknownShape(shape_byte))?;
+        let direction = SmoothingDirection::from_byte(direction_byte)
+            .ok_or(CurveDecodeError::UnknownDirection(direction_byte))?;
+
+        match &mut shape {
+            SmoothingShape::CustomBezier { p1, p2 } => {
+                for point in [p1, p2] {
+                    let x = reader.read_f32().ok_or(CurveDecodeError::UnexpectedEnd)?;
+                    let y = reader.read_f32().ok_or(CurveDecodeError::UnexpectedEnd)?;
+                    if !(0.0..=1.0).contains(&x) || !y.is_finite() {
+                        return Err(CurveDecodeError::InvalidControlPoint);
+                    }
+                    *point = Pos2::new(x, y);
+                }
+            }
+            SmoothingShape::Back(overshoot) => {
+                *overshoot = reader.read_f32().ok_or(CurveDecodeError::UnexpectedEnd)?;
+                if !overshoot.is_finite() {
+                    return Err(CurveDecodeError::NonFiniteValue);
+                }
+            }
+            _ => {}
+        }
+        Ok(Self::new(shape, direction))
+    }
+
     /// takes a function with range and domain [0, 1]
     /// and uses it to interpolate between values
     fn generic_interpolate(
@@ -499,8 +1022,333 @@ impl CurveYValue {
     }
 }
 
+/// how a curve continues once played past its end
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopMode {
+    /// the last value is held
+    HoldLast,
+
+    /// the curve repeats from its start
+    Loop,
+
+    /// the curve alternates between playing forward and backward
+    PingPong,
+}
+
+impl LoopMode {
+    /// provides a global method of cycling through modes
+    /// reverse order
+    pub fn prev(&self) -> Self {
+        match self {
+            Self::HoldLast => Self::PingPong,
+            Self::Loop => Self::HoldLast,
+            Self::PingPong => Self::Loop,
+        }
+    }
+
+    /// provides a global method of cycling through modes
+    /// forward order
+    pub fn next(&self) -> Self {
+        match self {
+            Self::HoldLast => Self::Loop,
+            Self::Loop => Self::PingPong,
+            Self::PingPong => Self::HoldLast,
+        }
+    }
+
+    /// the full name of the loop mode
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::HoldLast => "Hold Last",
+            Self::Loop => "Loop",
+            Self::PingPong => "Ping-Pong",
+        }
+    }
+}
+
+/// how a pasted curve makes room for itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasteMode {
+    /// the points after the paste are moved later by the pasted curve's duration
+    Insert,
+
+    /// the points covered by the pasted curve are replaced
+    Overwrite,
+}
+
+impl PasteMode {
+    /// the full name of the paste mode
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Insert => "Insert",
+            Self::Overwrite => "Overwrite",
+        }
+    }
+}
+
+/// the new ids of a curve's points and segments after it was transformed
+#[derive(Debug, Clone)]
+pub struct CurveIdRemap {
+    /// the new index of each point, or None if it was removed
+    points: Vec<Option<usize>>,
+
+    /// whether the curve was reversed, swapping the sides of its points
+    reversed: bool,
+
+    /// whether each point of the transformed curve is continuous
+    continuous: Vec<bool>,
+}
+
+impl CurveIdRemap {
+    /// creates the remap of a transformation that kept every point in place
+    fn identity(curve: &Curve) -> Self {
+        Self::new((0..curve.values.len()).map(Some).collect(), false, curve)
+    }
+
+    /// creates a remap from the new index of each point to the transformed curve
+    fn new(points: Vec<Option<usize>>, reversed: bool, curve: &Curve) -> Self {
+        Self {
+            points,
+            reversed,
+            continuous: curve.values.iter().map(CurveYValue::is_continuous).collect(),
+        }
+    }
+
+    /// gets the new id of the point, or None if it was removed
+    pub fn point(&self, point: CurvePointId) -> Option<CurvePointId> {
+        let index = (*self.points.get(point.index)?)?;
+        let side = if self.continuous[index] {
+            CurvePointSide::Continuous
+        } else if self.reversed {
+            match point.side {
+                CurvePointSide::Left => CurvePointSide::Right,
+                CurvePointSide::Right => CurvePointSide::Left,
+                CurvePointSide::Continuous => CurvePointSide::Continuous,
+            }
+        } else {
+            point.side
+        };
+        Some(CurvePointId { index, side })
+    }
+
+    /// gets the new id of the segment, or None if it was removed or split
+    pub fn segment(&self, segment: CurveSegmentId) -> Option<CurveSegmentId> {
+        let start = (*self.points.get(segment.index)?)?;
+        let end = (*self.points.get(segment.index + 1)?)?;
+        (start.abs_diff(end) == 1).then(|| CurveSegmentId { index: start.min(end) })
+    }
+}
+
+/// how the values of a curve, edited within [0, 1], map onto the values it outputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueMapping {
+    /// equal distances give equal differences
+    Linear,
+
+    /// equal distances give equal ratios, such as for times or cutoffs
+    /// requires a positive range
+    Logarithmic,
+
+    /// logarithmic, with values shown as the nearest pitch
+    /// requires a positive range
+    NoteFrequency,
+}
+
+impl ValueMapping {
+    /// provides a global method of cycling through mappings
+    /// reverse order
+    pub fn prev(&self) -> Self {
+        match self {
+            Self::Linear => Self::NoteFrequency,
+            Self::Logarithmic => Self::Linear,
+            Self::NoteFrequency => Self::Logarithmic,
+        }
+    }
+
+    /// provides a global method of cycling through mappings
+    /// forward order
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Linear => Self::Logarithmic,
+            Self::Logarithmic => Self::NoteFrequency,
+            Self::NoteFrequency => Self::Linear,
+        }
+    }
+
+    /// the full name of the mapping
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Linear => "Linear",
+            Self::Logarithmic => "Logarithmic",
+            Self::NoteFrequency => "Note Frequency",
+        }
+    }
+
+    /// returns true if the mapping scales exponentially, needing a positive range
+    pub fn is_logarithmic(&self) -> bool {
+        *self != Self::Linear
+    }
+
+    /// the tag used to identify the mapping when serialized
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::Linear => 0,
+            Self::Logarithmic => 1,
+            Self::NoteFrequency => 2,
+        }
+    }
+
+    /// gets the mapping from a tag created by to_byte
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::Linear),
+            1 => Some(Self::Logarithmic),
+            2 => Some(Self::NoteFrequency),
+            _ => None
+        }
+    }
+}
+
+/// the values a curve outputs, which its stored values within [0, 1] are mapped onto
+/// values outside of [0, 1] map past the ends of the range in the same way
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CurveRange {
+    /// the output of the value 0
+    min: f64,
+
+    /// the output of the value 1
+    max: f64,
+
+    mapping: ValueMapping,
+}
+
+impl CurveRange {
+    /// outputs the curve's values unchanged
+    pub const UNIT: Self = Self { min: 0.0, max: 1.0, mapping: ValueMapping::Linear };
+
+    /// the ratio between the ends of a positive range past which it is edited logarithmically
+    pub const WIDE_RATIO: f64 = 100.0;
+
+    /// the frequency of A4 used to show note frequencies
+    const A4: f64 = 440.0;
+
+    /// creates a range from min to max
+    /// fails if min is not less than max, or if the mapping is logarithmic and min is not positive
+    pub fn new(min: f64, max: f64, mapping: ValueMapping) -> Option<Self> {
+        if !min.is_finite() || !max.is_finite() || min >= max || (mapping.is_logarithmic() && min <= 0.0) {
+            return None;
+        }
+        Some(Self { min, max, mapping })
+    }
+
+    /// creates the range best suited to automating the input
+    /// note inputs are mapped as note frequencies, and wide positive ranges are mapped logarithmically
+    pub fn for_input(spec: &InputSpecification) -> Self {
+        let (min, max) = spec.range;
+        let mapping = if min <= 0.0 {
+            ValueMapping::Linear
+        } else if spec.is_note_input {
+            ValueMapping::NoteFrequency
+        } else if max / min >= Self::WIDE_RATIO {
+            ValueMapping::Logarithmic
+        } else {
+            ValueMapping::Linear
+        };
+        Self::new(min, max, mapping).unwrap_or(Self::UNIT)
+    }
+
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    pub fn mapping(&self) -> ValueMapping {
+        self.mapping
+    }
+
+    /// maps a stored value onto the value output for it
+    pub fn to_output(&self, value: f64) -> f64 {
+        if self.mapping.is_logarithmic() {
+            self.min * (self.max / self.min).powf(value)
+        } else {
+            self.min + (self.max - self.min) * value
+        }
+    }
+
+    /// maps an output value back onto the stored value producing it
+    /// for logarithmic mappings, outputs that are not positive are mapped as the lowest positive number
+    pub fn from_output(&self, output: f64) -> f64 {
+        if self.mapping.is_logarithmic() {
+            (output.max(f64::MIN_POSITIVE) / self.min).ln() / (self.max / self.min).ln()
+        } else {
+            (output - self.min) / (self.max - self.min)
+        }
+    }
+
+    /// shows the output of a stored value in the range's units
+    /// note frequencies are shown as the nearest pitch and how many cents they are from it
+    pub fn format(&self, value: f64) -> String {
+        let output = self.to_output(value);
+        if self.mapping == ValueMapping::NoteFrequency {
+            let cents = 1200.0 * (output / Self::A4).log2();
+            let semitones = (cents / 100.0).round();
+            if let Some(pitch) = Pitch::from_semitone_delta_a4(semitones as i32) {
+                let detune = (cents - semitones * 100.0).round() as i32;
+                return format!("{} {:+}c ({:.1} Hz)", pitch, detune, output);
+            }
+        }
+        format!("{:.3}", output)
+    }
+}
+
+/// an error occurring when attempting to read a serialized curve
+#[derive(Debug, Error)]
+pub enum CurveDecodeError {
+    #[error("The data does not describe a curve.")]
+    BadMagic,
+
+    #[error("Unsupported curve format version {0}.")]
+    UnsupportedVersion(u8),
+
+    #[error("The data ended before the curve was fully read.")]
+    UnexpectedEnd,
+
+    #[error("The data continues after the end of the curve.")]
+    TrailingData,
+
+    #[error("Unrecognized smoothing shape tag {0}.")]
+    UnknownShape(u8),
+
+    #[error("Unrecognized smoothing direction tag {0}.")]
+    UnknownDirection(u8),
+
+    #[error("A bezier control point lies outside of its segment.")]
+    InvalidControlPoint,
+
+    #[error("Unrecognized value mapping tag {0}.")]
+    UnknownMapping(u8),
+
+    #[error("The output range of the curve is empty or does not suit its mapping.")]
+    InvalidRange,
+
+    #[error("A curve must contain at least one segment.")]
+    Empty,
+
+    #[error("The curve contains a value that is not a real number.")]
+    NonFiniteValue,
+
+    #[error("End times must be positive, unique and sorted.")]
+    InvalidTimes,
+
+    #[error("The first and last points of a curve must be continuous.")]
+    DiscontinuousEndpoint,
+}
+
 /// A curve interpolating values of type T, stored with durations of type D
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Curve {
     /// there are n transitions such that n >= 1
     transitions: Vec<CurveShape>,
@@ -513,6 +1361,12 @@ pub struct Curve {
     ///		1) The start and end yvalues are singles
     values: Vec<CurveYValue>,
 
+    /// the values output for the stored values
+    range: CurveRange,
+
+    /// identifies the curve's contents, changing whenever the curve is edited
+    revision: u64,
+
     /// there are n end times
     /// end_times[i] corresponds to transitions[i] and values[i + 1]
     /// Invariants:
@@ -528,8 +1382,193 @@ impl Curve {
         Self {
             transitions: vec![CurveShape::LINEAR],
             values: vec![CurveYValue::new_single(value), CurveYValue::new_single(value)],
-            end_times: vec![duration]
+            end_times: vec![duration],
+            range: CurveRange::UNIT,
+            revision: Self::next_revision(),
+        }
+    }
+
+    /// creates a revision that no curve has had yet
+    fn next_revision() -> u64 {
+        static NEXT_REVISION: AtomicU64 = AtomicU64::new(0);
+        NEXT_REVISION.fetch_add(1, AtomicOrdering::Relaxed)
+    }
+
+    /// marks the curve as edited, invalidating anything created from its previous contents
+    fn touch(&mut self) {
+        self.revision = Self::next_revision();
+    }
+
+    /// gets an identifier for the curve's current contents
+    /// it changes whenever the curve is edited (and may change when an edit fails), and is never reused
+    /// so data created from the curve, such as a BakedCurve, is stale if its revision no longer matches
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// the identifier at the start of every serialized curve
+    const MAGIC: &'static [u8; 4] = b"SSCV";
+
+    /// the version of the serialized curve format
+    /// version 1 curves have no range, and are read with the unit range
+    const FORMAT_VERSION: u8 = 2;
+
+    /// serializes the curve (including discontinuities and its range) so that it may be saved as a preset
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(
+            Self::MAGIC.len() + 5 + self.transitions.len() * 26 + 16 + 17
+        );
+        out.extend_from_slice(Self::MAGIC);
+        out.push(Self::FORMAT_VERSION);
+        out.extend_from_slice(&(self.transitions.len() as u32).to_le_bytes());
+
+        for transition in &self.transitions {
+            transition.write_bytes(&mut out);
+        }
+        for value in &self.values {
+            out.extend_from_slice(&value.left_limit.to_le_bytes());
+            out.extend_from_slice(&value.right_limit.to_le_bytes());
+        }
+        for time in &self.end_times {
+            out.extend_from_slice(&time.to_le_bytes());
         }
+
+        out.extend_from_slice(&self.range.min.to_le_bytes());
+        out.extend_from_slice(&self.range.max.to_le_bytes());
+        out.push(self.range.mapping.to_byte());
+
+        out
+    }
+
+    /// deserializes a curve created by to_bytes
+    /// fails if the data is malformed or describes a curve that breaks the curve's invariants
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CurveDecodeError> {
+        type E = CurveDecodeError;
+        let mut reader = ByteReader::new(bytes);
+
+        if reader.read_bytes(Self::MAGIC.len()).ok_or(E::BadMagic)? != Self::MAGIC {
+            return Err(E::BadMagic);
+        }
+
+        let version = reader.read_u8().ok_or(E::UnexpectedEnd)?;
+        if version == 0 || version > Self::FORMAT_VERSION {
+            return Err(E::UnsupportedVersion(version));
+        }
+
+        let count = reader.read_u32().ok_or(E::UnexpectedEnd)? as usize;
+        if count == 0 {
+            return Err(E::Empty);
+        }
+
+        // avoid allocating for counts that the data could not possibly hold
+        if reader.remaining() < count * 26 + 16 {
+            return Err(E::UnexpectedEnd);
+        }
+
+        let mut transitions = Vec::with_capacity(count);
+        for _ in 0..count {
+            transitions.push(CurveShape::read_bytes(&mut reader)?);
+        }
+
+        let mut values = Vec::with_capacity(count + 1);
+        for _ in 0..=count {
+            let left_limit = reader.read_f64().ok_or(E::UnexpectedEnd)?;
+            let right_limit = reader.read_f64().ok_or(E::UnexpectedEnd)?;
+            if !left_limit.is_finite() || !right_limit.is_finite() {
+                return Err(E::NonFiniteValue);
+            }
+            values.push(CurveYValue::new_double(left_limit, right_limit));
+        }
+
+        let mut end_times = Vec::with_capacity(count);
+        let mut last_time = 0.0;
+        for _ in 0..count {
+            let time = reader.read_f64().ok_or(E::UnexpectedEnd)?;
+            if !time.is_finite() {
+                return Err(E::NonFiniteValue);
+            }
+            if time <= last_time {
+                return Err(E::InvalidTimes);
+            }
+            last_time = time;
+            end_times.push(time);
+        }
+
+        let range = if version >= 2 {
+            let min = reader.read_f64().ok_or(E::UnexpectedEnd)?;
+            let max = reader.read_f64().ok_or(E::UnexpectedEnd)?;
+            let mapping_byte = reader.read_u8().ok_or(E::UnexpectedEnd)?;
+            let mapping = ValueMapping::from_byte(mapping_byte).ok_or(E::UnknownMapping(mapping_byte))?;
+            CurveRange::new(min, max, mapping).ok_or(E::InvalidRange)?
+        } else {
+            CurveRange::UNIT
+        };
+
+        if !reader.is_empty() {
+            return Err(E::TrailingData);
+        }
+
+        if values.first().unwrap().is_discontinuous() || values.last().unwrap().is_discontinuous() {
+            return Err(E::DiscontinuousEndpoint);
+        }
+
+        Ok(Self {
+            transitions,
+            values,
+            end_times,
+            range,
+            revision: Self::next_revision(),
+        })
+    }
+
+    /// returns the rate of change of the value at the given time, in units of value per unit of time
+    /// at a point, this is the slope of the segment after it; outside of the curve, the value is held so it is 0
+    /// the slope is infinite where a segment's shape is vertical
+    /// O(log n)
+    pub fn slope_at_time(&self, time: f64) -> f64 {
+        if time < 0.0 {
+            return 0.0;
+        }
+
+        // the index of the transition to use
+        let index = match self.end_times.binary_search_by(|f| f.partial_cmp(&time).unwrap()) {
+            Ok(i) => i + 1,
+            Err(i) => i,
+        };
+        if index >= self.end_times.len() {
+            return 0.0;
+        }
+
+        self.transitions[index].slope(
+            time,
+            self.time_at_index(index),
+            self.end_times[index],
+            self.values[index].right_limit,
+            self.values[index + 1].left_limit
+        )
+    }
+
+    /// gets the values output for the curve's values
+    pub fn range(&self) -> CurveRange {
+        self.range
+    }
+
+    /// sets the values output for the curve's values, leaving the values themselves unchanged
+    pub fn set_range(&mut self, range: CurveRange) {
+        self.touch();
+        self.range = range;
+    }
+
+    /// returns the output of the curve's range at the given time
+    /// O(log n)
+    pub fn output_at_time(&self, time: f64) -> f64 {
+        self.range.to_output(self.value_at_time(time))
+    }
+
+    /// samples the curve into a table with the given number of entries per unit of time
+    /// for playback where the curve is read too often to search for its segment each time
+    pub fn bake(&self, resolution: f64) -> BakedCurve {
+        BakedCurve::new(self, resolution)
     }
 
     /// returns the value at the given time
@@ -574,11 +1613,47 @@ impl Curve {
         transition.interpolate(time, x_1, x_2, y_1, y_2)
     }
 
+    /// returns the value at the given time, with the curve repeating as described by the mode
+    /// looping modes also repeat the curve before time 0
+    /// O(log n)
+    pub fn value_at_time_looped(&self, time: f64, mode: LoopMode) -> f64 {
+        let duration = self.total_duration();
+        match mode {
+            LoopMode::HoldLast => self.value_at_time(time),
+            LoopMode::Loop => self.value_at_time(time.rem_euclid(duration)),
+            LoopMode::PingPong => {
+                let time = time.rem_euclid(2.0 * duration);
+                if time > duration {
+                    self.value_at_time(2.0 * duration - time)
+                } else {
+                    self.value_at_time(time)
+                }
+            }
+        }
+    }
+
     /// returns the total duration of the curve
     pub fn total_duration(&self) -> f64 {
         *self.end_times.last().unwrap()
     }
 
+    /// gets the lowest and highest values the curve reaches, including where segments overshoot their points
+    /// O(n)
+    pub fn value_bounds(&self) -> (f64, f64) {
+        let mut low = f64::INFINITY;
+        let mut high = f64::NEG_INFINITY;
+        for (index, transition) in self.transitions.iter().enumerate() {
+            let start = self.values[index].right_limit;
+            let end = self.values[index + 1].left_limit;
+            let (range_low, range_high) = transition.range();
+            for value in [start, end, start + range_low * (end - start), start + range_high * (end - start)] {
+                low = low.min(value);
+                high = high.max(value);
+            }
+        }
+        (low, high)
+    }
+
     /// attempts to create a segment from the given points
     /// fails if the points occur at the same time or if the points are separated by another point
     pub fn make_segment(&self, start: CurvePointId, end: CurvePointId) -> Option<CurveSegmentId>{
@@ -669,7 +1744,11 @@ impl Curve {
 
     }
 
-    /// deletes all segments between the given points (inclusive) and the following transition
+    /// deletes all points between the given points (inclusive) and the transitions following them
+    ///
+    /// a range ending on one side of a discontinuity only deletes that side,
+    /// so a range starting on a right-hand limit keeps the left-hand limit of its first point
+    /// and a range ending on a left-hand limit keeps the right-hand limit of its last point
     ///
     /// fails if this operation would leave less than two points in the curve
     ///
@@ -681,65 +1760,83 @@ impl Curve {
     pub fn remove_point_to_point(&mut self, point1: CurvePointId, point2: CurvePointId) -> Option<f64> {
         debug_assert!(self.point_is_valid(point1), "point1 is not contained in the curve");
         debug_assert!(self.point_is_valid(point2), "point2 is not contained in the curve");
-
-    	todo!("handle different cases for continuous and discontinuous points");
+        self.touch();
 
         // put points in order
-        let (start, end) = if point1.index < point2.index {
+        let (start, end) = if point1 <= point2 {
             (point1, point2)
         } else {
             (point2, point1)
         };
 
-        // ensure that we have selected a valid range
-        if end.index - start.index + 1 >= self.values.len() - 2 {
+        // partial ids on continuous points refer to the whole point
+        let start_whole = start.side != CurvePointSide::Right || self.values[start.index].is_continuous();
+        let end_whole = end.side != CurvePointSide::Left || self.values[end.index].is_continuous();
+
+        // the range of points that are deleted entirely, which may be empty
+        let first = if start_whole { start.index } else { start.index + 1 };
+        let last = if end_whole { end.index as isize } else { end.index as isize - 1 };
+        let count = (last - first as isize + 1).max(0) as usize;
+
+        // ensure that we leave at least two points
+        if count > self.values.len() - 2 {
             return None;
         }
 
-        // handle case where we are removing a single point
-        if start.index == end.index {
-            return self.remove_point(start);
+        // delete the sides of the discontinuities the range ends on
+        if !start_whole {
+            let y_val = &mut self.values[start.index];
+            y_val.right_limit = y_val.left_limit;
+        }
+        if !end_whole {
+            let y_val = &mut self.values[end.index];
+            y_val.left_limit = y_val.right_limit;
+        }
+
+        if count == 0 {
+            return Some(0.0);
         }
+        let last = last as usize;
 
-        if start.index <= 0 {
+        if first == 0 {
             // handle case where we delete the start point
-            let offset = self.end_times[end.index];
+            let offset = self.end_times[last];
 
             // delete entries
-            self.values.drain(0..=end.index);
-            self.transitions.drain(0..=end.index);
-            self.end_times.drain(0..=end.index);
+            self.values.drain(0..=last);
+            self.transitions.drain(0..=last);
+            self.end_times.drain(0..=last);
 
             // make things start at 0 again
             self.end_times.iter_mut().for_each(|f| *f -= offset);
 
             // preserve the invariant that the start yvalue must be a single
+            // the right-hand limit is kept as it leads into the remaining curve
             let start_y_val = self.values.first_mut().unwrap();
             start_y_val.left_limit = start_y_val.right_limit;
 
             return Some(offset);
         }
 
-        if end.index >= self.values.len() - 1 {
+        if last >= self.values.len() - 1 {
             // handle case where we delete the end point
-            self.values.drain(start.index..=end.index);
-            self.transitions.drain(start.index - 1..=end.index - 1);
-            self.end_times.drain(start.index - 1..=end.index - 1);
+            self.values.drain(first..=last);
+            self.transitions.drain(first - 1..=last - 1);
+            self.end_times.drain(first - 1..=last - 1);
+
+            // preserve the invariant that the end yvalue must be a single
+            // the left-hand limit is kept as it is what the remaining curve leads into
+            let end_y_val = self.values.last_mut().unwrap();
+            end_y_val.right_limit = end_y_val.left_limit;
 
         } else {
             // handle case where we only delete intermediate points
-            self.values.drain(start.index..=end.index);
-            self.transitions.drain(start.index..=end.index);
-            self.end_times.drain(start.index - 1..=end.index - 1);
+            // the transition into the range now leads to the point after it
+            self.values.drain(first..=last);
+            self.transitions.drain(first..=last);
+            self.end_times.drain(first - 1..=last - 1);
         }
 
-        // preserve invariant that the last curve segment must have the
-        // same start and end values if it is CurveShape::None
-        // note that we must handle this scenario for intermediate points as well,
-        // as we may delete the last transition when deleting the second-to-last point
-        let last = self.values.last_mut().unwrap();
-        last.left_limit = last.right_limit;
-
         Some(0.0)
     }
 
@@ -754,6 +1851,7 @@ impl Curve {
     /// note that i will be 0 if the first point was not deleted
     pub fn remove_point(&mut self, point: CurvePointId) -> Option<f64> {
         debug_assert!(self.point_is_valid(point), "point is not contained in the curve");
+        self.touch();
 
         if self.values.len() <= 2 {
             return None;
@@ -820,6 +1918,7 @@ impl Curve {
     ///
     /// returns the point added
     pub fn insert_point_at_time(&mut self, time: f64) -> Option<CurvePointId> {
+        self.touch();
         if time == 0.0 {
             None
 
@@ -858,6 +1957,7 @@ impl Curve {
     /// returns the new point (which may change if a discontinuous point becomes continuous)
     pub fn set_point_value(&mut self, point: CurvePointId, value: f64) -> CurvePointId {
         debug_assert!(self.point_is_valid(point), "point is not contained in the curve");
+        self.touch();
 
         match point.side {
             CurvePointSide::Right => {
@@ -899,6 +1999,7 @@ impl Curve {
     /// returns the new id of the point
     pub fn set_point_time(&mut self, point: CurvePointId, time: f64) -> CurvePointId {
         debug_assert!(self.point_is_valid(point), "point is not contained in the curve");
+        self.touch();
 
         if self.point_is_start(point) {
             // handle fusion
@@ -1062,9 +2163,366 @@ impl Curve {
         }
     }
 
+    /// stretches the curve in time, such that every point's time is multiplied by the factor
+    /// fails if the factor is not positive
+    /// returns true if the curve was scaled
+    pub fn scale_time(&mut self, factor: f64) -> bool {
+        self.touch();
+        if !factor.is_finite() || factor <= 0.0 {
+            return false;
+        }
+        self.end_times.iter_mut().for_each(|f| *f *= factor);
+        true
+    }
+
+    /// scales every value (including both limits of discontinuities) away from the pivot by the factor
+    /// a negative factor flips the curve around the pivot
+    /// returns true if the curve was scaled
+    pub fn scale_values(&mut self, factor: f64, pivot: f64) -> bool {
+        self.touch();
+        if !factor.is_finite() || !pivot.is_finite() {
+            return false;
+        }
+        for value in &mut self.values {
+            value.left_limit = pivot + (value.left_limit - pivot) * factor;
+            value.right_limit = pivot + (value.right_limit - pivot) * factor;
+        }
+        true
+    }
+
+    /// adds delta to every value (including both limits of discontinuities)
+    /// returns true if the curve was shifted
+    pub fn shift_values(&mut self, delta: f64) -> bool {
+        self.touch();
+        if !delta.is_finite() {
+            return false;
+        }
+        for value in &mut self.values {
+            value.left_limit += delta;
+            value.right_limit += delta;
+        }
+        true
+    }
+
+    /// plays the curve backward, such that the point at time t moves to the total duration minus t
+    /// returns the new ids, where the order of the points and the sides of discontinuities are reversed
+    pub fn reverse_time(&mut self) -> CurveIdRemap {
+        self.touch();
+        let total = self.total_duration();
+        let last = self.values.len() - 1;
+
+        // end time i is the time of point i + 1, which was point last - 1 - i
+        self.end_times = (0..last)
+            .map(|index| total - self.time_at_index(last - 1 - index))
+            .collect();
+        self.values.reverse();
+        for value in &mut self.values {
+            std::mem::swap(&mut value.left_limit, &mut value.right_limit);
+        }
+        self.transitions.reverse();
+        for transition in &mut self.transitions {
+            *transition = transition.reversed();
+        }
+
+        CurveIdRemap::new((0..=last).rev().map(Some).collect(), true, self)
+    }
+
+    /// flips every value (including both limits of discontinuities) over the pivot
+    /// fails if the pivot is not a real number
+    /// returns the new ids, which are those from before the curve was inverted
+    pub fn invert_values(&mut self, pivot: f64) -> Option<CurveIdRemap> {
+        if !self.scale_values(-1.0, pivot) {
+            return None;
+        }
+        Some(CurveIdRemap::identity(self))
+    }
+
+    /// replaces the curve after the given time with the curve before it played backward,
+    /// such that the curve is symmetric around the time and twice as long
+    /// fails if the time is not within the curve
+    /// returns the new ids, where points after the time are removed and the mirrored points are new
+    pub fn mirror_around(&mut self, time: f64) -> Option<CurveIdRemap> {
+        if !time.is_finite() || time <= 0.0 || time > self.total_duration() {
+            return None;
+        }
+
+        // the meeting point is continuous, as both halves end with the value approaching the time
+        let mut mirrored = self.extract_range(0.0, time)?;
+        let mut reflection = mirrored.clone();
+        reflection.reverse_time();
+        mirrored.join(&reflection);
+
+        let points = (0..self.values.len())
+            .map(|index| (self.time_at_index(index) <= time).then_some(index))
+            .collect();
+        *self = mirrored;
+        Some(CurveIdRemap::new(points, false, self))
+    }
+
+    /// removes points whose removal changes the curve by no more than the tolerance, such as to clean up recorded curves
+    /// the segments around a removed point are merged into one, taking whichever of their shapes (or linear) fits best
+    /// the change is always measured against the curve as it was before simplifying, so it does not build up
+    /// the ends of the curve and discontinuities are kept
+    /// returns the number of points removed
+    pub fn simplify(&mut self, tolerance: f64) -> usize {
+        if tolerance.is_nan() || tolerance < 0.0 {
+            return 0;
+        }
+        let original = self.clone();
+
+        // the best merge and its error for removing each point, or None if the point may not be removed
+        let mut merges: Vec<Option<(CurveShape, f64)>> = (0..self.values.len())
+            .map(|index| self.merge_around(&original, index))
+            .collect();
+
+        let mut removed = 0;
+        loop {
+            let best = merges.iter()
+                .enumerate()
+                .filter_map(|(index, merge)| merge.map(|(shape, error)| (index, shape, error)))
+                .filter(|(_, _, error)| *error <= tolerance)
+                .min_by(|a, b| a.2.total_cmp(&b.2));
+            let Some((index, shape, _)) = best else {
+                break;
+            };
+
+            self.transitions[index - 1] = shape;
+            self.transitions.remove(index);
+            self.values.remove(index);
+            self.end_times.remove(index - 1);
+            merges.remove(index);
+            removed += 1;
+
+            // only the merges of the neighboring points changed
+            merges[index - 1] = self.merge_around(&original, index - 1);
+            merges[index] = self.merge_around(&original, index);
+        }
+
+        if removed > 0 {
+            self.touch();
+        }
+        removed
+    }
+
+    /// finds the shape best replacing the segments around the point at index if it were removed,
+    /// and the largest difference it makes from the original curve
+    /// returns None for the ends of the curve and discontinuities, which may not be removed
+    fn merge_around(&self, original: &Curve, index: usize) -> Option<(CurveShape, f64)> {
+        if index == 0 || index >= self.values.len() - 1 || self.values[index].is_discontinuous() {
+            return None;
+        }
+
+        /// the number of evenly spaced times the merged segment is compared at, besides the original points
+        const SAMPLES: usize = 32;
+
+        let x_1 = self.time_at_index(index - 1);
+        let x_2 = self.time_at_index(index + 1);
+        let y_1 = self.values[index - 1].right_limit;
+        let y_2 = self.values[index + 1].left_limit;
+
+        // the original points within the segment, where differences are most likely to be largest
+        let first = original.end_times.partition_point(|f| *f <= x_1);
+        let last = original.end_times.partition_point(|f| *f < x_2);
+        let times: Vec<f64> = original.end_times[first..last].iter()
+            .copied()
+            .chain((1..SAMPLES).map(|step| x_1 + (x_2 - x_1) * step as f64 / SAMPLES as f64))
+            .collect();
+        let expected: Vec<f64> = times.iter().map(|time| original.value_at_time(*time)).collect();
+
+        [self.transitions[index - 1], self.transitions[index], CurveShape::LINEAR].into_iter()
+            .map(|shape| {
+                let error = times.iter()
+                    .zip(&expected)
+                    .map(|(time, value)| (shape.interpolate(*time, x_1, x_2, y_1, y_2) - value).abs())
+                    .fold(0.0, f64::max);
+                (shape, error)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// moves the time of each point toward the nearest multiple of grid
+    /// strength [0, 1] is how far each point moves, from not at all to fully onto the grid
+    /// points in excluded are not moved, nor are points that would reach a neighboring point
+    /// returns true if the curve was quantized
+    pub fn quantize_times(&mut self, grid: f64, strength: f64, excluded: &[CurvePointId]) -> bool {
+        self.touch();
+        if !grid.is_finite() || grid <= 0.0 || !strength.is_finite() {
+            return false;
+        }
+        let strength = strength.clamp(0.0, 1.0);
+
+        // the first point is always at 0, which is on the grid
+        let mut prev_time = 0.0;
+        for index in 1..self.values.len() {
+            let time = self.end_times[index - 1];
+            let next_time = self.end_times.get(index).copied().unwrap_or(f64::INFINITY);
+
+            if !excluded.iter().any(|point| point.index == index) {
+                let target = (time / grid).round() * grid;
+                let quantized = time + (target - time) * strength;
+                if prev_time < quantized && quantized < next_time {
+                    self.end_times[index - 1] = quantized;
+                }
+            }
+            prev_time = self.end_times[index - 1];
+        }
+        true
+    }
+
+    /// copies the section of the curve between the given times into a new curve starting at 0
+    /// segments cut by the range keep their shape over the shorter span
+    /// fails unless 0 <= start < end <= total duration
+    pub fn extract_range(&self, start: f64, end: f64) -> Option<Curve> {
+        if !(0.0 <= start && start < end && end <= self.total_duration()) {
+            return None;
+        }
+
+        // the indices of the points strictly inside the range, which may be none
+        let first = self.end_times.partition_point(|f| *f <= start) + 1;
+        let last = self.end_times.partition_point(|f| *f < end);
+
+        let mut values = vec![CurveYValue::new_single(self.value_at_time(start))];
+        let mut transitions = Vec::with_capacity(last + 2 - first);
+        let mut end_times = Vec::with_capacity(last + 2 - first);
+        for index in first..=last {
+            transitions.push(self.transitions[index - 1]);
+            end_times.push(self.end_times[index - 1] - start);
+            values.push(self.values[index].clone());
+        }
+
+        // the transition into the end of the range
+        transitions.push(self.transitions[last]);
+        end_times.push(end - start);
+        values.push(CurveYValue::new_single(self.value_before_time(end)));
+
+        Some(Curve {
+            transitions,
+            values,
+            end_times,
+            range: self.range,
+            revision: Self::next_revision(),
+        })
+    }
+
+    /// adds a curve after the end of this curve, with the last value of this curve held for the gap
+    /// where the curves meet, a discontinuity is made if their values differ
+    /// fails if the gap is negative
+    /// returns true if the curve was appended
+    pub fn append(&mut self, other: Curve, gap: f64) -> bool {
+        self.append_with(&other, gap, false)
+    }
+
+    /// adds a curve after the end of this curve, moving linearly between their values over the gap
+    /// if there is no gap, the point where the curves meet takes the average of their values
+    /// fails if the gap is negative
+    /// returns true if the curve was appended
+    pub fn append_crossfaded(&mut self, other: Curve, gap: f64) -> bool {
+        self.append_with(&other, gap, true)
+    }
+
+    /// joins the curves one after another, the same gap apart, keeping the range of the first
+    /// fails if there are no curves or if the gap is negative
+    pub fn concatenate(curves: impl IntoIterator<Item = Curve>, gap: f64) -> Option<Curve> {
+        let mut curves = curves.into_iter();
+        let mut joined = curves.next()?;
+        for curve in curves {
+            if !joined.append(curve, gap) {
+                return None;
+            }
+        }
+        Some(joined)
+    }
+
+    /// appends the curve, holding the last value over the gap or moving linearly across it if crossfading
+    fn append_with(&mut self, other: &Curve, gap: f64, crossfade: bool) -> bool {
+        if !gap.is_finite() || gap < 0.0 {
+            return false;
+        }
+        self.touch();
+        let end = self.values.last().unwrap().left_limit;
+        let start = other.values[0].right_limit;
+
+        if gap > 0.0 {
+            let total = self.total_duration();
+            self.values.push(CurveYValue::new_single(if crossfade { start } else { end }));
+            self.transitions.push(CurveShape::LINEAR);
+            self.end_times.push(total + gap);
+        }
+
+        let meeting = self.values.len() - 1;
+        self.join(other);
+        if crossfade && gap == 0.0 {
+            self.values[meeting] = CurveYValue::new_single((end + start) / 2.0);
+        }
+        true
+    }
+
+    /// pastes a curve starting at the given time
+    /// if the time is after the end of this curve, the last value is held until the paste
+    /// where the pasted curve meets this curve, a discontinuity is made if their values differ
+    /// fails if the time is negative
+    /// returns true if the curve was pasted
+    pub fn insert_curve_at(&mut self, time: f64, curve: &Curve, mode: PasteMode) -> bool {
+        self.touch();
+        if !time.is_finite() || time < 0.0 {
+            return false;
+        }
+        let total = self.total_duration();
+        let paste_end = time + curve.total_duration();
+
+        let before = if time > total {
+            let mut before = self.clone();
+            before.insert_point_at_time(time);
+            Some(before)
+        } else if time > 0.0 {
+            self.extract_range(0.0, time)
+        } else {
+            None
+        };
+        let after = match mode {
+            PasteMode::Insert if time < total => self.extract_range(time, total),
+            PasteMode::Overwrite if paste_end < total => self.extract_range(paste_end, total),
+            _ => None,
+        };
+
+        let mut result = match before {
+            Some(mut before) => {
+                before.join(curve);
+                before
+            }
+            None => curve.clone(),
+        };
+        if let Some(after) = after {
+            result.join(&after);
+        }
+        *self = result;
+        true
+    }
+
+    /// appends another curve directly after the end of this curve
+    /// the point where they meet takes its left-hand limit from this curve and its right-hand limit from the other
+    fn join(&mut self, other: &Curve) {
+        let offset = self.total_duration();
+        let left_limit = self.values.pop().unwrap().left_limit;
+        self.values.push(CurveYValue::new_double(left_limit, other.values[0].right_limit));
+        self.values.extend(other.values[1..].iter().cloned());
+        self.transitions.extend_from_slice(&other.transitions);
+        self.end_times.extend(other.end_times.iter().map(|f| f + offset));
+    }
+
+    /// returns the value approaching the given time from the left
+    /// this differs from value_at_time only at the right-hand limit of a discontinuity
+    fn value_before_time(&self, time: f64) -> f64 {
+        match self.end_times.binary_search_by(|f| f.partial_cmp(&time).unwrap()) {
+            Ok(i) => self.values[i + 1].left_limit,
+            Err(_) => self.value_at_time(time),
+        }
+    }
+
     /// sets the shape of the given segment
     pub fn set_segment_shape(&mut self, segment: CurveSegmentId, shape: CurveShape) {
         debug_assert!(self.segment_is_valid(segment), "segment is not contained in the curve");
+        self.touch();
         self.transitions[segment.index] = shape;
     }
 
@@ -1246,6 +2704,34 @@ impl Curve {
         point.index == self.values.len() - 1
     }
 
+    // returns true if the given point has different left-hand and right-hand limits
+    pub fn point_is_discontinuous(&self, point: CurvePointId) -> bool {
+        debug_assert!(self.point_is_valid(point), "point is not contained in the curve");
+        self.values[point.index].is_discontinuous()
+    }
+
+    // returns the left-hand limit of the given point
+    // on a continuous point, setting its value splits the point into a discontinuity
+    // the first and last points must stay continuous, so only intermediate points may be split
+    pub fn left_partial(&self, point: CurvePointId) -> CurvePointId {
+        debug_assert!(self.point_is_valid(point), "point is not contained in the curve");
+        CurvePointId {
+            index: point.index,
+            side: CurvePointSide::Left,
+        }
+    }
+
+    // returns the right-hand limit of the given point
+    // on a continuous point, setting its value splits the point into a discontinuity
+    // the first and last points must stay continuous, so only intermediate points may be split
+    pub fn right_partial(&self, point: CurvePointId) -> CurvePointId {
+        debug_assert!(self.point_is_valid(point), "point is not contained in the curve");
+        CurvePointId {
+            index: point.index,
+            side: CurvePointSide::Right,
+        }
+    }
+
     // returns true if the given point is contained in the curve and continuity matches the point
     // if the id is for a discontinuity, but the point is continuous, that is fine
     // but if the id is for a continuous point, but the point is discontinuous, there is a problem
@@ -1405,5 +2891,511 @@ pub enum CurveCommand {
 
     /// sets the shape for the given segment
     SetSegmentShape{segment: CurveSegmentId, shape: CurveShape},
+
+    /// stretches the curve in time by the given factor
+    ScaleTime{factor: f64},
+
+    /// scales the values of the curve away from the pivot by the given factor
+    ScaleValues{factor: f64, pivot: f64},
+
+    /// adds delta to every value of the curve
+    ShiftValues{delta: f64},
+
+    /// moves point times toward a grid, leaving the excluded points in place
+    QuantizeTimes{grid: f64, strength: f64, excluded: Vec<CurvePointId>},
+
+    /// pastes a curve at the given time
+    InsertCurve{time: f64, curve: Box<Curve>, mode: PasteMode},
+
+    /// sets the values output for the curve's values
+    SetRange{range: CurveRange},
+
+    /// adds a curve after the end of the curve, optionally moving between their values over the gap
+    Append{curve: Box<Curve>, gap: f64, crossfade: bool},
+
+    /// removes points whose removal changes the curve by no more than the tolerance
+    Simplify{tolerance: f64},
+
+    /// plays the curve backward
+    ReverseTime,
+
+    /// flips the values of the curve over the pivot
+    InvertValues{pivot: f64},
+
+    /// makes the curve symmetric around the given time, replacing the curve after it
+    MirrorAround{time: f64},
+
+    /// replaces the entire curve, used to undo edits that cannot be reversed point by point
+    Restore{curve: Box<Curve>},
+}
+
+/// the outcome of applying a CurveCommand
+#[derive(Debug, Clone)]
+pub enum CurveCommandResult {
+    /// the command was applied
+    Applied {
+        /// applying these commands in order undoes the command
+        inverse: Vec<CurveCommand>,
+
+        /// the id of the point created or moved by the command, which may differ from the id it was given
+        point: Option<CurvePointId>,
+    },
+
+    /// the command does not fit the curve, which was left unchanged
+    Rejected,
+}
+
+impl CurveCommandResult {
+    pub fn is_applied(&self) -> bool {
+        matches!(self, Self::Applied { .. })
+    }
+
+    /// the result of a command with a single inverse
+    fn applied(inverse: CurveCommand, point: Option<CurvePointId>) -> Self {
+        Self::Applied {
+            inverse: vec![inverse],
+            point,
+        }
+    }
+}
+
+impl Curve {
+    /// executes the command, returning the commands that undo it
+    /// commands holding ids that are not valid for the curve are rejected
+    pub fn apply(&mut self, command: CurveCommand) -> CurveCommandResult {
+        type R = CurveCommandResult;
+        match command {
+            CurveCommand::DeletePoint { point } => {
+                if !self.point_is_valid(point) || (point.is_continuous() && self.values.len() <= 2) {
+                    return R::Rejected;
+                }
+                let snapshot = self.snapshot();
+                self.remove_point(point);
+                R::applied(snapshot, None)
+            }
+
+            CurveCommand::DeletePointRange { start, end } => {
+                if !self.point_is_valid(start) || !self.point_is_valid(end) {
+                    return R::Rejected;
+                }
+                let snapshot = self.snapshot();
+                match self.remove_point_to_point(start, end) {
+                    Some(_) => R::applied(snapshot, None),
+                    None => R::Rejected,
+                }
+            }
+
+            CurveCommand::AddPoint { point, value, time } => {
+                if !self.point_is_valid(point) || !value.is_finite() || !time.is_finite() {
+                    return R::Rejected;
+                }
+                let after_next = self.next_point(point)
+                    .is_some_and(|next| time >= self.get_point_time(next));
+                if time <= self.get_point_time(point) || after_next {
+                    return R::Rejected;
+                }
+
+                let Some(added) = self.insert_point_at_time(time) else {
+                    return R::Rejected;
+                };
+                let added = self.set_point_value(added, value);
+                R::applied(CurveCommand::DeletePoint { point: added }, Some(added))
+            }
+
+            CurveCommand::PushPoint { value, duration } => {
+                if !value.is_finite() || !duration.is_finite() || duration <= 0.0 {
+                    return R::Rejected;
+                }
+                let Some(added) = self.insert_point_at_time(-duration) else {
+                    return R::Rejected;
+                };
+                let added = self.set_point_value(added, value);
+                R::applied(CurveCommand::DeletePoint { point: added }, Some(added))
+            }
+
+            CurveCommand::SetPointTime { point, time } => {
+                if !self.point_is_valid(point) || !time.is_finite() {
+                    return R::Rejected;
+                }
+                if self.point_is_start(point) && time > self.end_times[0] {
+                    return R::Rejected;
+                }
+                let snapshot = self.snapshot();
+                let moved = self.set_point_time(point, time);
+                R::applied(snapshot, Some(moved))
+            }
+
+            CurveCommand::SetPointValue { point, value } => {
+                if !self.point_is_valid(point) || !value.is_finite() {
+                    return R::Rejected;
+                }
+                // the first and last points must stay continuous
+                if point.is_partial() && !self.point_is_intermediate(point) {
+                    return R::Rejected;
+                }
+                let previous = self.get_point_value(point);
+                let changed = self.set_point_value(point, value);
+
+                // the original id restores a discontinuity even if the point became continuous
+                R::applied(CurveCommand::SetPointValue { point, value: previous }, Some(changed))
+            }
+
+            CurveCommand::SetRangeTime { start, end, time } => {
+                if !self.point_is_valid(start) || !self.point_is_valid(end) || !time.is_finite() {
+                    return R::Rejected;
+                }
+                let (start, end) = (start.min(end), start.max(end));
+                let delta = time - self.get_point_time(start);
+
+                // the range may not pass the points around it
+                if start.index > 0 && delta <= self.time_at_index(start.index - 1) - self.time_at_index(start.index) {
+                    return R::Rejected;
+                }
+                if end.index + 1 < self.values.len() && delta >= self.time_at_index(end.index + 1) - self.time_at_index(end.index) {
+                    return R::Rejected;
+                }
+
+                self.touch();
+                if start.index == 0 {
+                    // the curve always starts at zero, so the points after the range move instead
+                    self.end_times[end.index..].iter_mut().for_each(|f| *f -= delta);
+                } else {
+                    self.end_times[start.index - 1..end.index].iter_mut().for_each(|f| *f += delta);
+                }
+
+                let inverse_time = self.get_point_time(start) - delta;
+                R::applied(CurveCommand::SetRangeTime { start, end, time: inverse_time }, Some(start))
+            }
+
+            CurveCommand::SetSegmentShape { segment, shape } => {
+                if !self.segment_is_valid(segment) {
+                    return R::Rejected;
+                }
+                let previous = self.get_segment_shape(segment);
+                self.set_segment_shape(segment, shape);
+                R::applied(CurveCommand::SetSegmentShape { segment, shape: previous }, None)
+            }
+
+            // scaling back would not exactly reproduce the values, so the curve is restored instead
+            CurveCommand::ScaleTime { factor } => {
+                let snapshot = self.snapshot();
+                if !self.scale_time(factor) {
+                    return R::Rejected;
+                }
+                R::applied(snapshot, None)
+            }
+
+            CurveCommand::ScaleValues { factor, pivot } => {
+                let snapshot = self.snapshot();
+                if !self.scale_values(factor, pivot) {
+                    return R::Rejected;
+                }
+                R::applied(snapshot, None)
+            }
+
+            CurveCommand::ShiftValues { delta } => {
+                let snapshot = self.snapshot();
+                if !self.shift_values(delta) {
+                    return R::Rejected;
+                }
+                R::applied(snapshot, None)
+            }
+
+            CurveCommand::QuantizeTimes { grid, strength, excluded } => {
+                let snapshot = self.snapshot();
+                if !self.quantize_times(grid, strength, &excluded) {
+                    return R::Rejected;
+                }
+                R::applied(snapshot, None)
+            }
+
+            CurveCommand::InsertCurve { time, curve, mode } => {
+                let snapshot = self.snapshot();
+                if !self.insert_curve_at(time, &curve, mode) {
+                    return R::Rejected;
+                }
+                R::applied(snapshot, None)
+            }
+
+            CurveCommand::Append { curve, gap, crossfade } => {
+                let snapshot = self.snapshot();
+                if !self.append_with(&curve, gap, crossfade) {
+                    return R::Rejected;
+                }
+                R::applied(snapshot, None)
+            }
+
+            CurveCommand::Simplify { tolerance } => {
+                let snapshot = self.snapshot();
+                if self.simplify(tolerance) == 0 {
+                    return R::Rejected;
+                }
+                R::applied(snapshot, None)
+            }
+
+            // reversing twice would not exactly reproduce the times, so the curve is restored instead
+            CurveCommand::ReverseTime => {
+                let snapshot = self.snapshot();
+                self.reverse_time();
+                R::applied(snapshot, None)
+            }
+
+            CurveCommand::InvertValues { pivot } => {
+                let snapshot = self.snapshot();
+                if self.invert_values(pivot).is_none() {
+                    return R::Rejected;
+                }
+                R::applied(snapshot, None)
+            }
+
+            CurveCommand::MirrorAround { time } => {
+                let snapshot = self.snapshot();
+                if self.mirror_around(time).is_none() {
+                    return R::Rejected;
+                }
+                R::applied(snapshot, None)
+            }
+
+            CurveCommand::SetRange { range } => {
+                let previous = self.range;
+                self.set_range(range);
+                R::applied(CurveCommand::SetRange { range: previous }, None)
+            }
+
+            CurveCommand::Restore { curve } => {
+                let previous = std::mem::replace(self, *curve);
+                R::applied(CurveCommand::Restore { curve: Box::new(previous) }, None)
+            }
+        }
+    }
+
+    /// the time of the point with the given index, whichever side of it is meant
+    fn time_at_index(&self, index: usize) -> f64 {
+        if index == 0 {
+            0.0
+        } else {
+            self.end_times[index - 1]
+        }
+    }
+
+    /// a command restoring the curve as it is now
+    fn snapshot(&self) -> CurveCommand {
+        CurveCommand::Restore { curve: Box::new(self.clone()) }
+    }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the number of random cases checked by each property
+    const CASES: usize = 2000;
+
+    const TOLERANCE: f64 = 1e-9;
+
+    /// a curve of 2 to 8 points with random times, values, discontinuities and shapes
+    fn random_curve(random: &mut fastrand::Rng) -> Curve {
+        const SHAPES: [SmoothingShape; 3] = [SmoothingShape::Linear, SmoothingShape::Sine, SmoothingShape::Cubic];
+        const DIRECTIONS: [SmoothingDirection; 3] = [SmoothingDirection::In, SmoothingDirection::Out, SmoothingDirection::InOut];
+
+        let count = random.usize(2..=8);
+        let mut time = 0.0;
+        let end_times = (1..count)
+            .map(|_| {
+                time += 0.1 + random.f64();
+                time
+            })
+            .collect();
+        let values = (0..count)
+            .map(|index| {
+                let intermediate = index != 0 && index != count - 1;
+                if intermediate && random.bool() {
+                    CurveYValue::new_double(random.f64(), 1.0 + random.f64())
+                } else {
+                    CurveYValue::new_single(random.f64())
+                }
+            })
+            .collect();
+        let transitions = (1..count)
+            .map(|_| CurveShape::new(SHAPES[random.usize(..SHAPES.len())], DIRECTIONS[random.usize(..DIRECTIONS.len())]))
+            .collect();
+
+        let mut curve = Curve::new(0.0, 1.0);
+        curve.values = values;
+        curve.end_times = end_times;
+        curve.transitions = transitions;
+        curve
+    }
+
+    /// a valid id for a random point, naming a random side of continuous points
+    fn random_point(curve: &Curve, random: &mut fastrand::Rng) -> CurvePointId {
+        let index = random.usize(..curve.values.len());
+        let side = if curve.values[index].is_continuous() {
+            [CurvePointSide::Continuous, CurvePointSide::Left, CurvePointSide::Right][random.usize(..3)]
+        } else if random.bool() {
+            CurvePointSide::Left
+        } else {
+            CurvePointSide::Right
+        };
+        CurvePointId { index, side }
+    }
+
+    /// the limits and time of each point
+    fn points(curve: &Curve) -> Vec<(f64, f64, f64)> {
+        curve.values.iter()
+            .enumerate()
+            .map(|(index, value)| (value.left_limit, value.right_limit, curve.time_at_index(index)))
+            .collect()
+    }
+
+    fn assert_invariants(curve: &Curve) {
+        assert!(curve.values.len() >= 2, "the curve has fewer than two points");
+        assert_eq!(curve.transitions.len(), curve.values.len() - 1);
+        assert_eq!(curve.end_times.len(), curve.values.len() - 1);
+        assert!(curve.values.first().unwrap().is_continuous(), "the start point is discontinuous");
+        assert!(curve.values.last().unwrap().is_continuous(), "the end point is discontinuous");
+        assert!(curve.end_times[0] > 0.0, "the first end time is not positive");
+        assert!(curve.end_times.windows(2).all(|pair| pair[0] < pair[1]), "the end times are not increasing");
+    }
+
+    /// the points kept by deleting from start to end and the limits they should keep,
+    /// along with the first and last indices deleted entirely
+    fn expected_points(curve: &Curve, start: CurvePointId, end: CurvePointId) -> (Vec<(f64, f64, f64)>, usize, isize) {
+        let (start, end) = (start.min(end), start.max(end));
+        let start_whole = start.side != CurvePointSide::Right || curve.values[start.index].is_continuous();
+        let end_whole = end.side != CurvePointSide::Left || curve.values[end.index].is_continuous();
+        let first = if start_whole { start.index } else { start.index + 1 };
+        let last = if end_whole { end.index as isize } else { end.index as isize - 1 };
+
+        let mut expected = points(curve);
+        if !start_whole {
+            let (left, _, time) = expected[start.index];
+            expected[start.index] = (left, left, time);
+        }
+        if !end_whole {
+            let (_, right, time) = expected[end.index];
+            expected[end.index] = (right, right, time);
+        }
+        // nothing is deleted entirely, or the removal is refused for leaving fewer than two points
+        let deleted = (last - first as isize + 1).max(0) as usize;
+        if deleted == 0 || deleted > curve.values.len() - 2 {
+            return (expected, first, last);
+        }
+
+        let offset = if first == 0 { expected[last as usize + 1].2 } else { 0.0 };
+        expected.drain(first..=last as usize);
+        for point in &mut expected {
+            point.2 -= offset;
+        }
+        let (_, right, time) = expected[0];
+        expected[0] = (right, right, time);
+        let (left, _, time) = *expected.last().unwrap();
+        *expected.last_mut().unwrap() = (left, left, time);
+        (expected, first, last)
+    }
+
+    fn assert_close(actual: f64, expected: f64, what: &str) {
+        assert!((actual - expected).abs() <= TOLERANCE, "{} is {} rather than {}", what, actual, expected);
+    }
+
+    #[test]
+    fn remove_point_to_point_deletes_whole_points_and_partial_sides() {
+        let mut random = fastrand::Rng::with_seed(0x3839);
+        for _ in 0..CASES {
+            let mut curve = random_curve(&mut random);
+            let (start, end) = (random_point(&curve, &mut random), random_point(&curve, &mut random));
+            let original = curve.clone();
+            let (expected, first, last) = expected_points(&original, start, end);
+            let deleted = (last - first as isize + 1).max(0) as usize;
+
+            let Some(offset) = curve.remove_point_to_point(start, end) else {
+                assert!(deleted > original.values.len() - 2, "a removal leaving two points was refused");
+                assert_eq!(curve.to_bytes(), original.to_bytes(), "a refused removal changed the curve");
+                continue;
+            };
+
+            assert!(deleted <= original.values.len() - 2, "a removal leaving fewer than two points was accepted");
+            assert_invariants(&curve);
+            let actual = points(&curve);
+            assert_eq!(actual.len(), expected.len(), "removing {:?} to {:?} kept the wrong number of points", start, end);
+            for ((left, right, time), (expected_left, expected_right, expected_time)) in actual.into_iter().zip(expected) {
+                assert_eq!(left, expected_left, "removing {:?} to {:?} changed a left-hand limit", start, end);
+                assert_eq!(right, expected_right, "removing {:?} to {:?} changed a right-hand limit", start, end);
+                assert_close(time, expected_time, "a point's time");
+            }
+
+            let expected_offset = if first == 0 && deleted > 0 { original.time_at_index(last as usize + 1) } else { 0.0 };
+            assert_close(offset, expected_offset, "the change in start time");
+        }
+    }
+
+    #[test]
+    fn remove_point_to_point_keeps_values_outside_the_range() {
+        let mut random = fastrand::Rng::with_seed(0x3839_0001);
+        for _ in 0..CASES {
+            let mut curve = random_curve(&mut random);
+            let (start, end) = (random_point(&curve, &mut random), random_point(&curve, &mut random));
+            let original = curve.clone();
+            let (_, first, last) = expected_points(&original, start, end);
+            let Some(offset) = curve.remove_point_to_point(start, end) else {
+                continue;
+            };
+
+            // the last point before the range and the first point after it keep the limits facing away from the range
+            let (start, end) = (start.min(end), start.max(end));
+            if first > 0 {
+                let before = original.time_at_index(first - 1);
+                for _ in 0..20 {
+                    let time = random.f64() * before;
+                    assert_close(curve.value_at_time(time), original.value_at_time(time), "the value before the range");
+                }
+                if start.index == first - 1 {
+                    assert_close(
+                        curve.values[start.index].left_limit,
+                        original.values[start.index].left_limit,
+                        "the left-hand limit at the start of the range"
+                    );
+                }
+            }
+
+            let after_index = (last + 1) as usize;
+            if after_index < original.values.len() {
+                let after = original.time_at_index(after_index);
+                let duration = original.total_duration() - after;
+                for _ in 0..20 {
+                    let time = after + duration * (0.001 + 0.999 * random.f64());
+                    assert_close(curve.value_at_time(time - offset), original.value_at_time(time), "the value after the range");
+                }
+                if end.index == after_index {
+                    let kept = after_index - (last - first as isize + 1).max(0) as usize;
+                    assert_close(
+                        curve.values[kept].right_limit,
+                        original.values[end.index].right_limit,
+                        "the right-hand limit at the end of the range"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn delete_point_range_is_undone_by_its_inverse() {
+        let mut random = fastrand::Rng::with_seed(0x3839_0002);
+        for _ in 0..CASES {
+            let mut curve = random_curve(&mut random);
+            let (start, end) = (random_point(&curve, &mut random), random_point(&curve, &mut random));
+            let original = curve.to_bytes();
+
+            let CurveCommandResult::Applied { inverse, .. } = curve.apply(CurveCommand::DeletePointRange { start, end }) else {
+                assert_eq!(curve.to_bytes(), original, "a rejected command changed the curve");
+                continue;
+            };
+            assert_invariants(&curve);
+            for command in inverse {
+                assert!(curve.apply(command).is_applied(), "the inverse was rejected");
+            }
+            assert_eq!(curve.to_bytes(), original, "undoing the removal of {:?} to {:?} did not restore the curve", start, end);
+        }
+    }
+}
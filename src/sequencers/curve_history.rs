@@ -0,0 +1,124 @@
+use egui::{Key, KeyboardShortcut, Modifiers};
+
+use super::curve::{Curve, CurveCommand, CurveCommandResult};
+
+/// The undo history of a curve
+/// Each edit is stored as the commands that revert it, taken from the curve when the edit was applied.
+/// Undoing applies those commands and stores their own inverses for redo, so ids shifted by
+/// structural edits (inserting or deleting points) are always those of the curve as it is now.
+#[derive(Debug, Default)]
+pub struct CurveHistory {
+    /// the commands reverting each edit that may be undone, most recent last
+    undo_stack: Vec<Vec<CurveCommand>>,
+
+    /// the commands reapplying each edit that may be redone, most recently undone last
+    redo_stack: Vec<Vec<CurveCommand>>,
+
+    /// whether commands are being gathered into a single edit
+    grouping: bool,
+
+    /// whether the edit being gathered has been pushed to the undo stack yet
+    group_started: bool,
+}
+
+impl CurveHistory {
+    const UNDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+    const REDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::Z);
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// applies the command to the curve, recording it as an edit if it was applied
+    pub fn apply(&mut self, curve: &mut Curve, command: CurveCommand) -> CurveCommandResult {
+        let result = curve.apply(command);
+        if let CurveCommandResult::Applied { inverse, .. } = &result {
+            self.redo_stack.clear();
+            match self.undo_stack.last_mut() {
+                // the newest command must be reverted first, unless the edit already ends by
+                // restoring the curve from before the group, which reverts everything after it
+                Some(edit) if self.grouping && self.group_started => {
+                    if !matches!(edit.last(), Some(CurveCommand::Restore { .. })) {
+                        edit.splice(0..0, inverse.iter().cloned());
+                    }
+                }
+                _ => {
+                    self.undo_stack.push(inverse.clone());
+                    self.group_started = self.grouping;
+                }
+            }
+        }
+        result
+    }
+
+    /// starts gathering every applied command into a single edit, such as while dragging a point
+    pub fn begin_group(&mut self) {
+        self.grouping = true;
+        self.group_started = false;
+    }
+
+    /// stops gathering commands into the current edit
+    pub fn end_group(&mut self) {
+        self.grouping = false;
+        self.group_started = false;
+    }
+
+    /// returns true if there is an edit that may be undone
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    /// returns true if there is an edit that may be redone
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// reverts the most recent edit
+    /// returns the result of reverting it, holding the id of the point it moved back if any
+    pub fn undo(&mut self, curve: &mut Curve) -> Option<CurveCommandResult> {
+        let commands = self.undo_stack.pop()?;
+        let (redo, result) = Self::apply_all(curve, commands);
+        self.redo_stack.push(redo);
+        self.end_group();
+        Some(result)
+    }
+
+    /// reapplies the most recently undone edit
+    /// returns the result of reapplying it, holding the id of the point it moved if any
+    pub fn redo(&mut self, curve: &mut Curve) -> Option<CurveCommandResult> {
+        let commands = self.redo_stack.pop()?;
+        let (undo, result) = Self::apply_all(curve, commands);
+        self.undo_stack.push(undo);
+        self.end_group();
+        Some(result)
+    }
+
+    /// undoes on Ctrl+Z and redoes on Ctrl+Shift+Z (Cmd on macOS)
+    pub fn handle_shortcuts(&mut self, ui: &egui::Ui, curve: &mut Curve) -> Option<CurveCommandResult> {
+        // the redo shortcut is checked first, as the undo shortcut also matches with shift held
+        if ui.input_mut(|input| input.consume_shortcut(&Self::REDO_SHORTCUT)) {
+            self.redo(curve)
+        } else if ui.input_mut(|input| input.consume_shortcut(&Self::UNDO_SHORTCUT)) {
+            self.undo(curve)
+        } else {
+            None
+        }
+    }
+
+    /// applies the commands of an edit in order
+    /// returns the commands reverting them and the combined result
+    fn apply_all(curve: &mut Curve, commands: Vec<CurveCommand>) -> (Vec<CurveCommand>, CurveCommandResult) {
+        let mut inverses = Vec::with_capacity(commands.len());
+        let mut point = None;
+        for command in commands {
+            if let CurveCommandResult::Applied { inverse, point: moved } = curve.apply(command) {
+                inverses.push(inverse);
+                point = moved.or(point);
+            }
+        }
+
+        // the last command applied must be the first reverted
+        let inverse: Vec<CurveCommand> = inverses.into_iter().rev().flatten().collect();
+        (inverse.clone(), CurveCommandResult::Applied { inverse, point })
+    }
+}
@@ -0,0 +1,90 @@
+use super::curve::{Curve, CurveRange};
+
+/// Records the values of a parameter during playback, such as while a knob is turned, into a curve
+/// Each sample becomes a point joined linearly to the last, and the finished curve is simplified
+/// so that only the points needed to follow the movement within the tolerance are kept.
+#[derive(Debug, Clone)]
+pub struct CurveRecorder {
+    /// the range the recorded values are stored in
+    range: CurveRange,
+
+    /// the largest change simplifying may make, in stored values where the range spans 1
+    tolerance: f64,
+
+    /// the time of each sample, increasing
+    times: Vec<f64>,
+
+    /// the stored value of each sample
+    values: Vec<f64>,
+}
+
+impl CurveRecorder {
+    /// a tolerance of a fifth of a percent of the range, which is too small to hear on most parameters
+    pub const DEFAULT_TOLERANCE: f64 = 0.002;
+
+    /// samples closer together than this are dropped, which bounds the memory used by fast sample rates
+    pub const MIN_INTERVAL: f64 = 0.001;
+
+    pub fn new(range: CurveRange, tolerance: f64) -> Self {
+        Self {
+            range,
+            tolerance: tolerance.max(0.0),
+            times: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+
+    /// records the parameter's value (in the range's units) at the given time, such as the transport's position
+    /// samples that are not after the previous sample by at least MIN_INTERVAL are dropped
+    pub fn record(&mut self, time: f64, output: f64) {
+        if !time.is_finite() || !output.is_finite() {
+            return;
+        }
+        if let Some(last) = self.times.last() && time < last + Self::MIN_INTERVAL {
+            return;
+        }
+        self.times.push(time);
+        self.values.push(self.range.from_output(output));
+    }
+
+    /// returns true if nothing has been recorded
+    pub fn is_empty(&self) -> bool {
+        self.times.is_empty()
+    }
+
+    /// the time of the first sample, where the recorded curve begins
+    pub fn start_time(&self) -> Option<f64> {
+        self.times.first().copied()
+    }
+
+    /// discards everything recorded
+    pub fn clear(&mut self) {
+        self.times.clear();
+        self.values.clear();
+    }
+
+    /// creates the simplified curve from the samples, starting at the first sample, leaving the recorder empty
+    /// returns the time of the first sample, where the curve should be placed, and the curve
+    /// returns None if fewer than two samples were recorded
+    pub fn finish(&mut self) -> Option<(f64, Curve)> {
+        let times = std::mem::take(&mut self.times);
+        let values = std::mem::take(&mut self.values);
+        if times.len() < 2 {
+            return None;
+        }
+
+        let start = times[0];
+        let mut curve = Curve::new(values[0], times[1] - start);
+        let end = curve.last_point();
+        curve.set_point_value(end, values[1]);
+        for (time, value) in times.iter().zip(&values).skip(2) {
+            // points after the end of the curve are appended rather than inserted
+            let point = curve.insert_point_at_time(time - start)?;
+            curve.set_point_value(point, *value);
+        }
+
+        curve.set_range(self.range);
+        curve.simplify(self.tolerance);
+        Some((start, curve))
+    }
+}
@@ -0,0 +1,1084 @@
+use egui::{Align2, Button, Color32, FontId, Frame, Key, Modifiers, Painter, PointerButton, Pos2, Rect, Response, Sense, Shape, Stroke, StrokeKind, Ui, UiBuilder, Vec2};
+
+use crate::utils;
+
+use super::{
+    curve::{Curve, CurveCommand, CurveCommandResult, CurvePointId, CurveSegmentId, CurveShape},
+    curve_history::CurveHistory,
+};
+
+/// the spacing of the lines drawn behind a curve, which dragged points may snap to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CurveGrid {
+    /// the time between vertical lines
+    time: f64,
+
+    /// the stored value between horizontal lines
+    value: f64,
+
+    /// whether dragged points move onto the nearest lines
+    snap: bool,
+}
+
+impl CurveGrid {
+    /// creates a grid that points snap to
+    /// returns None if either spacing is not positive
+    pub fn new(time: f64, value: f64) -> Option<Self> {
+        if !(time.is_finite() && time > 0.0 && value.is_finite() && value > 0.0) {
+            return None;
+        }
+        Some(Self { time, value, snap: true })
+    }
+
+    /// sets whether dragged points move onto the nearest lines
+    pub fn with_snap(self, snap: bool) -> Self {
+        Self { snap, ..self }
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    pub fn snaps(&self) -> bool {
+        self.snap
+    }
+
+    /// returns the time of the nearest vertical line
+    pub fn snap_time(&self, time: f64) -> f64 {
+        (time / self.time).round() * self.time
+    }
+
+    /// returns the value of the nearest horizontal line
+    pub fn snap_value(&self, value: f64) -> f64 {
+        (value / self.value).round() * self.value
+    }
+}
+
+#[derive(Debug)]
+enum EditState {
+    /// no editing in progress
+    Viewing,
+
+    /// might start dragging point around screen
+    PreMoving(CurvePointId),
+
+    /// dragging a point around the screen
+    Moving(CurvePointId),
+
+    /// dragging a rectangle from the given screen position, selecting the points inside it
+    Selecting(Pos2),
+
+    /// dragging the selected points, grabbed at the given time and value
+    MovingSelection(f64, f64),
+
+    /// dragging the right-hand limit of a continuous point away from its left-hand limit
+    Splitting(CurvePointId),
+
+    /// using context menu for the given point
+    Configuring(CurvePointId, PointConfigMenu),
+}
+
+#[derive(Debug)]
+struct PointConfigMenu {
+    time_text: String,
+
+    /// the value of the point, or its left-hand limit if it is discontinuous
+    value_text: String,
+
+    /// the right-hand limit of the point if it is discontinuous
+    right_text: String,
+}
+
+impl EditState {
+    fn is_dragging(&self) -> bool {
+        matches!(*self, Self::Moving(_) | Self::Selecting(_) | Self::MovingSelection(..) | Self::Splitting(_))
+    }
+
+    fn is_moving_point(&self, point: CurvePointId) -> bool {
+        matches!(*self, Self::Moving(pt) if pt == point)
+    }
+}
+
+/// the area of the screen a curve is drawn in and the part of the curve it shows
+#[derive(Debug, Clone, Copy)]
+struct CurveView {
+    rect: Rect,
+    start_time: f64,
+    end_time: f64,
+    min_value: f64,
+    max_value: f64,
+}
+
+impl CurveView {
+    fn x(&self, time: f64) -> f32 {
+        ((time - self.start_time) / (self.end_time - self.start_time)) as f32 * self.rect.width() + self.rect.min.x
+    }
+
+    fn y(&self, value: f64) -> f32 {
+        self.rect.max.y - ((value - self.min_value) / (self.max_value - self.min_value)) as f32 * self.rect.height()
+    }
+
+    fn pos(&self, (time, value): (f64, f64)) -> Pos2 {
+        Pos2::new(self.x(time), self.y(value))
+    }
+
+    fn time(&self, x: f32) -> f64 {
+        (x - self.rect.min.x) as f64 / self.rect.width() as f64 * (self.end_time - self.start_time) + self.start_time
+    }
+
+    fn value(&self, y: f32) -> f64 {
+        (self.rect.max.y - y) as f64 / self.rect.height() as f64 * (self.max_value - self.min_value) + self.min_value
+    }
+}
+
+/// a function called with a curve after it is edited
+type ChangeCallback = Box<dyn FnMut(&Curve)>;
+
+/// An editor for a single curve, such as an envelope or an automation lane
+/// Points are dragged with the primary button and configured with the secondary button.
+/// Scrolling pans the view, Ctrl+scroll zooms it in time (and in value with Alt held), and double clicking
+/// a ruler shows the whole curve again.
+/// Dragging over empty space selects the points inside the rectangle, which are then dragged
+/// or deleted (with the delete key) together.
+/// Alt+dragging a point splits it into a discontinuity, moving its right-hand limit.
+/// Every edit is recorded in the widget's history and may be undone with Ctrl+Z while hovered.
+pub struct CurveWidget {
+    /// the curve being edited
+    curve: Curve,
+
+    /// the edits made to the curve
+    history: CurveHistory,
+
+    /// the current state of the editor
+    edit_state: EditState,
+
+    /// the last known mouse position on the editor
+    saved_mouse_pos: Pos2,
+
+    /// if the last edit state was configuring a point
+    last_config_point: Option<CurvePointId>,
+
+    /// the selected points, in order
+    selection: Vec<CurvePointId>,
+
+    /// whether the curve was edited since the editor was last shown
+    changed: bool,
+
+    /// the stored values at the bottom and top of the editor
+    value_range: (f64, f64),
+
+    /// the lines drawn behind the curve, if any
+    grid: Option<CurveGrid>,
+
+    /// the times shown at the left and right of the editor, or None to show the whole curve
+    visible_time: Option<(f64, f64)>,
+
+    /// the stored values shown at the bottom and top of the editor, or None to show the value range
+    visible_values: Option<(f64, f64)>,
+
+    /// called with the curve after every edit, including undo and redo
+    on_change: Option<ChangeCallback>,
+}
+
+impl CurveWidget {
+    const LINE_THICKNESS: f32 = 1.5;
+    const POINT_RADIUS: f32 = 3.0;
+    const POINT_INTERACT_RADIUS: f32 = 8.0;
+    const POINT_COLOR: Color32 = Color32::WHITE;
+    const FOCUS_POINT_COLOR: Color32 = Color32::RED;
+    const SELECTED_POINT_COLOR: Color32 = Color32::YELLOW;
+    const SELECTION_FILL: Color32 = Color32::from_rgba_premultiplied(16, 16, 16, 16);
+    const GRID_COLOR: Color32 = Color32::from_gray(60);
+
+    /// the number of lines each segment is drawn with
+    const SEGMENT_STEPS: usize = 48;
+
+    /// the closest a dragged selection may come to the points around it
+    const MIN_POINT_GAP: f64 = 1e-6;
+
+    /// grids denser than this many lines across the editor are not drawn
+    const MAX_GRID_LINES: f64 = 200.0;
+
+    /// the most the view may be magnified, as the whole curve over the part shown
+    const MAX_ZOOM: f64 = 1000.0;
+
+    const RULER_HEIGHT: f32 = 18.0;
+    const RULER_WIDTH: f32 = 48.0;
+    const RULER_FONT_SIZE: f32 = 10.0;
+
+    /// the closest ticks on a ruler may be, in points
+    const MIN_TICK_SPACING: f32 = 48.0;
+
+    pub const MIN_WIDTH: f32 = 200.0;
+    pub const MIN_HEIGHT: f32 = 200.0;
+
+    const CONFIG_WIDTH: f32 = 150.0;
+    const CONFIG_HEIGHT: f32 = 190.0;
+    const CONFIG_X_OFFSET: f32 = 10.0;
+    const CONFIG_Y_OFFSET: f32 = 10.0;
+
+    const POPUP_PADDING: f32 = 20.0;
+    const POPUP_MARGIN: f32 = 4.0;
+
+    pub fn new(curve: Curve) -> Self {
+        Self {
+            curve,
+            history: CurveHistory::new(),
+            edit_state: EditState::Viewing,
+            saved_mouse_pos: Pos2::ZERO,
+            last_config_point: None,
+            selection: Vec::new(),
+            changed: false,
+            value_range: (0.0, 1.0),
+            grid: None,
+            visible_time: None,
+            visible_values: None,
+            on_change: None,
+        }
+    }
+
+    /// sets the stored values shown at the bottom and top of the editor, which dragged points stay within
+    pub fn with_value_range(mut self, min: f64, max: f64) -> Self {
+        self.set_value_range(min, max);
+        self
+    }
+
+    /// sets the lines drawn behind the curve
+    pub fn with_grid(mut self, grid: CurveGrid) -> Self {
+        self.grid = Some(grid);
+        self
+    }
+
+    /// sets the function called with the curve after every edit, including undo and redo
+    pub fn on_change(mut self, callback: impl FnMut(&Curve) + 'static) -> Self {
+        self.on_change = Some(Box::new(callback));
+        self
+    }
+
+    pub fn curve(&self) -> &Curve {
+        &self.curve
+    }
+
+    /// replaces the curve being edited, discarding the history of the previous curve
+    pub fn set_curve(&mut self, curve: Curve) {
+        self.curve = curve;
+        self.history = CurveHistory::new();
+        self.edit_state = EditState::Viewing;
+        self.last_config_point = None;
+        self.selection.clear();
+    }
+
+    /// the selected points, in order
+    pub fn selection(&self) -> &[CurvePointId] {
+        &self.selection
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selection.clear();
+    }
+
+    pub fn value_range(&self) -> (f64, f64) {
+        self.value_range
+    }
+
+    /// sets the stored values shown at the bottom and top of the editor
+    /// returns false and leaves the range unchanged if min is not below max
+    pub fn set_value_range(&mut self, min: f64, max: f64) -> bool {
+        if !(min.is_finite() && max.is_finite() && min < max) {
+            return false;
+        }
+        self.value_range = (min, max);
+        true
+    }
+
+    pub fn grid(&self) -> Option<CurveGrid> {
+        self.grid
+    }
+
+    pub fn set_grid(&mut self, grid: Option<CurveGrid>) {
+        self.grid = grid;
+    }
+
+    /// the times shown at the left and right of the editor
+    pub fn visible_time(&self) -> (f64, f64) {
+        self.visible_time.unwrap_or((0.0, self.curve.total_duration()))
+    }
+
+    /// shows the given times, which are kept within the curve
+    /// returns false and leaves the view unchanged if start is not before end
+    pub fn set_visible_time(&mut self, start: f64, end: f64) -> bool {
+        if !(start.is_finite() && end.is_finite() && start < end) {
+            return false;
+        }
+        self.visible_time = clamp_window((start, end), (0.0, self.curve.total_duration()));
+        true
+    }
+
+    /// the stored values shown at the bottom and top of the editor
+    pub fn visible_values(&self) -> (f64, f64) {
+        self.visible_values.unwrap_or(self.value_range)
+    }
+
+    /// shows the given stored values, which are kept within the value range
+    /// returns false and leaves the view unchanged if min is not below max
+    pub fn set_visible_values(&mut self, min: f64, max: f64) -> bool {
+        if !(min.is_finite() && max.is_finite() && min < max) {
+            return false;
+        }
+        self.visible_values = clamp_window((min, max), self.value_range);
+        true
+    }
+
+    /// shows the whole curve and value range
+    pub fn zoom_to_fit(&mut self) {
+        self.visible_time = None;
+        self.visible_values = None;
+    }
+
+    /// draws the editor filling the available space and handles its input
+    /// the response is marked as changed if the curve was edited
+    pub fn show(&mut self, ui: &mut Ui) -> Response {
+        let request_dim = {
+            let available = ui.available_size();
+            Vec2::new(available.x.max(Self::MIN_WIDTH), available.y.max(Self::MIN_HEIGHT))
+        };
+        let (mut response, painter) = ui.allocate_painter(request_dim, Sense::click_and_drag());
+
+        if response.hovered() && self.history.handle_shortcuts(ui, &mut self.curve).is_some() {
+            // ids held by the editor may not refer to the same points after undoing
+            self.edit_state = EditState::Viewing;
+            self.last_config_point = None;
+            self.selection.clear();
+            self.notify();
+        }
+
+        let plot = Rect::from_min_max(
+            response.rect.min + Vec2::new(Self::RULER_WIDTH, Self::RULER_HEIGHT),
+            response.rect.max
+        );
+        self.navigate(ui, &response, plot);
+        let view = self.view(plot);
+        self.draw_rulers(ui, &painter, view);
+
+        // nothing but the rulers is drawn over the rulers
+        let painter = painter.with_clip_rect(plot.intersect(painter.clip_rect()));
+        let stroke = Stroke::new(Self::LINE_THICKNESS, Self::POINT_COLOR);
+
+        // mouse/interaction position relative to current ui
+        let mouse_pos = ui.input(|input| {
+            if let Some(pos) = input.pointer.latest_pos() {
+                self.saved_mouse_pos = pos;
+                pos
+            } else {
+                self.saved_mouse_pos
+            }
+        });
+
+        if let Some(grid) = self.grid {
+            self.draw_grid(&painter, view, grid);
+        }
+
+        if response.drag_started_by(PointerButton::Primary) {
+            let alt = ui.input(|input| input.modifiers.alt);
+            match self.edit_state {
+                EditState::PreMoving(point) if alt && self.curve.point_is_intermediate(point) && point.is_continuous() => {
+                    self.selection.clear();
+                    self.edit_state = EditState::Splitting(point);
+                }
+                EditState::PreMoving(point) if self.selection.len() > 1 && self.selection.contains(&point) => {
+                    let (time, value) = self.curve.get_point_coords(point);
+                    self.edit_state = EditState::MovingSelection(time, value);
+                }
+                EditState::PreMoving(point) => {
+                    self.selection.clear();
+                    self.edit_state = EditState::Moving(point);
+                }
+                _ => {
+                    let origin = ui.input(|input| input.pointer.press_origin()).unwrap_or(mouse_pos);
+                    if plot.contains(origin) {
+                        self.edit_state = EditState::Selecting(origin);
+                        self.last_config_point = None;
+                    }
+                }
+            }
+        }
+
+        // the selection or limit being dragged is drawn where it would be dropped
+        let preview = match self.edit_state {
+            EditState::MovingSelection(time, value) => {
+                let mut preview = self.curve.clone();
+                for command in self.selection_move(view, (time, value), mouse_pos) {
+                    preview.apply(command);
+                }
+                Some(preview)
+            }
+            EditState::Splitting(point) => {
+                let mut preview = self.curve.clone();
+                preview.apply(self.split(view, point, mouse_pos));
+                Some(preview)
+            }
+            _ => None,
+        };
+        let curve = preview.as_ref().unwrap_or(&self.curve);
+
+        // draw the point being moved and the segments around it
+        if let EditState::Moving(point_id) = self.edit_state {
+            let point = view.pos(self.drag_target(view, point_id, mouse_pos));
+
+            if let Some(l_point_id) = self.curve.prev_point(point_id) {
+                let l_point = view.pos(self.curve.get_point_coords(l_point_id));
+                match self.curve.make_segment(l_point_id, point_id) {
+                    Some(segment) => {
+                        let shape = self.curve.get_segment_shape(segment);
+                        painter.add(Shape::line(Self::segment_line(shape, l_point, point), stroke));
+                    }
+                    None => {
+                        painter.line_segment([l_point, point], stroke);
+                    }
+                }
+            }
+
+            if let Some(r_point_id) = self.curve.next_point(point_id) {
+                let r_point = view.pos(self.curve.get_point_coords(r_point_id));
+                match self.curve.make_segment(point_id, r_point_id) {
+                    Some(segment) => {
+                        let shape = self.curve.get_segment_shape(segment);
+                        painter.add(Shape::line(Self::segment_line(shape, point, r_point), stroke));
+                    }
+                    None => {
+                        painter.line_segment([point, r_point], stroke);
+                    }
+                }
+            }
+
+            painter.circle_filled(point, Self::POINT_RADIUS, Self::FOCUS_POINT_COLOR);
+        }
+
+        // draw non-moving edges
+        for (p1_id, p2_id) in curve.point_pairs_iter() {
+            if self.edit_state.is_moving_point(p1_id) || self.edit_state.is_moving_point(p2_id) {
+                continue;
+            }
+
+            let point1 = view.pos(curve.get_point_coords(p1_id));
+            let point2 = view.pos(curve.get_point_coords(p2_id));
+
+            if let Some(seg_id) = curve.make_segment(p1_id, p2_id) {
+                let shape = curve.get_segment_shape(seg_id);
+                painter.add(Shape::line(Self::segment_line(shape, point1, point2), stroke));
+            } else {
+                painter.line_segment([point1, point2], stroke);
+            }
+        }
+
+        // draw non-moving points
+        for point_id in curve.point_iter() {
+            if self.edit_state.is_moving_point(point_id) {
+                continue;
+            }
+            let coords = view.pos(curve.get_point_coords(point_id));
+
+            if let Some(response_pos) = response.interact_pointer_pos() && plot.contains(coords) {
+                let on_point = (response_pos - coords).length() <= Self::POINT_INTERACT_RADIUS;
+                if on_point {
+                    if response.secondary_clicked() {
+                        self.last_config_point = Some(point_id);
+                        self.edit_state = EditState::Configuring(point_id, self.config_menu(point_id));
+                    } else if response.is_pointer_button_down_on() && !self.edit_state.is_dragging() {
+                        self.edit_state = EditState::PreMoving(point_id);
+                    }
+                }
+            }
+
+            let should_focus = match self.edit_state {
+                EditState::Configuring(cfg_id, _) => curve.does_point_contain_partial(point_id, cfg_id),
+                EditState::PreMoving(point) => point_id == point,
+                _ => false
+            };
+
+            painter.circle_filled(
+                coords,
+                Self::POINT_RADIUS,
+                if should_focus {
+                    Self::FOCUS_POINT_COLOR
+                } else if self.selection.contains(&point_id) {
+                    Self::SELECTED_POINT_COLOR
+                } else {
+                    Self::POINT_COLOR
+                }
+            );
+        }
+
+        // detect if moving has stopped
+        if let EditState::Moving(point) = self.edit_state && ui.input(|input| !input.pointer.primary_down()) {
+            let (time, value) = self.drag_target(view, point, mouse_pos);
+
+            // the time and value are undone together
+            self.history.begin_group();
+            let mut new_point = point;
+            if self.curve.point_is_intermediate(point) && let Some(moved) = self.apply(CurveCommand::SetPointTime { point, time }) {
+                new_point = moved;
+            }
+            if let Some(moved) = self.apply(CurveCommand::SetPointValue { point: new_point, value }) {
+                new_point = moved;
+            }
+            self.history.end_group();
+
+            if let Some(last_point) = self.last_config_point && last_point == point {
+                self.last_config_point = Some(new_point);
+                self.edit_state = EditState::Configuring(new_point, self.config_menu(new_point));
+            } else {
+                self.edit_state = EditState::Viewing;
+            }
+        }
+
+        // draw the selection rectangle, selecting the points inside it once released
+        if let EditState::Selecting(origin) = self.edit_state {
+            let rect = Rect::from_two_pos(origin, mouse_pos).intersect(plot);
+            painter.rect(rect, 0.0, Self::SELECTION_FILL, Stroke::new(1.0, Self::SELECTED_POINT_COLOR), StrokeKind::Inside);
+
+            if ui.input(|input| !input.pointer.primary_down()) {
+                if !ui.input(|input| input.modifiers.shift) {
+                    self.selection.clear();
+                }
+                for point in self.curve.point_iter() {
+                    if rect.contains(view.pos(self.curve.get_point_coords(point))) && !self.selection.contains(&point) {
+                        self.selection.push(point);
+                    }
+                }
+                self.selection.sort();
+                self.edit_state = EditState::Viewing;
+            }
+        }
+
+        // detect if moving the selection has stopped
+        if let EditState::MovingSelection(time, value) = self.edit_state && ui.input(|input| !input.pointer.primary_down()) {
+            let commands = self.selection_move(view, (time, value), mouse_pos);
+
+            // setting values may change which side of a discontinuity an id refers to
+            self.history.begin_group();
+            let mut selection = Vec::new();
+            for command in commands {
+                let sets_value = matches!(command, CurveCommand::SetPointValue { .. });
+                if let Some(point) = self.apply(command) && sets_value {
+                    selection.push(point);
+                }
+            }
+            self.history.end_group();
+
+            if !selection.is_empty() {
+                selection.sort();
+                selection.dedup();
+                self.selection = selection;
+            }
+            self.edit_state = EditState::Viewing;
+        }
+
+        // detect if splitting has stopped
+        if let EditState::Splitting(point) = self.edit_state && ui.input(|input| !input.pointer.primary_down()) {
+            let command = self.split(view, point, mouse_pos);
+            if !matches!(command, CurveCommand::SetPointValue { value, .. } if value == self.curve.get_point_value(point)) {
+                self.apply(command);
+            }
+            self.edit_state = EditState::Viewing;
+            self.last_config_point = None;
+        }
+
+        // detect if editing has stopped, selecting clicked points
+        if response.clicked() {
+            let shift = ui.input(|input| input.modifiers.shift);
+            if let EditState::PreMoving(point) = self.edit_state {
+                if !shift {
+                    self.selection.clear();
+                }
+                match self.selection.binary_search(&point) {
+                    Ok(index) => {
+                        self.selection.remove(index);
+                    }
+                    Err(index) => self.selection.insert(index, point),
+                }
+            } else {
+                if !shift {
+                    self.selection.clear();
+                }
+                self.edit_state = EditState::Viewing;
+                self.last_config_point = None;
+            }
+        }
+
+        let configuring = matches!(self.edit_state, EditState::Configuring(..));
+        if response.hovered() && !configuring && !self.selection.is_empty()
+            && ui.input_mut(|input| input.consume_key(Modifiers::NONE, Key::Delete)) {
+            self.delete_selection();
+        }
+
+        if let Some(command) = self.config_popup(ui, view) {
+            let structural = matches!(command, CurveCommand::AddPoint { .. } | CurveCommand::DeletePoint { .. });
+            let moved = self.apply(command);
+            self.selection.clear();
+
+            if structural {
+                self.edit_state = EditState::Viewing;
+                self.last_config_point = None;
+            } else if let Some(point) = moved {
+                self.last_config_point = Some(point);
+                self.edit_state = EditState::Configuring(point, self.config_menu(point));
+            }
+        }
+
+        if std::mem::take(&mut self.changed) {
+            response.mark_changed();
+        }
+        response
+    }
+
+    /// applies the command to the curve through the history, calling the change callback if it was applied
+    /// returns the id of the point created or moved by the command, if any
+    fn apply(&mut self, command: CurveCommand) -> Option<CurvePointId> {
+        match self.history.apply(&mut self.curve, command) {
+            CurveCommandResult::Applied { point, .. } => {
+                self.notify();
+                point
+            }
+            CurveCommandResult::Rejected => None,
+        }
+    }
+
+    fn notify(&mut self) {
+        self.changed = true;
+        if let Some(callback) = &mut self.on_change {
+            callback(&self.curve);
+        }
+    }
+
+    /// deletes the selected points as a single edit
+    /// points that would leave the curve with fewer than two points are kept
+    fn delete_selection(&mut self) {
+        let runs = self.selection_runs();
+
+        // later runs are deleted first so that the ids of earlier runs stay valid
+        self.history.begin_group();
+        for (start, end) in runs.into_iter().rev() {
+            self.apply(CurveCommand::DeletePointRange { start, end });
+        }
+        self.history.end_group();
+
+        self.selection.clear();
+        self.edit_state = EditState::Viewing;
+        self.last_config_point = None;
+    }
+
+    /// the first and last point of each run of consecutive selected points
+    fn selection_runs(&self) -> Vec<(CurvePointId, CurvePointId)> {
+        let mut runs = Vec::new();
+        let mut run: Option<(CurvePointId, CurvePointId)> = None;
+        for point in self.curve.point_iter() {
+            if self.selection.contains(&point) {
+                run = Some(match run {
+                    Some((start, _)) => (start, point),
+                    None => (point, point),
+                });
+            } else if let Some(finished) = run.take() {
+                runs.push(finished);
+            }
+        }
+        runs.extend(run);
+        runs
+    }
+
+    /// the commands moving the selection grabbed at the given time and value to the mouse
+    /// the selection keeps its times if it holds the first or last point, and may not pass the points around it
+    fn selection_move(&self, view: CurveView, (grab_time, grab_value): (f64, f64), mouse_pos: Pos2) -> Vec<CurveCommand> {
+        let (time, value) = self.snap(view.time(mouse_pos.x), view.value(mouse_pos.y));
+        let runs = self.selection_runs();
+
+        let holds_ends = self.selection.iter().any(|point| !self.curve.point_is_intermediate(*point));
+        let mut delta_time = if holds_ends { 0.0 } else { time - grab_time };
+        if delta_time != 0.0 {
+            let (mut min_delta, mut max_delta) = (f64::NEG_INFINITY, f64::INFINITY);
+            for (start, end) in &runs {
+                let start_time = self.curve.get_point_time(*start);
+                let end_time = self.curve.get_point_time(*end);
+                for point in self.curve.point_iter() {
+                    let point_time = self.curve.get_point_time(point);
+                    if point_time < start_time {
+                        min_delta = min_delta.max(point_time - start_time);
+                    } else if point_time > end_time {
+                        max_delta = max_delta.min(point_time - end_time);
+                    }
+                }
+            }
+            let (min_delta, max_delta) = (min_delta + Self::MIN_POINT_GAP, max_delta - Self::MIN_POINT_GAP);
+            delta_time = if min_delta <= max_delta { delta_time.clamp(min_delta, max_delta) } else { 0.0 };
+        }
+
+        let values = self.selection.iter().map(|point| self.curve.get_point_value(*point));
+        let lowest = values.clone().fold(f64::INFINITY, f64::min);
+        let highest = values.fold(f64::NEG_INFINITY, f64::max);
+        let delta_value = (value - grab_value)
+            .min(self.value_range.1 - highest)
+            .max(self.value_range.0 - lowest);
+
+        let mut commands = Vec::new();
+        if delta_time != 0.0 {
+            for (start, end) in runs {
+                let time = self.curve.get_point_time(start) + delta_time;
+                commands.push(CurveCommand::SetRangeTime { start, end, time });
+            }
+        }
+        if delta_value != 0.0 {
+            for point in &self.selection {
+                let value = self.curve.get_point_value(*point) + delta_value;
+                commands.push(CurveCommand::SetPointValue { point: *point, value });
+            }
+        }
+        commands
+    }
+
+    /// the command setting the right-hand limit of a point being split to the mouse's value
+    fn split(&self, view: CurveView, point: CurvePointId, mouse_pos: Pos2) -> CurveCommand {
+        let (_, value) = self.snap(view.time(mouse_pos.x), view.value(mouse_pos.y));
+        CurveCommand::SetPointValue { point: self.curve.right_partial(point), value }
+    }
+
+    /// snaps the time and value to the grid if it snaps, keeping the value within the editor
+    fn snap(&self, time: f64, value: f64) -> (f64, f64) {
+        let (time, value) = match self.grid {
+            Some(grid) if grid.snaps() => (grid.snap_time(time), grid.snap_value(value)),
+            _ => (time, value),
+        };
+        (time, value.clamp(self.value_range.0, self.value_range.1))
+    }
+
+    /// the time and value a point being dragged to the mouse would be given
+    /// intermediate points stay between their neighbours, and the first and last points stay at their times
+    fn drag_target(&self, view: CurveView, point: CurvePointId, mouse_pos: Pos2) -> (f64, f64) {
+        let (time, value) = self.snap(view.time(mouse_pos.x), view.value(mouse_pos.y));
+        let time = match (self.curve.prev_point(point), self.curve.next_point(point)) {
+            (Some(l_point), Some(r_point)) => time.clamp(
+                self.curve.get_point_time(l_point),
+                self.curve.get_point_time(r_point),
+            ),
+            _ => self.curve.get_point_time(point),
+        };
+        (time, value)
+    }
+
+    fn draw_grid(&self, painter: &Painter, view: CurveView, grid: CurveGrid) {
+        let stroke = Stroke::new(1.0, Self::GRID_COLOR);
+
+        if (view.end_time - view.start_time) / grid.time() <= Self::MAX_GRID_LINES {
+            let mut time = (view.start_time / grid.time()).ceil() * grid.time();
+            while time <= view.end_time {
+                let x = view.x(time);
+                painter.line_segment([Pos2::new(x, view.rect.min.y), Pos2::new(x, view.rect.max.y)], stroke);
+                time += grid.time();
+            }
+        }
+
+        if (view.max_value - view.min_value) / grid.value() <= Self::MAX_GRID_LINES {
+            let mut value = (view.min_value / grid.value()).ceil() * grid.value();
+            while value <= view.max_value {
+                let y = view.y(value);
+                painter.line_segment([Pos2::new(view.rect.min.x, y), Pos2::new(view.rect.max.x, y)], stroke);
+                value += grid.value();
+            }
+        }
+    }
+
+    /// zooms the view with Ctrl+scroll (Alt+Ctrl+scroll for values) and pans it with scrolling or the middle button
+    /// double clicking a ruler shows the whole curve
+    fn navigate(&mut self, ui: &Ui, response: &Response, plot: Rect) {
+        let view = self.view(plot);
+        let time_bounds = (0.0, self.curve.total_duration());
+        let value_bounds = self.value_range;
+        let time_window = (view.start_time, view.end_time);
+        let value_window = (view.min_value, view.max_value);
+
+        if response.hovered() {
+            let (zoom, scroll, alt) = ui.input(|input| (input.zoom_delta(), input.smooth_scroll_delta, input.modifiers.alt));
+            let anchor = response.hover_pos().unwrap_or(plot.center());
+            if zoom != 1.0 {
+                if alt {
+                    self.visible_values = zoom_window(value_window, zoom as f64, view.value(anchor.y), value_bounds);
+                } else {
+                    self.visible_time = zoom_window(time_window, zoom as f64, view.time(anchor.x), time_bounds);
+                }
+            } else if scroll != Vec2::ZERO {
+                self.pan(view, scroll);
+            }
+
+            if response.double_clicked() && !plot.contains(anchor) {
+                self.zoom_to_fit();
+            }
+        }
+
+        if response.dragged_by(PointerButton::Middle) {
+            self.pan(view, response.drag_delta());
+        }
+
+        // the curve or value range may have changed since the view was set
+        self.visible_time = self.visible_time.and_then(|window| clamp_window(window, time_bounds));
+        self.visible_values = self.visible_values.and_then(|window| clamp_window(window, value_bounds));
+    }
+
+    /// moves the view with the content by the given screen distance
+    fn pan(&mut self, view: CurveView, delta: Vec2) {
+        if self.visible_time.is_some() {
+            let time_delta = view.time(view.rect.min.x) - view.time(view.rect.min.x + delta.x);
+            self.visible_time = clamp_window(
+                (view.start_time + time_delta, view.end_time + time_delta),
+                (0.0, self.curve.total_duration())
+            );
+        }
+        if self.visible_values.is_some() {
+            let value_delta = view.value(view.rect.max.y) - view.value(view.rect.max.y + delta.y);
+            self.visible_values = clamp_window(
+                (view.min_value + value_delta, view.max_value + value_delta),
+                self.value_range
+            );
+        }
+    }
+
+    fn view(&self, rect: Rect) -> CurveView {
+        let (start_time, end_time) = self.visible_time();
+        let (min_value, max_value) = self.visible_values();
+        CurveView { rect, start_time, end_time, min_value, max_value }
+    }
+
+    /// draws the time ruler above the editor and the value ruler to its left
+    /// the time ruler counts in the curve's units (beats for automation lanes) and the value ruler in the range's units
+    fn draw_rulers(&self, ui: &Ui, painter: &Painter, view: CurveView) {
+        let visuals = ui.visuals();
+        let rect = painter.clip_rect();
+        let time_ruler = Rect::from_min_max(Pos2::new(view.rect.min.x, rect.min.y), Pos2::new(rect.max.x, view.rect.min.y));
+        let value_ruler = Rect::from_min_max(Pos2::new(rect.min.x, view.rect.min.y), Pos2::new(view.rect.min.x, rect.max.y));
+        painter.rect_filled(time_ruler, 0.0, visuals.extreme_bg_color);
+        painter.rect_filled(value_ruler, 0.0, visuals.extreme_bg_color);
+
+        let font = FontId::monospace(Self::RULER_FONT_SIZE);
+        let color = visuals.weak_text_color();
+        let stroke = Stroke::new(1.0, color);
+
+        let step = ruler_step(view.end_time - view.start_time, view.rect.width() / Self::MIN_TICK_SPACING);
+        let decimals = (-step.log10().floor()).max(0.0) as usize;
+        let mut time = (view.start_time / step).ceil() * step;
+        while time <= view.end_time {
+            let x = view.x(time);
+            painter.line_segment([Pos2::new(x, time_ruler.max.y - 4.0), Pos2::new(x, time_ruler.max.y)], stroke);
+            painter.text(Pos2::new(x + 2.0, time_ruler.min.y), Align2::LEFT_TOP, format!("{:.*}", decimals, time), font.clone(), color);
+            time += step;
+        }
+
+        let range = self.curve.range();
+        let step = ruler_step(view.max_value - view.min_value, view.rect.height() / Self::MIN_TICK_SPACING);
+        let mut value = (view.min_value / step).ceil() * step;
+        while value <= view.max_value {
+            let y = view.y(value);
+            painter.line_segment([Pos2::new(value_ruler.max.x - 4.0, y), Pos2::new(value_ruler.max.x, y)], stroke);
+            painter.text(Pos2::new(value_ruler.max.x - 6.0, y), Align2::RIGHT_CENTER, ruler_label(range.to_output(value)), font.clone(), color);
+            value += step;
+        }
+    }
+
+    /// the screen positions a segment with the given shape is drawn through
+    fn segment_line(shape: CurveShape, start: Pos2, end: Pos2) -> Vec<Pos2> {
+        (0..=Self::SEGMENT_STEPS)
+            .map(|step| {
+                let x = step as f64 / Self::SEGMENT_STEPS as f64;
+                let y = shape.interpolate(x, 0.0, 1.0, start.y as f64, end.y as f64);
+                Pos2::new(start.x + (end.x - start.x) * x as f32, y as f32)
+            })
+            .collect()
+    }
+
+    fn config_menu(&self, point: CurvePointId) -> PointConfigMenu {
+        let range = self.curve.range();
+        let left = self.curve.get_point_value(self.curve.left_partial(point));
+        let right = self.curve.get_point_value(self.curve.right_partial(point));
+        PointConfigMenu {
+            time_text: self.curve.get_point_time(point).to_string(),
+            value_text: range.to_output(left).to_string(),
+            right_text: range.to_output(right).to_string(),
+        }
+    }
+
+    /// draws the menu of the point being configured, if any
+    /// returns the edit made in the menu
+    fn config_popup(&mut self, ui: &mut Ui, view: CurveView) -> Option<CurveCommand> {
+        let EditState::Configuring(point, menu_data) = &mut self.edit_state else {
+            return None;
+        };
+        let point = *point;
+        let curve = &self.curve;
+        let coords = view.pos(curve.get_point_coords(point));
+
+        let popup_pos = Pos2 {
+            x: (coords.x + Self::CONFIG_X_OFFSET).clamp(
+                view.rect.min.x + Self::POPUP_PADDING + Self::POPUP_MARGIN,
+                view.rect.max.x - Self::CONFIG_WIDTH - Self::POPUP_PADDING - Self::POPUP_MARGIN
+            ),
+            y: (coords.y + Self::CONFIG_Y_OFFSET).clamp(
+                view.rect.min.y + Self::POPUP_PADDING + Self::POPUP_MARGIN,
+                view.rect.max.y - Self::CONFIG_HEIGHT - Self::POPUP_PADDING - Self::POPUP_MARGIN
+            ),
+        };
+
+        let popup_rect = Rect::from_min_size(popup_pos, Vec2::new(Self::CONFIG_WIDTH, Self::CONFIG_HEIGHT));
+
+        let mut command = None;
+        ui.scope_builder(UiBuilder::new().max_rect(popup_rect).sense(Sense::click()), |ui| {
+            let frame = Frame::new()
+                .stroke(ui.visuals().window_stroke)
+                .fill(ui.visuals().window_fill)
+                .inner_margin(Self::POPUP_MARGIN);
+            frame.show(ui, |ui| {
+                if curve.point_is_discontinuous(point) {
+                    let left = curve.left_partial(point);
+                    let right = curve.right_partial(point);
+                    if let Some(edit) = Self::value_input(curve, ui, "Left", left, &mut menu_data.value_text) {
+                        command = Some(edit);
+                    }
+                    if let Some(edit) = Self::value_input(curve, ui, "Right", right, &mut menu_data.right_text) {
+                        command = Some(edit);
+                    }
+                    if ui.button("Join").clicked() {
+                        command = Some(CurveCommand::SetPointValue { point: right, value: curve.get_point_value(left) });
+                    }
+                } else if let Some(edit) = Self::value_input(curve, ui, "Value", point, &mut menu_data.value_text) {
+                    command = Some(edit);
+                }
+
+                if curve.point_is_intermediate(point) {
+                    ui.label("Time:");
+                    let mut time = curve.get_point_time(point);
+                    if utils::non_neg_number_input(ui, &mut menu_data.time_text, &mut time) {
+                        command = Some(CurveCommand::SetPointTime { point, time });
+                    }
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label("Add Point:");
+                    let left_button = Button::new("L");
+                    if ui.add_enabled(!curve.point_is_start(point), left_button).clicked() {
+                        let l_point = curve.prev_point(point).unwrap();
+                        let time = (curve.get_point_time(l_point) + curve.get_point_time(point)) / 2.0;
+                        let value = curve.value_at_time(time);
+                        command = Some(CurveCommand::AddPoint { point: l_point, value, time });
+                    }
+
+                    let right_button = Button::new("R");
+                    if ui.add_enabled(!curve.point_is_end(point), right_button).clicked() {
+                        let r_point = curve.next_point(point).unwrap();
+                        let time = (curve.get_point_time(point) + curve.get_point_time(r_point)) / 2.0;
+                        let value = curve.value_at_time(time);
+                        command = Some(CurveCommand::AddPoint { point, value, time });
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("LShape:");
+                    if let Some(segment) = Self::segment_shape_editor(curve, ui, curve.get_point_left_segment(point)) {
+                        command = Some(segment);
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("RShape:");
+                    if let Some(segment) = Self::segment_shape_editor(curve, ui, curve.get_point_right_segment(point)) {
+                        command = Some(segment);
+                    }
+                });
+
+                let delete_button = Button::new("Delete");
+                if ui.add_enabled(curve.point_is_intermediate(point), delete_button).clicked() {
+                    command = Some(CurveCommand::DeletePoint { point });
+                }
+            })
+        });
+        command
+    }
+
+    /// draws a labeled input for the value of a point (or one of its limits) in the range's units
+    /// returns the command setting the value if one was entered
+    fn value_input(curve: &Curve, ui: &mut Ui, label: &str, point: CurvePointId, text: &mut String) -> Option<CurveCommand> {
+        let range = curve.range();
+        let value = curve.get_point_value(point);
+        ui.label(format!("{}: {}", label, range.format(value)));
+        let mut output = range.to_output(value);
+        if utils::number_input(ui, text, &mut output) {
+            Some(CurveCommand::SetPointValue { point, value: range.from_output(output) })
+        } else {
+            None
+        }
+    }
+
+    /// draws buttons cycling the shape and direction of the segment, disabled if there is no segment
+    /// returns the command changing the segment's shape if a button was clicked
+    fn segment_shape_editor(curve: &Curve, ui: &mut Ui, segment: Option<CurveSegmentId>) -> Option<CurveCommand> {
+        let Some(segment) = segment else {
+            ui.add_enabled_ui(false, |ui| {
+                let _ = ui.button("----");
+                let _ = ui.button("---");
+            });
+            return None;
+        };
+
+        let shape = curve.get_segment_shape(segment);
+        let direction_button = Button::new(if shape.shape.uses_direction() {
+            shape.direction.name_brief()
+        } else {
+            "---"
+        });
+
+        if ui.button(shape.shape.name_brief_4()).clicked() {
+            return Some(CurveCommand::SetSegmentShape { segment, shape: shape.with_shape(shape.shape.next()) });
+        }
+        if ui.add_enabled(shape.shape.uses_direction(), direction_button).clicked() {
+            return Some(CurveCommand::SetSegmentShape { segment, shape: shape.with_direction(shape.direction.next()) });
+        }
+        None
+    }
+}
+
+/// the distance between ruler ticks, a 1, 2 or 5 times a power of ten fitting at most the given number of ticks in the span
+fn ruler_step(span: f64, max_ticks: f32) -> f64 {
+    let min_step = span / (max_ticks.max(1.0) as f64);
+    let magnitude = 10f64.powf(min_step.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|factor| factor * magnitude)
+        .find(|step| *step >= min_step)
+        .unwrap_or(10.0 * magnitude)
+}
+
+/// shows a ruler value with fewer decimals the larger it is, so labels stay narrow
+fn ruler_label(value: f64) -> String {
+    match value.abs() {
+        magnitude if magnitude >= 100.0 => format!("{:.0}", value),
+        magnitude if magnitude >= 10.0 => format!("{:.1}", value),
+        _ => format!("{:.2}", value),
+    }
+}
+
+/// keeps a window within the bounds, no smaller than MAX_ZOOM allows
+/// returns None if the window covers the bounds
+fn clamp_window((start, end): (f64, f64), (min, max): (f64, f64)) -> Option<(f64, f64)> {
+    let span = (end - start).max((max - min) / CurveWidget::MAX_ZOOM);
+    if span >= max - min {
+        return None;
+    }
+    let start = start.clamp(min, max - span);
+    Some((start, start + span))
+}
+
+/// magnifies a window by the factor, keeping the anchor at the same place in the window
+fn zoom_window((start, end): (f64, f64), factor: f64, anchor: f64, bounds: (f64, f64)) -> Option<(f64, f64)> {
+    let span = (end - start) / factor;
+    let start = anchor - (anchor - start) / factor;
+    clamp_window((start, start + span), bounds)
+}
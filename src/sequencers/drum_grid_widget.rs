@@ -0,0 +1,222 @@
+use egui::{Align2, Color32, FontId, PointerButton, Pos2, Rect, Response, Sense, Stroke, StrokeKind, Ui, Vec2};
+
+use super::{drum_sequencer::{DrumPattern, DrumStep}, note::BeatUnits};
+
+/// what is being changed by a drag that started on a step
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DragTarget {
+    Velocity(u8),
+    Probability(f32),
+}
+
+/// a step being dragged, with its row, index and value when the drag started
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct StepDrag {
+    row: usize,
+    step: usize,
+    target: DragTarget,
+}
+
+/// An editor for the steps of a drum pattern, with a row for each drum
+/// Clicking a step toggles it, and clicking the name of a row mutes it.
+/// Dragging a step up or down changes its velocity, or its probability with Shift held.
+/// Secondary clicking a step toggles its flam.
+pub struct DrumGridWidget {
+    /// the step being dragged, if any
+    drag: Option<StepDrag>,
+
+    /// the step highlighted as being played, if any
+    playhead: Option<usize>,
+}
+
+impl DrumGridWidget {
+    const LABEL_WIDTH: f32 = 96.0;
+    const LABEL_FONT_SIZE: f32 = 12.0;
+    const CELL_SIZE: f32 = 20.0;
+    const CELL_GAP: f32 = 2.0;
+    const CELL_ROUNDING: f32 = 2.0;
+
+    /// the height of the bar showing the probability of a step
+    const PROBABILITY_HEIGHT: f32 = 3.0;
+
+    /// the points dragged to move from the lowest to the highest velocity or probability
+    const DRAG_RANGE: f32 = 100.0;
+
+    const EMPTY_COLOR: Color32 = Color32::from_gray(40);
+    const BEAT_COLOR: Color32 = Color32::from_gray(55);
+    const STEP_COLOR: Color32 = Color32::from_rgb(230, 140, 40);
+    const PROBABILITY_COLOR: Color32 = Color32::WHITE;
+    const FLAM_COLOR: Color32 = Color32::WHITE;
+    const PLAYHEAD_COLOR: Color32 = Color32::from_gray(200);
+    const LABEL_COLOR: Color32 = Color32::from_gray(220);
+    const MUTED_LABEL_COLOR: Color32 = Color32::from_gray(100);
+
+    pub fn new() -> Self {
+        Self {
+            drag: None,
+            playhead: None,
+        }
+    }
+
+    pub fn playhead(&self) -> Option<usize> {
+        self.playhead
+    }
+
+    /// sets the step highlighted as being played, or None to highlight nothing
+    pub fn set_playhead(&mut self, step: Option<usize>) {
+        self.playhead = step;
+    }
+
+    /// the rectangle of a step within the grid
+    fn cell_rect(origin: Pos2, row: usize, step: usize) -> Rect {
+        let spacing = Self::CELL_SIZE + Self::CELL_GAP;
+        Rect::from_min_size(
+            origin + Vec2::new(Self::LABEL_WIDTH + step as f32 * spacing, row as f32 * spacing),
+            Vec2::splat(Self::CELL_SIZE),
+        )
+    }
+
+    /// gets the row and step under a position, where a step of None is the name of the row
+    fn cell_at(pattern: &DrumPattern, origin: Pos2, pos: Pos2) -> Option<(usize, Option<usize>)> {
+        let spacing = Self::CELL_SIZE + Self::CELL_GAP;
+        let relative = pos - origin;
+        if relative.y < 0.0 || relative.x < 0.0 {
+            return None;
+        }
+        let row = (relative.y / spacing) as usize;
+        if row >= pattern.rows().len() {
+            return None;
+        }
+        if relative.x < Self::LABEL_WIDTH {
+            return Some((row, None));
+        }
+        let step = ((relative.x - Self::LABEL_WIDTH) / spacing) as usize;
+        (step < pattern.steps()).then_some((row, Some(step)))
+    }
+
+    /// draws the editor and handles its input
+    /// the response is marked as changed if the pattern was edited
+    pub fn show(&mut self, ui: &mut Ui, pattern: &mut DrumPattern) -> Response {
+        let spacing = Self::CELL_SIZE + Self::CELL_GAP;
+        let size = Vec2::new(
+            Self::LABEL_WIDTH + pattern.steps() as f32 * spacing,
+            (pattern.rows().len() as f32 * spacing).max(Self::CELL_SIZE),
+        );
+        let (mut response, painter) = ui.allocate_painter(size, Sense::click_and_drag());
+        let origin = response.rect.min;
+        let mut changed = false;
+
+        // start dragging a step that has a hit
+        if response.drag_started_by(PointerButton::Primary)
+            && let Some(pos) = ui.input(|input| input.pointer.press_origin())
+            && let Some((row, Some(step))) = Self::cell_at(pattern, origin, pos)
+            && let Some(hit) = pattern.rows()[row].step(step)
+        {
+            let target = if ui.input(|input| input.modifiers.shift) {
+                DragTarget::Probability(hit.probability())
+            } else {
+                DragTarget::Velocity(hit.velocity())
+            };
+            self.drag = Some(StepDrag { row, step, target });
+        }
+
+        // change the value of the step being dragged by how far the pointer moved up
+        if let Some(drag) = self.drag
+            && let (Some(press), Some(pos)) = ui.input(|input| (input.pointer.press_origin(), input.pointer.latest_pos()))
+        {
+            let amount = (press.y - pos.y) / Self::DRAG_RANGE;
+            if let Some(row) = pattern.row_mut(drag.row) {
+                let before = row.step(drag.step).copied();
+                row.get_step_mut(drag.step, |step| match drag.target {
+                    DragTarget::Velocity(velocity) => {
+                        let velocity = velocity as f32 + amount * DrumStep::MAX_VELOCITY as f32;
+                        step.set_velocity(velocity.round().clamp(1.0, DrumStep::MAX_VELOCITY as f32) as u8);
+                    }
+                    DragTarget::Probability(probability) => step.set_probability(probability + amount),
+                });
+                changed |= row.step(drag.step).copied() != before;
+            }
+        }
+        if response.drag_stopped() || ui.input(|input| !input.pointer.primary_down()) {
+            self.drag = None;
+        }
+
+        // toggle steps, flams and mutes
+        if let Some(pos) = response.interact_pointer_pos()
+            && let Some((row_index, step)) = Self::cell_at(pattern, origin, pos)
+        {
+            let row = pattern.row_mut(row_index).unwrap();
+            match step {
+                None if response.clicked_by(PointerButton::Primary) => {
+                    row.muted = !row.muted;
+                    changed = true;
+                }
+                Some(step) if response.clicked_by(PointerButton::Primary) => {
+                    changed |= row.toggle_step(step);
+                }
+                Some(step) if response.secondary_clicked() => {
+                    changed |= row.get_step_mut(step, |step| {
+                        step.set_flam(if step.has_flam() { BeatUnits(0) } else { DrumStep::DEFAULT_FLAM });
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        // steps starting each beat are drawn lighter
+        let steps_per_beat = (BeatUnits::UNITS_PER_BEAT / pattern.step_length().0).max(1) as usize;
+        let font = FontId::proportional(Self::LABEL_FONT_SIZE);
+
+        for (row_index, row) in pattern.rows().iter().enumerate() {
+            let label_pos = origin + Vec2::new(0.0, row_index as f32 * spacing + Self::CELL_SIZE / 2.0);
+            let label_color = if row.muted { Self::MUTED_LABEL_COLOR } else { Self::LABEL_COLOR };
+            painter.text(label_pos, Align2::LEFT_CENTER, &row.name, font.clone(), label_color);
+
+            for (index, step) in row.steps().iter().enumerate() {
+                let rect = Self::cell_rect(origin, row_index, index);
+                let background = if index % steps_per_beat == 0 { Self::BEAT_COLOR } else { Self::EMPTY_COLOR };
+                painter.rect_filled(rect, Self::CELL_ROUNDING, background);
+
+                if let Some(step) = step {
+                    let alpha = step.velocity() as f32 / DrumStep::MAX_VELOCITY as f32;
+                    let mut color = Self::STEP_COLOR.gamma_multiply(alpha.max(0.2));
+                    if row.muted {
+                        color = color.gamma_multiply(0.5);
+                    }
+                    painter.rect_filled(rect, Self::CELL_ROUNDING, color);
+
+                    if step.probability() < 1.0 {
+                        let bar = Rect::from_min_size(
+                            rect.left_bottom() - Vec2::new(0.0, Self::PROBABILITY_HEIGHT),
+                            Vec2::new(rect.width() * step.probability(), Self::PROBABILITY_HEIGHT),
+                        );
+                        painter.rect_filled(bar, 0.0, Self::PROBABILITY_COLOR);
+                    }
+
+                    if step.has_flam() {
+                        let x = rect.left() + rect.width() / 4.0;
+                        painter.line_segment(
+                            [Pos2::new(x, rect.top() + 3.0), Pos2::new(x, rect.bottom() - 3.0)],
+                            Stroke::new(1.0, Self::FLAM_COLOR),
+                        );
+                    }
+                }
+
+                if self.playhead == Some(index) {
+                    painter.rect_stroke(rect, Self::CELL_ROUNDING, Stroke::new(1.0, Self::PLAYHEAD_COLOR), StrokeKind::Inside);
+                }
+            }
+        }
+
+        if changed {
+            response.mark_changed();
+        }
+        response
+    }
+}
+
+impl Default for DrumGridWidget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
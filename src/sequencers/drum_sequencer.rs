@@ -0,0 +1,535 @@
+use crate::{live_plugin_id::LivePluginId, playback::DrumState, sequencers::note::BeatUnits};
+
+/// A hit of a drum on one step of a drum pattern
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrumStep {
+    /// how hard the drum is hit, within 1 and MAX_VELOCITY
+    velocity: u8,
+
+    /// the chance [0, 1] that the step plays each time it is reached
+    probability: f32,
+
+    /// the time from the grace hit of a flam to the main hit, or 0 for a single hit
+    /// within 0 and MAX_FLAM
+    flam: BeatUnits,
+}
+
+impl DrumStep {
+    pub const DEFAULT_VELOCITY: u8 = 100;
+    pub const MAX_VELOCITY: u8 = 127;
+
+    /// a 32nd note
+    pub const MAX_FLAM: BeatUnits = BeatUnits::thirty_second();
+
+    /// the flam set when a flam is toggled on, a 64th note
+    pub const DEFAULT_FLAM: BeatUnits = BeatUnits::note_value(64);
+
+    /// the velocity of the grace hit of a flam as a fraction of the main hit
+    pub const FLAM_VELOCITY: f32 = 0.5;
+
+    /// creates a step that always plays a single hit
+    pub fn new(velocity: u8) -> Self {
+        Self {
+            velocity: velocity.clamp(1, Self::MAX_VELOCITY),
+            probability: 1.0,
+            flam: BeatUnits(0),
+        }
+    }
+
+    pub fn velocity(&self) -> u8 {
+        self.velocity
+    }
+
+    /// sets how hard the drum is hit
+    /// will be clamped to 1, MAX_VELOCITY
+    pub fn set_velocity(&mut self, velocity: u8) {
+        self.velocity = velocity.clamp(1, Self::MAX_VELOCITY);
+    }
+
+    pub fn probability(&self) -> f32 {
+        self.probability
+    }
+
+    /// sets the chance that the step plays each time it is reached
+    /// will be clamped to 0, 1, and NaN is treated as 1
+    pub fn set_probability(&mut self, probability: f32) {
+        self.probability = if probability.is_nan() { 1.0 } else { probability.clamp(0.0, 1.0) };
+    }
+
+    pub fn flam(&self) -> BeatUnits {
+        self.flam
+    }
+
+    /// sets the time from the grace hit of a flam to the main hit, or 0 for a single hit
+    /// will be clamped to 0, MAX_FLAM
+    pub fn set_flam(&mut self, flam: BeatUnits) {
+        self.flam = flam.clamp(BeatUnits(0), Self::MAX_FLAM);
+    }
+
+    /// returns true if the step is played as a flam
+    pub fn has_flam(&self) -> bool {
+        self.flam > BeatUnits(0)
+    }
+
+    /// the velocity of the grace hit of a flam
+    pub fn grace_velocity(&self) -> u8 {
+        ((self.velocity as f32 * Self::FLAM_VELOCITY).round() as u8).max(1)
+    }
+}
+
+impl Default for DrumStep {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_VELOCITY)
+    }
+}
+
+/// A row of a drum pattern, hitting one drum
+#[derive(Debug, Clone)]
+pub struct DrumRow {
+    pub name: String,
+
+    /// the drum hit by the row, if any
+    pub drum: Option<LivePluginId>,
+
+    /// whether the row is silenced
+    pub muted: bool,
+
+    /// the hit on each step, if any, with as many steps as the pattern
+    steps: Vec<Option<DrumStep>>,
+}
+
+impl DrumRow {
+    fn new(name: String, drum: Option<LivePluginId>, steps: usize) -> Self {
+        Self {
+            name,
+            drum,
+            muted: false,
+            steps: vec![None; steps],
+        }
+    }
+
+    /// gets the hit on each step, if any
+    pub fn steps(&self) -> &[Option<DrumStep>] {
+        &self.steps
+    }
+
+    /// gets the hit on a step
+    /// fails if the step is empty or out of bounds
+    pub fn step(&self, index: usize) -> Option<&DrumStep> {
+        self.steps.get(index)?.as_ref()
+    }
+
+    /// sets the hit on a step, or None to empty it
+    /// fails if index is out of bounds, returning false
+    pub fn set_step(&mut self, index: usize, step: Option<DrumStep>) -> bool {
+        let Some(slot) = self.steps.get_mut(index) else {
+            return false;
+        };
+        *slot = step;
+        true
+    }
+
+    /// empties a step with a hit, or gives an empty step the default hit
+    /// fails if index is out of bounds, returning false
+    pub fn toggle_step(&mut self, index: usize) -> bool {
+        let Some(slot) = self.steps.get_mut(index) else {
+            return false;
+        };
+        *slot = match slot {
+            Some(_) => None,
+            None => Some(DrumStep::default()),
+        };
+        true
+    }
+
+    /// gets the hit on a step mutably within a closure
+    /// fails if the step is empty or out of bounds, returning false
+    pub fn get_step_mut(&mut self, index: usize, f: impl FnOnce(&mut DrumStep)) -> bool {
+        match self.steps.get_mut(index) {
+            Some(Some(step)) => {
+                f(step);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// empties every step
+    pub fn clear(&mut self) {
+        self.steps.fill(None);
+    }
+}
+
+/// A grid of drum hits, with a row for each drum and a column for each step
+/// Steps are evenly spaced, and the pattern is as long as its steps.
+#[derive(Debug, Clone)]
+pub struct DrumPattern {
+    rows: Vec<DrumRow>,
+
+    /// the number of steps, within 1 and MAX_STEPS
+    steps: usize,
+
+    /// the time between steps, at least MIN_STEP_LENGTH
+    step_length: BeatUnits,
+}
+
+impl DrumPattern {
+    pub const DEFAULT_STEPS: usize = 16;
+    pub const MAX_STEPS: usize = 128;
+
+    /// a 16th note
+    pub const DEFAULT_STEP_LENGTH: BeatUnits = BeatUnits::sixteenth();
+
+    /// a 64th note
+    pub const MIN_STEP_LENGTH: BeatUnits = BeatUnits::note_value(64);
+
+    /// creates a pattern with no rows and a bar of 16th note steps
+    pub fn new() -> Self {
+        Self {
+            rows: Vec::new(),
+            steps: Self::DEFAULT_STEPS,
+            step_length: Self::DEFAULT_STEP_LENGTH,
+        }
+    }
+
+    pub fn rows(&self) -> &[DrumRow] {
+        &self.rows
+    }
+
+    pub fn row(&self, index: usize) -> Option<&DrumRow> {
+        self.rows.get(index)
+    }
+
+    pub fn row_mut(&mut self, index: usize) -> Option<&mut DrumRow> {
+        self.rows.get_mut(index)
+    }
+
+    /// adds an empty row at the bottom of the pattern, returning its index
+    pub fn add_row(&mut self, name: String, drum: Option<LivePluginId>) -> usize {
+        self.rows.push(DrumRow::new(name, drum, self.steps));
+        self.rows.len() - 1
+    }
+
+    /// removes the row with the given index
+    /// fails if the index is out of bounds
+    pub fn remove_row(&mut self, index: usize) -> Option<DrumRow> {
+        if index >= self.rows.len() {
+            return None;
+        }
+        Some(self.rows.remove(index))
+    }
+
+    /// moves a row so that it has the given index
+    /// fails if either index is out of bounds, returning false
+    pub fn move_row(&mut self, from: usize, to: usize) -> bool {
+        if from >= self.rows.len() || to >= self.rows.len() {
+            return false;
+        }
+        let row = self.rows.remove(from);
+        self.rows.insert(to, row);
+        true
+    }
+
+    /// gets the number of steps
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// sets the number of steps, emptying new steps and discarding steps past the end
+    /// will be clamped to 1, MAX_STEPS
+    pub fn set_steps(&mut self, steps: usize) {
+        self.steps = steps.clamp(1, Self::MAX_STEPS);
+        for row in &mut self.rows {
+            row.steps.resize(self.steps, None);
+        }
+    }
+
+    /// gets the time between steps
+    pub fn step_length(&self) -> BeatUnits {
+        self.step_length
+    }
+
+    /// sets the time between steps
+    /// does not let step length go below MIN_STEP_LENGTH
+    pub fn set_step_length(&mut self, step_length: BeatUnits) {
+        self.step_length = step_length.max(Self::MIN_STEP_LENGTH);
+    }
+
+    /// gets the time from the start of the pattern to a step
+    pub fn step_time(&self, step: usize) -> BeatUnits {
+        BeatUnits(self.step_length.0 * step as i32)
+    }
+
+    /// gets the length of the pattern
+    pub fn length(&self) -> BeatUnits {
+        self.step_time(self.steps)
+    }
+
+    /// gets the step playing at the given position of the transport in beats, with the pattern repeating from beat 0
+    /// such as to draw a playhead
+    pub fn step_at(&self, beats: f64) -> Option<usize> {
+        let length = self.length().into_beats();
+        if self.steps == 0 || !beats.is_finite() {
+            return None;
+        }
+        let step = (beats.rem_euclid(length) / self.step_length.into_beats()) as usize;
+        Some(step.min(self.steps - 1))
+    }
+}
+
+impl Default for DrumPattern {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A pattern of a drum chain played some number of times in a row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainLink {
+    /// the index of the pattern in the chain
+    pub pattern: usize,
+
+    /// the number of times the pattern is played, where a link played 0 times is skipped
+    pub times: u32,
+}
+
+/// Drum patterns played one after another, such as a verse beat followed by a fill
+/// A pattern may be linked any number of times, and the chain may loop back to its first link.
+#[derive(Debug, Clone)]
+pub struct DrumChain {
+    patterns: Vec<DrumPattern>,
+
+    /// the order the patterns are played in, each referring to a pattern in patterns
+    links: Vec<ChainLink>,
+
+    /// whether the chain starts over after its last link
+    pub looping: bool,
+}
+
+impl DrumChain {
+    pub fn new() -> Self {
+        Self {
+            patterns: Vec::new(),
+            links: Vec::new(),
+            looping: true,
+        }
+    }
+
+    pub fn patterns(&self) -> &[DrumPattern] {
+        &self.patterns
+    }
+
+    pub fn pattern(&self, index: usize) -> Option<&DrumPattern> {
+        self.patterns.get(index)
+    }
+
+    pub fn pattern_mut(&mut self, index: usize) -> Option<&mut DrumPattern> {
+        self.patterns.get_mut(index)
+    }
+
+    /// adds a pattern without linking it, returning its index
+    pub fn add_pattern(&mut self, pattern: DrumPattern) -> usize {
+        self.patterns.push(pattern);
+        self.patterns.len() - 1
+    }
+
+    /// removes the pattern with the given index along with every link to it
+    /// links to later patterns are kept pointing at the same patterns
+    /// fails if the index is out of bounds
+    pub fn remove_pattern(&mut self, index: usize) -> Option<DrumPattern> {
+        if index >= self.patterns.len() {
+            return None;
+        }
+        self.links.retain(|link| link.pattern != index);
+        for link in &mut self.links {
+            if link.pattern > index {
+                link.pattern -= 1;
+            }
+        }
+        Some(self.patterns.remove(index))
+    }
+
+    /// gets the order the patterns are played in
+    pub fn links(&self) -> &[ChainLink] {
+        &self.links
+    }
+
+    /// links a pattern to the end of the chain, returning the index of the link
+    /// fails if there is no pattern with the given index
+    pub fn add_link(&mut self, pattern: usize, times: u32) -> Option<usize> {
+        if pattern >= self.patterns.len() {
+            return None;
+        }
+        self.links.push(ChainLink { pattern, times });
+        Some(self.links.len() - 1)
+    }
+
+    /// removes the link with the given index
+    /// fails if the index is out of bounds
+    pub fn remove_link(&mut self, index: usize) -> Option<ChainLink> {
+        if index >= self.links.len() {
+            return None;
+        }
+        Some(self.links.remove(index))
+    }
+
+    /// sets the number of times a link plays its pattern
+    /// fails if index is out of bounds, returning false
+    pub fn set_link_times(&mut self, index: usize, times: u32) -> bool {
+        let Some(link) = self.links.get_mut(index) else {
+            return false;
+        };
+        link.times = times;
+        true
+    }
+
+    /// gets the time taken to play every link once
+    pub fn length(&self) -> BeatUnits {
+        self.links.iter()
+            .map(|link| BeatUnits(self.patterns[link.pattern].length().0 * link.times as i32))
+            .fold(BeatUnits(0), |total, length| total + length)
+    }
+
+    /// finds each play of a pattern that overlaps the range of beats [start, end)
+    /// pushes the index of the pattern and the beat it starts on to output
+    fn instances(&self, output: &mut Vec<(usize, f64)>, start: f64, end: f64) {
+        let total = self.length().into_beats();
+        if total <= 0.0 || end <= 0.0 {
+            return;
+        }
+
+        let first_pass = if self.looping { (start / total).floor().max(0.0) } else { 0.0 };
+        let mut origin = first_pass * total;
+        while origin < end {
+            for link in &self.links {
+                let length = self.patterns[link.pattern].length().into_beats();
+                let link_end = origin + length * link.times as f64;
+                if link_end > start && origin < end {
+                    let first = ((start - origin) / length).floor().max(0.0) as u32;
+                    let mut repetition = first;
+                    while repetition < link.times && origin + repetition as f64 * length < end {
+                        output.push((link.pattern, origin + repetition as f64 * length));
+                        repetition += 1;
+                    }
+                }
+                origin = link_end;
+            }
+            if !self.looping {
+                break;
+            }
+        }
+    }
+}
+
+impl Default for DrumChain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Plays drum patterns by hitting the drums their rows are bound to
+/// Drums are one-shot, so each step sends a single DrumState::Hit, or two for a flam,
+/// with the grace hit on the step and the main hit after the flam.
+/// Whether a step with a probability plays is decided by the seed, the step and the beat its pattern started on,
+/// so playing the same range again gives the same hits.
+#[derive(Debug, Clone)]
+pub struct DrumPlayer {
+    pub seed: u64,
+
+    /// reused when finding the patterns of a chain
+    instances: Vec<(usize, f64)>,
+}
+
+impl DrumPlayer {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            instances: Vec::new(),
+        }
+    }
+
+    /// hits the drums of the steps within the range of beats [start, end) passed during one sample
+    /// the pattern starts on beat 0 and loops forever
+    pub fn update(&mut self, pattern: &DrumPattern, start: f64, end: f64, hit: &mut dyn FnMut(LivePluginId, DrumState)) {
+        let length = pattern.length().into_beats();
+        let mut repetition = (start / length).floor().max(0.0);
+        while repetition * length < end {
+            self.play_instance(pattern, repetition * length, start, end, hit);
+            repetition += 1.0;
+        }
+    }
+
+    /// like update, playing the patterns of a chain in order from beat 0
+    pub fn update_chain(&mut self, chain: &DrumChain, start: f64, end: f64, hit: &mut dyn FnMut(LivePluginId, DrumState)) {
+        let mut instances = std::mem::take(&mut self.instances);
+        instances.clear();
+        chain.instances(&mut instances, start, end);
+        for (pattern, offset) in &instances {
+            self.play_instance(&chain.patterns[*pattern], *offset, start, end, hit);
+        }
+        self.instances = instances;
+    }
+
+    /// hits the drums of one play of a pattern starting at offset within [start, end)
+    fn play_instance(&self, pattern: &DrumPattern, offset: f64, start: f64, end: f64, hit: &mut dyn FnMut(LivePluginId, DrumState)) {
+        // every hit of a step is within the step, so only the steps overlapping the range are checked
+        let step_beats = pattern.step_length().into_beats();
+        let first = ((start - offset) / step_beats).floor().max(0.0) as usize;
+        let last = (((end - offset) / step_beats).ceil().max(0.0) as usize).min(pattern.steps());
+
+        for (row_index, row) in pattern.rows().iter().enumerate() {
+            let Some(drum) = row.drum else {
+                continue;
+            };
+            if row.muted {
+                continue;
+            }
+
+            for index in first..last {
+                let Some(step) = row.step(index) else {
+                    continue;
+                };
+                let time = pattern.step_time(index);
+                let flam = step.flam().min(pattern.step_length() - BeatUnits(1));
+
+                let mut hits = [(time, step.velocity()), (time, step.velocity())];
+                let count = if flam > BeatUnits(0) {
+                    hits[0].1 = step.grace_velocity();
+                    hits[1].0 = time + flam;
+                    2
+                } else {
+                    1
+                };
+
+                let in_range = hits[..count].iter().any(|(time, _)| {
+                    let beat = offset + time.into_beats();
+                    start <= beat && beat < end
+                });
+                if !in_range || !self.plays(offset, row_index, index, step.probability()) {
+                    continue;
+                }
+
+                for (time, velocity) in &hits[..count] {
+                    let beat = offset + time.into_beats();
+                    if start <= beat && beat < end {
+                        hit(drum, DrumState::Hit(*velocity));
+                    }
+                }
+            }
+        }
+    }
+
+    /// decides whether a step with the given probability plays in the play of its pattern starting at offset
+    fn plays(&self, offset: f64, row: usize, step: usize, probability: f32) -> bool {
+        if probability >= 1.0 {
+            return true;
+        }
+        if probability <= 0.0 {
+            return false;
+        }
+        let seed = self.seed
+            ^ offset.to_bits().rotate_left(17)
+            ^ ((row as u64) << 32 | step as u64);
+        fastrand::Rng::with_seed(seed).f32() < probability
+    }
+}
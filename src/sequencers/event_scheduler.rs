@@ -0,0 +1,265 @@
+use std::sync::{atomic::{AtomicU64, Ordering}, Arc};
+
+use rtrb::{Consumer, Producer, RingBuffer};
+
+use crate::{frame::{self, Frame}, pitch::TuningSystem, playback::{InputId, InputSpecification, LivePlugin, LiveSynth, NoteEvent, NoteId}, sequencers::{pattern_player::PatternPlayer, piano_sequencer::PianoPattern, transport::{Playhead, PlayheadPosition, Transport}}};
+
+/// A note event to be sent on a given sample
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScheduledEvent {
+    /// the sample the event is sent before, counted from the first sample played
+    pub sample: u64,
+    pub event: NoteEvent,
+}
+
+/// Collects the events a pattern player sends during one sample, stamping them with the sample
+/// Pretends to be a synth so that the player plays into it as it would into a live synth.
+#[derive(Debug, Default)]
+struct EventStamp {
+    sample: u64,
+    events: Vec<ScheduledEvent>,
+}
+
+impl EventStamp {
+    fn push(&mut self, event: NoteEvent) {
+        self.events.push(ScheduledEvent { sample: self.sample, event });
+    }
+}
+
+impl LivePlugin for EventStamp {
+    fn reset(&mut self) {}
+
+    fn get_inputs(&self) -> Vec<InputSpecification> {
+        Vec::new()
+    }
+
+    fn set_input(&mut self, _id: InputId, _value: f64) {}
+}
+
+impl LiveSynth for EventStamp {
+    // frequency changes are dropped later by synths that do not allow them
+    fn allow_frequency_change(&self) -> bool {
+        true
+    }
+
+    fn allow_aftertouch(&self) -> bool {
+        true
+    }
+
+    fn set_note_on(&mut self, id: NoteId, freq: f32, velocity: u8) {
+        self.push(NoteEvent::On { id, freq, velocity });
+    }
+
+    fn set_note_off(&mut self, id: NoteId, freq: f32) {
+        self.push(NoteEvent::Off { id, freq });
+    }
+
+    fn set_note_freq(&mut self, id: NoteId, freq: f32) {
+        self.push(NoteEvent::Freq { id, freq });
+    }
+
+    fn set_note_aftertouch(&mut self, id: NoteId, aftertouch: f32) {
+        self.push(NoteEvent::Aftertouch { id, aftertouch });
+    }
+
+    fn all_notes_off(&mut self) {}
+
+    fn set_input(&mut self, _id: InputId, _value: f64) {}
+
+    fn update(&mut self, _sample_rate: u32) -> Frame {
+        frame::SILENCE
+    }
+}
+
+/// Plays a piano pattern ahead of the audio thread, queueing its events stamped with the sample they are sent on
+/// The audio thread sends each event right before rendering its sample, so notes start on the exact sample
+/// rather than at the start of the buffer in which they were noticed.
+/// The notes' pitch curves (glides and vibrato) are queued as frequency changes in the same way.
+/// Events that do not fit in the queue are kept and queued first by the next call, arriving late rather than never.
+/// As the transport runs ahead of the audio thread, its position is queued as well and published to the playhead
+/// of the scheduler once reached, so that editors draw the position being heard.
+#[derive(Debug)]
+pub struct EventScheduler {
+    player: PatternPlayer,
+    queue: Producer<ScheduledEvent>,
+    stamp: EventStamp,
+
+    /// the position of the transport at every POSITION_INTERVAL samples
+    positions: Producer<(u64, PlayheadPosition)>,
+    playhead: Playhead,
+
+    /// events that did not fit in the queue, oldest first
+    pending: Vec<ScheduledEvent>,
+
+    /// the first sample not yet scheduled
+    scheduled: u64,
+
+    /// the next sample the audio thread will render, shared with ScheduledEvents
+    played: Arc<AtomicU64>,
+}
+
+impl EventScheduler {
+    /// the number of events the queue holds by default
+    pub const DEFAULT_CAPACITY: usize = 8192;
+
+    /// the samples between positions of the transport sent to the playhead
+    pub const POSITION_INTERVAL: u64 = 64;
+
+    /// the number of positions the queue holds, positions that do not fit are dropped
+    const POSITION_CAPACITY: usize = 1024;
+
+    /// creates a scheduler along with the queue read by the audio thread
+    /// capacity is the most events that may wait in the queue
+    pub fn new(tuning: TuningSystem, capacity: usize) -> (Self, ScheduledEvents) {
+        let (producer, consumer) = RingBuffer::new(capacity.max(1));
+        let (position_producer, position_consumer) = RingBuffer::new(Self::POSITION_CAPACITY);
+        let played = Arc::new(AtomicU64::new(0));
+        let playhead = Playhead::new();
+        let scheduler = Self {
+            player: PatternPlayer::new(tuning),
+            queue: producer,
+            stamp: EventStamp::default(),
+            positions: position_producer,
+            playhead: playhead.clone(),
+            pending: Vec::new(),
+            scheduled: 0,
+            played: played.clone(),
+        };
+        let events = ScheduledEvents {
+            queue: consumer,
+            positions: position_consumer,
+            playhead,
+            sample: 0,
+            played,
+        };
+        (scheduler, events)
+    }
+
+    /// gets a playhead following the position being played by the audio thread,
+    /// rather than the position of the transport, which runs ahead
+    pub fn playhead(&self) -> Playhead {
+        self.playhead.clone()
+    }
+
+    pub fn player(&self) -> &PatternPlayer {
+        &self.player
+    }
+
+    /// gets the player, such as to change its tuning
+    pub fn player_mut(&mut self) -> &mut PatternPlayer {
+        &mut self.player
+    }
+
+    /// the first sample not yet scheduled
+    pub fn scheduled_until(&self) -> u64 {
+        self.scheduled
+    }
+
+    /// the next sample the audio thread will render
+    pub fn played_until(&self) -> u64 {
+        self.played.load(Ordering::Relaxed)
+    }
+
+    /// plays the pattern against the transport for the given number of samples following those already scheduled
+    /// the transport is advanced by one sample at a time, and sounding notes are released while it is stopped
+    /// sample_rate must be the rate of the renderer playing the queue
+    pub fn schedule(&mut self, pattern: &PianoPattern, transport: &mut Transport, sample_rate: u32, samples: usize) {
+        let seconds = 1.0 / sample_rate as f64;
+        for _ in 0..samples {
+            self.stamp.sample = self.scheduled;
+            let (start, end) = transport.advance(seconds);
+            if self.scheduled.is_multiple_of(Self::POSITION_INTERVAL) {
+                let _ = self.positions.push((self.scheduled, transport.position()));
+            }
+            if transport.state().playing {
+                self.player.update(pattern, &mut self.stamp, start, end);
+            } else if self.player.is_sounding() {
+                self.player.release_all(&mut self.stamp);
+            }
+            self.scheduled += 1;
+        }
+        self.flush();
+    }
+
+    /// schedules samples until lookahead samples past the sample the audio thread will render next
+    /// if scheduling fell behind the audio thread, the samples already played are skipped
+    /// by advancing the transport past them without playing, so that events are never sent late
+    /// returns the number of samples skipped
+    pub fn schedule_ahead(&mut self, pattern: &PianoPattern, transport: &mut Transport, sample_rate: u32, lookahead: usize) -> u64 {
+        let played = self.played_until();
+        let skipped = played.saturating_sub(self.scheduled);
+        if skipped > 0 {
+            transport.advance(skipped as f64 / sample_rate as f64);
+            self.scheduled = played;
+        }
+
+        let target = played + lookahead as u64;
+        let samples = target.saturating_sub(self.scheduled) as usize;
+        self.schedule(pattern, transport, sample_rate, samples);
+        skipped
+    }
+
+    /// releases every sounding note on the next sample scheduled, such as when the pattern is replaced
+    pub fn release_all(&mut self) {
+        self.stamp.sample = self.scheduled;
+        self.player.release_all(&mut self.stamp);
+        self.flush();
+    }
+
+    /// moves the events of the stamp and those waiting to the queue, as far as they fit
+    fn flush(&mut self) {
+        self.pending.append(&mut self.stamp.events);
+        let fitting = self.pending.len().min(self.queue.slots());
+        for event in self.pending.drain(..fitting) {
+            let _ = self.queue.push(event);
+        }
+    }
+}
+
+/// The audio thread's end of an event scheduler
+/// Counts the samples rendered, sending each event once its sample is reached.
+#[derive(Debug)]
+pub struct ScheduledEvents {
+    queue: Consumer<ScheduledEvent>,
+
+    /// the positions of the transport, published to the playhead once reached
+    positions: Consumer<(u64, PlayheadPosition)>,
+    playhead: Playhead,
+
+    /// the sample about to be rendered
+    sample: u64,
+
+    /// the sample about to be rendered, shared with the scheduler
+    played: Arc<AtomicU64>,
+}
+
+impl ScheduledEvents {
+    /// the sample about to be rendered
+    pub fn sample(&self) -> u64 {
+        self.sample
+    }
+
+    /// gets a playhead following the position being played
+    pub fn playhead(&self) -> Playhead {
+        self.playhead.clone()
+    }
+
+    /// sends the events due by the sample about to be rendered, then moves on to the next sample
+    /// events scheduled for samples already rendered are sent immediately
+    pub fn advance(&mut self, mut send: impl FnMut(NoteEvent)) {
+        while let Ok(event) = self.queue.peek()
+            && event.sample <= self.sample
+        {
+            send(event.event);
+            let _ = self.queue.pop();
+        }
+        while let Ok((sample, position)) = self.positions.peek()
+            && *sample <= self.sample
+        {
+            self.playhead.publish(*position);
+            let _ = self.positions.pop();
+        }
+        self.sample += 1;
+        self.played.store(self.sample, Ordering::Relaxed);
+    }
+}
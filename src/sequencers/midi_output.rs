@@ -0,0 +1,179 @@
+use crate::{midi::MidiMessage, sequencers::piano_sequencer::{NoteHandle, PianoPattern}};
+
+/// A note of the pattern currently held down on the external synth
+#[derive(Debug, Clone)]
+struct SoundingNote {
+    handle: NoteHandle,
+
+    /// the beat of the transport at which the repetition of the pattern the note started in began
+    offset: f64,
+
+    /// the beat of the transport at which the repetition ends, cutting off the note
+    cutoff: f64,
+    channel: u8,
+    key: u8,
+
+    /// the last pitch bend sent for the note
+    bend: i16,
+}
+
+/// Plays a piano pattern on an external synth as MIDI note events
+/// Pitch that strays from the key a note started on, such as during a glide, is sent as pitch bend.
+/// Since pitch bend affects a whole channel, each sounding note is given the least used channel;
+/// with fewer channels than sounding notes, notes sharing a channel also share their bend.
+#[derive(Debug, Clone)]
+pub struct PatternMidiOutput {
+    /// the channels [0, 15] notes may be played on
+    channels: Vec<u8>,
+
+    /// the pitch bend range of the receiving synth in semitones
+    bend_range: f64,
+
+    sounding: Vec<SoundingNote>,
+
+    /// reused when querying the pattern
+    query: Vec<NoteHandle>,
+}
+
+impl PatternMidiOutput {
+    /// the velocity notes are played at
+    pub const VELOCITY: u8 = 100;
+
+    /// the pitch bend range most synths default to in semitones
+    pub const DEFAULT_BEND_RANGE: f64 = 2.0;
+
+    /// creates an output playing notes on the given channels [0, 15]
+    /// if channels is empty, channel 0 is used
+    pub fn new(channels: Vec<u8>, bend_range: f64) -> Self {
+        let mut channels: Vec<u8> = channels.into_iter().map(|channel| channel.min(15)).collect();
+        if channels.is_empty() {
+            channels.push(0);
+        }
+        Self {
+            channels,
+            bend_range: bend_range.max(f64::EPSILON),
+            sounding: Vec::new(),
+            query: Vec::new(),
+        }
+    }
+
+    pub fn channels(&self) -> &[u8] {
+        &self.channels
+    }
+
+    pub fn bend_range(&self) -> f64 {
+        self.bend_range
+    }
+
+    /// the key nearest to a pitch in cents from A4
+    fn nearest_key(cents: f64) -> u8 {
+        (69.0 + (cents / 100.0).round()).clamp(0.0, 127.0) as u8
+    }
+
+    /// the pitch bend that moves a key to a pitch in cents from A4
+    fn bend(cents: f64, key: u8, bend_range: f64) -> i16 {
+        let semitones = cents / 100.0 - (key as f64 - 69.0);
+        (semitones / bend_range * 8192.0).round().clamp(-8192.0, 8191.0) as i16
+    }
+
+    /// the channel with the fewest sounding notes
+    fn allocate_channel(&self) -> u8 {
+        *self.channels.iter()
+            .min_by_key(|&&channel| self.sounding.iter().filter(|note| note.channel == channel).count())
+            .expect("there should always be at least one channel")
+    }
+
+    /// sends the events of the notes within the range of beats passed by the transport
+    /// notes starting in [start, end) are played in each repetition of the pattern the range reaches,
+    /// notes ending or cut off by the end of their repetition by end are released,
+    /// and the pitch bend of sounding notes follows their pitch at end
+    pub fn update(&mut self, pattern: &PianoPattern, start: f64, end: f64, mut send: impl FnMut(MidiMessage)) {
+        // release notes that ended, were removed, were cut off by the end of their repetition,
+        // or are no longer reached after a jump
+        let mut index = 0;
+        while index < self.sounding.len() {
+            let SoundingNote { offset, cutoff, .. } = self.sounding[index];
+            let bounds = self.sounding[index].handle.note(|note| {
+                note.map(|note| (offset + note.start_time().into_beats(), (offset + note.end_time().into_beats()).min(cutoff)))
+            });
+            let released = bounds.is_none_or(|(note_start, note_end)| note_end <= end || note_start > end);
+            if released {
+                let note = self.sounding.swap_remove(index);
+                send(MidiMessage::NoteOff { channel: note.channel, key: note.key, velocity: 0 });
+            } else {
+                index += 1;
+            }
+        }
+
+        // follow glides
+        for note in &mut self.sounding {
+            let time = end - note.offset;
+            let Some(cents) = note.handle.note(|note| note.and_then(|note| note.get_cent_delta_a4(time))) else {
+                continue;
+            };
+            let bend = Self::bend(cents, note.key, self.bend_range);
+            if bend != note.bend {
+                send(MidiMessage::PitchBend { channel: note.channel, bend });
+                note.bend = bend;
+            }
+        }
+
+        if start >= end {
+            return;
+        }
+
+        let mut query = std::mem::take(&mut self.query);
+        for span in pattern.spans(start, end) {
+            query.clear();
+            pattern.playback_pattern().query_range(&mut query, span.start, span.end);
+            for handle in &query {
+                if self.sounding.iter().any(|note| note.offset == span.offset && note.handle.ptr_eq(handle)) {
+                    continue;
+                }
+
+                let started = handle.note(|note| note.and_then(|note| {
+                    let note_start = note.start_time().into_beats();
+                    if span.start <= note_start && note_start < span.end {
+                        note.get_cent_delta_a4(note_start)
+                    } else {
+                        None
+                    }
+                }));
+                let Some(cents) = started else {
+                    continue;
+                };
+
+                let channel = self.allocate_channel();
+                let key = Self::nearest_key(cents);
+                let bend = Self::bend(cents, key, self.bend_range);
+                send(MidiMessage::PitchBend { channel, bend });
+                send(MidiMessage::NoteOn { channel, key, velocity: Self::VELOCITY });
+                self.sounding.push(SoundingNote {
+                    handle: handle.clone(),
+                    offset: span.offset,
+                    cutoff: span.cutoff,
+                    channel,
+                    key,
+                    bend,
+                });
+            }
+        }
+        self.query = query;
+    }
+
+    /// releases every sounding note and recenters pitch bend, such as when the transport stops
+    pub fn release_all(&mut self, mut send: impl FnMut(MidiMessage)) {
+        for note in self.sounding.drain(..) {
+            send(MidiMessage::NoteOff { channel: note.channel, key: note.key, velocity: 0 });
+        }
+        for &channel in &self.channels {
+            send(MidiMessage::PitchBend { channel, bend: 0 });
+        }
+    }
+}
+
+impl Default for PatternMidiOutput {
+    fn default() -> Self {
+        Self::new(vec![0], Self::DEFAULT_BEND_RANGE)
+    }
+}
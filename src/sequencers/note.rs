@@ -1,9 +1,9 @@
 use core::f64;
-use std::{cmp::Ordering, ops::{Add, AddAssign, Neg, Sub, SubAssign}, vec};
+use std::{cmp::Ordering, fmt::{self, Display}, ops::{Add, AddAssign, Neg, Sub, SubAssign}, str::FromStr, vec};
 
 use thiserror::Error;
 
-use crate::{pitch::DetunedPitch, sequencers::curve::CurveShape};
+use crate::{pitch::DetunedPitch, playback::{NoteEvent, NoteId}, sequencers::curve::CurveShape};
 
 /// BeatUnits
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -17,16 +17,227 @@ impl BeatUnits {
     /// 20160, divisible by 5, 7, 9, and 64.
     pub const UNITS_PER_BEAT: i32 = 5 * 7 * 9 * 64;
 
+    /// the amount of units per whole note, where a beat is a quarter note
+    pub const UNITS_PER_WHOLE: i32 = 4 * Self::UNITS_PER_BEAT;
+
+    /// the denominators of the note values named when formatting, from a whole note to a 128th note
+    const NOTE_VALUES: [i32; 8] = [1, 2, 4, 8, 16, 32, 64, 128];
+
+    /// gets the time in beats
     pub const fn into_beats(&self) -> f64 {
-        self.0 as f64 * Self::UNITS_PER_BEAT as f64
+        self.0 as f64 / Self::UNITS_PER_BEAT as f64
+    }
+
+    /// converts a time in beats to the nearest unit, saturating at MIN and MAX
+    /// the inverse of into_beats
+    pub fn from_beats(beats: f64) -> Self {
+        Self::from_units_f64(beats * Self::UNITS_PER_BEAT as f64)
     }
 
-    pub const fn from_beats(beats: f64) -> Self {
-        let max_magnitude = Self::UNITS_PER_BEAT as f64 * i32::MAX as f64;
-        Self((beats * Self::UNITS_PER_BEAT as f64).clamp(-max_magnitude, max_magnitude) as i32)
+    /// rounds a number of units to the nearest unit, saturating at MIN and MAX
+    pub fn from_units_f64(units: f64) -> Self {
+        Self(units.round().clamp(i32::MIN as f64, i32::MAX as f64) as i32)
+    }
+
+    /// rounds to the nearest multiple of grid, rounding halfway values up
+    /// grid must be positive
+    pub fn round_to(&self, grid: BeatUnits) -> BeatUnits {
+        debug_assert!(grid.0 > 0, "grid must be positive");
+        let grid = grid.0.max(1) as i64;
+        let rounded = (self.0 as i64 + grid / 2).div_euclid(grid) * grid;
+        Self(rounded.clamp(i32::MIN as i64, i32::MAX as i64) as i32)
+    }
+
+    /// the given number of beats, saturating at MIN and MAX
+    pub const fn beats(beats: i32) -> Self {
+        Self(beats.saturating_mul(Self::UNITS_PER_BEAT))
+    }
+
+    /// the length of a note lasting 1/denominator of a whole note, such as 8 for an eighth note
+    /// denominator must be positive
+    pub const fn note_value(denominator: i32) -> Self {
+        Self(Self::UNITS_PER_WHOLE / denominator)
+    }
+
+    pub const fn whole() -> Self {
+        Self::note_value(1)
+    }
+
+    pub const fn half() -> Self {
+        Self::note_value(2)
+    }
+
+    /// a quarter note, which lasts one beat
+    pub const fn quarter() -> Self {
+        Self::note_value(4)
+    }
+
+    pub const fn eighth() -> Self {
+        Self::note_value(8)
+    }
+
+    pub const fn sixteenth() -> Self {
+        Self::note_value(16)
+    }
+
+    pub const fn thirty_second() -> Self {
+        Self::note_value(32)
+    }
+
+    /// the length of each note of a triplet played in the time of two notes of the given value,
+    /// such as triplet_of(BeatUnits::eighth()) for eighth note triplets
+    pub const fn triplet_of(value: Self) -> Self {
+        Self((value.0 as i64 * 2 / 3) as i32)
+    }
+
+    /// the length lasting half again as long, as written with a dot after a note value
+    pub const fn dotted(self) -> Self {
+        Self(self.0.saturating_add(self.0 / 2))
+    }
+
+    /// gets the note value of the given length as its denominator and whether it is dotted or a triplet,
+    /// such as (8, false, true) for an eighth note triplet
+    fn as_note_value(units: i64) -> Option<(i32, bool, bool)> {
+        Self::NOTE_VALUES.into_iter().find_map(|denominator| {
+            let base = (Self::UNITS_PER_WHOLE / denominator) as i64;
+            if units == base {
+                Some((denominator, false, false))
+            } else if units * 2 == base * 3 {
+                Some((denominator, true, false))
+            } else if units * 3 == base * 2 {
+                Some((denominator, false, true))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// parses a note value such as "1/8", "3/16", "1/4." or "1/8T" from its numerator and denominator
+    /// each dot adds half of the length added by the previous one, and a T makes it a triplet
+    /// returns the length in units
+    fn parse_note_value(numerator: &str, denominator: &str) -> Result<i64, BeatUnitsParseError> {
+        let numerator = numerator.trim();
+        let denominator = denominator.trim();
+        let (denominator, triplet) = match denominator.strip_suffix(['T', 't']) {
+            Some(denominator) => (denominator, true),
+            None => (denominator, false),
+        };
+        let digits = denominator.trim_end_matches('.');
+        let dots = (denominator.len() - digits.len()) as u32;
+        let digits = digits.trim_end();
+
+        let numerator: u128 = numerator.parse()
+            .map_err(|_| BeatUnitsParseError::InvalidNumber(numerator.to_string()))?;
+        let denominator: u128 = digits.parse()
+            .map_err(|_| BeatUnitsParseError::InvalidNumber(digits.to_string()))?;
+        if denominator == 0 {
+            return Err(BeatUnitsParseError::ZeroDenominator);
+        }
+
+        // the length is kept as a fraction of units until it is known to be whole
+        let dotted = 2u128.checked_pow(dots + 1).ok_or(BeatUnitsParseError::Overflow)?;
+        let mut units = numerator.checked_mul(Self::UNITS_PER_WHOLE as u128)
+            .and_then(|units| units.checked_mul(dotted - 1))
+            .ok_or(BeatUnitsParseError::Overflow)?;
+        let mut divisor = denominator.checked_mul(dotted / 2).ok_or(BeatUnitsParseError::Overflow)?;
+        if triplet {
+            units = units.checked_mul(2).ok_or(BeatUnitsParseError::Overflow)?;
+            divisor = divisor.checked_mul(3).ok_or(BeatUnitsParseError::Overflow)?;
+        }
+
+        if units % divisor != 0 {
+            return Err(BeatUnitsParseError::NotRepresentable(format!("{numerator}/{denominator}")));
+        }
+        i64::try_from(units / divisor).map_err(|_| BeatUnitsParseError::Overflow)
+    }
+}
+
+/// Formats whole numbers of beats as beats, such as "1 beat" or "3 beats",
+/// and other lengths as note values, such as "1/8", "1/4.", "1/8T" or "3/16"
+impl Display for BeatUnits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let units = (self.0 as i64).abs();
+        let sign = if self.0 < 0 { "-" } else { "" };
+        if units % Self::UNITS_PER_BEAT as i64 == 0 {
+            let beats = units / Self::UNITS_PER_BEAT as i64;
+            let plural = if beats == 1 { "" } else { "s" };
+            return write!(f, "{sign}{beats} beat{plural}");
+        }
+
+        match Self::as_note_value(units) {
+            Some((denominator, true, _)) => write!(f, "{sign}1/{denominator}."),
+            Some((denominator, _, true)) => write!(f, "{sign}1/{denominator}T"),
+            Some((denominator, _, _)) => write!(f, "{sign}1/{denominator}"),
+            None => {
+                let divisor = gcd(units, Self::UNITS_PER_WHOLE as i64);
+                write!(f, "{sign}{}/{}", units / divisor, Self::UNITS_PER_WHOLE as i64 / divisor)
+            }
+        }
     }
 }
 
+/// an error occurring when parsing a length of time
+#[derive(Debug, Error)]
+pub enum BeatUnitsParseError {
+    #[error("Missing length. Length must be a note value such as '1/8', '1/4.' or '1/8T', or a number of beats such as '3 beats'.")]
+    Empty,
+
+    #[error("Unable to parse number '{0}'.")]
+    InvalidNumber(String),
+
+    #[error("The denominator of a note value must not be 0.")]
+    ZeroDenominator,
+
+    #[error("The note value '{0}' is not a whole number of units.")]
+    NotRepresentable(String),
+
+    #[error("The length is too long to be represented.")]
+    Overflow,
+}
+
+/// Parses note values such as "1/8", "3/16", "1/4." (dotted) or "1/8T" (triplet),
+/// and numbers of beats such as "3 beats", "1 beat", "1.5 beats" or "2", possibly preceeded by '-'
+impl FromStr for BeatUnits {
+    type Err = BeatUnitsParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        let (negative, length) = match trimmed.strip_prefix('-') {
+            Some(length) => (true, length.trim_start()),
+            None => (false, trimmed),
+        };
+        if length.is_empty() {
+            return Err(BeatUnitsParseError::Empty);
+        }
+
+        let units = if let Some((numerator, denominator)) = length.split_once('/') {
+            Self::parse_note_value(numerator, denominator)?
+        } else {
+            let number = length.strip_suffix("beats")
+                .or_else(|| length.strip_suffix("beat"))
+                .unwrap_or(length)
+                .trim_end();
+            let beats: f64 = number.parse()
+                .ok()
+                .filter(|beats: &f64| beats.is_finite() && !number.starts_with(['-', '+']))
+                .ok_or_else(|| BeatUnitsParseError::InvalidNumber(number.to_string()))?;
+            // values too large for an i64 saturate, and are rejected below
+            (beats * Self::UNITS_PER_BEAT as f64).round() as i64
+        };
+
+        let units = if negative { -units } else { units };
+        i32::try_from(units).map(Self).map_err(|_| BeatUnitsParseError::Overflow)
+    }
+}
+
+/// the greatest common divisor of two non-negative numbers
+fn gcd(mut a: i64, mut b: i64) -> i64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
 impl Neg for BeatUnits {
     type Output = Self;
 
@@ -111,8 +322,18 @@ pub enum DeleteNotePartialError {
     NoSplit,
 }
 
+/// an error occurring when attempting to change the duration of a transition
+#[derive(Debug, Error)]
+pub enum TransitionDurationError {
+    #[error("Attempted to change a transition not stored in the note.")]
+    TransitionOutOfBounds,
+
+    #[error("The duration is outside the bounds allowed by the surrounding partials.")]
+    DurationOutOfBounds,
+}
+
 /// the entire note
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Note {
     /// the duration of the first transition
     /// Invariants:
@@ -149,9 +370,20 @@ pub struct Note {
     /// 	2) partials[i] has its ending transition at transitions[i + 1]
     /// 	3) there are partials.len() + 1 transitions at any given time
     transitions: Vec<CurveShape>,
+
+    /// how hard the note is struck, passed to synths when the note starts
+    /// Invariants:
+    /// 	1) within 1 and MAX_VELOCITY, as a velocity of 0 stops a note in MIDI
+    velocity: u8,
+
+    /// the names of the groups the note belongs to, kept through edits and copies, each appearing once
+    groups: Vec<String>,
 }
 
 impl Note {
+    pub const DEFAULT_VELOCITY: u8 = 100;
+    pub const MAX_VELOCITY: u8 = 127;
+
     pub fn new(pitch: DetunedPitch, start: BeatUnits, duration: BeatUnits) -> Self {
         Self {
             fade_in_duration: BeatUnits(0),
@@ -160,7 +392,48 @@ impl Note {
             fade_out_pitch: pitch,
             partials: vec![Box::new(NotePartial::new(pitch, start, duration))],
             transitions: vec![CurveShape::LINEAR, CurveShape::LINEAR],
+            velocity: Self::DEFAULT_VELOCITY,
+            groups: Vec::new(),
+        }
+    }
+
+    /// gets how hard the note is struck
+    pub fn velocity(&self) -> u8 {
+        self.velocity
+    }
+
+    /// sets how hard the note is struck
+    /// will be clamped to 1, MAX_VELOCITY
+    pub fn set_velocity(&mut self, velocity: u8) {
+        self.velocity = velocity.clamp(1, Self::MAX_VELOCITY);
+    }
+
+    /// gets the names of the groups the note belongs to
+    pub fn groups(&self) -> &[String] {
+        &self.groups
+    }
+
+    /// returns true if the note belongs to the group with the given name
+    pub fn in_group(&self, name: &str) -> bool {
+        self.groups.iter().any(|group| group == name)
+    }
+
+    /// adds the note to the group with the given name
+    /// fails if the note already belongs to the group, returning false
+    pub fn add_to_group(&mut self, name: &str) -> bool {
+        if self.in_group(name) {
+            return false;
         }
+        self.groups.push(name.to_owned());
+        true
+    }
+
+    /// removes the note from the group with the given name
+    /// fails if the note does not belong to the group, returning false
+    pub fn remove_from_group(&mut self, name: &str) -> bool {
+        let count = self.groups.len();
+        self.groups.retain(|group| group != name);
+        self.groups.len() != count
     }
 
     /// gets the start time of the note in millibeats
@@ -179,11 +452,114 @@ impl Note {
         self.end_time() - self.start_time()
     }
 
+    /// moves the whole note so that it starts at the given time
+    /// does not let start time go below 0
+    /// partials, transitions and vibrato keep their timing relative to the start of the note
+    pub fn set_start_time(&mut self, time: BeatUnits) {
+        let delta = time.max(BeatUnits(0)) - self.start_time();
+        for partial in &mut self.partials {
+            partial.set_start_time(partial.start_time() + delta);
+        }
+    }
+
+    /// lengthens or shortens the last partial so that the note ends at the given time
+    /// the last partial keeps at least NotePartial::MIN_DURATION, so the note may end later than requested
+    pub fn set_end_time(&mut self, time: BeatUnits) {
+        let fade_out_duration = self.fade_out_duration;
+        self.partials.last_mut().unwrap().set_end_time(time - fade_out_duration);
+    }
+
+    /// scales every time of the note by factor, measured from time 0, including fades and vibrato
+    /// partials keep at least NotePartial::MIN_DURATION and vibrato keeps the same number of cycles
+    /// factor must be finite and positive
+    pub fn stretch(&mut self, factor: f64) {
+        debug_assert!(factor.is_finite() && factor > 0.0, "factor must be finite and positive");
+        let scale = |time: BeatUnits| BeatUnits::from_units_f64(time.0 as f64 * factor);
+
+        let mut previous_end = BeatUnits(0);
+        for partial in &mut self.partials {
+            let start = scale(partial.start).max(previous_end);
+            let end = scale(partial.end_time()).max(start + NotePartial::MIN_DURATION);
+            partial.start = start;
+            partial.duration = end - start;
+            previous_end = end;
+
+            let vibrato = &mut partial.vibrato;
+            vibrato.start = scale(vibrato.start).clamp(start, end);
+            vibrato.duration = scale(vibrato.duration).min(end - vibrato.start);
+            vibrato.fade_in_duration = scale(vibrato.fade_in_duration).min(vibrato.duration);
+            vibrato.fade_out_duration = scale(vibrato.fade_out_duration).min(vibrato.duration);
+            vibrato.freq = (vibrato.freq as f64 / factor) as f32;
+        }
+
+        self.fade_in_duration = scale(self.fade_in_duration).min(self.partials[0].start);
+        self.fade_out_duration = scale(self.fade_out_duration);
+    }
+
+    /// shortens the note so that it ends at or before the given time
+    /// partials starting at or after the time are dropped along with the fade out,
+    /// and a fade out reaching past the time is shortened
+    /// fails without changing anything if the first partial does not start before the time, returning false
+    pub fn cut_at(&mut self, time: BeatUnits) -> bool {
+        if self.partials[0].start >= time {
+            return false;
+        }
+        if self.end_time() <= time {
+            return true;
+        }
+
+        let keep = self.partials.partition_point(|partial| partial.start < time);
+        if keep < self.partials.len() {
+            self.partials.truncate(keep);
+            self.transitions.truncate(keep + 1);
+            self.fade_out_duration = BeatUnits(0);
+            self.fade_out_pitch = self.partials.last().unwrap().pitch;
+        }
+
+        let last = self.partials.last_mut().unwrap();
+        if last.end_time() >= time {
+            last.set_end_time(time);
+            self.fade_out_duration = BeatUnits(0);
+            self.fade_out_pitch = last.pitch;
+        } else {
+            self.fade_out_duration = self.fade_out_duration.min(time - last.end_time());
+        }
+        true
+    }
+
+    /// moves every pitch of the note, including the pitches faded in from and out to, by the given number of quarter tones
+    /// fails without changing anything if any pitch would be out of the representable range, returning false
+    pub fn transpose(&mut self, quarters: i32) -> bool {
+        let in_range = [self.fade_in_pitch, self.fade_out_pitch].iter()
+            .chain(self.partials.iter().map(|partial| &partial.pitch))
+            .all(|pitch| pitch.transposed(quarters).is_some());
+        if in_range {
+            self.map_pitches(|pitch| pitch.transposed(quarters).unwrap());
+        }
+        in_range
+    }
+
+    /// replaces every pitch of the note, including the pitches faded in from and out to, with the result of f
+    pub fn map_pitches(&mut self, mut f: impl FnMut(DetunedPitch) -> DetunedPitch) {
+        self.fade_in_pitch = f(self.fade_in_pitch);
+        self.fade_out_pitch = f(self.fade_out_pitch);
+        for partial in &mut self.partials {
+            partial.pitch = f(partial.pitch);
+        }
+    }
+
     /// returns true if this note is playing at the given time
     pub fn contains_time(&self, time: f64) -> bool {
         self.start_time().into_beats() <= time && time <= self.end_time().into_beats()
     }
 
+    /// gets the lowest and highest pitch of the partials in cents from A4
+    pub fn pitch_range_a4(&self) -> (i32, i32) {
+        self.partials.iter()
+            .map(|partial| partial.pitch.cent_delta_a4())
+            .fold((i32::MAX, i32::MIN), |(low, high), cents| (low.min(cents), high.max(cents)))
+    }
+
     /// gets the number of partial notes
     pub fn num_partials(&self) -> usize {
         self.partials.len()
@@ -212,6 +588,45 @@ impl Note {
         }
     }
 
+    /// gets the gain of the note at the given time
+    /// between partials, the gain moves from one partial's gain to the next along the transition's shape
+    /// during fades, the gain of the nearest partial is held
+    pub fn get_gain(&self, time: f64) -> Option<f64> {
+        if !self.contains_time(time) {
+            return None;
+        }
+
+        match self.time_index(time) {
+            Ok(i) => Some(self.partials[i].gain as f64),
+            Err(0) => Some(self.partials[0].gain as f64),
+            Err(i) if i >= self.partials.len() => Some(self.partials.last().unwrap().gain as f64),
+            Err(i) => {
+                let (before, after) = (&self.partials[i - 1], &self.partials[i]);
+                Some(self.transitions[i].interpolate(
+                    time,
+                    before.end_time().into_beats(),
+                    after.start_time().into_beats(),
+                    before.gain as f64,
+                    after.gain as f64
+                ))
+            }
+        }
+    }
+
+    /// gets the velocity of the note scaled by its gain at the given time
+    pub fn velocity_at(&self, time: f64) -> Option<u8> {
+        self.get_gain(time).map(|gain| {
+            (self.velocity as f64 * gain).round().clamp(1.0, Self::MAX_VELOCITY as f64) as u8
+        })
+    }
+
+    /// creates the event starting the note on a synth at the given frequency
+    /// the velocity is scaled by the gain at the start of the note
+    pub fn note_on(&self, id: NoteId, freq: f32) -> NoteEvent {
+        let velocity = self.velocity_at(self.start_time().into_beats()).unwrap_or(self.velocity);
+        NoteEvent::On { id, freq, velocity }
+    }
+
     /// gets the index of the note partial or transition at the given time
     /// if out of bounds, gets the index of the nearest transition
     /// Ok(i) -> look at partials[i]
@@ -308,6 +723,99 @@ impl Note {
         }
     }
 
+    /// gets the duration of the transition at the given index, which is 0 for an unused fade in or out
+    /// fails if the index is out of bounds
+    pub fn get_transition_duration(&self, index: usize) -> Option<BeatUnits> {
+        if index == 0 {
+            Some(self.fade_in_duration)
+        } else if index == self.transitions.len() - 1 {
+            Some(self.fade_out_duration)
+        } else if index < self.transitions.len() {
+            Some(self.partials[index].start_time() - self.partials[index - 1].end_time())
+        } else {
+            None
+        }
+    }
+
+    /// gets the shortest and longest durations the transition at the given index may be set to
+    /// a fade in may not start before 0, and a transition between partials may not shorten
+    /// the partial before it below NotePartial::MIN_DURATION
+    /// fails if the index is out of bounds
+    pub fn get_transition_duration_bounds(&self, index: usize) -> Option<(BeatUnits, BeatUnits)> {
+        if index == 0 {
+            Some((BeatUnits(0), self.partials[0].start_time()))
+        } else if index == self.transitions.len() - 1 {
+            Some((BeatUnits(0), BeatUnits::MAX - self.partials.last().unwrap().end_time()))
+        } else if index < self.transitions.len() {
+            let longest = self.partials[index].start_time() - self.partials[index - 1].start_time() - NotePartial::MIN_DURATION;
+            Some((BeatUnits(0), longest))
+        } else {
+            None
+        }
+    }
+
+    /// sets the duration of the transition at the given index, such as the glide between two partials
+    /// the transition keeps its end, so a fade in starts the note earlier or later and a transition
+    /// between partials lengthens or shortens the partial before it
+    /// the fade out keeps its start instead, ending the note earlier or later
+    /// a fade in or out set to 0 goes back to the pitch of the partial it fades from
+    /// returns the duration the transition originally had upon success
+    /// fails if the index or duration is out of bounds, as given by get_transition_duration_bounds
+    pub fn set_transition_duration(&mut self, index: usize, duration: BeatUnits) -> Result<BeatUnits, TransitionDurationError> {
+        let (shortest, longest) = self.get_transition_duration_bounds(index)
+            .ok_or(TransitionDurationError::TransitionOutOfBounds)?;
+        if duration < shortest || duration > longest {
+            return Err(TransitionDurationError::DurationOutOfBounds);
+        }
+        let old_duration = self.get_transition_duration(index).unwrap();
+
+        if index == 0 {
+            self.fade_in_duration = duration;
+            if duration == BeatUnits(0) {
+                self.fade_in_pitch = self.partials[0].pitch;
+            }
+        } else if index == self.transitions.len() - 1 {
+            self.fade_out_duration = duration;
+            if duration == BeatUnits(0) {
+                self.fade_out_pitch = self.partials.last().unwrap().pitch;
+            }
+        } else {
+            let end = self.partials[index].start_time();
+            self.partials[index - 1].set_end_time(end - duration);
+        }
+        Ok(old_duration)
+    }
+
+    /// gets the pitch the note fades in from
+    pub fn fade_in_pitch(&self) -> DetunedPitch {
+        self.fade_in_pitch
+    }
+
+    /// sets the pitch the note fades in from
+    /// fails if the note does not fade in, returning false
+    pub fn set_fade_in_pitch(&mut self, pitch: DetunedPitch) -> bool {
+        if self.fade_in_duration == BeatUnits(0) {
+            return false;
+        }
+        self.fade_in_pitch = pitch;
+        true
+    }
+
+    /// gets the pitch the note fades out to
+    pub fn fade_out_pitch(&self) -> DetunedPitch {
+        self.fade_out_pitch
+    }
+
+    /// sets the pitch the note fades out to
+    /// fails if the note does not fade out, returning false
+    pub fn set_fade_out_pitch(&mut self, pitch: DetunedPitch) -> bool {
+        if self.fade_out_duration == BeatUnits(0) {
+            return false;
+        }
+        self.fade_out_pitch = pitch;
+        true
+    }
+
     /// checks if this note overlaps with another note
     pub fn overlaps(&self, other: &Note) -> bool {
         self.start_time() <= other.end_time() && other.start_time() <= self.end_time()
@@ -414,6 +922,8 @@ impl Note {
             fade_in_pitch: other_fade_in_pitch,
             fade_out_duration: self.fade_out_duration,
             fade_out_pitch: self.fade_out_pitch,
+            velocity: self.velocity,
+            groups: self.groups.clone(),
         });
 
         self.fade_out_duration = BeatUnits(0);
@@ -440,9 +950,17 @@ impl Note {
     /// of this note to connect with the other note. if false, the other note's transition
     /// will be used
     ///
+    /// the combined note belongs to the groups of both notes
+    ///
     /// (debug build) panics if the notes overlap
     pub fn combine_notes(&mut self, mut other: Note, use_this_transition: bool) {
-        debug_assert!(self.overlaps_allow_point(&other), "You may not combine notes that overlap.");
+        debug_assert!(!self.overlaps_allow_point(&other), "You may not combine notes that overlap.");
+
+        for group in std::mem::take(&mut other.groups) {
+            if !self.in_group(&group) {
+                self.groups.push(group);
+            }
+        }
 
         if self.start_time() < other.start_time() {
             if use_this_transition {
@@ -471,6 +989,9 @@ impl Note {
 
             self.fade_in_duration = other.fade_in_duration;
             self.fade_in_pitch = other.fade_in_pitch;
+
+            // the earlier note is the one struck
+            self.velocity = other.velocity;
         }
     }
 
@@ -515,8 +1036,9 @@ impl Note {
             return None;
         }
 
+        // the first partial may not move into the fade in before it
         let lower_bound = if start == 0 {
-            BeatUnits(0)
+            self.fade_in_duration
         } else {
             self.partials[start - 1].end_time()
         };
@@ -544,7 +1066,7 @@ impl Note {
         }
 
         let lower_bound = if start == 0 {
-            -self.partials[start].start_time()
+            self.fade_in_duration - self.partials[start].start_time()
         } else {
             self.partials[start - 1].end_time() - self.partials[start].start_time()
         };
@@ -600,7 +1122,7 @@ impl<'a> Iterator for NoteTransitionIter<'a> {
 }
 
 /// a part of a note with a constant base pitch and possible vibrato
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct NotePartial {
     /// the pitch of the partial note
     pub pitch: DetunedPitch,
@@ -619,10 +1141,16 @@ pub struct NotePartial {
     /// Invariants:
     ///  1) vibrato is contained entirely wiithin the note's up time
     vibrato: Vibrato,
+
+    /// the linear gain of the partial, scaling the note's velocity
+    /// Invariants:
+    /// 	1) within 0.0 and MAX_GAIN
+    gain: f32,
 }
 
 impl NotePartial {
     pub const MIN_DURATION: BeatUnits = BeatUnits(1);
+    pub const MAX_GAIN: f32 = 4.0;
 
     pub fn new(pitch: DetunedPitch, start: BeatUnits, duration: BeatUnits) -> Self {
         Self {
@@ -630,6 +1158,7 @@ impl NotePartial {
             start,
             duration: duration.max(Self::MIN_DURATION),
             vibrato: Vibrato::new(),
+            gain: 1.0,
         }
     }
 
@@ -649,6 +1178,16 @@ impl NotePartial {
         &self.vibrato
     }
 
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// sets the linear gain of the partial
+    /// will be clamped to 0.0, MAX_GAIN
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = if gain.is_nan() { 1.0 } else { gain.clamp(0.0, Self::MAX_GAIN) };
+    }
+
     /// allows mutable access the contained vibrato via a closure
     /// after the closure executes, ensures that the vibrato maintains the invariants
     /// of a partial note
@@ -703,7 +1242,7 @@ impl NotePartial {
 }
 
 /// a description of vibrato for a length of time
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Vibrato {
     /// the time when vibrato starts in millibeats
     /// Invariants:
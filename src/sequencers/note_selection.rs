@@ -0,0 +1,204 @@
+use super::{
+    note::{BeatUnits, Note},
+    pattern_history::PatternHistory,
+    piano_sequencer::{NoteHandle, PatternCommand, PatternCommandResult, PianoPattern},
+};
+
+/// A set of notes of a piano pattern that are edited together
+/// Edits are recorded in a history as a single edit, replacing the edited notes with copies,
+/// and the selection is updated to hold the copies.
+/// Handles to notes removed by other edits are ignored, and may be forgotten with prune.
+/// Notes may also be named as groups, which are stored on the notes themselves so that they
+/// outlast the selection and are kept through edits, undoing and copying.
+#[derive(Debug, Clone, Default)]
+pub struct NoteSelection {
+    /// the selected notes, in the order they were selected
+    notes: Vec<NoteHandle>,
+}
+
+impl NoteSelection {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// gets handles to the selected notes, in the order they were selected
+    pub fn notes(&self) -> &[NoteHandle] {
+        &self.notes
+    }
+
+    /// gets the number of selected notes that still exist
+    pub fn len(&self) -> usize {
+        self.notes.iter().filter(|handle| handle.is_live()).count()
+    }
+
+    /// returns true if no selected note still exists
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// returns true if the note is selected
+    pub fn contains(&self, handle: &NoteHandle) -> bool {
+        self.notes.iter().any(|selected| selected.ptr_eq(handle))
+    }
+
+    /// adds the note to the selection
+    /// fails if the note is already selected or no longer exists, returning false
+    pub fn select(&mut self, handle: NoteHandle) -> bool {
+        if !handle.is_live() || self.contains(&handle) {
+            return false;
+        }
+        self.notes.push(handle);
+        true
+    }
+
+    /// removes the note from the selection
+    /// fails if the note is not selected, returning false
+    pub fn deselect(&mut self, handle: &NoteHandle) -> bool {
+        let count = self.notes.len();
+        self.notes.retain(|selected| !selected.ptr_eq(handle));
+        self.notes.len() != count
+    }
+
+    /// selects the note if it is not selected, and deselects it otherwise
+    pub fn toggle(&mut self, handle: NoteHandle) {
+        if !self.deselect(&handle) {
+            self.select(handle);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.notes.clear();
+    }
+
+    /// forgets the handles of notes that no longer exist
+    pub fn prune(&mut self) {
+        self.notes.retain(NoteHandle::is_live);
+    }
+
+    /// adds the notes occuring within start and end with a partial whose pitch is within low and high
+    /// in cents from A4 (inclusive) to the selection, such as the notes inside a lasso
+    /// returns the number of notes added
+    pub fn select_area(&mut self, pattern: &PianoPattern, start: BeatUnits, end: BeatUnits, low: i32, high: i32) -> usize {
+        if end < start {
+            return 0;
+        }
+        pattern.query_range_pitched_inplace(start.into_beats(), end.into_beats(), low, high)
+            .into_iter()
+            .filter(|handle| self.select(handle.clone()))
+            .count()
+    }
+
+    /// adds the notes belonging to the group with the given name to the selection
+    /// returns the number of notes added
+    pub fn select_group(&mut self, pattern: &PianoPattern, name: &str) -> usize {
+        pattern.iter()
+            .filter(|handle| handle.note(|note| note.is_some_and(|note| note.in_group(name))))
+            .filter(|handle| self.select(handle.clone()))
+            .count()
+    }
+
+    /// gets the earliest start and latest end of the selected notes
+    pub fn bounds(&self) -> Option<(BeatUnits, BeatUnits)> {
+        self.notes.iter()
+            .filter_map(|handle| handle.note(|note| note.map(|note| (note.start_time(), note.end_time()))))
+            .reduce(|(start, end), (other_start, other_end)| (start.min(other_start), end.max(other_end)))
+    }
+
+    /// moves the selected notes by offset and transposes them by the given number of quarter tones as a unit
+    /// the offset is limited so that no note moves before the start of the pattern
+    /// fails without changing anything if any pitch would be out of the representable range, returning false
+    pub fn move_by(&mut self, pattern: &mut PianoPattern, history: &mut PatternHistory, offset: BeatUnits, quarters: i32) -> bool {
+        let Some((start, _)) = self.bounds() else {
+            return true;
+        };
+        let offset = offset.max(BeatUnits(0) - start);
+        self.edit(pattern, history, |note| {
+            note.set_start_time(note.start_time() + offset);
+            note.transpose(quarters)
+        })
+    }
+
+    /// transposes the selected notes by the given number of quarter tones as a unit
+    /// fails without changing anything if any pitch would be out of the representable range, returning false
+    pub fn transpose(&mut self, pattern: &mut PianoPattern, history: &mut PatternHistory, quarters: i32) -> bool {
+        self.move_by(pattern, history, BeatUnits(0), quarters)
+    }
+
+    /// removes the selected notes from the pattern as a single edit, leaving the selection empty
+    /// returns the number of notes removed
+    pub fn delete(&mut self, pattern: &mut PianoPattern, history: &mut PatternHistory) -> usize {
+        history.begin_group();
+        let removed = self.live_notes()
+            .into_iter()
+            .filter(|note| history.apply(pattern, PatternCommand::RemoveNote { note: Box::new(note.clone()) }).is_applied())
+            .count();
+        history.end_group();
+        self.notes.clear();
+        removed
+    }
+
+    /// adds the selected notes to the group with the given name
+    /// returns the number of notes that were not already in the group
+    pub fn add_to_group(&mut self, pattern: &mut PianoPattern, history: &mut PatternHistory, name: &str) -> usize {
+        let count = self.count_where(|note| !note.in_group(name));
+        self.edit(pattern, history, |note| {
+            note.add_to_group(name);
+            true
+        });
+        count
+    }
+
+    /// removes the selected notes from the group with the given name
+    /// returns the number of notes that were in the group
+    pub fn remove_from_group(&mut self, pattern: &mut PianoPattern, history: &mut PatternHistory, name: &str) -> usize {
+        let count = self.count_where(|note| note.in_group(name));
+        self.edit(pattern, history, |note| {
+            note.remove_from_group(name);
+            true
+        });
+        count
+    }
+
+    /// the number of selected notes for which the predicate is true
+    fn count_where(&self, predicate: impl Fn(&Note) -> bool) -> usize {
+        self.notes.iter()
+            .filter(|handle| handle.note(|note| note.is_some_and(&predicate)))
+            .count()
+    }
+
+    /// copies of the selected notes that still exist
+    fn live_notes(&self) -> Vec<Note> {
+        self.notes.iter()
+            .filter_map(|handle| handle.note(|note| note.cloned()))
+            .collect()
+    }
+
+    /// edits a copy of each selected note, then replaces the notes that changed with their copies as a single edit
+    /// every note is removed before any copy is added, so that copies are never mistaken for the notes they replace
+    /// fails without changing anything if the edit fails for any note, returning false
+    pub fn edit(&mut self, pattern: &mut PianoPattern, history: &mut PatternHistory, mut edit: impl FnMut(&mut Note) -> bool) -> bool {
+        self.prune();
+        let mut edits = Vec::new();
+        for (index, note) in self.live_notes().into_iter().enumerate() {
+            let mut edited = note.clone();
+            if !edit(&mut edited) {
+                return false;
+            }
+            if edited != note {
+                edits.push((index, note, edited));
+            }
+        }
+
+        history.begin_group();
+        for (_, note, _) in &edits {
+            history.apply(pattern, PatternCommand::RemoveNote { note: Box::new(note.clone()) });
+        }
+        for (index, _, edited) in edits {
+            if let PatternCommandResult::Applied { notes, .. } = history.apply(pattern, PatternCommand::AddNote { note: Box::new(edited) }) {
+                self.notes[index] = notes[0].clone();
+            }
+        }
+        history.end_group();
+        true
+    }
+}
@@ -0,0 +1,128 @@
+use egui::{Key, KeyboardShortcut, Modifiers};
+
+use super::piano_sequencer::{PatternCommand, PatternCommandResult, PianoPattern};
+
+/// The undo history of a piano pattern
+/// Each edit is stored as the commands that revert it, taken from the pattern when the edit was applied.
+/// Undoing applies those commands and stores their own inverses for redo.
+/// Commands refer to notes by value, so they stay valid as undoing and redoing replace notes.
+#[derive(Debug, Default)]
+pub struct PatternHistory {
+    /// the commands reverting each edit that may be undone, most recent last
+    undo_stack: Vec<Vec<PatternCommand>>,
+
+    /// the commands reapplying each edit that may be redone, most recently undone last
+    redo_stack: Vec<Vec<PatternCommand>>,
+
+    /// whether commands are being gathered into a single edit
+    grouping: bool,
+
+    /// whether the edit being gathered has been pushed to the undo stack yet
+    group_started: bool,
+}
+
+impl PatternHistory {
+    const UNDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+    const REDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::Z);
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// applies the command to the pattern, recording it as an edit if it was applied
+    pub fn apply(&mut self, pattern: &mut PianoPattern, command: PatternCommand) -> PatternCommandResult {
+        let result = pattern.apply(command);
+        if let PatternCommandResult::Applied { inverse, .. } = &result {
+            self.redo_stack.clear();
+            match self.undo_stack.last_mut() {
+                // the newest command must be reverted first
+                Some(edit) if self.grouping && self.group_started => {
+                    edit.splice(0..0, inverse.iter().cloned());
+                }
+                _ => {
+                    self.undo_stack.push(inverse.clone());
+                    self.group_started = self.grouping;
+                }
+            }
+        }
+        result
+    }
+
+    /// starts gathering every applied command into a single edit, such as when deleting several notes
+    pub fn begin_group(&mut self) {
+        self.grouping = true;
+        self.group_started = false;
+    }
+
+    /// stops gathering commands into the current edit
+    pub fn end_group(&mut self) {
+        self.grouping = false;
+        self.group_started = false;
+    }
+
+    /// returns true if there is an edit that may be undone
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    /// returns true if there is an edit that may be redone
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// discards every edit, such as when a different pattern is edited
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.end_group();
+    }
+
+    /// reverts the most recent edit
+    /// returns the result of reverting it, holding handles to the notes it restored
+    pub fn undo(&mut self, pattern: &mut PianoPattern) -> Option<PatternCommandResult> {
+        let commands = self.undo_stack.pop()?;
+        let (redo, result) = Self::apply_all(pattern, commands);
+        self.redo_stack.push(redo);
+        self.end_group();
+        Some(result)
+    }
+
+    /// reapplies the most recently undone edit
+    /// returns the result of reapplying it, holding handles to the notes it changed
+    pub fn redo(&mut self, pattern: &mut PianoPattern) -> Option<PatternCommandResult> {
+        let commands = self.redo_stack.pop()?;
+        let (undo, result) = Self::apply_all(pattern, commands);
+        self.undo_stack.push(undo);
+        self.end_group();
+        Some(result)
+    }
+
+    /// undoes on Ctrl+Z and redoes on Ctrl+Shift+Z (Cmd on macOS)
+    pub fn handle_shortcuts(&mut self, ui: &egui::Ui, pattern: &mut PianoPattern) -> Option<PatternCommandResult> {
+        // the redo shortcut is checked first, as the undo shortcut also matches with shift held
+        if ui.input_mut(|input| input.consume_shortcut(&Self::REDO_SHORTCUT)) {
+            self.redo(pattern)
+        } else if ui.input_mut(|input| input.consume_shortcut(&Self::UNDO_SHORTCUT)) {
+            self.undo(pattern)
+        } else {
+            None
+        }
+    }
+
+    /// applies the commands of an edit in order
+    /// returns the commands reverting them and the combined result
+    fn apply_all(pattern: &mut PianoPattern, commands: Vec<PatternCommand>) -> (Vec<PatternCommand>, PatternCommandResult) {
+        let mut inverses = Vec::with_capacity(commands.len());
+        let mut notes = Vec::new();
+        for command in commands {
+            if let PatternCommandResult::Applied { inverse, notes: changed } = pattern.apply(command) {
+                inverses.push(inverse);
+                notes.extend(changed);
+            }
+        }
+
+        // the last command applied must be the first reverted
+        let inverse: Vec<PatternCommand> = inverses.into_iter().rev().flatten().collect();
+        (inverse.clone(), PatternCommandResult::Applied { inverse, notes })
+    }
+}
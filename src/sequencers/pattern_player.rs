@@ -0,0 +1,207 @@
+use crate::{frame::Frame, playback::{LiveSynth, NoteId}, playback_tree::Arrangement, pitch::TuningSystem, sequencers::{piano_sequencer::{NoteHandle, PatternSpan, PianoPattern}, transport::Transport}};
+
+/// A note of the pattern currently sounding on the synth
+#[derive(Debug, Clone)]
+struct SoundingNote {
+    handle: NoteHandle,
+
+    /// the beat of the transport at which the repetition of the pattern the note started in began
+    offset: f64,
+
+    /// the beat of the transport at which the repetition ends, cutting off the note
+    cutoff: f64,
+
+    /// the change in pitch of the note in cents
+    transpose: f64,
+    id: NoteId,
+
+    /// the pitch last sent for the note in cents from A4
+    cents: f64,
+}
+
+/// Plays a piano pattern on a live synth, one sample at a time
+/// Notes start and stop on the sample whose span of beats holds their start and end,
+/// and the frequency of sounding notes follows their glides and vibrato on every sample.
+#[derive(Debug, Clone)]
+pub struct PatternPlayer {
+    tuning: TuningSystem,
+
+    sounding: Vec<SoundingNote>,
+
+    /// the id given to the next note started
+    next_id: NoteId,
+
+    /// reused when querying the pattern
+    query: Vec<NoteHandle>,
+}
+
+impl PatternPlayer {
+    /// the first id given to notes, above the ids of MIDI keys so that both may play the same synth
+    pub const FIRST_NOTE_ID: NoteId = 1 << 16;
+
+    /// changes in pitch smaller than this many cents are not sent to the synth
+    pub const PITCH_TOLERANCE: f64 = 0.1;
+
+    pub fn new(tuning: TuningSystem) -> Self {
+        Self {
+            tuning,
+            sounding: Vec::new(),
+            next_id: Self::FIRST_NOTE_ID,
+            query: Vec::new(),
+        }
+    }
+
+    pub fn tuning(&self) -> &TuningSystem {
+        &self.tuning
+    }
+
+    /// sets the tuning of notes started after this call
+    pub fn set_tuning(&mut self, tuning: TuningSystem) {
+        self.tuning = tuning;
+    }
+
+    /// returns true if any note of the pattern is sounding
+    pub fn is_sounding(&self) -> bool {
+        !self.sounding.is_empty()
+    }
+
+    fn frequency(&self, cents: f64) -> f32 {
+        self.tuning.get_cent_delta_a4_frequency(cents) as f32
+    }
+
+    fn allocate_id(&mut self) -> NoteId {
+        let id = self.next_id;
+        self.next_id = self.next_id.checked_add(1).unwrap_or(Self::FIRST_NOTE_ID);
+        id
+    }
+
+    /// sends the events of the notes within the range of beats passed during one sample
+    /// notes starting in [start, end) are played in each repetition of the pattern the range reaches,
+    /// notes ending or cut off by the end of their repetition by end are released,
+    /// and the frequency of sounding notes follows their pitch at end
+    pub fn update(&mut self, pattern: &PianoPattern, synth: &mut dyn LiveSynth, start: f64, end: f64) {
+        self.follow(synth, end);
+        for span in pattern.spans(start, end) {
+            self.play_span(pattern, synth, span);
+        }
+    }
+
+    /// like update, playing the clips of a track of an arrangement
+    /// nothing new is played while the track is muted
+    pub fn update_track(&mut self, arrangement: &Arrangement, track: usize, synth: &mut dyn LiveSynth, start: f64, end: f64) {
+        self.follow(synth, end);
+        for (pattern, span) in arrangement.track_spans(track, start, end) {
+            self.play_span(pattern, synth, span);
+        }
+    }
+
+    /// releases notes that ended by the given beat and follows the pitch of the rest
+    fn follow(&mut self, synth: &mut dyn LiveSynth, end: f64) {
+        // release notes that ended, were removed, were cut off by the end of their repetition,
+        // or are no longer reached after a jump
+        let mut index = 0;
+        while index < self.sounding.len() {
+            let SoundingNote { offset, cutoff, .. } = self.sounding[index];
+            let bounds = self.sounding[index].handle.note(|note| {
+                note.map(|note| (offset + note.start_time().into_beats(), (offset + note.end_time().into_beats()).min(cutoff)))
+            });
+            let released = bounds.is_none_or(|(note_start, note_end)| note_end <= end || note_start > end);
+            if released {
+                let note = self.sounding.swap_remove(index);
+                synth.set_note_off(note.id, self.frequency(note.cents));
+            } else {
+                index += 1;
+            }
+        }
+
+        // follow glides and vibrato
+        if synth.allow_frequency_change() {
+            for index in 0..self.sounding.len() {
+                let note = &self.sounding[index];
+                let time = end - note.offset;
+                let Some(cents) = note.handle.note(|note| note.and_then(|note| note.get_cent_delta_a4(time))) else {
+                    continue;
+                };
+                let cents = cents + note.transpose;
+                if (cents - note.cents).abs() >= Self::PITCH_TOLERANCE {
+                    synth.set_note_freq(note.id, self.frequency(cents));
+                    self.sounding[index].cents = cents;
+                }
+            }
+        }
+    }
+
+    /// starts the notes of the pattern that start within the span
+    fn play_span(&mut self, pattern: &PianoPattern, synth: &mut dyn LiveSynth, span: PatternSpan) {
+        let mut query = std::mem::take(&mut self.query);
+        query.clear();
+        pattern.playback_pattern().query_range(&mut query, span.start, span.end);
+        for handle in &query {
+            if self.sounding.iter().any(|note| note.offset == span.offset && note.handle.ptr_eq(handle)) {
+                continue;
+            }
+
+            let id = self.next_id;
+            let started = handle.note(|note| note.and_then(|note| {
+                let note_start = note.start_time().into_beats();
+                if span.start <= note_start && note_start < span.end {
+                    let cents = note.get_cent_delta_a4(note_start)? + span.transpose;
+                    Some((cents, note.note_on(id, self.frequency(cents))))
+                } else {
+                    None
+                }
+            }));
+            let Some((cents, event)) = started else {
+                continue;
+            };
+
+            self.allocate_id();
+            event.apply(synth);
+            self.sounding.push(SoundingNote {
+                handle: handle.clone(),
+                offset: span.offset,
+                cutoff: span.cutoff,
+                transpose: span.transpose,
+                id,
+                cents,
+            });
+        }
+        self.query = query;
+    }
+
+    /// plays the pattern against the transport for the given number of samples, passing each sample the synth produces to output
+    /// the transport is advanced by one sample at a time, and sounding notes are released while it is stopped
+    pub fn render(
+        &mut self,
+        pattern: &PianoPattern,
+        synth: &mut dyn LiveSynth,
+        transport: &mut Transport,
+        sample_rate: u32,
+        samples: usize,
+        mut output: impl FnMut(Frame)
+    ) {
+        let seconds = 1.0 / sample_rate as f64;
+        for _ in 0..samples {
+            let (start, end) = transport.advance(seconds);
+            if transport.state().playing {
+                self.update(pattern, synth, start, end);
+            } else if self.is_sounding() {
+                self.release_all(synth);
+            }
+            output(synth.update(sample_rate));
+        }
+    }
+
+    /// releases every sounding note, such as when the transport stops
+    pub fn release_all(&mut self, synth: &mut dyn LiveSynth) {
+        for note in std::mem::take(&mut self.sounding) {
+            synth.set_note_off(note.id, self.frequency(note.cents));
+        }
+    }
+}
+
+impl Default for PatternPlayer {
+    fn default() -> Self {
+        Self::new(TuningSystem::default())
+    }
+}
@@ -0,0 +1,601 @@
+use egui::{Align2, Color32, FontId, Key, PointerButton, Pos2, Rect, Response, Sense, Stroke, StrokeKind, Ui, Vec2};
+
+use crate::pitch::{DetunedPitch, Pitch, Scale, ScaleKind, Tone, Accidental};
+
+use super::{
+    note::{BeatUnits, Note},
+    note_selection::NoteSelection,
+    pattern_history::PatternHistory,
+    piano_sequencer::{NoteHandle, PatternCommand, PatternCommandResult, PianoPattern},
+};
+
+/// what the editor is doing with the pointer
+#[derive(Debug, Clone)]
+enum EditState {
+    Viewing,
+
+    /// dragging the selected notes to a new time and pitch by the grabbed note from where the drag started
+    Moving(NoteHandle, Pos2),
+
+    /// dragging a note up or down to detune it from where the drag started
+    Detuning(NoteHandle, Pos2),
+
+    /// dragging a rectangle from where the drag started, selecting the notes inside when released
+    Selecting(Pos2),
+}
+
+/// an edit to notes, applied once the editor no longer holds handles to the notes shown
+enum NoteEdit {
+    Move { offset: BeatUnits, quarters: i32 },
+    Detune(i32),
+    Delete,
+}
+
+/// An editor for the notes of a piano pattern, with a row for each pitch
+/// Rows are a semitone apart, or a quarter tone apart with the quarter tone grid on,
+/// and rows outside the pattern's scale lock (or the black keys without one) are shaded.
+/// Double clicking inserts a note, resolved with the notes it overlaps by the pattern's overlap policy,
+/// and secondary clicking a note deletes it.
+/// Clicking a note selects it (Shift+click adds it to the selection), dragging over empty space selects
+/// the notes inside the rectangle, and dragging a selected note moves every selected note with it.
+/// The delete key deletes the selected notes.
+/// Alt+dragging a note up or down detunes every pitch of the note in cents.
+/// Scrolling pans the view and Ctrl+scroll zooms it in time.
+/// Every edit is recorded in the widget's history and may be undone with Ctrl+Z while hovered.
+/// A playhead may be drawn at the position being played, which the view may follow a page at a time.
+pub struct PianoRollWidget {
+    /// whether there is a row for every quarter tone rather than every semitone
+    quarter_tones: bool,
+
+    /// the time notes snap to when inserted or moved, at least BeatUnits(1)
+    grid: BeatUnits,
+
+    /// the length of inserted notes, at least BeatUnits(1)
+    note_length: BeatUnits,
+
+    /// the time at the left of the editor in units
+    visible_start: f64,
+
+    /// the time across the editor in units, within MIN_VISIBLE and MAX_VISIBLE
+    visible_length: f64,
+
+    /// the pitch at the bottom of the editor in quarter tones from A4
+    lowest: f32,
+
+    /// the time drawn as being played, if any
+    playhead: Option<BeatUnits>,
+
+    /// whether the view turns to the next page when the playhead passes the right of the editor
+    follow_playhead: bool,
+
+    edit_state: EditState,
+
+    /// the notes moved and deleted together
+    selection: NoteSelection,
+
+    /// the edits made to the pattern
+    history: PatternHistory,
+
+    /// reused when querying the pattern
+    query: Vec<NoteHandle>,
+}
+
+impl PianoRollWidget {
+    const ROW_HEIGHT: f32 = 12.0;
+    const LABEL_WIDTH: f32 = 48.0;
+    const LABEL_FONT_SIZE: f32 = 9.0;
+    const NOTE_ROUNDING: f32 = 2.0;
+
+    pub const MIN_WIDTH: f32 = 200.0;
+    pub const MIN_HEIGHT: f32 = 200.0;
+
+    /// the most a note may be detuned either way in cents, as a detune of 100 is another pitch
+    pub const MAX_DETUNE: i32 = 99;
+
+    /// the cents a note is detuned by for each point dragged
+    const DETUNE_PER_POINT: f32 = 1.0;
+
+    /// the least time shown across the editor, a 16th note
+    const MIN_VISIBLE: f64 = BeatUnits::UNITS_PER_BEAT as f64 / 4.0;
+
+    /// the most time shown across the editor, 16 bars
+    const MAX_VISIBLE: f64 = BeatUnits::UNITS_PER_BEAT as f64 * 64.0;
+
+    /// the lowest and highest pitches shown, C0 and C10, in quarter tones from A4
+    const LOWEST_QUARTERS: f32 = -114.0;
+    const HIGHEST_QUARTERS: f32 = 126.0;
+
+    /// grids denser than this many lines across the editor are not drawn
+    const MAX_GRID_LINES: f64 = 200.0;
+
+    const BACKGROUND_COLOR: Color32 = Color32::from_gray(45);
+    const SHADED_ROW_COLOR: Color32 = Color32::from_gray(32);
+    const QUARTER_ROW_COLOR: Color32 = Color32::from_gray(24);
+    const GRID_COLOR: Color32 = Color32::from_gray(60);
+    const BEAT_COLOR: Color32 = Color32::from_gray(85);
+    const NOTE_COLOR: Color32 = Color32::from_rgb(80, 170, 230);
+    const FOCUS_NOTE_COLOR: Color32 = Color32::from_rgb(230, 140, 40);
+    const SELECTED_NOTE_COLOR: Color32 = Color32::from_rgb(170, 220, 250);
+    const LASSO_COLOR: Color32 = Color32::from_gray(220);
+    const PLAYHEAD_COLOR: Color32 = Color32::from_rgb(240, 240, 120);
+    const LABEL_COLOR: Color32 = Color32::from_gray(200);
+
+    pub fn new() -> Self {
+        Self {
+            quarter_tones: false,
+            grid: BeatUnits::sixteenth(),
+            note_length: BeatUnits::sixteenth(),
+            visible_start: 0.0,
+            visible_length: BeatUnits::UNITS_PER_BEAT as f64 * 4.0,
+            // C3
+            lowest: -42.0,
+            playhead: None,
+            follow_playhead: true,
+            edit_state: EditState::Viewing,
+            selection: NoteSelection::new(),
+            history: PatternHistory::new(),
+            query: Vec::new(),
+        }
+    }
+
+    pub fn quarter_tones(&self) -> bool {
+        self.quarter_tones
+    }
+
+    /// sets whether there is a row for every quarter tone rather than every semitone
+    pub fn set_quarter_tones(&mut self, quarter_tones: bool) {
+        self.quarter_tones = quarter_tones;
+    }
+
+    pub fn grid(&self) -> BeatUnits {
+        self.grid
+    }
+
+    /// sets the time notes snap to when inserted or moved
+    /// does not let grid go below BeatUnits(1)
+    pub fn set_grid(&mut self, grid: BeatUnits) {
+        self.grid = grid.max(BeatUnits(1));
+    }
+
+    pub fn note_length(&self) -> BeatUnits {
+        self.note_length
+    }
+
+    /// sets the length of inserted notes
+    /// does not let length go below BeatUnits(1)
+    pub fn set_note_length(&mut self, length: BeatUnits) {
+        self.note_length = length.max(BeatUnits(1));
+    }
+
+    /// gets the times shown at the left and right of the editor
+    pub fn visible_time(&self) -> (BeatUnits, BeatUnits) {
+        let start = self.visible_start.round() as i32;
+        (BeatUnits(start), BeatUnits(start + self.visible_length.round() as i32))
+    }
+
+    /// sets the times shown at the left and right of the editor
+    /// the time shown is clamped to a 16th note, 16 bars
+    /// fails if end is not after start, returning false
+    pub fn set_visible_time(&mut self, start: BeatUnits, end: BeatUnits) -> bool {
+        if end <= start {
+            return false;
+        }
+        self.visible_start = start.0.max(0) as f64;
+        self.visible_length = ((end - start).0 as f64).clamp(Self::MIN_VISIBLE, Self::MAX_VISIBLE);
+        true
+    }
+
+    /// gets the pitch of the bottom row in quarter tones from A4
+    pub fn lowest_row(&self) -> i32 {
+        self.row_pitch(self.lowest)
+    }
+
+    /// scrolls so that the bottom row has the given pitch in quarter tones from A4
+    pub fn set_lowest_row(&mut self, quarters: i32) {
+        self.lowest = (quarters as f32).clamp(Self::LOWEST_QUARTERS, Self::HIGHEST_QUARTERS);
+    }
+
+    pub fn playhead(&self) -> Option<BeatUnits> {
+        self.playhead
+    }
+
+    /// sets the time within the pattern drawn as being played, or None to draw nothing
+    /// the editor must be shown again to move the playhead, such as on every frame during playback
+    pub fn set_playhead(&mut self, time: Option<BeatUnits>) {
+        self.playhead = time;
+    }
+
+    pub fn follow_playhead(&self) -> bool {
+        self.follow_playhead
+    }
+
+    /// sets whether the view turns to the page holding the playhead whenever it leaves the view
+    pub fn set_follow_playhead(&mut self, follow: bool) {
+        self.follow_playhead = follow;
+    }
+
+    /// discards the history of edits and the selection, which must be done before showing a different pattern
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+        self.selection.clear();
+        self.edit_state = EditState::Viewing;
+    }
+
+    /// gets the selected notes
+    pub fn selection(&self) -> &NoteSelection {
+        &self.selection
+    }
+
+    /// gets the selected notes mutably, such as to select notes from a script
+    pub fn selection_mut(&mut self) -> &mut NoteSelection {
+        &mut self.selection
+    }
+
+    /// the quarter tones between rows
+    fn row_quarters(&self) -> i32 {
+        if self.quarter_tones { 1 } else { 2 }
+    }
+
+    /// the pitch of the row nearest to a pitch in quarter tones from A4
+    fn row_pitch(&self, quarters: f32) -> i32 {
+        let row_quarters = self.row_quarters();
+        (quarters / row_quarters as f32).round() as i32 * row_quarters
+    }
+
+    fn x(&self, plot: Rect, time: BeatUnits) -> f32 {
+        plot.left() + ((time.0 as f64 - self.visible_start) / self.visible_length) as f32 * plot.width()
+    }
+
+    fn time(&self, plot: Rect, x: f32) -> BeatUnits {
+        BeatUnits((self.visible_start + ((x - plot.left()) / plot.width()) as f64 * self.visible_length).round() as i32)
+    }
+
+    /// the y position of the center of a pitch in quarter tones from A4
+    fn y(&self, plot: Rect, quarters: f32) -> f32 {
+        plot.bottom() - (quarters - self.lowest) * Self::ROW_HEIGHT / self.row_quarters() as f32
+    }
+
+    /// the pitch in quarter tones from A4 at a y position
+    fn quarters(&self, plot: Rect, y: f32) -> f32 {
+        self.lowest + (plot.bottom() - y) * self.row_quarters() as f32 / Self::ROW_HEIGHT
+    }
+
+    /// returns true if the row with the given pitch in quarter tones from A4 is drawn shaded
+    fn is_shaded(pattern: &PianoPattern, quarters: i32) -> bool {
+        // without a scale lock, the black keys are shaded
+        let c_major = Scale::new(Pitch { octave: 4, tone: Tone::C, accidental: Accidental::Natural }, ScaleKind::Major);
+        let scale = pattern.scale_lock().copied().unwrap_or(c_major);
+        quarters % 2 != 0 || !scale.contains_semitone_a4(quarters / 2)
+    }
+
+    /// pans and zooms the view with the scroll wheel
+    fn navigate(&mut self, ui: &Ui, response: &Response, plot: Rect) {
+        if !response.hovered() {
+            return;
+        }
+        let (zoom, scroll) = ui.input(|input| (input.zoom_delta(), input.smooth_scroll_delta));
+        if zoom != 1.0 {
+            let anchor = response.hover_pos().map_or(self.visible_start, |pos| self.time(plot, pos.x).0 as f64);
+            let length = (self.visible_length / zoom as f64).clamp(Self::MIN_VISIBLE, Self::MAX_VISIBLE);
+            self.visible_start = anchor - (anchor - self.visible_start) * length / self.visible_length;
+            self.visible_length = length;
+        } else if scroll != Vec2::ZERO {
+            self.visible_start -= (scroll.x / plot.width()) as f64 * self.visible_length;
+            self.lowest += scroll.y / Self::ROW_HEIGHT * self.row_quarters() as f32;
+        }
+        self.visible_start = self.visible_start.max(0.0);
+        self.lowest = self.lowest.clamp(Self::LOWEST_QUARTERS, Self::HIGHEST_QUARTERS);
+    }
+
+    /// the rectangles of the partials of a note
+    fn partial_rects(&self, plot: Rect, note: &Note) -> Vec<Rect> {
+        note.partial_iter()
+            .map(|partial| {
+                let y = self.y(plot, partial.pitch.cent_delta_a4() as f32 / Pitch::CENTS_PER_MICROTONE as f32);
+                Rect::from_min_max(
+                    Pos2::new(self.x(plot, partial.start_time()), y - Self::ROW_HEIGHT / 2.0),
+                    Pos2::new(self.x(plot, partial.end_time()), y + Self::ROW_HEIGHT / 2.0),
+                )
+            })
+            .collect()
+    }
+
+    /// the edit made by dragging the grabbed note from origin to pos
+    /// moves snap the start of the grabbed note to the grid, and are limited so that no selected note moves before 0
+    fn drag_edit(&self, plot: Rect, note: &Note, pos: Pos2) -> Option<NoteEdit> {
+        match &self.edit_state {
+            EditState::Viewing | EditState::Selecting(_) => None,
+            EditState::Moving(_, origin) => {
+                let offset = self.time(plot, pos.x) - self.time(plot, origin.x);
+                let rows = ((origin.y - pos.y) / Self::ROW_HEIGHT).round() as i32;
+                let start = (note.start_time() + offset).round_to(self.grid).max(BeatUnits(0));
+                let earliest = self.selection.bounds().map_or(note.start_time(), |(start, _)| start);
+                Some(NoteEdit::Move {
+                    offset: (start - note.start_time()).max(BeatUnits(0) - earliest),
+                    quarters: rows * self.row_quarters(),
+                })
+            }
+            EditState::Detuning(_, origin) => {
+                Some(NoteEdit::Detune(((origin.y - pos.y) * Self::DETUNE_PER_POINT).round() as i32))
+            }
+        }
+    }
+
+    /// applies an edit to a note, returning the edited note
+    fn apply_edit(mut note: Note, edit: &NoteEdit) -> Option<Note> {
+        match edit {
+            NoteEdit::Move { offset, quarters } => {
+                note.set_start_time(note.start_time() + *offset);
+                // a note moved out of the representable range keeps its pitch
+                note.transpose(*quarters);
+            }
+            NoteEdit::Detune(cents) => note.map_pitches(|pitch| DetunedPitch {
+                detune: (pitch.detune as i32 + cents).clamp(-Self::MAX_DETUNE, Self::MAX_DETUNE) as i8,
+                ..pitch
+            }),
+            NoteEdit::Delete => return None,
+        }
+        Some(note)
+    }
+
+    /// snaps every pitch of the note to the scale lock, as inserting it would
+    fn snapped(scale: Option<Scale>, mut note: Note) -> Note {
+        if let Some(scale) = scale {
+            note.map_pitches(|pitch| scale.snap(pitch));
+        }
+        note
+    }
+
+    /// draws the editor filling the available space and handles its input
+    /// the response is marked as changed if the pattern was edited
+    pub fn show(&mut self, ui: &mut Ui, pattern: &mut PianoPattern) -> Response {
+        let request_dim = {
+            let available = ui.available_size();
+            Vec2::new(available.x.max(Self::MIN_WIDTH), available.y.max(Self::MIN_HEIGHT))
+        };
+        let (mut response, painter) = ui.allocate_painter(request_dim, Sense::click_and_drag());
+        let plot = Rect::from_min_max(response.rect.min + Vec2::new(Self::LABEL_WIDTH, 0.0), response.rect.max);
+        self.navigate(ui, &response, plot);
+        if self.follow_playhead
+            && let Some(playhead) = self.playhead
+        {
+            let time = playhead.0 as f64;
+            if time < self.visible_start || self.visible_start + self.visible_length <= time {
+                self.visible_start = time.max(0.0);
+            }
+        }
+
+        let mut changed = false;
+        if response.hovered() && self.history.handle_shortcuts(ui, pattern).is_some() {
+            // the notes being dragged or selected may have been replaced
+            self.edit_state = EditState::Viewing;
+            self.selection.prune();
+            changed = true;
+        }
+
+        let mouse_pos = ui.input(|input| input.pointer.latest_pos()).unwrap_or(plot.center());
+
+        // the notes shown, with the rectangles of their partials
+        // pitches a row beyond the edges are included for notes that are partly shown
+        let (start, end) = self.visible_time();
+        let margin = self.row_quarters() as f32;
+        let cents = |quarters: f32| quarters * Pitch::CENTS_PER_MICROTONE as f32;
+        let low = cents(self.quarters(plot, plot.bottom()) - margin).floor() as i32;
+        let high = cents(self.quarters(plot, plot.top()) + margin).ceil() as i32;
+        let mut query = std::mem::take(&mut self.query);
+        query.clear();
+        pattern.query_range_pitched(&mut query, start.into_beats(), end.into_beats(), low, high);
+        let notes: Vec<(NoteHandle, Vec<Rect>)> = query.drain(..)
+            .filter_map(|handle| {
+                let rects = handle.note(|note| note.map(|note| self.partial_rects(plot, note)))?;
+                Some((handle, rects))
+            })
+            .collect();
+        self.query = query;
+        let hovered = |pos: Pos2| notes.iter().rev().find(|(_, rects)| rects.iter().any(|rect| rect.contains(pos)));
+        let shift = ui.input(|input| input.modifiers.shift);
+
+        // start dragging a note, or a rectangle over empty space
+        if response.drag_started_by(PointerButton::Primary)
+            && let Some(origin) = ui.input(|input| input.pointer.press_origin())
+        {
+            if let Some((handle, _)) = hovered(origin) {
+                if ui.input(|input| input.modifiers.alt) {
+                    self.edit_state = EditState::Detuning(handle.clone(), origin);
+                } else {
+                    if !self.selection.contains(handle) {
+                        if !shift {
+                            self.selection.clear();
+                        }
+                        self.selection.select(handle.clone());
+                    }
+                    self.edit_state = EditState::Moving(handle.clone(), origin);
+                }
+            } else if plot.contains(origin) {
+                if !shift {
+                    self.selection.clear();
+                }
+                self.edit_state = EditState::Selecting(origin);
+            }
+        }
+
+        // find the edit made this frame, applied after drawing
+        let mut pending: Option<(NoteHandle, NoteEdit)> = None;
+        let mut moved: Option<NoteEdit> = None;
+        let mut insert: Option<Note> = None;
+        let mut delete_selection = false;
+        let grabbed = match &self.edit_state {
+            EditState::Moving(handle, _) | EditState::Detuning(handle, _) => Some(handle.clone()),
+            EditState::Viewing | EditState::Selecting(_) => None,
+        };
+        let released = ui.input(|input| !input.pointer.primary_down());
+        if let EditState::Selecting(origin) = self.edit_state && released {
+            let rect = Rect::from_two_pos(origin, mouse_pos);
+            let low = cents(self.quarters(plot, rect.bottom())).floor() as i32;
+            let high = cents(self.quarters(plot, rect.top())).ceil() as i32;
+            self.selection.select_area(pattern, self.time(plot, rect.left()), self.time(plot, rect.right()), low, high);
+            self.edit_state = EditState::Viewing;
+        } else if let Some(handle) = &grabbed && released {
+            let edit = handle.note(|note| note.and_then(|note| self.drag_edit(plot, note, mouse_pos)));
+            match (&self.edit_state, edit) {
+                (EditState::Moving(..), Some(edit)) => moved = Some(edit),
+                (_, edit) => pending = edit.map(|edit| (handle.clone(), edit)),
+            }
+            self.edit_state = EditState::Viewing;
+        } else if response.clicked_by(PointerButton::Primary)
+            && let Some(pos) = response.interact_pointer_pos()
+        {
+            match hovered(pos) {
+                Some((handle, _)) if shift => self.selection.toggle(handle.clone()),
+                Some((handle, _)) => {
+                    self.selection.clear();
+                    self.selection.select(handle.clone());
+                }
+                None if !shift => self.selection.clear(),
+                None => {}
+            }
+        }
+        if response.secondary_clicked()
+            && let Some(pos) = response.interact_pointer_pos()
+            && let Some((handle, _)) = hovered(pos)
+        {
+            pending = Some((handle.clone(), NoteEdit::Delete));
+        } else if response.double_clicked()
+            && let Some(pos) = response.interact_pointer_pos()
+            && plot.contains(pos)
+            && hovered(pos).is_none()
+            && let Some(pitch) = Pitch::from_quarter_delta_a4(self.row_pitch(self.quarters(plot, pos.y)))
+        {
+            let start = BeatUnits(self.time(plot, pos.x).0.div_euclid(self.grid.0) * self.grid.0).max(BeatUnits(0));
+            insert = Some(Note::new(DetunedPitch { base_pitch: pitch, detune: 0 }, start, self.note_length));
+        } else if response.hovered() && ui.input(|input| input.key_pressed(Key::Delete) || input.key_pressed(Key::Backspace)) {
+            delete_selection = true;
+        }
+
+        // rows and their labels
+        painter.rect_filled(plot, 0.0, Self::BACKGROUND_COLOR);
+        let font = FontId::proportional(Self::LABEL_FONT_SIZE);
+        let row_quarters = self.row_quarters();
+        let mut row = self.row_pitch(self.lowest) - row_quarters;
+        while self.y(plot, row as f32) + Self::ROW_HEIGHT / 2.0 >= plot.top() {
+            let y = self.y(plot, row as f32);
+            let rect = Rect::from_min_max(Pos2::new(plot.left(), y - Self::ROW_HEIGHT / 2.0), Pos2::new(plot.right(), y + Self::ROW_HEIGHT / 2.0))
+                .intersect(plot);
+            if row % 2 != 0 {
+                painter.rect_filled(rect, 0.0, Self::QUARTER_ROW_COLOR);
+            } else if Self::is_shaded(pattern, row) {
+                painter.rect_filled(rect, 0.0, Self::SHADED_ROW_COLOR);
+            }
+            if let Some(pitch) = Pitch::from_quarter_delta_a4(row) && y <= plot.bottom() && y >= plot.top() {
+                painter.text(Pos2::new(response.rect.left() + 2.0, y), Align2::LEFT_CENTER, pitch.to_string(), font.clone(), Self::LABEL_COLOR);
+            }
+            row += row_quarters;
+        }
+
+        // grid lines, with the lines on beats drawn brighter
+        if self.visible_length / (self.grid.0 as f64) <= Self::MAX_GRID_LINES {
+            let mut line = BeatUnits((start.0.div_euclid(self.grid.0)) * self.grid.0);
+            while line <= end {
+                let x = self.x(plot, line);
+                let color = if line.0 % BeatUnits::UNITS_PER_BEAT == 0 { Self::BEAT_COLOR } else { Self::GRID_COLOR };
+                if x >= plot.left() {
+                    painter.vline(x, plot.y_range(), Stroke::new(1.0, color));
+                }
+                line += self.grid;
+            }
+        }
+
+        // notes, with the notes being dragged drawn where they would be dropped
+        let painter = painter.with_clip_rect(plot.intersect(painter.clip_rect()));
+        let drag_edit = grabbed.as_ref()
+            .and_then(|grabbed| grabbed.note(|note| note.and_then(|note| self.drag_edit(plot, note, mouse_pos))));
+        for (handle, rects) in &notes {
+            let is_selected = self.selection.contains(handle);
+            let is_dragged = match &self.edit_state {
+                EditState::Moving(..) => is_selected,
+                EditState::Detuning(grabbed, _) => grabbed.ptr_eq(handle),
+                EditState::Viewing | EditState::Selecting(_) => false,
+            };
+            let velocity = handle.note(|note| note.map_or(Note::DEFAULT_VELOCITY, |note| note.velocity()));
+            let base_color = if is_selected { Self::SELECTED_NOTE_COLOR } else { Self::NOTE_COLOR };
+            let mut color = base_color.gamma_multiply((velocity as f32 / Note::MAX_VELOCITY as f32).max(0.3));
+            if is_dragged {
+                color = color.gamma_multiply(0.4);
+            }
+            for rect in rects {
+                painter.rect_filled(*rect, Self::NOTE_ROUNDING, color);
+            }
+            for pair in rects.windows(2) {
+                painter.line_segment([pair[0].right_center(), pair[1].left_center()], Stroke::new(1.0, color));
+            }
+
+            if is_dragged && let Some(edit) = &drag_edit {
+                let preview = handle.note(|note| Self::apply_edit(note?.clone(), edit));
+                for rect in preview.map(|note| self.partial_rects(plot, &note)).unwrap_or_default() {
+                    painter.rect_filled(rect, Self::NOTE_ROUNDING, Self::FOCUS_NOTE_COLOR);
+                }
+            }
+        }
+
+        if let EditState::Selecting(origin) = self.edit_state {
+            let rect = Rect::from_two_pos(origin, mouse_pos);
+            painter.rect_stroke(rect, 0.0, Stroke::new(1.0, Self::LASSO_COLOR), StrokeKind::Inside);
+        }
+
+        if let Some(playhead) = self.playhead {
+            let x = self.x(plot, playhead);
+            if plot.left() <= x && x <= plot.right() {
+                painter.vline(x, plot.y_range(), Stroke::new(1.0, Self::PLAYHEAD_COLOR));
+            }
+        }
+
+        // edit through the history, replacing edited notes with copies so that handles held elsewhere do not prevent editing
+        drop(notes);
+        let scale = pattern.scale_lock().copied();
+        if let Some((handle, edit)) = pending && let Some(note) = handle.note(|note| note.cloned()) {
+            let command = match Self::apply_edit(note.clone(), &edit).map(|edited| Self::snapped(scale, edited)) {
+                Some(edited) if edited == note => None,
+                Some(edited) => Some(PatternCommand::ReplaceNote { note: Box::new(note), with: Box::new(edited) }),
+                None => Some(PatternCommand::RemoveNote { note: Box::new(note) }),
+            };
+            if let Some(command) = command {
+                let result = self.history.apply(pattern, command);
+                if let PatternCommandResult::Applied { notes, .. } = &result
+                    && let Some(replacement) = notes.first()
+                    && self.selection.deselect(&handle)
+                {
+                    self.selection.select(replacement.clone());
+                }
+                changed |= result.is_applied();
+            }
+        }
+        if let Some(edit) = moved {
+            // edited notes are replaced, so the selection holds different notes if anything moved
+            self.selection.prune();
+            let before = self.selection.notes().to_vec();
+            self.selection.edit(pattern, &mut self.history, |note| {
+                *note = Self::snapped(scale, Self::apply_edit(note.clone(), &edit).unwrap());
+                true
+            });
+            changed |= before.iter().zip(self.selection.notes()).any(|(before, after)| !before.ptr_eq(after));
+        }
+        if delete_selection {
+            changed |= self.selection.delete(pattern, &mut self.history) > 0;
+        }
+        if let Some(note) = insert {
+            let note = Box::new(Self::snapped(scale, note));
+            changed |= self.history.apply(pattern, PatternCommand::InsertNote { note }).is_applied();
+        }
+
+        if changed {
+            response.mark_changed();
+        }
+        response
+    }
+}
+
+impl Default for PianoRollWidget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,8 +1,8 @@
-use std::{cmp::Ordering, rc::{Rc, Weak}};
+use std::{cell::OnceCell, cmp::Ordering, sync::{Arc, Weak}};
 
-use crate::sequencers::note::{BeatUnits, Note};
+use crate::{pitch::{DetunedPitch, Scale}, sequencers::{arpeggiator::Arpeggiator, chord::stack_intervals, note::{BeatUnits, Note, Vibrato}}};
 
-/// a wrapper around a Weak<Note> that prevents any kind of promotion to an Rc
+/// a wrapper around a Weak<Note> that prevents any kind of promotion to an Arc
 /// strong count is limited to 1
 #[derive(Debug, Clone)]
 pub struct NoteHandle(Weak<Note>);
@@ -25,7 +25,7 @@ impl NoteHandle {
 
     /// checks if the owned note owns this handle
     pub fn is_handle_of(&self, owner: &OwnedNote) -> bool {
-        self.0.as_ptr() == Rc::as_ptr(&owner.0)
+        self.0.as_ptr() == Arc::as_ptr(&owner.0)
     }
 
     /// checks how many other handles exist to the same note
@@ -39,14 +39,14 @@ impl NoteHandle {
     }
 }
 
-/// a wrapper around Rc<Note> that prevents any kind of duplication of the note
+/// a wrapper around Arc<Note> that prevents any kind of duplication of the note
 #[derive(Debug)]
-pub struct OwnedNote(Rc<Note>);
+pub struct OwnedNote(Arc<Note>);
 
 impl OwnedNote {
     /// creates a new instance of an owned note
     pub fn new(note: Note) -> Self {
-        Self(Rc::new(note))
+        Self(Arc::new(note))
     }
 
     /// accesses the note immutably
@@ -57,25 +57,298 @@ impl OwnedNote {
     /// accesses the note mutably
     /// fails if there exist handles to this note
     pub fn note_mut(&mut self) -> Option<&mut Note> {
-        Rc::get_mut(&mut self.0)
+        Arc::get_mut(&mut self.0)
     }
 
     /// checks how many handles exist to the note
     pub fn handle_count(&self) -> usize {
-        Rc::weak_count(&self.0)
+        Arc::weak_count(&self.0)
     }
 
     /// checks if the handle points to this owned note
     pub fn is_owner_of(&self, handle: &NoteHandle) -> bool {
-        Rc::as_ptr(&self.0) == handle.0.as_ptr()
+        Arc::as_ptr(&self.0) == handle.0.as_ptr()
+    }
+
+    /// creates a handle to the note
+    pub fn handle(&self) -> NoteHandle {
+        NoteHandle(Arc::downgrade(&self.0))
+    }
+}
+
+/// how a note inserted into a pattern is resolved with the notes it overlaps in time, whatever their pitch
+/// notes that only touch at a point do not overlap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlapPolicy {
+    /// the note is inserted alongside the notes it overlaps, as in a chord
+    Layer,
+
+    /// the note is not inserted if it overlaps any note
+    Reject,
+
+    /// notes starting before the note are cut where it starts, and notes starting during it are removed
+    Truncate,
+
+    /// like Truncate, but the note starting latest before the note is joined to it with Note::combine_notes,
+    /// gliding into the note as in a legato line
+    Merge,
+}
+
+/// which parts of a note are moved toward the grid when quantizing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuantizeMode {
+    /// only the start is moved, the note keeps its length
+    Start,
+
+    /// the start is moved and the length is moved toward a whole number of grid steps (at least one)
+    StartAndLength,
+}
+
+/// how many times a pattern plays back to back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatternRepeat {
+    /// the pattern plays the given number of times, then falls silent
+    Times(u32),
+
+    /// the pattern loops until playback stops
+    Forever,
+}
+
+/// a command that can be passed to a piano pattern
+/// notes are referred to by value, acting on a note of the pattern equal to the one given,
+/// so that commands stay valid after undoing and redoing replace the notes they refer to
+/// the scale lock is not applied, so that undoing restores notes exactly
+#[derive(Debug, Clone)]
+pub enum PatternCommand {
+    /// adds the note
+    AddNote{note: Box<Note>},
+
+    /// adds the note after resolving the notes it overlaps by the pattern's overlap policy, which may cut or remove them
+    InsertNote{note: Box<Note>},
+
+    /// removes the note
+    RemoveNote{note: Box<Note>},
+
+    /// moves the note to start at the given time and transposes it by the given number of quarter tones
+    MoveNote{note: Box<Note>, start: BeatUnits, quarters: i32},
+
+    /// moves the end of the note, lengthening or shortening its last partial
+    ResizeNote{note: Box<Note>, end: BeatUnits},
+
+    /// splits the note in two before the partial with the given index
+    SplitNote{note: Box<Note>, partial: usize},
+
+    /// joins two notes that do not overlap into one, see Note::combine_notes
+    CombineNotes{note: Box<Note>, other: Box<Note>, use_this_transition: bool},
+
+    /// replaces the vibrato of the partial with the given index
+    SetVibrato{note: Box<Note>, partial: usize, vibrato: Vibrato},
+
+    /// replaces the note with another, used to undo edits that cannot be reversed exactly
+    ReplaceNote{note: Box<Note>, with: Box<Note>},
+}
+
+/// the outcome of applying a PatternCommand
+#[derive(Debug, Clone)]
+pub enum PatternCommandResult {
+    /// the command was applied
+    Applied {
+        /// applying these commands in order undoes the command
+        inverse: Vec<PatternCommand>,
+
+        /// handles to the notes created or changed by the command
+        notes: Vec<NoteHandle>,
+    },
+
+    /// the command does not fit the pattern, which was left unchanged
+    Rejected,
+}
+
+impl PatternCommandResult {
+    pub fn is_applied(&self) -> bool {
+        matches!(self, Self::Applied { .. })
+    }
+}
+
+/// a section of a pattern reached while the transport passes a range of beats
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PatternSpan {
+    /// the beat of the transport at which this repetition of the pattern starts
+    pub offset: f64,
+
+    /// the start of the section in beats within the pattern (inclusive)
+    pub start: f64,
+
+    /// the end of the section in beats within the pattern (exclusive)
+    pub end: f64,
+
+    /// the beat of the transport at which this repetition ends, cutting off notes still sounding
+    pub cutoff: f64,
+
+    /// the change in pitch of every note in cents
+    pub transpose: f64,
+}
+
+/// iterates over the sections of a pattern reached while the transport passes a range of beats
+#[derive(Debug, Clone)]
+pub struct PatternSpans {
+    /// the beat of the transport at which the first repetition starts
+    origin: f64,
+
+    /// the length of one repetition in beats
+    length: f64,
+
+    /// the end of the played repetitions in beats after origin
+    limit: f64,
+
+    /// the part of the range not yet iterated over, in beats after origin
+    start: f64,
+    end: f64,
+
+    /// the repetition holding start, counted from 0
+    repetition: f64,
+
+    transpose: f64,
+}
+
+impl PatternSpans {
+    /// transposes every note reached by the given number of cents
+    pub fn with_transpose(mut self, cents: f64) -> Self {
+        self.transpose = cents;
+        self
+    }
+}
+
+impl Iterator for PatternSpans {
+    type Item = PatternSpan;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let end = self.end.min(self.limit);
+        let offset = self.repetition * self.length;
+        if self.start >= end || offset >= end {
+            return None;
+        }
+
+        // the repetition is counted rather than found from start so that rounding can never repeat a span
+        let span = PatternSpan {
+            offset: self.origin + offset,
+            start: (self.start - offset).clamp(0.0, self.length),
+            end: end.min(offset + self.length) - offset,
+            cutoff: self.origin + offset + self.length,
+            transpose: self.transpose,
+        };
+        self.repetition += 1.0;
+        self.start = offset + self.length;
+        Some(span)
+    }
+}
+
+/// an in order traversal of the notes of a pattern occuring within a range in beats
+/// nodes ending before the range are skipped along with their subtrees,
+/// and the traversal stops at the first node starting after the range
+pub struct PatternIter<'a> {
+    pattern: &'a PianoPattern,
+
+    /// the nodes whose left subtrees have been entered but which have not been visited
+    stack: Vec<NodeId>,
+
+    /// the node whose notes are being yielded, and the index of the next note
+    current: Option<(NodeId, usize)>,
+    start: f64,
+    end: f64,
+}
+
+impl<'a> PatternIter<'a> {
+    fn new(pattern: &'a PianoPattern, start: f64, end: f64) -> Self {
+        let mut iter = Self {
+            pattern,
+            stack: Vec::new(),
+            current: None,
+            start,
+            end,
+        };
+        iter.push_left(pattern.root);
+        iter
+    }
+
+    /// enters the left spine of the subtree, stopping at subtrees that end before the range
+    fn push_left(&mut self, mut node: Option<NodeId>) {
+        while let Some(id) = node
+            && self.pattern.nodes[id].max.into_beats() >= self.start
+        {
+            self.stack.push(id);
+            node = self.pattern.nodes[id].left;
+        }
+    }
+}
+
+impl Iterator for PatternIter<'_> {
+    type Item = NoteHandle;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((id, index)) = self.current {
+                if let Some(note) = self.pattern.nodes[id].notes.get(index) {
+                    self.current = Some((id, index + 1));
+                    return Some(note.handle());
+                }
+                self.current = None;
+            }
+
+            let id = self.stack.pop()?;
+            let node = &self.pattern.nodes[id];
+            let NodeKey(start, end) = node.key();
+
+            // every node left to visit starts after this one
+            if start.into_beats() > self.end {
+                self.stack.clear();
+                return None;
+            }
+
+            self.push_left(node.right);
+            if self.start <= end.into_beats() {
+                self.current = Some((id, 0));
+            }
+        }
     }
 }
 
 /// a pattern of notes data is stored as an augmented avl tree
+/// the nodes of the tree are kept in an arena and refer to each other by index,
+/// so a pattern owns all of its notes and can be sent to the playback thread
+/// like a clip, a pattern has a length independent of its notes and may repeat
+/// notes reaching past the length are cut off at the end of each repetition
 pub struct PianoPattern {
-    root: *mut Node
+    /// every node of the tree, indexed by NodeId
+    /// removed nodes are left without notes until they are reused
+    nodes: Vec<Node>,
+
+    /// the removed nodes, reused before the arena grows
+    free: Vec<NodeId>,
+
+    root: Option<NodeId>,
+
+    /// the length of one repetition, at least BeatUnits(1)
+    length: BeatUnits,
+
+    repeat: PatternRepeat,
+
+    /// the arpeggiator the pattern is played through, if any
+    arpeggiator: Option<Arpeggiator>,
+
+    /// the notes played by the arpeggiator, created when first played after an edit
+    arpeggiated: OnceCell<Box<PianoPattern>>,
+
+    /// the scale inserted notes are snapped to, if any
+    scale_lock: Option<Scale>,
+
+    /// how inserted notes are resolved with the notes they overlap
+    overlap_policy: OverlapPolicy,
 }
 
+/// the index of a node within the arena of its pattern
+type NodeId = usize;
+
 /// a node in the avl tree of a piano pattern
 struct Node {
     /// the list of notes stored in the node
@@ -86,16 +359,19 @@ struct Node {
     /// the maximum end time found in self or either subtree
     max: BeatUnits,
 
+    /// the lowest and highest pitch of any partial found in self or either subtree, in cents from A4
+    pitches: (i32, i32),
+
     /// the height of the node
     /// its easier to implement an avl tree using height
     /// should also not affect size due to alignment of this struct being at least 4
     height: usize,
 
     /// the left child
-    left: *mut Node,
+    left: Option<NodeId>,
 
     /// the right child
-    right: *mut Node,
+    right: Option<NodeId>,
 }
 
 impl Node {
@@ -140,9 +416,132 @@ impl Ord for NodeKey {
 }
 
 impl PianoPattern {
+    /// the length of new patterns, a bar of four beats
+    pub const DEFAULT_LENGTH: BeatUnits = BeatUnits::beats(4);
+
     pub fn new() -> Self {
         Self {
-            root: std::ptr::null_mut()
+            nodes: Vec::new(),
+            free: Vec::new(),
+            root: None,
+            length: Self::DEFAULT_LENGTH,
+            repeat: PatternRepeat::Times(1),
+            arpeggiator: None,
+            arpeggiated: OnceCell::new(),
+            scale_lock: None,
+            overlap_policy: OverlapPolicy::Layer,
+        }
+    }
+
+    /// discards anything derived from the notes, called on every edit
+    fn touch(&mut self) {
+        self.arpeggiated.take();
+    }
+
+    /// gets the arpeggiator the pattern is played through
+    pub fn arpeggiator(&self) -> Option<&Arpeggiator> {
+        self.arpeggiator.as_ref()
+    }
+
+    /// sets the arpeggiator the pattern is played through, or None to play the notes as written
+    /// the notes of the pattern are not changed
+    pub fn set_arpeggiator(&mut self, arpeggiator: Option<Arpeggiator>) {
+        self.arpeggiator = arpeggiator;
+        self.touch();
+    }
+
+    /// gets the notes to be played, which are the notes of the pattern unless it has an arpeggiator
+    /// arpeggiated notes are created on the first call after an edit, and handles to them die on the next edit
+    pub fn playback_pattern(&self) -> &PianoPattern {
+        match &self.arpeggiator {
+            Some(arpeggiator) => self.arpeggiated.get_or_init(|| Box::new(arpeggiator.apply(self))),
+            None => self,
+        }
+    }
+
+    /// gets the scale inserted notes are snapped to
+    pub fn scale_lock(&self) -> Option<&Scale> {
+        self.scale_lock.as_ref()
+    }
+
+    /// sets the scale inserted notes are snapped to, or None to insert notes as given
+    /// notes already in the pattern are not changed
+    pub fn set_scale_lock(&mut self, scale: Option<Scale>) {
+        self.scale_lock = scale;
+    }
+
+    /// gets how inserted notes are resolved with the notes they overlap
+    pub fn overlap_policy(&self) -> OverlapPolicy {
+        self.overlap_policy
+    }
+
+    /// sets how inserted notes are resolved with the notes they overlap
+    /// notes already in the pattern are not changed
+    pub fn set_overlap_policy(&mut self, policy: OverlapPolicy) {
+        self.overlap_policy = policy;
+    }
+
+    /// gets the length of one repetition of the pattern
+    pub fn length(&self) -> BeatUnits {
+        self.length
+    }
+
+    /// sets the length of one repetition of the pattern
+    /// does not let length go below BeatUnits(1)
+    pub fn set_length(&mut self, length: BeatUnits) {
+        self.length = length.max(BeatUnits(1));
+        self.touch();
+    }
+
+    /// gets how many times the pattern plays
+    pub fn repeat(&self) -> PatternRepeat {
+        self.repeat
+    }
+
+    /// sets how many times the pattern plays
+    pub fn set_repeat(&mut self, repeat: PatternRepeat) {
+        self.repeat = repeat;
+    }
+
+    /// gets the length of every repetition together
+    /// fails if the pattern loops forever
+    pub fn total_length(&self) -> Option<BeatUnits> {
+        self.repeated_length(self.repeat)
+    }
+
+    /// gets the length of the pattern played back to back the given number of times
+    /// fails if the pattern loops forever
+    pub fn repeated_length(&self, repeat: PatternRepeat) -> Option<BeatUnits> {
+        match repeat {
+            PatternRepeat::Times(times) => {
+                let total = self.length.0 as i64 * times as i64;
+                Some(BeatUnits(total.min(i32::MAX as i64) as i32))
+            }
+            PatternRepeat::Forever => None,
+        }
+    }
+
+    /// gets the sections of the pattern reached while the transport passes [start, end) in beats,
+    /// one for each repetition the range touches
+    /// the pattern starts at beat 0 of the transport, and nothing is reached before it or after the last repetition
+    pub fn spans(&self, start: f64, end: f64) -> PatternSpans {
+        self.spans_from(0.0, self.repeat, start, end)
+    }
+
+    /// gets the sections of the pattern reached while the transport passes [start, end) in beats,
+    /// with the first repetition starting at origin and the pattern played the given number of times
+    /// rather than its own repeat, such as for a clip in an arrangement
+    pub fn spans_from(&self, origin: f64, repeat: PatternRepeat, start: f64, end: f64) -> PatternSpans {
+        let length = self.length.into_beats();
+        let start = (start - origin).max(0.0);
+        PatternSpans {
+            origin,
+            length,
+            limit: self.repeated_length(repeat).map_or(f64::INFINITY, |total| total.into_beats()),
+            start,
+            end: end - origin,
+            repetition: (start / length).floor(),
+            transpose: 0.0,
         }
     }
 
@@ -153,6 +552,19 @@ impl PianoPattern {
         output
     }
 
+    /// iterates over every note in order of start time, then end time
+    pub fn iter(&self) -> PatternIter<'_> {
+        PatternIter::new(self, f64::NEG_INFINITY, f64::INFINITY)
+    }
+
+    /// iterates over the notes occuring within the given range in beats in order of start time, then end time
+    /// like query_range, without collecting the notes first
+    /// panics if start > end
+    pub fn iter_range(&self, start: f64, end: f64) -> PatternIter<'_> {
+        assert!(start <= end, "Start must be less than or equal to end.");
+        PatternIter::new(self, start, end)
+    }
+
     /// queries the pattern for a list of notes occuring at the given in beats
     /// puts notes into the given vector
     pub fn query_time(&self, output: &mut Vec<NoteHandle>, time: f64) {
@@ -166,6 +578,51 @@ impl PianoPattern {
         output
     }
 
+    /// queries the pattern for a list of notes occuring within the given range in beats
+    /// with a partial whose pitch is within low and high in cents from A4 (inclusive)
+    pub fn query_range_pitched_inplace(&self, start: f64, end: f64, low: i32, high: i32) -> Vec<NoteHandle> {
+        let mut output = Vec::new();
+        self.query_range_pitched(&mut output, start, end, low, high);
+        output
+    }
+
+    /// queries the pattern for a list of notes occuring within the given range in beats
+    /// with a partial whose pitch is within low and high in cents from A4 (inclusive)
+    /// subtrees without a pitch in the range are skipped, so only notes near the range are visited
+    /// puts notes into the given vector
+    /// panics if start > end
+    pub fn query_range_pitched(&self, output: &mut Vec<NoteHandle>, start: f64, end: f64, low: i32, high: i32) {
+        assert!(start <= end, "Start must be less than or equal to end.");
+
+        // whether the node's subtree may hold notes in the range
+        let reaches = |id: NodeId| {
+            let node = &self.nodes[id];
+            start <= node.max.into_beats() && node.pitches.0 <= high && low <= node.pitches.1
+        };
+
+        // the call stack of nodes to search
+        let mut stack: Vec<NodeId> = self.root.filter(|&root| reaches(root)).into_iter().collect();
+
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[id];
+            let NodeKey(start_bu, end_bu) = node.key();
+            let (node_start, node_end) = (start_bu.into_beats(), end_bu.into_beats());
+
+            // add to output if necessary
+            if node_start <= end && start <= node_end {
+                let in_range = node.notes.iter()
+                    .filter(|note| note.note().partial_iter().any(|partial| (low..=high).contains(&partial.pitch.cent_delta_a4())));
+                output.extend(in_range.map(OwnedNote::handle));
+            }
+
+            // notes to the right start later, so are only considered while this node starts within the range
+            stack.extend(node.left.filter(|&left| reaches(left)));
+            if node_start <= end {
+                stack.extend(node.right.filter(|&right| reaches(right)));
+            }
+        }
+    }
+
     /// queries the pattern for a list of notes occuring within the given range in beats
     /// puts notes into the given vector
     /// panics if start > end
@@ -175,30 +632,34 @@ impl PianoPattern {
         // the call stack of nodes to search
         let mut stack = Vec::new();
 
-        if !self.root.is_null() && unsafe { (*self.root).max.into_beats() > start } {
-            stack.push(self.root);
+        if let Some(root) = self.root
+            && self.nodes[root].max.into_beats() >= start
+        {
+            stack.push(root);
         }
 
-        unsafe {
-            while !stack.is_empty() {
-                let node = &(*stack.pop().unwrap());
-                let NodeKey(start_bu, end_bu) = node.key();
-                let (node_start, node_end) = (start_bu.into_beats(), end_bu.into_beats());
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[id];
+            let NodeKey(start_bu, end_bu) = node.key();
+            let (node_start, node_end) = (start_bu.into_beats(), end_bu.into_beats());
 
-                // add to output if necessary
-                if node_start <= end && start <= node_end {
-                    for note in &node.notes {
-                        output.push(NoteHandle(Rc::downgrade(&note.0)));
-                    }
-                }
+            // add to output if necessary
+            if node_start <= end && start <= node_end {
+                output.extend(node.notes.iter().map(OwnedNote::handle));
+            }
 
-                // check which children we need to consider
-                if !node.left.is_null() && start <= node_start && start <= (*node.left).max.into_beats() {
-                    stack.push(node.left);
-                }
-                if !node.right.is_null() && node_start <= end && start <= (*node.right).max.into_beats() {
-                    stack.push(node.right);
-                }
+            // check which children we need to consider
+            // notes to the left start earlier, but may still reach the range
+            if let Some(left) = node.left
+                && start <= self.nodes[left].max.into_beats()
+            {
+                stack.push(left);
+            }
+            if let Some(right) = node.right
+                && node_start <= end
+                && start <= self.nodes[right].max.into_beats()
+            {
+                stack.push(right);
             }
         }
     }
@@ -206,287 +667,739 @@ impl PianoPattern {
     /// removes the note from the tree, 
     /// returning the owned reference if found
     pub fn remove(&mut self, note: NoteHandle) -> Option<OwnedNote> {
-        if !note.is_live() || self.root.is_null() {
+        let root = self.root?;
+        if !note.is_live() {
             return None;
         }
+        self.touch();
 
         let note_key = note.note(|f| NodeKey::from_note(f.unwrap()) );
 
         // the path taken on the search for the note
-        let mut ancestors = vec![self.root];
-
-        // perform deletion and update path taken to deletion
-        unsafe { loop {
-            let node = *ancestors.last().unwrap();
+        let mut ancestors = vec![root];
 
-            // the child to search next
-            let child = match note_key.cmp(&(*node).key()) {
-                Ordering::Less => &mut (*node).left,
-                Ordering::Greater => &mut (*node).right,
-                Ordering::Equal => {
-                    // we found the node, break
-                    break;
-                }
+        // find the node holding the note
+        loop {
+            let node = &self.nodes[*ancestors.last().unwrap()];
+            let child = match note_key.cmp(&node.key()) {
+                Ordering::Less => node.left,
+                Ordering::Greater => node.right,
+                Ordering::Equal => break,
             };
+            ancestors.push(child?);
+        }
 
-            if child.is_null() {
-                return None;
-            } else {
-                ancestors.push(*child);
-            }
-        }}
-
-        unsafe {
-            // the node potentially containing the note
-            let node = *ancestors.last().unwrap();
-
-            // search and remove the note in the node
-            let mut output = None;
-            (*node).notes.retain(|n| {
-                if n.is_owner_of(&note) {
-                    output = Some(OwnedNote(n.0.clone()));
-                    false
-                } else {
-                    true
-                }
-            });
-
-            // we dont need to delete the node if it's non-empty: exit early
-            if !(*node).notes.is_empty() {
-                return output;
-            }
+        // search and remove the note in the node
+        let id = ancestors.pop().unwrap();
+        let notes = &mut self.nodes[id].notes;
+        let output = notes.remove(notes.iter().position(|n| n.is_owner_of(&note))?);
 
-            // pre-emptively remove the note from the list of ancestors
-            // to avoid access-after-free error
-            ancestors.pop();
+        // we dont need to delete the node if it's non-empty, only narrow the pitch ranges above it
+        if !notes.is_empty() {
+            ancestors.push(id);
+            self.retract(ancestors);
+            return Some(output);
+        }
 
-            // the pointer within the tree to the node being removed
-            let parent_ptr = if let Some(p) = ancestors.last().copied() {
-                if (*p).left == node {
-                    &mut (*p).left
-                } else {
-                    &mut (*p).right
-                }
-            } else {
-                &mut self.root
-            };
+        let parent = ancestors.last().copied();
+        let (left, right) = (self.nodes[id].left, self.nodes[id].right);
+        let replacement = if let (Some(_), Some(right)) = (left, right) {
+            // handle case where we must find successor (2 children)
 
+            // path to the successor following the deleted note
+            let mut successor_ancestors = Vec::new();
 
-            // delete the node
-            if !(*node).right.is_null() && !(*node).left.is_null(){
-                // handle case where we must find successor (2 children)
+            // find successor
+            let mut successor = right;
+            while let Some(left) = self.nodes[successor].left {
+                successor_ancestors.push(successor);
+                successor = left;
+            }
 
-                // path to the successor following the deleted note
-                let mut successor_ancestors = Vec::new();
+            // delete successor from parent, unless the parent is the node being replaced
+            if let Some(successor_parent) = successor_ancestors.last().copied() {
+                self.nodes[successor_parent].left = self.nodes[successor].right;
+                self.nodes[successor].right = Some(right);
+            }
 
-                // find successor
-                let mut successor = (*node).right;
-                while !(*successor).left.is_null() {
-                    successor_ancestors.push(successor);
-                    successor = (*successor).left;
-                }
-                // clean up/rebalance from new location
-                ancestors.push(successor);
+            // replace node with successor
+            self.nodes[successor].left = left;
 
-                // delete successor from parent
-                if let Some(successor_parent) = successor_ancestors.last_mut().copied() {
-                    (*successor_parent).left = (*successor).right;
-                }
+            // moving successor may have caused imbalance from where it was taken up to the root
+            ancestors.push(successor);
+            ancestors.extend(successor_ancestors);
+            Some(successor)
+        } else {
+            // handle cases where we don't need to look for successor
+            // (no children, or 1 child)
+            left.or(right)
+        };
 
-                // replace node with successor
-                (*successor).left = (*node).left;
-                *parent_ptr = successor;
+        // remove from tree
+        self.replace_child(parent, id, replacement);
+        self.free.push(id);
 
-                // moving successor may have caused imbalance, so fix it
-                self.retract(successor_ancestors);
+        // balance tree
+        self.retract(ancestors);
 
-            } else {
-                // handle cases where we don't need to look for successor
-                // (no children, or 1 child)
+        // return the owned note
+        Some(output)
+    }
 
-                // remove from tree
-                *parent_ptr = if (*node).right.is_null() {
-                    (*node).left
-                } else {
-                        (*node).right
-                    };
-            }
 
-            // drop node
-            drop(Box::from_raw(node));
+    /// inserts the note into the tree
+    /// if the pattern has a scale lock, every pitch of the note is first snapped to the scale,
+    /// unless the note has outstanding handles and so cannot be edited
+    /// the notes it overlaps are then resolved by the overlap policy, replacing cut notes with copies
+    /// returns a handle to the inserted note, which is a new note if it was merged,
+    /// or None if the note was rejected by the overlap policy
+    pub fn insert(&mut self, mut note: OwnedNote) -> Option<NoteHandle> {
+        if let Some(scale) = self.scale_lock
+            && let Some(note) = note.note_mut()
+        {
+            note.map_pitches(|pitch| scale.snap(pitch));
+        }
+        self.insert_resolved(note).map(|(handle, _)| handle)
+    }
 
-            // balance tree
-            Self::retract(self, ancestors);
+    /// inserts the note, ignoring the scale lock, after resolving the notes it overlaps by the overlap policy
+    /// returns a handle to the inserted note and the commands undoing the insertion, or None if it was rejected
+    fn insert_resolved(&mut self, mut note: OwnedNote) -> Option<(NoteHandle, Vec<PatternCommand>)> {
+        let mut restore = Vec::new();
+        if self.overlap_policy != OverlapPolicy::Layer {
+            let (start, end) = (note.note().start_time(), note.note().end_time());
+            let overlapping: Vec<NoteHandle> = self.iter_range(start.into_beats(), end.into_beats())
+                .filter(|handle| handle.note(|other| other.is_some_and(|other| other.overlaps_allow_point(note.note()))))
+                .collect();
+            if self.overlap_policy == OverlapPolicy::Reject && !overlapping.is_empty() {
+                return None;
+            }
 
-            // return the owned note
-            output
+            let merged = (self.overlap_policy == OverlapPolicy::Merge).then(|| {
+                overlapping.iter()
+                    .filter(|handle| handle.note(|other| other.unwrap().start_time() < start))
+                    .max_by_key(|handle| handle.note(|other| other.unwrap().start_time()))
+                    .cloned()
+            }).flatten();
+
+            for handle in overlapping {
+                let is_merged = merged.as_ref().is_some_and(|merged| merged.ptr_eq(&handle));
+                let existing = handle.note(|other| other.unwrap().clone());
+                self.remove(handle);
+
+                let mut cut = existing.clone();
+                let kept = existing.start_time() < start && cut.cut_at(start);
+                if kept && is_merged {
+                    cut.combine_notes(note.note().clone(), true);
+                    note = OwnedNote::new(cut);
+                    restore.push(PatternCommand::AddNote { note: Box::new(existing) });
+                } else if kept {
+                    self.insert_exact(cut.clone());
+                    restore.push(PatternCommand::ReplaceNote { note: Box::new(cut), with: Box::new(existing) });
+                } else {
+                    restore.push(PatternCommand::AddNote { note: Box::new(existing) });
+                }
+            }
         }
-    }
 
+        let mut inverse = vec![PatternCommand::RemoveNote { note: Box::new(note.note().clone()) }];
+        inverse.append(&mut restore);
+        let handle = note.handle();
+        self.insert_node(note);
+        Some((handle, inverse))
+    }
 
-    /// inserts the note into the tree
-    pub fn insert(&mut self, note: OwnedNote) {
-        if self.root.is_null() {
+    /// inserts the note into the tree as given, ignoring the scale lock
+    fn insert_node(&mut self, note: OwnedNote) {
+        self.touch();
+        let Some(root) = self.root else {
+            self.root = Some(self.alloc(Node::new(note)));
             return;
-        }
+        };
 
         let note_key = NodeKey::from_note(note.note());
-        let mut ancestors = vec![self.root];
+        let mut ancestors = vec![root];
 
         // perform insertion and update path taken to insertion
         loop {
-            let node = unsafe { &mut **ancestors.last().unwrap() };
-            let child = match note_key.cmp(&node.key()){
-                Ordering::Less => &mut node.left,
-                Ordering::Greater => &mut node.right,
+            let id = *ancestors.last().unwrap();
+            let node = &mut self.nodes[id];
+            let ordering = note_key.cmp(&node.key());
+            let child = match ordering {
+                Ordering::Less => node.left,
+                Ordering::Greater => node.right,
                 Ordering::Equal => {
+                    // the note may widen the pitch ranges above it
                     node.notes.push(note);
-                    return;
+                    break;
                 }
             };
 
-            if child.is_null() {
-                *child = Box::into_raw(Box::new(Node::new(note)));
-                break;
+            if let Some(child) = child {
+                ancestors.push(child);
             } else {
-                ancestors.push(*child);
+                let child = Some(self.alloc(Node::new(note)));
+                if ordering == Ordering::Less {
+                    self.nodes[id].left = child;
+                } else {
+                    self.nodes[id].right = child;
+                }
+                break;
             }
         }
 
         // rebalance
-        unsafe { self.retract(ancestors) };
+        self.retract(ancestors);
     }
 
-    /// performs retracting on the given path from the root
-    /// you must ensure that the path is valid and has no cycles
-    unsafe fn retract(&mut self, mut path: Vec<*mut Node>) {
-        while let Some(node) = path.pop() {
-            unsafe {
-                // the pointer to the node in the tree
-                let node_ptr = if let Some(parent) = path.last() {
-                    if node == (**parent).left {
-                        &mut (**parent).left
-                    } else {
-                        &mut (**parent).right
-                    }
-                } else {
-                    &mut self.root
-                };
+    /// moves the start of each note toward the nearest multiple of grid
+    /// strength [0, 1] is how far each note moves, from not at all to fully onto the grid
+    /// notes are moved whole, so partials keep their timing relative to the start of their note
+    /// moved notes with outstanding handles are replaced by moved copies, so those handles no longer refer to notes of the pattern
+    /// returns the number of notes that were moved
+    pub fn quantize(&mut self, grid: BeatUnits, strength: f64, mode: QuantizeMode) -> usize {
+        if grid.0 <= 0 || !strength.is_finite() {
+            return 0;
+        }
+        let strength = strength.clamp(0.0, 1.0);
+        let toward = |from: BeatUnits, to: BeatUnits| {
+            from + BeatUnits(((to - from).0 as f64 * strength).round() as i32)
+        };
+
+        let mut moved = 0;
+        let notes = self.take_notes()
+            .into_iter()
+            .map(|owned| {
+                let mut quantized = owned.note().clone();
+                let (start, duration) = (quantized.start_time(), quantized.duration());
+                quantized.set_start_time(toward(start, start.round_to(grid)));
+                if mode == QuantizeMode::StartAndLength {
+                    let length = duration.round_to(grid).max(grid);
+                    quantized.set_end_time(quantized.start_time() + toward(duration, length));
+                }
 
-                Self::recalculate_max(node);
-                Self::recalculate_height(node);
+                if quantized == *owned.note() {
+                    return owned;
+                }
+                moved += 1;
+                Self::edited(owned, |note| *note = quantized)
+            })
+            .collect();
 
-                let initial_height = (*node).height;
-                *node_ptr = Self::rebalance(node);
-                if initial_height == (**node_ptr).height {
-                    return;
+        self.rebuild(notes);
+        moved
+    }
+
+    /// moves each note by a random offset within timing_range either way
+    /// and changes its velocity by a random amount within velocity_range either way
+    /// the same seed gives the same offsets for the same pattern, so results can be reproduced
+    /// changed notes with outstanding handles are replaced by changed copies, so those handles no longer refer to notes of the pattern
+    /// returns the number of notes that were changed
+    pub fn humanize(&mut self, timing_range: BeatUnits, velocity_range: u8, seed: u64) -> usize {
+        let timing_range = timing_range.0.max(0);
+        let velocity_range = velocity_range as i32;
+        let mut random = fastrand::Rng::with_seed(seed);
+
+        let mut changed = 0;
+        let notes = self.take_notes()
+            .into_iter()
+            .map(|owned| {
+                // offsets are drawn for every note so that unchanged notes do not shift the others' offsets
+                let offset = BeatUnits(random.i32(-timing_range..=timing_range));
+                let velocity_offset = random.i32(-velocity_range..=velocity_range);
+
+                let mut humanized = owned.note().clone();
+                humanized.set_start_time(humanized.start_time() + offset);
+                humanized.set_velocity((humanized.velocity() as i32 + velocity_offset).clamp(1, Note::MAX_VELOCITY as i32) as u8);
+
+                if humanized == *owned.note() {
+                    return owned;
                 }
-            }
+                changed += 1;
+                Self::edited(owned, |note| *note = humanized)
+            })
+            .collect();
+
+        self.rebuild(notes);
+        changed
+    }
+
+    /// moves every pitch of every note by the given number of quarter tones, where there are 2 per semitone
+    /// notes with outstanding handles are replaced by transposed copies, so those handles no longer refer to notes of the pattern
+    /// fails without changing anything if any pitch would be out of the representable range, returning false
+    pub fn transpose(&mut self, quarters: i32) -> bool {
+        self.transpose_where(quarters, |_| Some(())).is_some()
+    }
+
+    /// like transpose, moving only the given notes
+    /// handles to notes not in the pattern are ignored
+    /// returns handles to the transposed notes, in the order they were given, which replace the given handles
+    /// fails without changing anything if any pitch would be out of the representable range
+    pub fn transpose_notes(&mut self, notes: &[NoteHandle], quarters: i32) -> Option<Vec<NoteHandle>> {
+        let mut handles: Vec<Option<NoteHandle>> = vec![None; notes.len()];
+        let transposed = self.transpose_where(quarters, |owned| notes.iter().position(|handle| handle.is_handle_of(owned)))?;
+        for (position, handle) in transposed {
+            handles[position] = Some(handle);
         }
+        Some(handles.into_iter().flatten().collect())
     }
 
-    /// rebalances at the node, returning the new root
-    unsafe fn rebalance(node: *mut Node) -> *mut Node {
-        unsafe {
-            let bf = Self::balance_factor(node);
-            if bf > 1 {
-                if Self::balance_factor((*node).left) < 0 {
-                    (*node).left = Self::rotate_left((*node).left);
-                }
-                Self::rotate_right(node)
+    /// transposes each note for which select returns Some, returning handles to the transposed notes with what select returned
+    /// fails without changing anything if any pitch would be out of the representable range
+    fn transpose_where<T>(&mut self, quarters: i32, mut select: impl FnMut(&OwnedNote) -> Option<T>) -> Option<Vec<(T, NoteHandle)>> {
+        let notes = self.take_notes();
+
+        // transpose copies first so that nothing changes if any note is out of range
+        let mut copies = Vec::new();
+        let mut in_range = true;
+        for (index, owned) in notes.iter().enumerate() {
+            if let Some(selected) = select(owned) {
+                let mut note = owned.note().clone();
+                in_range &= note.transpose(quarters);
+                copies.push((index, selected, note));
+            }
+        }
 
-            } else if bf < -1 {
-                if Self::balance_factor((*node).right) < 0 {
-                    (*node).right = Self::rotate_right((*node).right);
+        let mut copies = copies.into_iter().peekable();
+        let mut transposed = Vec::new();
+        let notes = notes.into_iter().enumerate()
+            .map(|(index, owned)| match copies.next_if(|(copy_index, _, _)| *copy_index == index) {
+                Some((_, selected, note)) if in_range => {
+                    let owned = OwnedNote::new(note);
+                    transposed.push((selected, owned.handle()));
+                    owned
                 }
-                Self::rotate_left(node)
+                _ => owned,
+            })
+            .collect();
+        self.rebuild(notes);
+        in_range.then_some(transposed)
+    }
 
-            } else {
-                node
+    /// moves every note by delta
+    /// notes moved before the start of the pattern are stopped at 0
+    /// notes with outstanding handles are replaced by moved copies, so those handles no longer refer to notes of the pattern
+    pub fn shift_all(&mut self, delta: BeatUnits) {
+        let notes = self.take_notes()
+            .into_iter()
+            .map(|owned| Self::edited(owned, |note| note.set_start_time(note.start_time() + delta)))
+            .collect();
+        self.rebuild(notes);
+    }
+
+    /// scales the timing of every note and the length of the pattern by factor, measured from the start of the pattern
+    /// notes with outstanding handles are replaced by stretched copies, so those handles no longer refer to notes of the pattern
+    /// fails without changing anything if factor is not finite and positive, returning false
+    pub fn stretch(&mut self, factor: f64) -> bool {
+        if !factor.is_finite() || factor <= 0.0 {
+            return false;
+        }
+        let notes = self.take_notes()
+            .into_iter()
+            .map(|owned| Self::edited(owned, |note| note.stretch(factor)))
+            .collect();
+        self.rebuild(notes);
+        self.set_length(BeatUnits::from_units_f64(self.length.0 as f64 * factor));
+        true
+    }
+
+    /// keeps only the part of the pattern from start to end, which becomes the whole pattern
+    /// notes starting before start are removed, notes reaching past end are cut at end,
+    /// and the remaining notes are moved so that start becomes the start of the pattern
+    /// notes with outstanding handles are replaced by cropped copies, so those handles no longer refer to notes of the pattern
+    /// fails without changing anything if end is not after start, returning false
+    pub fn crop(&mut self, start: BeatUnits, end: BeatUnits) -> bool {
+        if end <= start {
+            return false;
+        }
+        let notes = self.take_notes()
+            .into_iter()
+            .filter(|owned| owned.note().start_time() >= start && owned.note().get_partial(0).unwrap().start_time() < end)
+            .map(|owned| Self::edited(owned, |note| {
+                note.cut_at(end);
+                note.set_start_time(note.start_time() - start);
+            }))
+            .collect();
+        self.rebuild(notes);
+        self.set_length(end - start);
+        true
+    }
+
+    /// copies the notes starting from start up to but not including end, moving the copies so that start falls on paste_at
+    /// notes keep their full length, even if they reach past end
+    /// if the pattern has a scale lock, the copies are snapped to it like inserted notes
+    /// returns handles to the copies in order of start time
+    pub fn duplicate_range(&mut self, start: BeatUnits, end: BeatUnits, paste_at: BeatUnits) -> Vec<NoteHandle> {
+        let copies = self.copy_range(start, end, paste_at);
+        self.paste(copies)
+    }
+
+    /// like duplicate_range, inserting the copies into another pattern
+    /// if the other pattern has a scale lock, the copies are snapped to it like inserted notes
+    pub fn duplicate_range_into(&self, start: BeatUnits, end: BeatUnits, target: &mut PianoPattern, paste_at: BeatUnits) -> Vec<NoteHandle> {
+        target.paste(self.copy_range(start, end, paste_at))
+    }
+
+    /// clones the notes starting from start up to but not including end, moved so that start falls on paste_at
+    fn copy_range(&self, start: BeatUnits, end: BeatUnits, paste_at: BeatUnits) -> Vec<Note> {
+        if end <= start {
+            return Vec::new();
+        }
+        let delta = paste_at - start;
+        self.iter_range(start.into_beats(), end.into_beats())
+            .filter_map(|handle| handle.note(|note| {
+                let note = note.unwrap();
+                (start..end).contains(&note.start_time()).then(|| {
+                    let mut copy = note.clone();
+                    copy.set_start_time(copy.start_time() + delta);
+                    copy
+                })
+            }))
+            .collect()
+    }
+
+    /// inserts each note, returning handles to them in the given order, leaving out notes rejected by the overlap policy
+    fn paste(&mut self, notes: Vec<Note>) -> Vec<NoteHandle> {
+        notes.into_iter()
+            .filter_map(|note| self.insert(OwnedNote::new(note)))
+            .collect()
+    }
+
+    /// applies the edit to the note, or to a copy of it if it has outstanding handles
+    fn edited(mut owned: OwnedNote, edit: impl FnOnce(&mut Note)) -> OwnedNote {
+        if let Some(note) = owned.note_mut() {
+            edit(note);
+            owned
+        } else {
+            let mut note = owned.note().clone();
+            edit(&mut note);
+            OwnedNote::new(note)
+        }
+    }
+
+    /// replaces the tree with a balanced tree of the given notes, built in one pass after sorting
+    /// the pattern must be empty, as after take_notes
+    fn rebuild(&mut self, mut notes: Vec<OwnedNote>) {
+        debug_assert!(self.root.is_none(), "the pattern must be emptied before being rebuilt");
+        self.touch();
+        notes.sort_by_key(|owned| NodeKey::from_note(owned.note()));
+
+        // notes with the same key share a node
+        let mut groups: Vec<Vec<OwnedNote>> = Vec::new();
+        for owned in notes {
+            match groups.last_mut() {
+                Some(group) if NodeKey::from_note(group[0].note()) == NodeKey::from_note(owned.note()) => group.push(owned),
+                _ => groups.push(vec![owned]),
             }
         }
+
+        let count = groups.len();
+        self.nodes.reserve(count);
+        self.root = self.build(&mut groups.into_iter(), count);
     }
 
-    unsafe fn rotate_left(node: *mut Node) -> *mut Node {
-        unsafe {
-            let r_child = (*node).right;
+    /// builds a balanced tree from the next count groups of notes, which must be in order of key, returning its root
+    fn build(&mut self, groups: &mut impl Iterator<Item = Vec<OwnedNote>>, count: usize) -> Option<NodeId> {
+        if count == 0 {
+            return None;
+        }
+        let left = self.build(groups, count / 2);
+        let id = self.alloc(Node {
+            max: BeatUnits(0),
+            pitches: (0, 0),
+            notes: groups.next().unwrap(),
+            height: 0,
+            left,
+            right: None,
+        });
+        self.nodes[id].right = self.build(groups, count - count / 2 - 1);
+        self.recalculate_height(id);
+        self.recalculate_bounds(id);
+        Some(id)
+    }
 
-            (*node).right = (*r_child).left;
-            (*r_child).left = node;
+    /// removes every note from the pattern, leaving it empty
+    /// notes are returned in order of start time
+    fn take_notes(&mut self) -> Vec<OwnedNote> {
+        self.touch();
+        let mut notes = Vec::new();
+        let mut stack = Vec::new();
+        let mut node = self.root.take();
 
-            Self::recalculate_height(node);
-            Self::recalculate_height(r_child);
-            Self::recalculate_max(node);
-            Self::recalculate_max(r_child);
-            r_child
+        // in order traversal
+        while node.is_some() || !stack.is_empty() {
+            while let Some(id) = node {
+                stack.push(id);
+                node = self.nodes[id].left;
+            }
+            let id = stack.pop().unwrap();
+            notes.append(&mut self.nodes[id].notes);
+            node = self.nodes[id].right;
         }
+        self.nodes.clear();
+        self.free.clear();
+        notes
     }
 
-    unsafe fn rotate_right(node: *mut Node) -> *mut Node {
-        unsafe {
-            let l_child = (*node).left;
+    /// inserts a note for each of the given numbers of semitones above the root, all with the same timing,
+    /// such as the intervals of a ChordQuality
+    /// returns handles to the inserted notes, from the root up, leaving out notes rejected by the overlap policy
+    /// if the pattern has a scale lock, each pitch is snapped to the scale
+    /// fails without inserting anything if any pitch would be out of the representable range
+    pub fn insert_chord(
+        &mut self,
+        root: DetunedPitch,
+        intervals: &[u32],
+        start: BeatUnits,
+        duration: BeatUnits
+    ) -> Option<Vec<NoteHandle>> {
+        let pitches = stack_intervals(root, intervals)?;
+        let handles = pitches.into_iter()
+            .filter_map(|pitch| {
+                let pitch = self.scale_lock.map_or(pitch, |scale| scale.snap(pitch));
+                self.insert(OwnedNote::new(Note::new(pitch, start, duration)))
+            })
+            .collect();
+        Some(handles)
+    }
 
-            (*node).left = (*l_child).right;
-            (*l_child).right = node;
+    /// stores the node in the arena, reusing a removed node if there is one
+    fn alloc(&mut self, node: Node) -> NodeId {
+        if let Some(id) = self.free.pop() {
+            self.nodes[id] = node;
+            id
+        } else {
+            self.nodes.push(node);
+            self.nodes.len() - 1
+        }
+    }
 
-            Self::recalculate_height(node);
-            Self::recalculate_height(l_child);
-            Self::recalculate_max(node);
-            Self::recalculate_max(l_child);
-            l_child
+    /// points whatever pointed to old, either the parent or the root, to new instead
+    fn replace_child(&mut self, parent: Option<NodeId>, old: NodeId, new: Option<NodeId>) {
+        match parent {
+            Some(parent) if self.nodes[parent].left == Some(old) => self.nodes[parent].left = new,
+            Some(parent) => self.nodes[parent].right = new,
+            None => self.root = new,
         }
     }
 
-    /// recalculates max at the given node only based on its children
-    unsafe fn recalculate_max(node: *mut Node) {
-        unsafe {
-            let left_max = if (*node).left.is_null() {
-                BeatUnits(0)
-            } else {
-                (*(*node).left).max
-            };
-            let right_max = if (*node).right.is_null() {
-                BeatUnits(0)
-            } else {
-                (*(*node).right).max
-            };
-            (*node).max = left_max.max(right_max).max((*node).max);
+    /// performs retracting on the given path from the root
+    /// the path must be valid
+    fn retract(&mut self, mut path: Vec<NodeId>) {
+        while let Some(id) = path.pop() {
+            // heights and maxes may change all the way up, so there is no stopping early
+            self.recalculate_bounds(id);
+            self.recalculate_height(id);
+            let balanced = self.rebalance(id);
+            self.replace_child(path.last().copied(), id, Some(balanced));
+        }
+    }
+
+    /// rebalances at the node, returning the new root
+    fn rebalance(&mut self, id: NodeId) -> NodeId {
+        let bf = self.balance_factor(id);
+        if bf > 1 {
+            let left = self.nodes[id].left.unwrap();
+            if self.balance_factor(left) < 0 {
+                self.nodes[id].left = Some(self.rotate_left(left));
+            }
+            self.rotate_right(id)
+
+        } else if bf < -1 {
+            let right = self.nodes[id].right.unwrap();
+            if self.balance_factor(right) > 0 {
+                self.nodes[id].right = Some(self.rotate_right(right));
+            }
+            self.rotate_left(id)
+
+        } else {
+            id
         }
     }
 
+    fn rotate_left(&mut self, id: NodeId) -> NodeId {
+        let r_child = self.nodes[id].right.unwrap();
+
+        self.nodes[id].right = self.nodes[r_child].left;
+        self.nodes[r_child].left = Some(id);
+
+        self.recalculate_height(id);
+        self.recalculate_height(r_child);
+        self.recalculate_bounds(id);
+        self.recalculate_bounds(r_child);
+        r_child
+    }
+
+    fn rotate_right(&mut self, id: NodeId) -> NodeId {
+        let l_child = self.nodes[id].left.unwrap();
+
+        self.nodes[id].left = self.nodes[l_child].right;
+        self.nodes[l_child].right = Some(id);
+
+        self.recalculate_height(id);
+        self.recalculate_height(l_child);
+        self.recalculate_bounds(id);
+        self.recalculate_bounds(l_child);
+        l_child
+    }
+
+    /// recalculates max and the pitch range at the given node only based on its children and its own notes
+    fn recalculate_bounds(&mut self, id: NodeId) {
+        let max_of = |child: Option<NodeId>| child.map_or(BeatUnits(0), |child| self.nodes[child].max);
+        let node = &self.nodes[id];
+        let max = max_of(node.left).max(max_of(node.right)).max(node.end_time());
+
+        let pitches = [node.left, node.right].into_iter()
+            .flatten()
+            .map(|child| self.nodes[child].pitches)
+            .chain(node.notes.iter().map(|note| note.note().pitch_range_a4()))
+            .reduce(|(low, high), (other_low, other_high)| (low.min(other_low), high.max(other_high)))
+            .unwrap();
+
+        let node = &mut self.nodes[id];
+        node.max = max;
+        node.pitches = pitches;
+    }
+
+    /// the height of the subtree counted from its parent, or 0 if there is no subtree
+    fn subtree_height(&self, child: Option<NodeId>) -> usize {
+        child.map_or(0, |child| self.nodes[child].height + 1)
+    }
+
     /// recalculates height at the given node only based on its children
-    unsafe fn recalculate_height(node: *mut Node) {
-        unsafe {
-            let left_height = if (*node).left.is_null() {
-                0
-            } else {
-                (*(*node).left).height + 1
-            };
-            let right_height = if (*node).right.is_null() {
-                0
-            } else {
-                (*(*node).right).height + 1
-            };
-            (*node).height = left_height.max(right_height);
+    fn recalculate_height(&mut self, id: NodeId) {
+        let node = &self.nodes[id];
+        self.nodes[id].height = self.subtree_height(node.left).max(self.subtree_height(node.right));
+    }
+
+    /// how much taller the left subtree is than the right, counting heights as in recalculate_height
+    fn balance_factor(&self, id: NodeId) -> i32 {
+        let node = &self.nodes[id];
+        self.subtree_height(node.left) as i32 - self.subtree_height(node.right) as i32
+    }
+
+}
+
+impl PianoPattern {
+    /// executes the command, returning the commands that undo it
+    /// commands referring to notes that are not in the pattern are rejected
+    pub fn apply(&mut self, command: PatternCommand) -> PatternCommandResult {
+        type C = PatternCommand;
+        type R = PatternCommandResult;
+        match command {
+            C::AddNote { note } => {
+                let handle = self.insert_exact((*note).clone());
+                R::Applied { inverse: vec![C::RemoveNote { note }], notes: vec![handle] }
+            }
+
+            C::InsertNote { note } => match self.insert_resolved(OwnedNote::new(*note)) {
+                Some((handle, inverse)) => R::Applied { inverse, notes: vec![handle] },
+                None => R::Rejected,
+            },
+
+            C::RemoveNote { note } => {
+                let Some(handle) = self.find_equal(&note, None) else {
+                    return R::Rejected;
+                };
+                self.remove(handle);
+                R::Applied { inverse: vec![C::AddNote { note }], notes: Vec::new() }
+            }
+
+            C::MoveNote { note, start, quarters } => {
+                let mut moved = (*note).clone();
+                if !moved.transpose(quarters) {
+                    return R::Rejected;
+                }
+                moved.set_start_time(start);
+                self.replace_equal(note, moved)
+            }
+
+            C::ResizeNote { note, end } => {
+                let mut resized = (*note).clone();
+                resized.set_end_time(end);
+                self.replace_equal(note, resized)
+            }
+
+            C::SplitNote { note, partial } => {
+                let mut first = (*note).clone();
+                let Some(second) = first.split_before_partial(partial) else {
+                    return R::Rejected;
+                };
+                let Some(handle) = self.find_equal(&note, None) else {
+                    return R::Rejected;
+                };
+                self.remove(handle);
+                let notes = vec![self.insert_exact(first.clone()), self.insert_exact(second.clone())];
+                let inverse = vec![
+                    C::RemoveNote { note: Box::new(second) },
+                    C::ReplaceNote { note: Box::new(first), with: note },
+                ];
+                R::Applied { inverse, notes }
+            }
+
+            C::CombineNotes { note, other, use_this_transition } => {
+                if note.overlaps_allow_point(&other) {
+                    return R::Rejected;
+                }
+                let Some(handle) = self.find_equal(&note, None) else {
+                    return R::Rejected;
+                };
+                let Some(other_handle) = self.find_equal(&other, Some(&handle)) else {
+                    return R::Rejected;
+                };
+                self.remove(handle);
+                self.remove(other_handle);
+
+                let mut combined = (*note).clone();
+                combined.combine_notes((*other).clone(), use_this_transition);
+                let notes = vec![self.insert_exact(combined.clone())];
+                let inverse = vec![
+                    C::ReplaceNote { note: Box::new(combined), with: note },
+                    C::AddNote { note: other },
+                ];
+                R::Applied { inverse, notes }
+            }
+
+            C::SetVibrato { note, partial, vibrato } => {
+                let mut edited = (*note).clone();
+                if !edited.get_partial_mut(partial, |partial| partial.vibrato_mut(|old| *old = vibrato.clone())) {
+                    return R::Rejected;
+                }
+                self.replace_equal(note, edited)
+            }
+
+            C::ReplaceNote { note, with } => self.replace_equal(note, *with),
         }
     }
 
-    unsafe fn balance_factor(node: *mut Node) -> i32 {
-        unsafe {
-            let left_height = if let Some(left) = (*node).left.as_mut() {
-                left.height
-            } else {
-                0
-            };
-            let right_height = if let Some(right) = (*node).right.as_mut() {
-                right.height
-            } else {
-                0
-            };
-            right_height as i32 - left_height as i32
+    /// replaces the note of the pattern equal to note with the replacement
+    fn replace_equal(&mut self, note: Box<Note>, replacement: Note) -> PatternCommandResult {
+        let Some(handle) = self.find_equal(&note, None) else {
+            return PatternCommandResult::Rejected;
+        };
+        self.remove(handle);
+        let handle = self.insert_exact(replacement.clone());
+        PatternCommandResult::Applied {
+            inverse: vec![PatternCommand::ReplaceNote { note: Box::new(replacement), with: note }],
+            notes: vec![handle],
         }
     }
 
+    /// finds a note of the pattern equal to the given note, other than the one excluded
+    fn find_equal(&self, note: &Note, excluded: Option<&NoteHandle>) -> Option<NoteHandle> {
+        let start = note.start_time().into_beats();
+        self.iter_range(start, start).find(|handle| {
+            !excluded.is_some_and(|excluded| excluded.ptr_eq(handle)) && handle.note(|found| found == Some(note))
+        })
+    }
+
+    /// inserts the note as given, ignoring the scale lock, returning a handle to it
+    fn insert_exact(&mut self, note: Note) -> NoteHandle {
+        let owned = OwnedNote::new(note);
+        let handle = owned.handle();
+        self.insert_node(owned);
+        handle
+    }
 }
 
 impl Node {
@@ -494,10 +1407,11 @@ impl Node {
     fn new(note: OwnedNote) -> Self {
         Self {
             max: note.note().end_time(),
+            pitches: note.note().pitch_range_a4(),
             notes: vec![note],
             height: 0,
-            left: std::ptr::null_mut(),
-            right: std::ptr::null_mut(),
+            left: None,
+            right: None,
         }
     }
 }
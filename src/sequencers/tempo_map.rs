@@ -0,0 +1,216 @@
+use super::{curve::CurveShape, note::BeatUnits, transport::InternalClock};
+
+/// A change of tempo at a beat of the song
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoChange {
+    /// the beat the change happens on
+    pub beat: f64,
+
+    /// the tempo in beats per minute
+    pub bpm: f64,
+
+    /// the shape the tempo follows to the next change, or None to hold the tempo until the next change
+    pub ramp: Option<CurveShape>,
+}
+
+/// The tempo of a song over time, used to convert between beats and seconds
+/// The tempo is held or ramped from each change to the next and held after the last change.
+/// The time each change is reached is kept so that conversions only integrate within one change.
+#[derive(Debug, Clone)]
+pub struct TempoMap {
+    /// the changes of tempo in order of strictly increasing beat, the first always on beat 0
+    changes: Vec<TempoChange>,
+
+    /// the number of seconds from beat 0 to each change
+    seconds: Vec<f64>,
+}
+
+impl TempoMap {
+    /// the number of steps each ramp is integrated over
+    const RAMP_STEPS: usize = 64;
+
+    /// the most Newton iterations taken when finding the beat at a time
+    const MAX_ITERATIONS: usize = 16;
+
+    /// creates a map holding the given tempo
+    pub fn new(bpm: f64) -> Self {
+        Self {
+            changes: vec![TempoChange {
+                beat: 0.0,
+                bpm: Self::clamp_bpm(bpm),
+                ramp: None,
+            }],
+            seconds: vec![0.0],
+        }
+    }
+
+    fn clamp_bpm(bpm: f64) -> f64 {
+        if bpm.is_nan() {
+            InternalClock::DEFAULT_BPM
+        } else {
+            bpm.clamp(InternalClock::MIN_BPM, InternalClock::MAX_BPM)
+        }
+    }
+
+    /// gets the changes of tempo in order of beat
+    pub fn changes(&self) -> &[TempoChange] {
+        &self.changes
+    }
+
+    /// adds a change of tempo, returning its index
+    /// a change already on the same beat is replaced, and changes before beat 0 are moved onto it
+    pub fn add_change(&mut self, beat: f64, bpm: f64, ramp: Option<CurveShape>) -> usize {
+        let change = TempoChange {
+            beat: if beat.is_finite() { beat.max(0.0) } else { 0.0 },
+            bpm: Self::clamp_bpm(bpm),
+            ramp,
+        };
+
+        let index = self.changes.partition_point(|other| other.beat < change.beat);
+        if self.changes.get(index).is_some_and(|other| other.beat == change.beat) {
+            self.changes[index] = change;
+        } else {
+            self.changes.insert(index, change);
+        }
+        self.recalculate_seconds();
+        index
+    }
+
+    /// removes the change with the given index
+    /// fails if the index is out of bounds or is the first change, which sets the starting tempo
+    pub fn remove_change(&mut self, index: usize) -> Option<TempoChange> {
+        if index == 0 || index >= self.changes.len() {
+            return None;
+        }
+        let change = self.changes.remove(index);
+        self.recalculate_seconds();
+        Some(change)
+    }
+
+    /// gets a change mutably within a closure, then keeps it in order and within bounds
+    /// the first change stays on beat 0
+    /// returns the new index of the change
+    /// fails if index is out of bounds
+    pub fn get_change_mut(&mut self, index: usize, f: impl FnOnce(&mut TempoChange)) -> Option<usize> {
+        let mut change = *self.changes.get(index)?;
+        f(&mut change);
+        if index == 0 {
+            change.beat = 0.0;
+            change.bpm = Self::clamp_bpm(change.bpm);
+            self.changes[0] = change;
+            self.recalculate_seconds();
+            return Some(0);
+        }
+
+        self.changes.remove(index);
+        Some(self.add_change(change.beat, change.bpm, change.ramp))
+    }
+
+    /// gets the index of the change in effect at the given beat
+    fn change_index(&self, beat: f64) -> usize {
+        self.changes.partition_point(|change| change.beat <= beat).saturating_sub(1)
+    }
+
+    /// gets the tempo of the change with the given index at a beat after it
+    fn segment_bpm(&self, index: usize, beat: f64) -> f64 {
+        let change = &self.changes[index];
+        match (change.ramp, self.changes.get(index + 1)) {
+            (Some(shape), Some(next)) => {
+                Self::clamp_bpm(shape.interpolate(beat, change.beat, next.beat, change.bpm, next.bpm))
+            }
+            _ => change.bpm,
+        }
+    }
+
+    /// gets the number of seconds from the change with the given index to a beat after it
+    fn segment_seconds(&self, index: usize, beat: f64) -> f64 {
+        let change = &self.changes[index];
+        let beats = beat - change.beat;
+        if change.ramp.is_none() || index + 1 >= self.changes.len() {
+            return beats * 60.0 / change.bpm;
+        }
+
+        // simpson's rule over the time taken by each beat
+        let step = beats / Self::RAMP_STEPS as f64;
+        let mut total = 0.0;
+        for i in 0..=Self::RAMP_STEPS {
+            let weight = if i == 0 || i == Self::RAMP_STEPS {
+                1.0
+            } else if i % 2 == 1 {
+                4.0
+            } else {
+                2.0
+            };
+            total += weight * 60.0 / self.segment_bpm(index, change.beat + step * i as f64);
+        }
+        total * step / 3.0
+    }
+
+    fn recalculate_seconds(&mut self) {
+        self.seconds.clear();
+        self.seconds.push(0.0);
+        for index in 1..self.changes.len() {
+            let seconds = self.seconds[index - 1] + self.segment_seconds(index - 1, self.changes[index].beat);
+            self.seconds.push(seconds);
+        }
+    }
+
+    /// gets the tempo at the given beat
+    pub fn bpm_at(&self, beat: f64) -> f64 {
+        let index = self.change_index(beat);
+        self.segment_bpm(index, beat.max(0.0))
+    }
+
+    /// gets the number of seconds from beat 0 to the given beat
+    /// beats before 0 are reached at the starting tempo
+    pub fn seconds_at_beat(&self, beat: f64) -> f64 {
+        let index = self.change_index(beat);
+        if beat < 0.0 {
+            return beat * 60.0 / self.changes[0].bpm;
+        }
+        self.seconds[index] + self.segment_seconds(index, beat)
+    }
+
+    /// gets the beat reached the given number of seconds after beat 0
+    pub fn beat_at_seconds(&self, seconds: f64) -> f64 {
+        if seconds <= 0.0 {
+            return seconds * self.changes[0].bpm / 60.0;
+        }
+
+        let index = self.seconds.partition_point(|start| *start <= seconds) - 1;
+        let change = &self.changes[index];
+        let held = change.beat + (seconds - self.seconds[index]) * change.bpm / 60.0;
+        if change.ramp.is_none() || index + 1 >= self.changes.len() {
+            return held;
+        }
+
+        // newton's method, as the rate of change of seconds is the time taken by one beat
+        let next_beat = self.changes[index + 1].beat;
+        let mut beat = held.clamp(change.beat, next_beat);
+        for _ in 0..Self::MAX_ITERATIONS {
+            let error = self.seconds[index] + self.segment_seconds(index, beat) - seconds;
+            let next = (beat - error * self.segment_bpm(index, beat) / 60.0).clamp(change.beat, next_beat);
+            if (next - beat).abs() < 1e-9 {
+                return next;
+            }
+            beat = next;
+        }
+        beat
+    }
+
+    /// gets the number of seconds from the start of the song to the given time
+    pub fn seconds_at(&self, time: BeatUnits) -> f64 {
+        self.seconds_at_beat(time.into_beats())
+    }
+
+    /// gets the sample at which the given time is reached, counted from the start of the song
+    pub fn sample_at(&self, time: BeatUnits, sample_rate: u32) -> f64 {
+        self.seconds_at(time) * sample_rate as f64
+    }
+}
+
+impl Default for TempoMap {
+    fn default() -> Self {
+        Self::new(InternalClock::DEFAULT_BPM)
+    }
+}
@@ -0,0 +1,427 @@
+use std::sync::{atomic::{AtomicBool, AtomicU64, Ordering}, mpsc::{self, Receiver, Sender}, Arc};
+
+use super::{note::BeatUnits, tempo_map::TempoMap};
+
+/// The state of a clock after advancing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockState {
+    pub playing: bool,
+
+    /// the position of the transport in beats
+    pub position: f64,
+
+    /// the current tempo in beats per minute
+    pub bpm: f64,
+}
+
+/// Drives the position of the transport
+pub trait ClockSource: Send {
+    /// advances the clock by the given number of seconds
+    fn advance(&mut self, seconds: f64) -> ClockState;
+
+    /// whether the clock follows an external device
+    /// external clocks ignore start, stop and tempo requests from the user
+    fn is_external(&self) -> bool;
+
+    /// starts playing from the beginning
+    fn start(&mut self);
+
+    /// stops playing, keeping the position
+    fn stop(&mut self);
+
+    /// resumes playing from the current position
+    fn resume(&mut self);
+
+    /// requests a new tempo
+    fn set_bpm(&mut self, bpm: f64);
+
+    /// requests that the tempo follow the map, or the requested tempo if None
+    fn set_tempo_map(&mut self, map: Option<TempoMap>);
+}
+
+/// A free running clock at a set tempo, or following a tempo map
+#[derive(Debug, Clone)]
+pub struct InternalClock {
+    state: ClockState,
+
+    /// the tempo requested with set_bpm, used when there is no tempo map
+    bpm: f64,
+
+    tempo_map: Option<TempoMap>,
+}
+
+impl InternalClock {
+    pub const DEFAULT_BPM: f64 = 120.0;
+    pub const MIN_BPM: f64 = 1.0;
+    pub const MAX_BPM: f64 = 999.0;
+
+    pub fn new(bpm: f64) -> Self {
+        let bpm = bpm.clamp(Self::MIN_BPM, Self::MAX_BPM);
+        Self {
+            state: ClockState {
+                playing: false,
+                position: 0.0,
+                bpm,
+            },
+            bpm,
+            tempo_map: None,
+        }
+    }
+
+    pub fn tempo_map(&self) -> Option<&TempoMap> {
+        self.tempo_map.as_ref()
+    }
+
+    /// the tempo at the current position
+    fn current_bpm(&self) -> f64 {
+        match &self.tempo_map {
+            Some(map) => map.bpm_at(self.state.position),
+            None => self.bpm,
+        }
+    }
+}
+
+impl Default for InternalClock {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_BPM)
+    }
+}
+
+impl ClockSource for InternalClock {
+    fn advance(&mut self, seconds: f64) -> ClockState {
+        // the tempo is taken at the start of each step, which is exact between ramps
+        // and close enough within them for steps as short as a sample
+        if self.state.playing {
+            self.state.position += seconds * self.current_bpm() / 60.0;
+        }
+        self.state.bpm = self.current_bpm();
+        self.state
+    }
+
+    fn is_external(&self) -> bool {
+        false
+    }
+
+    fn start(&mut self) {
+        self.state.position = 0.0;
+        self.state.playing = true;
+    }
+
+    fn stop(&mut self) {
+        self.state.playing = false;
+    }
+
+    fn resume(&mut self) {
+        self.state.playing = true;
+    }
+
+    fn set_bpm(&mut self, bpm: f64) {
+        self.bpm = bpm.clamp(Self::MIN_BPM, Self::MAX_BPM);
+        self.state.bpm = self.current_bpm();
+    }
+
+    fn set_tempo_map(&mut self, map: Option<TempoMap>) {
+        self.tempo_map = map;
+        self.state.bpm = self.current_bpm();
+    }
+}
+
+/// A system realtime message from a MIDI clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiClockMessage {
+    /// a clock pulse received at the given time in microseconds
+    Pulse(u64),
+    Start,
+    Stop,
+    Continue,
+}
+
+/// A clock following the MIDI clock (24 pulses per quarter note) of an external device
+/// Between pulses the position moves at the measured tempo, but never past the next pulse.
+#[derive(Debug)]
+pub struct MidiClock {
+    messages: Receiver<MidiClockMessage>,
+    playing: bool,
+
+    /// pulses received since the transport started
+    pulses: u64,
+
+    /// seconds since the last pulse
+    since_pulse: f64,
+
+    /// true after a start or continue until the next pulse, which marks the position rather than advancing it
+    awaiting_pulse: bool,
+
+    last_timestamp: Option<u64>,
+
+    /// the most recent intervals between pulses in microseconds, covering one beat
+    intervals: [u64; Self::PULSES_PER_BEAT as usize],
+    interval_count: usize,
+    next_interval: usize,
+
+    bpm: f64,
+}
+
+impl MidiClock {
+    pub const PULSES_PER_BEAT: u64 = 24;
+
+    /// creates a clock along with the sender MIDI messages are passed to
+    pub fn new() -> (Self, Sender<MidiClockMessage>) {
+        let (sender, messages) = mpsc::channel();
+        let clock = Self {
+            messages,
+            playing: false,
+            pulses: 0,
+            since_pulse: 0.0,
+            awaiting_pulse: false,
+            last_timestamp: None,
+            intervals: [0; Self::PULSES_PER_BEAT as usize],
+            interval_count: 0,
+            next_interval: 0,
+            bpm: InternalClock::DEFAULT_BPM,
+        };
+        (clock, sender)
+    }
+
+    fn handle_pulse(&mut self, timestamp: u64) {
+        if let Some(last) = self.last_timestamp {
+            let interval = timestamp.saturating_sub(last);
+            if interval > 0 {
+                self.intervals[self.next_interval] = interval;
+                self.next_interval = (self.next_interval + 1) % self.intervals.len();
+                self.interval_count = (self.interval_count + 1).min(self.intervals.len());
+
+                let total: u64 = self.intervals[..self.interval_count].iter().sum();
+                let beat_micros = total as f64 / self.interval_count as f64 * Self::PULSES_PER_BEAT as f64;
+                self.bpm = 60_000_000.0 / beat_micros;
+            }
+        }
+        self.last_timestamp = Some(timestamp);
+
+        if self.playing {
+            if self.awaiting_pulse {
+                self.awaiting_pulse = false;
+            } else {
+                self.pulses += 1;
+            }
+            self.since_pulse = 0.0;
+        }
+    }
+
+    fn handle(&mut self, message: MidiClockMessage) {
+        match message {
+            MidiClockMessage::Pulse(timestamp) => self.handle_pulse(timestamp),
+            MidiClockMessage::Start => {
+                self.pulses = 0;
+                self.since_pulse = 0.0;
+                self.awaiting_pulse = true;
+                self.playing = true;
+            }
+            MidiClockMessage::Continue => {
+                self.since_pulse = 0.0;
+                self.awaiting_pulse = true;
+                self.playing = true;
+            }
+            MidiClockMessage::Stop => self.playing = false,
+        }
+    }
+}
+
+impl ClockSource for MidiClock {
+    fn advance(&mut self, seconds: f64) -> ClockState {
+        while let Ok(message) = self.messages.try_recv() {
+            self.handle(message);
+        }
+
+        let pulse_beats = 1.0 / Self::PULSES_PER_BEAT as f64;
+        // the position is held from a start or continue until its first pulse
+        if self.playing && !self.awaiting_pulse {
+            self.since_pulse += seconds;
+        }
+
+        // move smoothly towards the next pulse, waiting there if it is late
+        let progress = self.since_pulse * self.bpm / 60.0;
+        let position = self.pulses as f64 * pulse_beats + progress.min(pulse_beats);
+
+        ClockState {
+            playing: self.playing,
+            position,
+            bpm: self.bpm,
+        }
+    }
+
+    fn is_external(&self) -> bool {
+        true
+    }
+
+    fn start(&mut self) {}
+
+    fn stop(&mut self) {}
+
+    fn resume(&mut self) {}
+
+    fn set_bpm(&mut self, _bpm: f64) {}
+
+    fn set_tempo_map(&mut self, _map: Option<TempoMap>) {}
+}
+
+/// The position of a transport at some moment
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PlayheadPosition {
+    pub playing: bool,
+
+    /// the position in beats
+    pub beats: f64,
+
+    /// the seconds played since the transport last started from the beginning
+    pub seconds: f64,
+}
+
+impl PlayheadPosition {
+    /// the position rounded to the nearest unit
+    pub fn time(&self) -> BeatUnits {
+        BeatUnits::from_beats(self.beats)
+    }
+}
+
+/// The position of a transport, published by the audio thread and read by editors without locking
+/// Clones share the same position, so an editor may keep one to draw a moving playhead.
+/// Each field is stored separately, so a read may mix the fields of consecutive samples.
+#[derive(Debug, Clone, Default)]
+pub struct Playhead {
+    playing: Arc<AtomicBool>,
+
+    /// the bits of the position in beats
+    beats: Arc<AtomicU64>,
+
+    /// the bits of the seconds played
+    seconds: Arc<AtomicU64>,
+}
+
+impl Playhead {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// gets the position last published
+    pub fn position(&self) -> PlayheadPosition {
+        PlayheadPosition {
+            playing: self.playing.load(Ordering::Relaxed),
+            beats: f64::from_bits(self.beats.load(Ordering::Relaxed)),
+            seconds: f64::from_bits(self.seconds.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// makes the position visible to every clone
+    pub fn publish(&self, position: PlayheadPosition) {
+        self.playing.store(position.playing, Ordering::Relaxed);
+        self.beats.store(position.beats.to_bits(), Ordering::Relaxed);
+        self.seconds.store(position.seconds.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// The playback position shared by every sequencer, driven by a clock source
+/// The position is published to a playhead every time the transport advances.
+pub struct Transport {
+    clock: Box<dyn ClockSource>,
+    state: ClockState,
+
+    /// the seconds played since the transport last started from the beginning
+    seconds: f64,
+
+    playhead: Playhead,
+}
+
+impl Transport {
+    pub fn new(mut clock: Box<dyn ClockSource>) -> Self {
+        let state = clock.advance(0.0);
+        let transport = Self {
+            clock,
+            state,
+            seconds: 0.0,
+            playhead: Playhead::new(),
+        };
+        transport.playhead.publish(transport.position());
+        transport
+    }
+
+    /// replaces the clock, such as when switching to or from an external clock
+    pub fn set_clock(&mut self, clock: Box<dyn ClockSource>) {
+        self.clock = clock;
+        self.state = self.clock.advance(0.0);
+        self.seconds = 0.0;
+        self.playhead.publish(self.position());
+    }
+
+    pub fn is_external(&self) -> bool {
+        self.clock.is_external()
+    }
+
+    /// advances by the given number of seconds
+    /// returns the range of beats passed, which is empty while stopped
+    pub fn advance(&mut self, seconds: f64) -> (f64, f64) {
+        let start = self.state.position;
+        self.state = self.clock.advance(seconds);
+
+        // a clock moving backwards, such as an external clock starting again, restarts the count
+        if self.state.position < start {
+            self.seconds = 0.0;
+        } else if self.state.playing {
+            self.seconds += seconds;
+        }
+        self.playhead.publish(self.position());
+
+        if self.state.playing {
+            (start.min(self.state.position), self.state.position)
+        } else {
+            (self.state.position, self.state.position)
+        }
+    }
+
+    pub fn state(&self) -> ClockState {
+        self.state
+    }
+
+    /// gets the current position
+    pub fn position(&self) -> PlayheadPosition {
+        PlayheadPosition {
+            playing: self.state.playing,
+            beats: self.state.position,
+            seconds: self.seconds,
+        }
+    }
+
+    /// gets a playhead following the position of the transport, such as for an editor on another thread
+    pub fn playhead(&self) -> Playhead {
+        self.playhead.clone()
+    }
+
+    pub fn start(&mut self) {
+        self.clock.start();
+        self.seconds = 0.0;
+    }
+
+    pub fn stop(&mut self) {
+        self.clock.stop();
+    }
+
+    pub fn resume(&mut self) {
+        self.clock.resume();
+    }
+
+    pub fn set_bpm(&mut self, bpm: f64) {
+        self.clock.set_bpm(bpm);
+    }
+
+    /// makes the tempo follow the map, or the set tempo if None
+    /// external clocks keep following their device
+    pub fn set_tempo_map(&mut self, map: Option<TempoMap>) {
+        self.clock.set_tempo_map(map);
+    }
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self::new(Box::new(InternalClock::default()))
+    }
+}
@@ -0,0 +1,291 @@
+use std::{fmt::Display, fs, io, path::PathBuf, str::FromStr};
+
+use directories::ProjectDirs;
+use thiserror::Error;
+
+use crate::{audio_config::{self, ChannelMap, CueDestination}, limiter::LimiterSettings, midi::MpeSettings, pitch::TuningSettings, program_bank::{ProgramBank, ProgramEntry}};
+
+/// The color theme of the app
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Theme {
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+impl Theme {
+    pub const SYSTEM_TEXT: &'static str = "System";
+    pub const LIGHT_TEXT: &'static str = "Light";
+    pub const DARK_TEXT: &'static str = "Dark";
+
+    pub const ALL: [Self; 3] = [Self::System, Self::Light, Self::Dark];
+
+    pub fn display_string(&self) -> &'static str {
+        match self {
+            Self::System => Self::SYSTEM_TEXT,
+            Self::Light => Self::LIGHT_TEXT,
+            Self::Dark => Self::DARK_TEXT,
+        }
+    }
+
+    /// the egui theme preference corresponding to this theme
+    pub fn preference(&self) -> egui::ThemePreference {
+        match self {
+            Self::System => egui::ThemePreference::System,
+            Self::Light => egui::ThemePreference::Light,
+            Self::Dark => egui::ThemePreference::Dark,
+        }
+    }
+}
+
+impl Display for Theme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.display_string())
+    }
+}
+
+impl FromStr for Theme {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter()
+            .find(|theme| theme.display_string() == s)
+            .ok_or(())
+    }
+}
+
+/// An error occurring while reading or writing settings
+#[derive(Debug, Error)]
+pub enum SettingsError {
+    #[error("Could not determine the configuration directory.")]
+    NoConfigDirectory,
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// User preferences that persist between runs of the app
+/// Stored as a list of 'key = value' lines in the user's configuration directory
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AppSettings {
+    /// the name of the chosen audio host
+    pub host: Option<String>,
+
+    /// the name of the chosen output device
+    pub output_device: Option<String>,
+
+    /// the preferred sample rate of the output stream
+    pub sample_rate: Option<u32>,
+
+    /// the preferred number of channels of the output stream
+    pub channels: Option<u16>,
+
+    /// the preferred buffer size of the output stream in frames
+    /// if none, the device's default is used
+    pub buffer_size: Option<u32>,
+
+    /// the patch output played on each device channel
+    /// if none, the default map for the device is used
+    pub channel_map: Option<ChannelMap>,
+
+    /// the sample rate patches are processed at before being resampled to the device rate
+    /// if none, patches run at the device rate
+    pub internal_sample_rate: Option<u32>,
+
+    /// the name of the MIDI input port notes are received from
+    pub midi_input: Option<String>,
+
+    /// the channel [0, 15] notes are played on the patch from, or none for all channels
+    pub midi_channel: Option<u8>,
+
+    /// the name of the MIDI output port sequenced notes are sent to
+    pub midi_output: Option<String>,
+
+    /// how per-note expression is received from the MIDI input
+    pub mpe: MpeSettings,
+
+    /// whether the computer keyboard plays notes
+    pub computer_keyboard: bool,
+
+    /// the patches selected by MIDI program changes
+    pub program_bank: ProgramBank,
+
+    /// where the cue output is played, if anywhere
+    pub cue: Option<CueDestination>,
+
+    /// the safety limiter applied to the output stream
+    pub limiter: LimiterSettings,
+
+    /// the tuning of every patch and of the notes played into them
+    pub tuning: TuningSettings,
+
+    /// the size of the window when the app was last closed
+    pub window_size: Option<[f32; 2]>,
+
+    pub theme: Theme,
+}
+
+impl AppSettings {
+    const FILE_NAME: &'static str = "settings.cfg";
+
+    /// the path of the settings file
+    pub fn path() -> Option<PathBuf> {
+        ProjectDirs::from("", "", "Starship")
+            .map(|dirs| dirs.config_dir().join(Self::FILE_NAME))
+    }
+
+    /// loads the saved settings
+    /// missing files, unknown keys and malformed values are ignored, leaving the default
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| Self::parse(&text))
+            .unwrap_or_default()
+    }
+
+    /// writes the settings to the settings file
+    pub fn save(&self) -> Result<(), SettingsError> {
+        let path = Self::path().ok_or(SettingsError::NoConfigDirectory)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    /// reads settings from text created by to_string
+    pub fn parse(text: &str) -> Self {
+        let mut settings = Self::default();
+
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+
+            match key.trim() {
+                "host" => settings.host = Some(value.to_string()),
+                "output_device" => settings.output_device = Some(value.to_string()),
+                "sample_rate" => settings.sample_rate = value.parse().ok(),
+                "channels" => settings.channels = value.parse().ok(),
+                "buffer_size" => settings.buffer_size = value.parse().ok(),
+                "internal_sample_rate" => {
+                    settings.internal_sample_rate = value.parse()
+                        .ok()
+                        .filter(|rate| audio_config::INTERNAL_SAMPLE_RATE_OPTIONS.contains(rate));
+                }
+                "channel_map" => settings.channel_map = value.parse().ok(),
+                "cue" => settings.cue = value.parse().ok(),
+                "midi_input" => settings.midi_input = Some(value.to_string()),
+                "midi_channel" => settings.midi_channel = value.parse().ok().filter(|channel| *channel < 16),
+                "midi_output" => settings.midi_output = Some(value.to_string()),
+                "mpe_enabled" => {
+                    if let Ok(enabled) = value.parse() {
+                        settings.mpe.enabled = enabled;
+                    }
+                }
+                "mpe_bend_range" => {
+                    if let Ok(range) = value.parse::<f32>() {
+                        settings.mpe.bend_range = range.clamp(0.0, MpeSettings::MAX_BEND_RANGE);
+                    }
+                }
+                "program" => {
+                    if let Ok(entry) = value.parse::<ProgramEntry>() {
+                        settings.program_bank.set(entry.program, entry.path);
+                    }
+                }
+                "computer_keyboard" => settings.computer_keyboard = value.parse().unwrap_or_default(),
+                "limiter_enabled" => {
+                    if let Ok(enabled) = value.parse() {
+                        settings.limiter.enabled = enabled;
+                    }
+                }
+                "limiter_threshold" => {
+                    if let Ok(threshold) = value.parse::<f32>() {
+                        settings.limiter.threshold_db = threshold.clamp(
+                            LimiterSettings::MIN_THRESHOLD_DB,
+                            LimiterSettings::MAX_THRESHOLD_DB
+                        );
+                    }
+                }
+                "tuning_reference_a4" => {
+                    if let Ok(a4) = value.parse::<f64>() {
+                        settings.tuning.reference_a4 = a4.clamp(
+                            TuningSettings::MIN_REFERENCE_A4,
+                            TuningSettings::MAX_REFERENCE_A4
+                        );
+                    }
+                }
+                "tuning_scale" => settings.tuning.scale = (!value.is_empty()).then(|| value.into()),
+                "tuning_mapping" => settings.tuning.mapping = (!value.is_empty()).then(|| value.into()),
+                "window_size" => {
+                    settings.window_size = value.split_once(',').and_then(|(x, y)| {
+                        Some([x.trim().parse().ok()?, y.trim().parse().ok()?])
+                    });
+                }
+                "theme" => settings.theme = value.parse().unwrap_or_default(),
+                _ => {}
+            }
+        }
+
+        settings
+    }
+}
+
+impl Display for AppSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(host) = &self.host {
+            writeln!(f, "host = {}", host)?;
+        }
+        if let Some(device) = &self.output_device {
+            writeln!(f, "output_device = {}", device)?;
+        }
+        if let Some(rate) = self.sample_rate {
+            writeln!(f, "sample_rate = {}", rate)?;
+        }
+        if let Some(channels) = self.channels {
+            writeln!(f, "channels = {}", channels)?;
+        }
+        if let Some(frames) = self.buffer_size {
+            writeln!(f, "buffer_size = {}", frames)?;
+        }
+        if let Some(map) = &self.channel_map {
+            writeln!(f, "channel_map = {}", map)?;
+        }
+        if let Some(rate) = self.internal_sample_rate {
+            writeln!(f, "internal_sample_rate = {}", rate)?;
+        }
+        if let Some(port) = &self.midi_input {
+            writeln!(f, "midi_input = {}", port)?;
+        }
+        if let Some(channel) = self.midi_channel {
+            writeln!(f, "midi_channel = {}", channel)?;
+        }
+        if let Some(port) = &self.midi_output {
+            writeln!(f, "midi_output = {}", port)?;
+        }
+        if let Some(cue) = &self.cue {
+            writeln!(f, "cue = {}", cue)?;
+        }
+        writeln!(f, "mpe_enabled = {}", self.mpe.enabled)?;
+        writeln!(f, "mpe_bend_range = {}", self.mpe.bend_range)?;
+        writeln!(f, "computer_keyboard = {}", self.computer_keyboard)?;
+        for entry in self.program_bank.entries() {
+            writeln!(f, "program = {}", entry)?;
+        }
+        writeln!(f, "limiter_enabled = {}", self.limiter.enabled)?;
+        writeln!(f, "limiter_threshold = {}", self.limiter.threshold_db)?;
+        writeln!(f, "tuning_reference_a4 = {}", self.tuning.reference_a4)?;
+        if let Some(path) = &self.tuning.scale {
+            writeln!(f, "tuning_scale = {}", path.display())?;
+        }
+        if let Some(path) = &self.tuning.mapping {
+            writeln!(f, "tuning_mapping = {}", path.display())?;
+        }
+        if let Some([x, y]) = self.window_size {
+            writeln!(f, "window_size = {}, {}", x, y)?;
+        }
+        writeln!(f, "theme = {}", self.theme)
+    }
+}
@@ -0,0 +1,64 @@
+use std::time::{Duration, Instant};
+
+use egui::{Align2, Area, Context, Frame, Id, Order};
+
+/// A short message shown over the ui without blocking interaction
+#[derive(Debug, Clone)]
+struct Toast {
+    message: String,
+    expires: Instant,
+}
+
+/// A stack of toasts drawn in the bottom right corner of the window
+#[derive(Debug, Clone, Default)]
+pub struct Toasts {
+    toasts: Vec<Toast>,
+}
+
+impl Toasts {
+    /// how long a toast remains visible
+    pub const DURATION: Duration = Duration::from_secs(5);
+
+    const MARGIN: f32 = 8.0;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// queues a message to be shown
+    pub fn push(&mut self, message: impl Into<String>) {
+        self.toasts.push(Toast {
+            message: message.into(),
+            expires: Instant::now() + Self::DURATION,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.toasts.is_empty()
+    }
+
+    /// removes expired toasts and draws the rest
+    pub fn show(&mut self, ctx: &Context) {
+        let now = Instant::now();
+        self.toasts.retain(|toast| toast.expires > now);
+
+        let Some(next_expiry) = self.toasts.iter().map(|toast| toast.expires).min() else {
+            return;
+        };
+
+        Area::new(Id::new("toasts"))
+            .order(Order::Foreground)
+            .anchor(Align2::RIGHT_BOTTOM, [-Self::MARGIN, -Self::MARGIN])
+            .interactable(false)
+            .show(ctx, |ui| {
+                for toast in &self.toasts {
+                    Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.label(&toast.message);
+                    });
+                }
+            });
+
+        // make sure the toast disappears even if nothing else triggers a repaint
+        ctx.request_repaint_after(next_expiry - now);
+    }
+}
@@ -222,3 +222,61 @@ macro_rules! non_negative_checkable_impl {
 }
 
 non_negative_checkable_impl!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+
+/// A cursor over a byte slice used when decoding serialized data.
+/// All multi-byte values are read as little endian.
+#[derive(Debug, Clone)]
+pub struct ByteReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            position: 0
+        }
+    }
+
+    /// the number of bytes that have not yet been read
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    /// returns true if all bytes have been read
+    pub fn is_empty(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    /// reads the next count bytes
+    /// fails if there are not enough bytes remaining
+    pub fn read_bytes(&mut self, count: usize) -> Option<&'a [u8]> {
+        if self.remaining() < count {
+            return None;
+        }
+        let out = &self.data[self.position..self.position + count];
+        self.position += count;
+        Some(out)
+    }
+
+    pub fn read_u8(&mut self) -> Option<u8> {
+        self.read_bytes(1).map(|b| b[0])
+    }
+
+    pub fn read_u16(&mut self) -> Option<u16> {
+        self.read_bytes(2).map(|b| u16::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    pub fn read_u32(&mut self) -> Option<u32> {
+        self.read_bytes(4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    pub fn read_f32(&mut self) -> Option<f32> {
+        self.read_bytes(4).map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    pub fn read_f64(&mut self) -> Option<f64> {
+        self.read_bytes(8).map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+    }
+}
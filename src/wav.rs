@@ -0,0 +1,220 @@
+use std::{fs::{self, File}, io::{self, BufWriter, Seek, SeekFrom, Write}, path::Path};
+
+use thiserror::Error;
+
+use crate::utils::ByteReader;
+
+/// Writes interleaved 32-bit float samples to a WAV file
+/// The header is rewritten with the final sizes when the writer is finished.
+#[derive(Debug)]
+pub struct WavWriter {
+    file: BufWriter<File>,
+    channels: u16,
+    sample_rate: u32,
+    samples_written: u64,
+}
+
+impl WavWriter {
+    const HEADER_SIZE: u32 = 44;
+    const FORMAT_IEEE_FLOAT: u16 = 3;
+    const BITS_PER_SAMPLE: u16 = 32;
+    const BYTES_PER_SAMPLE: u64 = Self::BITS_PER_SAMPLE as u64 / 8;
+
+    /// the most bytes of samples the RIFF size field can describe
+    const MAX_DATA_SIZE: u64 = (u32::MAX - (Self::HEADER_SIZE - 8)) as u64;
+
+    /// creates the file at the given path and writes a placeholder header
+    pub fn create(path: &Path, channels: u16, sample_rate: u32) -> io::Result<Self> {
+        let mut writer = Self {
+            file: BufWriter::new(File::create(path)?),
+            channels,
+            sample_rate,
+            samples_written: 0
+        };
+        writer.write_header()?;
+        Ok(writer)
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// the number of frames (samples per channel) written so far
+    pub fn frames_written(&self) -> u64 {
+        self.samples_written / self.channels.max(1) as u64
+    }
+
+    /// appends interleaved samples to the file
+    /// fails without writing if the file would grow past the size a WAV file can describe
+    pub fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        let samples_written = self.samples_written + samples.len() as u64;
+        if samples_written * Self::BYTES_PER_SAMPLE > Self::MAX_DATA_SIZE {
+            return Err(io::Error::new(io::ErrorKind::FileTooLarge, "WAV files are limited to 4 GiB"));
+        }
+        for sample in samples {
+            self.file.write_all(&sample.to_le_bytes())?;
+        }
+        self.samples_written = samples_written;
+        Ok(())
+    }
+
+    /// writes the final header and flushes the file
+    pub fn finish(mut self) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.file.flush()
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        // write_samples keeps the data within MAX_DATA_SIZE
+        let data_size = (self.samples_written * Self::BYTES_PER_SAMPLE) as u32;
+        let block_align = self.channels as u32 * Self::BYTES_PER_SAMPLE as u32;
+
+        let file = &mut self.file;
+        file.write_all(b"RIFF")?;
+        file.write_all(&(Self::HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+        file.write_all(b"WAVE")?;
+
+        file.write_all(b"fmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        file.write_all(&Self::FORMAT_IEEE_FLOAT.to_le_bytes())?;
+        file.write_all(&self.channels.to_le_bytes())?;
+        file.write_all(&self.sample_rate.to_le_bytes())?;
+        file.write_all(&(self.sample_rate * block_align).to_le_bytes())?;
+        file.write_all(&(block_align as u16).to_le_bytes())?;
+        file.write_all(&Self::BITS_PER_SAMPLE.to_le_bytes())?;
+
+        file.write_all(b"data")?;
+        file.write_all(&data_size.to_le_bytes())?;
+        Ok(())
+    }
+}
+
+/// An error occurring while reading a WAV file
+#[derive(Debug, Error)]
+pub enum WavError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+
+    #[error("The file is not a WAV file.")]
+    NotWav,
+
+    #[error("The file is missing its '{0}' chunk.")]
+    MissingChunk(&'static str),
+
+    #[error("Unsupported sample format {0} with {1} bits per sample.")]
+    UnsupportedFormat(u16, u16),
+}
+
+/// The decoded samples of a WAV file
+#[derive(Debug, Clone, PartialEq)]
+pub struct WavData {
+    pub channels: u16,
+    pub sample_rate: u32,
+
+    /// interleaved samples in [-1, 1]
+    pub samples: Vec<f32>,
+}
+
+impl WavData {
+    const FORMAT_PCM: u16 = 1;
+    const FORMAT_IEEE_FLOAT: u16 = 3;
+    const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+    /// reads and decodes the WAV file at the given path
+    pub fn read(path: &Path) -> Result<Self, WavError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    /// decodes a WAV file with 8, 16, 24, or 32 bit integer or 32 bit float samples
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WavError> {
+        let mut reader = ByteReader::new(bytes);
+        if reader.read_bytes(4) != Some(b"RIFF") {
+            return Err(WavError::NotWav);
+        }
+        reader.read_u32().ok_or(WavError::NotWav)?;
+        if reader.read_bytes(4) != Some(b"WAVE") {
+            return Err(WavError::NotWav);
+        }
+
+        // (format, channels, sample rate, bits per sample)
+        let mut format = None;
+        let mut data = None;
+        while let (Some(id), Some(size)) = (reader.read_bytes(4), reader.read_u32()) {
+            // chunks are padded to an even size, and the last may be truncated
+            let size = size as usize;
+            let chunk = reader.read_bytes(size.min(reader.remaining())).ok_or(WavError::NotWav)?;
+            if size % 2 == 1 {
+                reader.read_u8();
+            }
+
+            match id {
+                b"fmt " => {
+                    let mut fmt = ByteReader::new(chunk);
+                    let (Some(mut tag), Some(channels), Some(sample_rate)) = (fmt.read_u16(), fmt.read_u16(), fmt.read_u32()) else {
+                        return Err(WavError::MissingChunk("fmt "));
+                    };
+                    fmt.read_bytes(6);
+                    let bits = fmt.read_u16().ok_or(WavError::MissingChunk("fmt "))?;
+                    if tag == Self::FORMAT_EXTENSIBLE {
+                        // the actual format is the start of the sub format guid
+                        fmt.read_bytes(8);
+                        tag = fmt.read_u16().ok_or(WavError::UnsupportedFormat(tag, bits))?;
+                    }
+                    format = Some((tag, channels, sample_rate, bits));
+                }
+                b"data" => data = Some(chunk),
+                _ => {}
+            }
+        }
+
+        let (tag, channels, sample_rate, bits) = format.ok_or(WavError::MissingChunk("fmt "))?;
+        let data = data.ok_or(WavError::MissingChunk("data"))?;
+        if channels == 0 {
+            return Err(WavError::UnsupportedFormat(tag, bits));
+        }
+
+        let samples = match (tag, bits) {
+            (Self::FORMAT_PCM, 8) => data.iter()
+                .map(|byte| (*byte as f32 - 128.0) / 128.0)
+                .collect(),
+            (Self::FORMAT_PCM, 16) => data.chunks_exact(2)
+                .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+                .collect(),
+            (Self::FORMAT_PCM, 24) => data.chunks_exact(3)
+                .map(|b| (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8388608.0)
+                .collect(),
+            (Self::FORMAT_PCM, 32) => data.chunks_exact(4)
+                .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2147483648.0)
+                .collect(),
+            (Self::FORMAT_IEEE_FLOAT, 32) => data.chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+            _ => return Err(WavError::UnsupportedFormat(tag, bits)),
+        };
+
+        Ok(Self {
+            channels,
+            sample_rate,
+            samples,
+        })
+    }
+
+    /// the number of frames (samples per channel)
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels as usize
+    }
+
+    /// the samples mixed down to a single channel
+    pub fn to_mono(&self) -> Vec<f32> {
+        let channels = self.channels as usize;
+        self.samples
+            .chunks_exact(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect()
+    }
+}